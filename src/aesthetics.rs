@@ -1,19 +1,27 @@
-use crate::escher::{ArrowTag, CircleTag, Hover, Tag};
+use crate::escher::{Adjacency, ArrowTag, CircleTag, Editable, Hover, Secondary, Tag};
+use crate::font::MapFont;
 use crate::funcplot::{
-    build_grad, from_grad_clamped, lerp, max_f32, min_f32, path_to_vec, plot_box_point, plot_hist,
-    plot_kde, plot_line, plot_scales, zero_lerp, IgnoreSave,
+    build_grad, category_color, dash_path, from_grad_clamped, hexagon, lerp, max_f32, min_f32,
+    path_to_vec, plot_box_point, plot_error_bar, plot_gauge, plot_hist, plot_kde, plot_line,
+    plot_scales, plot_wedge, plot_whisker, ribbon_path, widen_degenerate, zero_lerp, ArrowStyle,
+    ErrorBar, Favorability, IgnoreSave, Kernel, NumberFormat,
 };
 use crate::geom::{
-    AesFilter, AnyTag, Drag, GeomArrow, GeomHist, GeomMetabolite, HistPlot, HistTag, PopUp, Side,
-    VisCondition, Xaxis,
+    AesFilter, AnyTag, AxisTitle, Drag, ErrorBarTag, GeomArrow, GeomHist, GeomMetabolite, GeomPie,
+    GeomRegistry, GeomStack, GeomThermo, HistPlot, HistTag, LeaderLine, PieTag, PopUp, RibbonTag,
+    Side, SigLabel, StackTag, ThermoTag, VisCondition, WhiskerTag, Xaxis,
 };
-use crate::gui::{or_color, ActiveData, UiState};
+use crate::gui::{ActiveData, HoverState, Selection, UiState};
+use crate::scale::DefaultFontSize;
+use crate::stats::{self, Summary};
+use crate::theme::Theme;
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use bevy::prelude::*;
+use bevy::sprite::Anchor;
 use bevy_prototype_lyon::prelude::{
-    shapes, Fill, GeometryBuilder, Path, ShapeBundle, ShapePath, Stroke,
+    shapes, Fill, FillOptions, FillRule, GeometryBuilder, Path, ShapeBundle, Stroke,
 };
 
 pub struct AesPlugin;
@@ -21,22 +29,74 @@ pub struct AesPlugin;
 impl Plugin for AesPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<RestoreEvent>()
+            .init_resource::<GeomRegistry>()
+            .init_resource::<Groups>()
+            .init_resource::<Units>()
+            // read by highlight_groups, also without the map or the GUI
+            .init_resource::<HoverState>()
+            .init_resource::<Selection>()
+            .init_resource::<Adjacency>()
+            .init_resource::<MapFont>()
+            .add_systems(
+                Update,
+                summarize_samples
+                    .before(plot_arrow_size)
+                    .before(plot_metabolite_size)
+                    .before(plot_arrow_color)
+                    .before(plot_metabolite_color),
+            )
             .add_systems(Update, plot_arrow_size)
             .add_systems(Update, plot_metabolite_size)
             .add_systems(Update, plot_arrow_color)
             .add_systems(Update, plot_metabolite_color)
+            .add_systems(Update, plot_ribbons)
+            .add_systems(
+                Update,
+                color_ribbons
+                    .after(plot_ribbons)
+                    .after(emphasize_knockouts)
+                    .after(highlight_groups),
+            )
             .add_systems(Update, restore_geoms::<CircleTag>)
             .add_systems(Update, restore_geoms::<ArrowTag>)
             .add_systems(Update, normalize_histogram_height)
+            .add_systems(
+                Update,
+                stack_popups
+                    .after(normalize_histogram_height)
+                    .before(unscale_histogram_children),
+            )
             .add_systems(Update, unscale_histogram_children)
+            .add_systems(Update, color_popup_keys.after(normalize_histogram_height))
+            .add_systems(
+                Update,
+                emphasize_knockouts
+                    .after(plot_arrow_color)
+                    .after(plot_arrow_size)
+                    .before(highlight_groups),
+            )
+            .add_systems(
+                Update,
+                highlight_groups
+                    .after(plot_arrow_color)
+                    .after(plot_metabolite_color)
+                    .after(normalize_histogram_height),
+            )
             .add_systems(Update, fill_conditions)
             .add_systems(Update, filter_histograms)
             .add_systems(Update, activate_settings)
+            .add_systems(Update, replot_distributions)
             .add_systems(Update, follow_the_axes)
+            .add_systems(Update, draw_leader_lines)
+            .add_systems(Update, (plot_axis_titles, show_axis_titles))
+            .add_systems(Update, plot_significance)
             // TODO: check since these were before load_map
             .add_systems(PostUpdate, (build_axes, build_hover_axes, build_point_axes))
-            .add_systems(Update, (plot_side_hist, plot_hover_hist))
-            .add_systems(Update, (plot_side_box, change_color.before(plot_side_box)));
+            .add_systems(Update, (plot_side_hist, plot_hover_hist, plot_whiskers))
+            .add_systems(Update, share_xlimits.before(plot_side_hist))
+            .add_systems(Update, color_whiskers.after(plot_whiskers))
+            .add_systems(Update, (plot_side_box, change_color.before(plot_side_box)))
+            .add_systems(Update, (plot_thermo, plot_stack, plot_pie));
     }
 }
 
@@ -56,6 +116,74 @@ pub struct Gy {}
 pub struct Point<T>(pub Vec<T>);
 #[derive(Component)]
 pub struct Distribution<T>(pub Vec<Vec<T>>);
+/// Samples of each identifier of a color or size, summarized into the [`Point`]
+/// of the same entity with [`UiState::summary`]. The samples before the
+/// transformation of their dataset, if any, are summarized into its [`Untransformed`].
+#[derive(Component)]
+pub struct Summarized {
+    pub samples: Vec<Vec<f32>>,
+    pub original: Option<Vec<Vec<f32>>>,
+}
+
+impl Summarized {
+    /// Summary of the samples of each identifier and of their original values.
+    pub fn summarize(&self, summary: Summary, q: f32) -> (Vec<f32>, Option<Vec<f32>>) {
+        let summarize = |samples: &Vec<Vec<f32>>| {
+            samples
+                .iter()
+                .map(|samples| summary.apply(samples, q))
+                .collect::<Vec<f32>>()
+        };
+        (
+            summarize(&self.samples),
+            self.original.as_ref().map(summarize),
+        )
+    }
+}
+
+/// Weights of each sample in the [`Distribution`] of the same entity.
+#[derive(Component)]
+pub struct Weights(pub Vec<Vec<f32>>);
+/// Name of the data column of the same entity, shown as the title of its axis.
+#[derive(Component)]
+pub struct ColumnName(pub String);
+/// Identifiers of the same entity whose values are missing (null or NaN).
+#[derive(Component)]
+pub struct Missing(pub Vec<String>);
+/// Values of the [`Point`] or [`Distribution`] of the same entity before
+/// the transformation of their dataset was applied.
+#[derive(Component)]
+pub struct Untransformed<T>(pub Vec<T>);
+/// Named parts of the value of each identifier of the same entity, e.g., the
+/// usage of each isozyme of a reaction.
+#[derive(Component)]
+pub struct Parts(pub Vec<Vec<(String, f32)>>);
+
+/// Sorted names of all the [`Parts`], which decide their colors.
+pub fn categories<'a>(parts: impl Iterator<Item = &'a Parts>) -> Vec<String> {
+    parts
+        .flat_map(|parts| parts.0.iter().flatten().map(|(name, _)| name))
+        .unique()
+        .sorted()
+        .cloned()
+        .collect()
+}
+
+/// Standard deviations of the [`Point`] values of the same entity.
+#[derive(Component)]
+pub struct Uncertainty(pub Vec<f32>);
+/// Drawing layer of the dataset of the same entity. Plots of higher layers
+/// are drawn over those of lower layers, regardless of the loading order.
+#[derive(Component, Clone, Copy)]
+pub struct Layer(pub i32);
+
+/// Separation in z between the plots of consecutive [`Layer`]s, larger than
+/// the increments used to avoid flickering between conditions.
+const LAYER_STEP: f32 = 0.01;
+
+fn layer_z(layer: Option<&Layer>) -> f32 {
+    layer.map(|l| l.0 as f32 * LAYER_STEP).unwrap_or(0.)
+}
 
 #[derive(Component)]
 pub struct Gsize {}
@@ -67,6 +195,29 @@ pub struct Gcolor {}
 #[derive(Component)]
 pub struct Unscale;
 
+/// Key of a popup histogram with its column and condition, placed `y` units
+/// above the popup regardless of the scale of the histogram.
+#[derive(Component)]
+struct PopUpKey {
+    y: f32,
+}
+
+/// Font size of the keys of the popups.
+const POPUP_KEY_SIZE: f32 = 14.;
+
+/// Position of a popup histogram and its key as spawned, to which it goes
+/// back when it is no longer stacked by [`stack_popups`].
+#[derive(Component)]
+struct PopUpBase {
+    y: f32,
+    key_y: f32,
+}
+
+/// Background and scales of a popup, only shown in the bottom row when the
+/// popups of all conditions are stacked.
+#[derive(Component)]
+struct PopUpFrame;
+
 /// Marker for things that need to change the color when UiChanges.
 #[derive(Component)]
 struct ColorListener {
@@ -75,39 +226,147 @@ struct ColorListener {
     max_val: f32,
 }
 
+/// Group (e.g., subsystem) of the reactions and metabolites, given in the data.
+#[derive(Resource, Default)]
+pub struct Groups {
+    pub reactions: HashMap<String, String>,
+    pub metabolites: HashMap<String, String>,
+}
+
+impl Groups {
+    /// Names of all the groups, sorted.
+    pub fn names(&self) -> Vec<&String> {
+        self.reactions
+            .values()
+            .chain(self.metabolites.values())
+            .unique()
+            .sorted()
+            .collect()
+    }
+}
+
+/// Units of the plotted variables by the title of their column, given in the
+/// data or in the settings.
+#[derive(Resource, Default)]
+pub struct Units(pub HashMap<String, String>);
+
+impl Units {
+    /// Unit of the column `title`, empty if it has none.
+    pub fn of(&self, title: &str) -> &str {
+        self.0
+            .get(title)
+            .map(|unit| unit.trim())
+            .unwrap_or_default()
+    }
+
+    /// Title of a column followed by its unit in brackets, e.g., "flux [mmol/gDW/h]".
+    pub fn label(&self, title: &str) -> String {
+        match self.of(title) {
+            "" => title.to_string(),
+            unit => format!("{title} [{unit}]"),
+        }
+    }
+}
+
+/// Marker of the arrows and circles dimmed by [`highlight_groups`].
+#[derive(Component)]
+struct Dimmed;
+
+/// Marker of the arrows and circles colored by their group in [`highlight_groups`].
+#[derive(Component)]
+struct GroupColored;
+
+/// Alpha of the elements outside of the selected group.
+const DIM_ALPHA: f32 = 0.15;
+
+/// Original path of an arrow dashed by [`emphasize_knockouts`].
+#[derive(Component)]
+struct KnockedOut(bevy_prototype_lyon::prelude::tess::path::Path);
+
+const KNOCKOUT_WIDTH: f32 = 4.;
+const KNOCKOUT_DASH: f32 = 18.;
+const KNOCKOUT_GAP: f32 = 12.;
+
 /// Everytime this is sent, all data and plots are removed, leaving
 /// the map as default. This is triggered when new data is added.
 #[derive(Event)]
 pub struct RestoreEvent;
 
+/// Summarize the samples of the colors and sizes with the statistic chosen
+/// in the settings, again whenever it changes.
+pub fn summarize_samples(
+    ui_state: Res<UiState>,
+    mut last: Local<Option<(Summary, f32)>>,
+    added: Query<(), Added<Summarized>>,
+    mut aes_query: Query<(
+        &Summarized,
+        &mut Point<f32>,
+        Option<&mut Untransformed<f32>>,
+    )>,
+) {
+    let current = (ui_state.summary, ui_state.summary_quantile);
+    if (*last == Some(current)) && added.is_empty() {
+        return;
+    }
+    *last = Some(current);
+    for (summarized, mut point, original) in aes_query.iter_mut() {
+        let (values, original_values) = summarized.summarize(current.0, current.1);
+        point.0 = values;
+        if let (Some(mut original), Some(values)) = (original, original_values) {
+            original.0 = values;
+        }
+    }
+}
+
+/// Range of the values mapped to a color or size: the one of `values` or, if
+/// [`UiState::global_scale`], the one of all the conditions in `all` so that
+/// the scales are comparable when switching conditions.
+pub fn scale_range<'a>(
+    ui_state: &UiState,
+    values: &[f32],
+    all: impl Iterator<Item = &'a [f32]>,
+) -> (f32, f32) {
+    if ui_state.global_scale {
+        all.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), values| {
+            (min.min(min_f32(values)), max.max(max_f32(values)))
+        })
+    } else {
+        (min_f32(values), max_f32(values))
+    }
+}
+
 /// Plot arrow size.
 pub fn plot_arrow_size(
     ui_state: Res<UiState>,
     mut query: Query<(&mut Stroke, &ArrowTag)>,
-    mut aes_query: Query<(&Point<f32>, &Aesthetics, &GeomArrow), With<Gsize>>,
+    aes_query: Query<(&Point<f32>, &Aesthetics, &GeomArrow), With<Gsize>>,
 ) {
-    for (sizes, aes, _geom) in aes_query.iter_mut() {
+    for (sizes, aes, geom) in aes_query.iter() {
         if let Some(condition) = &aes.condition {
             if condition != &ui_state.condition {
                 continue;
             }
         }
-        let min_val = min_f32(&sizes.0);
-        let max_val = max_f32(&sizes.0);
+        let all = aes_query.iter().map(|(sizes, ..)| sizes.0.as_slice());
+        let (min_val, max_val) = scale_range(&ui_state, &sizes.0, all);
         for (mut stroke, arrow) in query.iter_mut() {
-            if let Some(index) = aes.identifiers.iter().position(|r| r == &arrow.id) {
+            let width = if geom.style == ArrowStyle::Ribbon {
+                // the width is drawn by the ribbons of plot_ribbons
+                RIBBON_LINE_WIDTH
+            } else if let Some(index) = aes.identifiers.iter().position(|r| r == &arrow.id) {
                 let unscaled_width = sizes.0[index];
                 let f = if ui_state.zero_white { zero_lerp } else { lerp };
-                stroke.options.line_width = f(
+                f(
                     unscaled_width,
                     min_val,
                     max_val,
                     ui_state.min_reaction,
                     ui_state.max_reaction,
-                );
+                )
             } else {
-                stroke.options.line_width = 10.;
-            }
+                10.
+            };
+            set_width(&mut stroke, width);
         }
     }
 }
@@ -116,29 +375,33 @@ pub fn plot_arrow_size(
 pub fn plot_arrow_color(
     ui_state: Res<UiState>,
     mut query: Query<(&mut Stroke, &ArrowTag), Without<Fill>>,
-    mut aes_query: Query<(&Point<f32>, &Aesthetics, &GeomArrow), With<Gcolor>>,
+    aes_query: Query<(&Point<f32>, &Aesthetics, &GeomArrow, Option<&Missing>), With<Gcolor>>,
 ) {
-    for (colors, aes, _) in aes_query.iter_mut() {
+    for (colors, aes, _, missing) in aes_query.iter() {
         if let Some(condition) = &aes.condition {
             if condition != &ui_state.condition {
                 continue;
             }
         }
-        let min_val = min_f32(&colors.0);
-        let max_val = max_f32(&colors.0);
+        let all = aes_query.iter().map(|(colors, ..)| colors.0.as_slice());
+        let (min_val, max_val) = scale_range(&ui_state, &colors.0, all);
+        let (min_color, max_color) = ui_state.color_scale("Reaction", aes.condition.as_deref());
         let grad = build_grad(
             ui_state.zero_white,
             min_val,
             max_val,
-            &ui_state.min_reaction_color,
-            &ui_state.max_reaction_color,
+            &min_color,
+            &max_color,
         );
         for (mut stroke, tag) in query.iter_mut() {
-            if let Some(index) = aes.identifiers.iter().position(|r| r == tag.id()) {
-                stroke.color = from_grad_clamped(&grad, colors.0[index], min_val, max_val);
+            let color = if let Some(index) = aes.identifiers.iter().position(|r| r == tag.id()) {
+                from_grad_clamped(&grad, colors.0[index], min_val, max_val)
+            } else if is_missing(missing, tag.id()) {
+                to_color(&ui_state.missing_color)
             } else {
-                stroke.color = Color::rgb(0.85, 0.85, 0.85);
-            }
+                Color::rgb(0.85, 0.85, 0.85)
+            };
+            set_stroke_color(&mut stroke, color);
         }
     }
 }
@@ -147,47 +410,219 @@ pub fn plot_arrow_color(
 pub fn plot_metabolite_color(
     ui_state: Res<UiState>,
     mut query: Query<(&mut Fill, &CircleTag)>,
-    mut aes_query: Query<(&Point<f32>, &Aesthetics, &GeomMetabolite), With<Gcolor>>,
+    aes_query: Query<(&Point<f32>, &Aesthetics, &GeomMetabolite, Option<&Missing>), With<Gcolor>>,
 ) {
-    for (colors, aes, _) in aes_query.iter_mut() {
+    for (colors, aes, _, missing) in aes_query.iter() {
         if let Some(condition) = &aes.condition {
             if condition != &ui_state.condition {
                 continue;
             }
         }
-        let min_val = min_f32(&colors.0);
-        let max_val = max_f32(&colors.0);
+        let all = aes_query.iter().map(|(colors, ..)| colors.0.as_slice());
+        let (min_val, max_val) = scale_range(&ui_state, &colors.0, all);
+        let (min_color, max_color) = ui_state.color_scale("Metabolite", aes.condition.as_deref());
         let grad = build_grad(
             ui_state.zero_white,
             min_val,
             max_val,
-            &ui_state.min_metabolite_color,
-            &ui_state.max_metabolite_color,
+            &min_color,
+            &max_color,
         );
         for (mut fill, tag) in query.iter_mut() {
-            if let Some(index) = aes.identifiers.iter().position(|r| r == tag.id()) {
-                fill.color = from_grad_clamped(&grad, colors.0[index], min_val, max_val);
+            let color = if let Some(index) = aes.identifiers.iter().position(|r| r == tag.id()) {
+                from_grad_clamped(&grad, colors.0[index], min_val, max_val)
+            } else if is_missing(missing, tag.id()) {
+                to_color(&ui_state.missing_color)
             } else {
-                fill.color = Color::rgb(0.85, 0.85, 0.85);
+                Color::rgb(0.85, 0.85, 0.85)
+            };
+            set_fill_color(&mut fill, color);
+        }
+    }
+}
+
+/// Width of the line of the arrows drawn over by their ribbons.
+const RIBBON_LINE_WIDTH: f32 = 2.;
+
+/// Settings that the ribbons of [`plot_ribbons`] were last built with.
+#[derive(PartialEq)]
+struct RibbonScale {
+    min_reaction: f32,
+    max_reaction: f32,
+    condition: String,
+    global_scale: bool,
+    knockouts: bool,
+    knockout_threshold: f32,
+    /// Number of sizes plotted, since the data may have been removed.
+    sizes: usize,
+}
+
+/// Draw the size of the reactions as tapered ribbons for the datasets whose
+/// [`GeomArrow::style`] is [`ArrowStyle::Ribbon`]: the width encodes the
+/// magnitude of the value and the taper its sign (see [`ribbon_path`]).
+///
+/// The ribbons are only rebuilt if the data, the scale or the condition
+/// changed, or if the map was reloaded.
+fn plot_ribbons(
+    mut commands: Commands,
+    ui_state: Res<UiState>,
+    mut last_scale: Local<Option<RibbonScale>>,
+    new_arrows: Query<(), Added<ArrowTag>>,
+    aes_query: Query<(Ref<Point<f32>>, Ref<Aesthetics>, Ref<GeomArrow>), With<Gsize>>,
+    arrows: Query<(Entity, &Path, &Transform, &ArrowTag, &Stroke), Without<RibbonTag>>,
+    ribbons: Query<Entity, With<RibbonTag>>,
+) {
+    let scale = RibbonScale {
+        min_reaction: ui_state.min_reaction,
+        max_reaction: ui_state.max_reaction,
+        condition: ui_state.condition.clone(),
+        global_scale: ui_state.global_scale,
+        knockouts: ui_state.knockouts,
+        knockout_threshold: ui_state.knockout_threshold,
+        sizes: aes_query.iter().count(),
+    };
+    let rescaled = last_scale.as_ref() != Some(&scale) || !new_arrows.is_empty();
+    *last_scale = Some(scale);
+    if !(rescaled
+        || aes_query
+            .iter()
+            .any(|(sizes, aes, geom)| sizes.is_changed() || aes.is_changed() || geom.is_changed()))
+    {
+        return;
+    }
+    for entity in ribbons.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (sizes, aes, geom) in aes_query.iter() {
+        if geom.style != ArrowStyle::Ribbon {
+            continue;
+        }
+        if let Some(condition) = &aes.condition {
+            if condition != &ui_state.condition {
+                continue;
             }
         }
+        let all = aes_query
+            .iter()
+            .map(|(sizes, ..)| sizes.into_inner().0.as_slice());
+        let (min_val, max_val) = scale_range(&ui_state, &sizes.0, all);
+        let max_abs = min_val.abs().max(max_val.abs());
+        for (entity, path, trans, arrow, stroke) in arrows.iter() {
+            let Some(value) = aes
+                .identifiers
+                .iter()
+                .position(|r| r == &arrow.id)
+                .and_then(|i| sizes.0.get(i))
+                .filter(|value| !value.is_nan())
+            else {
+                continue;
+            };
+            // knocked out reactions are drawn as dashed lines instead
+            if ui_state.knockouts && value.abs() <= ui_state.knockout_threshold {
+                continue;
+            }
+            let width = lerp(
+                value.abs(),
+                0.,
+                max_abs,
+                ui_state.min_reaction,
+                ui_state.max_reaction,
+            );
+            commands.spawn((
+                ShapeBundle {
+                    path: ribbon_path(path, arrow.direction, width, *value < 0.),
+                    spatial: SpatialBundle::from_transform(
+                        trans.with_translation(trans.translation - Vec3::Z * 5e-7),
+                    ),
+                    ..default()
+                },
+                Fill {
+                    options: FillOptions::default().with_fill_rule(FillRule::NonZero),
+                    color: stroke.color,
+                },
+                RibbonTag { arrow: entity },
+            ));
+        }
+    }
+}
+
+/// Paint the ribbons with the color of their arrows, which follows the color
+/// aesthetic, the knockouts and the highlighting, and hide them with them.
+fn color_ribbons(
+    arrows: Query<(&Stroke, &Visibility), (With<ArrowTag>, Without<RibbonTag>)>,
+    mut ribbons: Query<(&mut Fill, &mut Visibility, &RibbonTag)>,
+) {
+    for (mut fill, mut visibility, ribbon) in ribbons.iter_mut() {
+        if let Ok((stroke, arrow_visibility)) = arrows.get(ribbon.arrow) {
+            set_fill_color(&mut fill, stroke.color);
+            if *visibility != *arrow_visibility {
+                *visibility = *arrow_visibility;
+            }
+        }
+    }
+}
+
+// Any change to the stroke, fill or path of a shape makes it be tessellated
+// again, so they are only written if the value differs.
+
+fn set_width(stroke: &mut Mut<Stroke>, width: f32) {
+    if stroke.options.line_width != width {
+        stroke.options.line_width = width;
+    }
+}
+
+fn set_stroke_color(stroke: &mut Mut<Stroke>, color: Color) {
+    if stroke.color != color {
+        stroke.color = color;
+    }
+}
+
+fn set_fill_color(fill: &mut Mut<Fill>, color: Color) {
+    if fill.color != color {
+        fill.color = color;
     }
 }
 
+fn is_missing(missing: Option<&Missing>, id: &str) -> bool {
+    missing.is_some_and(|missing| missing.0.iter().any(|m| m == id))
+}
+
+fn to_color(color: &bevy_egui::egui::Rgba) -> Color {
+    Color::rgba_linear(color.r(), color.g(), color.b(), color.a())
+}
+
 /// Plot size as numerical variable in metabolic circles.
+///
+/// The hexagons are only rebuilt if the data, the range of sizes or the
+/// condition changed, or if the map was reloaded.
 pub fn plot_metabolite_size(
     ui_state: Res<UiState>,
+    mut last_scale: Local<Option<(f32, f32, String, bool)>>,
     mut query: Query<(&mut Path, &CircleTag)>,
-    mut aes_query: Query<(&Point<f32>, &Aesthetics), (With<Gsize>, With<GeomMetabolite>)>,
+    new_circles: Query<(), Added<CircleTag>>,
+    aes_query: Query<(Ref<Point<f32>>, Ref<Aesthetics>), (With<Gsize>, With<GeomMetabolite>)>,
 ) {
-    for (sizes, aes) in aes_query.iter_mut() {
+    let scale = (
+        ui_state.min_metabolite,
+        ui_state.max_metabolite,
+        ui_state.condition.clone(),
+        ui_state.global_scale,
+    );
+    let rescaled = last_scale.as_ref() != Some(&scale) || !new_circles.is_empty();
+    *last_scale = Some(scale);
+    for (sizes, aes) in aes_query.iter() {
+        if !(rescaled || sizes.is_changed() || aes.is_changed()) {
+            continue;
+        }
         if let Some(condition) = &aes.condition {
             if condition != &ui_state.condition {
                 continue;
             }
         }
-        let min_val = min_f32(&sizes.0);
-        let max_val = max_f32(&sizes.0);
+        let all = aes_query
+            .iter()
+            .map(|(sizes, _)| sizes.into_inner().0.as_slice());
+        let (min_val, max_val) = scale_range(&ui_state, &sizes.0, all);
         for (mut path, arrow) in query.iter_mut() {
             let radius = if let Some(index) = aes.identifiers.iter().position(|r| r == &arrow.id) {
                 lerp(
@@ -200,18 +635,14 @@ pub fn plot_metabolite_size(
             } else {
                 20.
             };
-            let polygon = shapes::RegularPolygon {
-                sides: 6,
-                feature: shapes::RegularPolygonFeature::Radius(radius),
-                ..shapes::RegularPolygon::default()
-            };
-            *path = ShapePath::build_as(&polygon);
+            *path = hexagon(radius);
         }
     }
 }
 
 /// Remove colors and sizes from circles and arrows after new data is dropped.
 fn restore_geoms<T: Tag>(
+    theme: Res<Theme>,
     mut restore_event: EventReader<RestoreEvent>,
     mut query: ParamSet<(
         Query<(&mut Fill, &mut Path), With<T>>,
@@ -219,114 +650,441 @@ fn restore_geoms<T: Tag>(
     )>,
 ) {
     for _ in restore_event.read() {
+        let default_hexagon = hexagon(20.);
         for (mut fill, mut path) in query.p0().iter_mut() {
             // met colors
-            fill.color = T::default_color();
-            let polygon = shapes::RegularPolygon {
-                sides: 6,
-                feature: shapes::RegularPolygonFeature::Radius(20.),
-                ..shapes::RegularPolygon::default()
-            };
+            fill.color = T::default_color(&theme);
             // met size
-            *path = ShapePath::build_as(&polygon);
+            *path = Path(default_hexagon.0.clone());
         }
         for mut stroke in query.p1().iter_mut() {
-            stroke.color = T::default_color();
+            stroke.color = T::default_color(&theme);
             stroke.options.line_width = 10.0;
         }
     }
 }
 
+/// Draw the reactions whose value is zero, or under the threshold in absolute
+/// value, as thin dashed lines so that they do not blend into the low end of
+/// the color scale.
+///
+/// The colors and sizes of the data are painted every frame, so the style is
+/// applied on top of them; the original path is kept to undo the dashes.
+fn emphasize_knockouts(
+    mut commands: Commands,
+    ui_state: Res<UiState>,
+    theme: Res<Theme>,
+    aes_query: Query<
+        (&Point<f32>, &Aesthetics),
+        (With<GeomArrow>, Or<(With<Gcolor>, With<Gsize>)>),
+    >,
+    mut arrows: Query<
+        (
+            Entity,
+            &mut Stroke,
+            &mut Path,
+            &ArrowTag,
+            Option<&KnockedOut>,
+        ),
+        Without<Fill>,
+    >,
+) {
+    let knocked: HashSet<&str> = aes_query
+        .iter()
+        .filter(|_| ui_state.knockouts)
+        .filter(|(_, aes)| {
+            aes.condition
+                .as_ref()
+                .is_none_or(|condition| condition == &ui_state.condition)
+        })
+        .flat_map(|(values, aes)| aes.identifiers.iter().zip(values.0.iter()))
+        .filter(|(_, value)| value.abs() <= ui_state.knockout_threshold)
+        .map(|(id, _)| id.as_str())
+        .collect();
+    for (entity, mut stroke, mut path, arrow, knocked_out) in arrows.iter_mut() {
+        if knocked.contains(arrow.id.as_str()) {
+            stroke.color = to_color(&ui_state.knockout_color);
+            stroke.options.line_width = KNOCKOUT_WIDTH;
+            if knocked_out.is_none() {
+                commands.entity(entity).insert(KnockedOut(path.0.clone()));
+                *path = dash_path(&path, KNOCKOUT_DASH, KNOCKOUT_GAP);
+            }
+        } else if let Some(KnockedOut(original)) = knocked_out {
+            // repainted by the data in the next frame, if any
+            path.0 = original.clone();
+            stroke.color = ArrowTag::default_color(&theme);
+            stroke.options.line_width = 10.;
+            commands.entity(entity).remove::<KnockedOut>();
+        }
+    }
+}
+
+/// Dim the elements outside of the selected group and color the arrows and
+/// circles with the qualitative palette of their group.
+///
+/// The hovered reaction or metabolite, or else the clicked one, is linked to
+/// the elements that it consumes, produces or is produced by; the rest is dimmed.
+///
+/// The elements whose value in the current condition is out of the range of
+/// the filter by value are also dimmed, or hidden if [`UiState::hide_filtered`].
+///
+/// The colors of the data are painted every frame, so both are applied on
+/// top of them and restored to the default color when turned off.
+fn highlight_groups(
+    mut commands: Commands,
+    ui_state: Res<UiState>,
+    theme: Res<Theme>,
+    groups: Res<Groups>,
+    hover_state: Res<HoverState>,
+    selection: Res<Selection>,
+    adjacency: Res<Adjacency>,
+    mut arrows: Query<
+        (
+            Entity,
+            &mut Stroke,
+            &ArrowTag,
+            Has<Dimmed>,
+            Has<GroupColored>,
+        ),
+        Without<Fill>,
+    >,
+    mut circles: Query<(
+        Entity,
+        &mut Fill,
+        &CircleTag,
+        Has<Dimmed>,
+        Has<GroupColored>,
+    )>,
+    mut hists: Query<(&mut Fill, &HistTag, &AesFilter), Without<CircleTag>>,
+    values: Query<
+        (&Aesthetics, &Point<f32>, Has<Gcolor>, Has<GeomMetabolite>),
+        (
+            Or<(With<Gcolor>, With<Gsize>)>,
+            Or<(With<GeomArrow>, With<GeomMetabolite>)>,
+        ),
+    >,
+) {
+    // the value of the color is filtered, or else the one of the size
+    let mut reaction_values: HashMap<&str, (bool, f32)> = HashMap::new();
+    let mut metabolite_values: HashMap<&str, (bool, f32)> = HashMap::new();
+    if ui_state.reaction_filter.is_some() || ui_state.metabolite_filter.is_some() {
+        for (aes, point, color, met) in values.iter() {
+            if aes
+                .condition
+                .as_ref()
+                .is_some_and(|c| c != &ui_state.condition)
+            {
+                continue;
+            }
+            let by_id = if met {
+                &mut metabolite_values
+            } else {
+                &mut reaction_values
+            };
+            for (id, value) in aes.identifiers.iter().zip(point.0.iter()) {
+                let entry = by_id.entry(id.as_str()).or_insert((color, *value));
+                if color || !entry.0 {
+                    *entry = (color, *value);
+                }
+            }
+        }
+    }
+    let filtered_alpha = if ui_state.hide_filtered {
+        0.
+    } else {
+        DIM_ALPHA
+    };
+    // alpha of an element, if it is dimmed
+    let alpha = |dimmed: bool, id: &str, met: bool| {
+        let values = if met {
+            &metabolite_values
+        } else {
+            &reaction_values
+        };
+        if !ui_state.passes_filter(met, values.get(id).map(|(_, value)| *value)) {
+            Some(filtered_alpha)
+        } else {
+            dimmed.then_some(DIM_ALPHA)
+        }
+    };
+    let names = groups.names();
+    let palette = |group: Option<&String>| {
+        group
+            .and_then(|g| names.iter().position(|name| *name == g))
+            .map(category_color)
+    };
+    let linked = hover_state
+        .id
+        .as_ref()
+        .map(|id| (id, hover_state.met))
+        .or(selection.id.as_ref().map(|id| (id, selection.met)))
+        .map(|(id, met)| adjacency.linked(id, met));
+    let dims = |group: Option<&String>, id: &str, met: bool| {
+        let unlinked = linked.as_ref().is_some_and(|(reactions, metabolites)| {
+            !if met { metabolites } else { reactions }.contains(id)
+        });
+        unlinked
+            || ui_state
+                .group
+                .as_ref()
+                .is_some_and(|selected| group != Some(selected))
+    };
+    let mut dimmed_reactions = HashMap::new();
+    for (entity, mut stroke, arrow, dimmed, colored) in arrows.iter_mut() {
+        let group = groups.reactions.get(&arrow.id);
+        match palette(group).filter(|_| ui_state.group_colors) {
+            Some(color) => {
+                // the alpha is left to the dimming
+                if stroke.color.with_a(1.) != color {
+                    stroke.color = color;
+                }
+                if !colored {
+                    commands.entity(entity).insert(GroupColored);
+                }
+            }
+            None if colored => {
+                stroke.color = ArrowTag::default_color(&theme);
+                commands.entity(entity).remove::<GroupColored>();
+            }
+            None => (),
+        }
+        if let Some(alpha) = alpha(dims(group, &arrow.id, false), &arrow.id, false) {
+            dimmed_reactions.insert(arrow.node_id, alpha);
+            if stroke.color.a() != alpha {
+                stroke.color.set_a(alpha);
+            }
+            if !dimmed {
+                commands.entity(entity).insert(Dimmed);
+            }
+        } else if dimmed {
+            stroke.color.set_a(1.);
+            commands.entity(entity).remove::<Dimmed>();
+        }
+    }
+    for (entity, mut fill, circle, dimmed, colored) in circles.iter_mut() {
+        let group = groups.metabolites.get(&circle.id);
+        match palette(group).filter(|_| ui_state.group_colors) {
+            Some(color) => {
+                // the alpha is left to the dimming
+                if fill.color.with_a(1.) != color {
+                    fill.color = color;
+                }
+                if !colored {
+                    commands.entity(entity).insert(GroupColored);
+                }
+            }
+            None if colored => {
+                fill.color = CircleTag::default_color(&theme);
+                commands.entity(entity).remove::<GroupColored>();
+            }
+            None => (),
+        }
+        if let Some(alpha) = alpha(dims(group, &circle.id, true), &circle.id, true) {
+            if fill.color.a() != alpha {
+                fill.color.set_a(alpha);
+            }
+            if !dimmed {
+                commands.entity(entity).insert(Dimmed);
+            }
+        } else if dimmed {
+            fill.color.set_a(1.);
+            commands.entity(entity).remove::<Dimmed>();
+        }
+    }
+    // the histograms are painted again every frame
+    for (mut fill, hist, filter) in hists.iter_mut() {
+        if let Some(dim) = dimmed_reactions.get(&hist.node_id).filter(|_| !filter.met) {
+            let alpha = fill.color.a() * dim;
+            fill.color.set_a(alpha);
+        }
+    }
+}
+
+/// Distance between the center of a metabolite circle and its axes.
+const MET_AXIS_AWAY: f32 = 40.;
+/// Length of the axes of the metabolites, which have no arrow to measure.
+const MET_AXIS_SIZE: f32 = 80.;
+
+/// Axis of a `side` of a metabolite: above its circle for the right side and
+/// upside down below it for the left side, so that the plots grow away from it.
+pub fn met_axis_transform(trans: &Transform, side: &Side) -> Option<Transform> {
+    let (away, rotation) = match side {
+        Side::Right => (MET_AXIS_AWAY, 0.),
+        Side::Left => (-MET_AXIS_AWAY, std::f32::consts::PI),
+        Side::Up => return None,
+    };
+    Some(
+        Transform::from_xyz(trans.translation.x, trans.translation.y + away, 0.5)
+            .with_rotation(Quat::from_rotation_z(rotation)),
+    )
+}
+
+/// Where the axes of an aesthetic go: the identifier, node, length, position
+/// and relative height of each of them.
+type AxisAnchor = (String, u64, f32, Transform, f32);
+
+/// Anchors of the axes of the metabolites in `identifiers`. Metabolites drawn
+/// several times get a single axis, next to a primary circle if there is any.
+fn met_anchors(
+    identifiers: &[String],
+    side: &Side,
+    circles: &Query<(&Transform, &CircleTag, &Editable, Has<Secondary>)>,
+) -> Vec<AxisAnchor> {
+    circles
+        .iter()
+        .filter(|(_, circle, _, _)| identifiers.contains(&circle.id))
+        .filter_map(|(trans, circle, editable, secondary)| {
+            let Editable::Metabolite(node_id) = editable else {
+                return None;
+            };
+            let transform = met_axis_transform(trans, side)?;
+            Some((
+                secondary,
+                (circle.id.clone(), *node_id, MET_AXIS_SIZE, transform, 1.),
+            ))
+        })
+        .sorted_by_key(|(secondary, _)| *secondary)
+        .map(|(_, anchor)| anchor)
+        .collect()
+}
+
+/// Anchors of the axes of the reactions in `identifiers`, perpendicular to
+/// their arrows unless they were moved and saved with the map.
+fn arrow_anchors(
+    identifiers: &[String],
+    side: &Side,
+    arrows: &Query<(&Transform, &ArrowTag, &Path)>,
+) -> Vec<AxisAnchor> {
+    let mut anchors = Vec::new();
+    for (trans, arrow, path) in arrows.iter() {
+        if !identifiers.iter().any(|r| r == &arrow.id) {
+            continue;
+        }
+        let size = path_to_vec(path).length();
+        let (rotation_90, away) = match side {
+            Side::Right => (-Vec2::Y.angle_between(arrow.direction.perp()), -30.),
+            Side::Left => (-Vec2::NEG_Y.angle_between(arrow.direction.perp()), 30.),
+            _ => {
+                warn!("Tried to plot Up direction for non-popup '{}'", arrow.id);
+                continue;
+            }
+        };
+        let transform: Transform = if let Some(Some(ser_transform)) =
+            arrow.hists.as_ref().map(|x| x.get(side))
+        {
+            // there were saved histogram positions
+            ser_transform.clone().into()
+        } else {
+            // histogram perpendicular to the direction of the arrow
+            // the arrow direction is decided by a fallible heuristic!
+            let mut transform = Transform::from_xyz(trans.translation.x, trans.translation.y, 0.5)
+                .with_rotation(Quat::from_rotation_z(rotation_90));
+            transform.translation.x += arrow.direction.perp().x * away;
+            transform.translation.y += arrow.direction.perp().y * away;
+            transform
+        };
+        let height = arrow
+            .hist_heights
+            .as_ref()
+            .and_then(|heights| heights.get(side))
+            .copied()
+            .unwrap_or(1.);
+        anchors.push((arrow.id.clone(), arrow.node_id, size, transform, height));
+    }
+    anchors
+}
+
 /// Build axes for histograms, summarising all external information.
-/// Each Side of an arrow is assigned a different axis, shared across conditions.
+/// Each Side of an arrow or a metabolite is assigned a different axis, shared
+/// across conditions.
 fn build_axes(
     mut commands: Commands,
-    mut query: Query<(&Transform, &ArrowTag, &Path)>,
+    query: Query<(&Transform, &ArrowTag, &Path)>,
+    circles: Query<(&Transform, &CircleTag, &Editable, Has<Secondary>)>,
     mut aes_query: Query<
-        (&Distribution<f32>, &Aesthetics, &mut GeomHist),
+        (
+            &Distribution<f32>,
+            &Aesthetics,
+            &mut GeomHist,
+            &AesFilter,
+            Option<&ColumnName>,
+        ),
         (With<Gy>, Without<PopUp>),
     >,
+    ui_state: Res<UiState>,
 ) {
-    let mut axes: HashMap<String, HashMap<Side, (Xaxis, Transform)>> = HashMap::new();
-    let mut means: HashMap<Side, Vec<f32>> = HashMap::new();
+    let mut axes: HashMap<(String, bool), HashMap<Side, (Xaxis, Transform)>> = HashMap::new();
+    let mut means: HashMap<(Side, bool), Vec<f32>> = HashMap::new();
     // first gather all x-limits for different conditions and the arrow and side
-    for (dist, aes, mut geom) in aes_query.iter_mut() {
+    for (dist, aes, mut geom, filter, column) in aes_query.iter_mut() {
         if geom.in_axis {
             continue;
         }
-        means.entry(geom.side.clone()).or_default().push(
-            dist.0
-                .iter()
-                .map(|cloud| cloud.iter().sum::<f32>() / cloud.len() as f32)
-                .sum::<f32>()
-                / dist.0.len() as f32,
-        );
-        let xlimits = (
-            min_f32(&dist.0.iter().map(|x| min_f32(x)).collect::<Vec<f32>>()),
-            max_f32(&dist.0.iter().map(|x| max_f32(x)).collect::<Vec<f32>>()),
-        );
-        for (trans, arrow, path) in query.iter_mut() {
-            if aes.identifiers.iter().any(|r| r == &arrow.id) {
-                let size = path_to_vec(path).length();
-                let (rotation_90, away) = match geom.side {
-                    Side::Right => (-Vec2::Y.angle_between(arrow.direction.perp()), -30.),
-                    Side::Left => (-Vec2::NEG_Y.angle_between(arrow.direction.perp()), 30.),
-                    _ => {
-                        warn!("Tried to plot Up direction for non-popup '{}'", arrow.id);
-                        continue;
-                    }
-                };
-                let transform: Transform = if let Some(Some(ser_transform)) =
-                    arrow.hists.as_ref().map(|x| x.get(&geom.side))
-                {
-                    // there were saved histogram positions
-                    ser_transform.clone().into()
-                } else {
-                    // histogram perpendicular to the direction of the arrow
-                    // the arrow direction is decided by a fallible heuristic!
-                    let mut transform =
-                        Transform::from_xyz(trans.translation.x, trans.translation.y, 0.5)
-                            .with_rotation(Quat::from_rotation_z(rotation_90));
-                    transform.translation.x += arrow.direction.perp().x * away;
-                    transform.translation.y += arrow.direction.perp().y * away;
-                    transform
-                };
-                let axis_entry = axes
-                    .entry(arrow.id.clone())
-                    .or_default()
-                    .entry(geom.side.clone())
-                    .or_insert((
-                        Xaxis {
-                            id: arrow.id.clone(),
-                            arrow_size: size,
-                            xlimits,
-                            side: geom.side.clone(),
-                            plot: geom.plot.clone(),
-                            node_id: arrow.node_id,
-                            conditions: Vec::new(),
-                        },
-                        transform,
-                    ));
-                axis_entry.0.xlimits = (
-                    f32::min(axis_entry.0.xlimits.0, xlimits.0),
-                    f32::max(axis_entry.0.xlimits.1, xlimits.1),
-                );
+        means
+            .entry((geom.side.clone(), filter.met))
+            .or_default()
+            .push(
+                dist.0
+                    .iter()
+                    .map(|cloud| cloud.iter().sum::<f32>() / cloud.len() as f32)
+                    .sum::<f32>()
+                    / dist.0.len() as f32,
+            );
+        // streamed over the samples since sampling chains can be very long
+        let xlimits = dist.0.iter().fold((0f32, 0f32), |(min, max), samples| {
+            (min.min(min_f32(samples)), max.max(max_f32(samples)))
+        });
+        let anchors = if filter.met {
+            met_anchors(&aes.identifiers, &geom.side, &circles)
+        } else {
+            arrow_anchors(&aes.identifiers, &geom.side, &query)
+        };
+        for (id, node_id, size, transform, height) in anchors {
+            let axis_entry = axes
+                .entry((id.clone(), filter.met))
+                .or_default()
+                .entry(geom.side.clone())
+                .or_insert((
+                    Xaxis {
+                        id,
+                        arrow_size: size,
+                        xlimits,
+                        own_xlimits: xlimits,
+                        side: geom.side.clone(),
+                        plot: geom.plot.clone(),
+                        node_id,
+                        conditions: Vec::new(),
+                        titles: Vec::new(),
+                        flat: false,
+                        height,
+                        met: filter.met,
+                    },
+                    transform,
+                ));
+            axis_entry.0.xlimits = (
+                f32::min(axis_entry.0.xlimits.0, xlimits.0),
+                f32::max(axis_entry.0.xlimits.1, xlimits.1),
+            );
 
-                if let Some(cond) = aes.condition.as_ref() {
-                    axis_entry.0.conditions.push(cond.clone());
-                }
-                geom.in_axis = true;
+            if let Some(cond) = aes.condition.as_ref() {
+                axis_entry.0.conditions.push(cond.clone());
+            }
+            if let Some(column) = column {
+                axis_entry.0.add_title(&column.0);
             }
+            geom.in_axis = true;
         }
     }
-    for (_, _, mut geom) in aes_query.iter_mut() {
-        if let Some(side_means) = means.get(&geom.side) {
+    for (_, _, mut geom, filter, _) in aes_query.iter_mut() {
+        if let Some(side_means) = means.get(&(geom.side.clone(), filter.met)) {
             geom.mean = Some(side_means.iter().sum::<f32>() / side_means.len() as f32);
         }
     }
 
-    for (axis, trans) in axes.into_values().flat_map(|side| side.into_values()) {
-        let size = axis.arrow_size;
+    for (mut axis, trans) in axes.into_values().flat_map(|side| side.into_values()) {
+        axis.flat = widen_degenerate(&mut axis.xlimits);
+        axis.own_xlimits = axis.xlimits;
+        let size = ui_state.axis_length(axis.arrow_size);
         commands.spawn((axis, Drag::default(), plot_line(size, trans)));
     }
 }
@@ -334,65 +1092,53 @@ fn build_axes(
 /// Build axis.
 fn build_point_axes(
     mut commands: Commands,
-    mut query: Query<(&Transform, &ArrowTag, &Path)>,
+    query: Query<(&Transform, &ArrowTag, &Path)>,
+    circles: Query<(&Transform, &CircleTag, &Editable, Has<Secondary>)>,
     mut aes_query: Query<
-        (&Aesthetics, &mut GeomHist),
+        (&Aesthetics, &mut GeomHist, &AesFilter, Option<&ColumnName>),
         (With<Gy>, Without<PopUp>, With<Point<f32>>),
     >,
 ) {
-    let mut axes: HashMap<String, HashMap<Side, (Xaxis, Transform)>> = HashMap::new();
+    let mut axes: HashMap<(String, bool), HashMap<Side, (Xaxis, Transform)>> = HashMap::new();
     // first gather all x-limits for different conditions and the arrow and side
-    for (aes, mut geom) in aes_query.iter_mut() {
+    for (aes, mut geom, filter, column) in aes_query.iter_mut() {
         if geom.in_axis {
             continue;
         }
-        for (trans, arrow, path) in query.iter_mut() {
-            if aes.identifiers.iter().any(|r| r == &arrow.id) {
-                let size = path_to_vec(path).length();
-                let (rotation_90, away) = match geom.side {
-                    Side::Right => (-Vec2::Y.angle_between(arrow.direction.perp()), -30.),
-                    Side::Left => (-Vec2::NEG_Y.angle_between(arrow.direction.perp()), 30.),
-                    _ => {
-                        warn!("Tried to plot Up direction for non-popup '{}'", arrow.id);
-                        continue;
-                    }
-                };
-                let transform: Transform = if let Some(Some(ser_transform)) =
-                    arrow.hists.as_ref().map(|x| x.get(&geom.side))
-                {
-                    // there were saved histogram positions
-                    ser_transform.clone().into()
-                } else {
-                    // histogram perpendicular to the direction of the arrow
-                    // the arrow direction is decided by a fallible heuristic!
-                    let mut transform =
-                        Transform::from_xyz(trans.translation.x, trans.translation.y, 0.5)
-                            .with_rotation(Quat::from_rotation_z(rotation_90));
-                    transform.translation.x += arrow.direction.perp().x * away;
-                    transform.translation.y += arrow.direction.perp().y * away;
-                    transform
-                };
-                let axis_entry = axes
-                    .entry(arrow.id.clone())
-                    .or_default()
-                    .entry(geom.side.clone())
-                    .or_insert((
-                        Xaxis {
-                            id: arrow.id.clone(),
-                            arrow_size: size,
-                            xlimits: (0., 0.),
-                            side: geom.side.clone(),
-                            plot: geom.plot.clone(),
-                            node_id: arrow.node_id,
-                            conditions: Vec::new(),
-                        },
-                        transform,
-                    ));
-                if let Some(cond) = aes.condition.as_ref() {
-                    axis_entry.0.conditions.push(cond.clone());
-                }
-                geom.in_axis = true;
+        let anchors = if filter.met {
+            met_anchors(&aes.identifiers, &geom.side, &circles)
+        } else {
+            arrow_anchors(&aes.identifiers, &geom.side, &query)
+        };
+        for (id, node_id, size, transform, _) in anchors {
+            let axis_entry = axes
+                .entry((id.clone(), filter.met))
+                .or_default()
+                .entry(geom.side.clone())
+                .or_insert((
+                    Xaxis {
+                        id,
+                        arrow_size: size,
+                        xlimits: (0., 0.),
+                        own_xlimits: (0., 0.),
+                        side: geom.side.clone(),
+                        plot: geom.plot.clone(),
+                        node_id,
+                        conditions: Vec::new(),
+                        titles: Vec::new(),
+                        flat: false,
+                        height: 1.,
+                        met: filter.met,
+                    },
+                    transform,
+                ));
+            if let Some(cond) = aes.condition.as_ref() {
+                axis_entry.0.conditions.push(cond.clone());
+            }
+            if let Some(column) = column {
+                axis_entry.0.add_title(&column.0);
             }
+            geom.in_axis = true;
         }
     }
 
@@ -438,25 +1184,230 @@ fn build_hover_axes(
             }
         }
     }
-
-    for (node_id, xlimits) in axes {
-        for mut hover in query.iter_mut().filter(|h| h.node_id == node_id) {
-            hover.xlimits = Some(xlimits)
-        }
+
+    for (node_id, mut xlimits) in axes {
+        widen_degenerate(&mut xlimits);
+        for mut hover in query.iter_mut().filter(|h| h.node_id == node_id) {
+            hover.xlimits = Some(xlimits)
+        }
+    }
+}
+
+/// Font size of the titles of the axes.
+const AXIS_TITLE_SIZE: f32 = 25.;
+
+/// Write the names of the data columns under the axes of the histograms and boxes.
+fn plot_axis_titles(
+    mut commands: Commands,
+    ui_state: Res<UiState>,
+    theme: Res<Theme>,
+    units: Res<Units>,
+    map_font: Res<MapFont>,
+    axes: Query<(Entity, &Xaxis), Added<Xaxis>>,
+) {
+    let font = map_font.handle.clone();
+    for (entity, axis) in axes.iter().filter(|(_, axis)| !axis.titles.is_empty()) {
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        axis_title(&axis.titles, &units),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: AXIS_TITLE_SIZE,
+                            color: theme.text(),
+                        },
+                    )
+                    .with_justify(JustifyText::Center),
+                    transform: Transform::from_xyz(0., -AXIS_TITLE_SIZE, 0.2),
+                    // the axis line is hidden unless the axes are shown, so the
+                    // visibility of the title is set explicitly instead of inherited
+                    visibility: if ui_state.axis_titles {
+                        Visibility::Visible
+                    } else {
+                        Visibility::Hidden
+                    },
+                    ..default()
+                },
+                DefaultFontSize {
+                    size: AXIS_TITLE_SIZE,
+                },
+                AxisTitle,
+                IgnoreSave,
+            ));
+        });
+    }
+}
+
+/// Titles of the columns plotted in an axis, with their units.
+fn axis_title(titles: &[String], units: &Units) -> String {
+    titles.iter().map(|title| units.label(title)).join(", ")
+}
+
+/// Toggle the axis titles, write the units edited in the settings and keep
+/// them from being stretched with their axis.
+fn show_axis_titles(
+    ui_state: Res<UiState>,
+    units: Res<Units>,
+    axes: Query<(&Transform, &Xaxis), Without<AxisTitle>>,
+    mut titles: Query<(&mut Transform, &mut Visibility, &mut Text, &Parent), With<AxisTitle>>,
+) {
+    for (mut trans, mut vis, mut text, parent) in titles.iter_mut() {
+        if ui_state.is_changed() {
+            *vis = if ui_state.axis_titles {
+                Visibility::Visible
+            } else {
+                Visibility::Hidden
+            };
+        }
+        if let Ok((axis_trans, axis)) = axes.get(parent.get()) {
+            let scale_x = 1. / axis_trans.scale.x;
+            if trans.scale.x != scale_x {
+                trans.scale.x = scale_x;
+            }
+            if units.is_changed() {
+                text.sections[0].value = axis_title(&axis.titles, &units);
+            }
+        }
+    }
+}
+
+/// Test the difference between the selected pair of conditions for the
+/// distributions of every axis and write the result under it.
+fn plot_significance(
+    mut commands: Commands,
+    mut last: Local<Option<(Option<stats::SigTest>, (String, String), bool)>>,
+    ui_state: Res<UiState>,
+    theme: Res<Theme>,
+    map_font: Res<MapFont>,
+    new_axes: Query<(), Added<Xaxis>>,
+    axes: Query<(Entity, &Xaxis, &Transform), Without<SigLabel>>,
+    dists: Query<
+        (&Distribution<f32>, Option<&Weights>, &Aesthetics, &GeomHist),
+        (With<Gy>, Without<PopUp>),
+    >,
+    mut labels: Query<(Entity, &mut Transform, &Parent), With<SigLabel>>,
+) {
+    let settings = (
+        ui_state.sig_test,
+        ui_state.sig_conditions.clone(),
+        ui_state.sig_pvalues,
+    );
+    // UiState is changed every frame the GUI is shown
+    if last.as_ref() == Some(&settings) && new_axes.is_empty() {
+        for (_, mut trans, parent) in labels.iter_mut() {
+            if let Ok((_, _, axis_trans)) = axes.get(parent.get()) {
+                trans.scale.x = 1. / axis_trans.scale.x;
+            }
+        }
+        return;
+    }
+    *last = Some(settings);
+    for (entity, _, _) in labels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(test) = ui_state.sig_test else {
+        return;
+    };
+    let (cond_a, cond_b) = &ui_state.sig_conditions;
+    if cond_a == cond_b {
+        return;
+    }
+    let font = map_font.handle.clone();
+    let mut weighted = 0;
+    for (entity, axis, axis_trans) in axes.iter() {
+        let mut samples: (Option<&Vec<f32>>, Option<&Vec<f32>>) = (None, None);
+        let mut has_weights = false;
+        for (dist, weights, aes, _) in dists
+            .iter()
+            .filter(|(_, _, _, geom)| geom.side == axis.side)
+        {
+            let Some(index) = aes.identifiers.iter().position(|r| r == &axis.id) else {
+                continue;
+            };
+            let is_weighted = weights
+                .and_then(|w| w.0.get(index))
+                .is_some_and(|w| !w.is_empty());
+            match aes.condition.as_ref() {
+                Some(c) if c == cond_a => samples.0 = dist.0.get(index),
+                Some(c) if c == cond_b => samples.1 = dist.0.get(index),
+                _ => continue,
+            }
+            has_weights |= is_weighted;
+        }
+        let (Some(a), Some(b)) = samples else {
+            continue;
+        };
+        // the tests treat every sample the same, which would be misleading
+        if has_weights {
+            weighted += 1;
+            continue;
+        }
+        let Some(p) = test.p_value(a, b) else {
+            continue;
+        };
+        let label = if ui_state.sig_pvalues {
+            format!("p = {p:.2e}")
+        } else {
+            stats::stars(p).to_string()
+        };
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        label,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: AXIS_TITLE_SIZE,
+                            color: theme.text(),
+                        },
+                    )
+                    .with_justify(JustifyText::Center),
+                    transform: Transform::from_xyz(0., -2. * AXIS_TITLE_SIZE, 0.2)
+                        .with_scale(Vec3::new(1. / axis_trans.scale.x, 1., 1.)),
+                    // shown even if the axis line is hidden, which it is by default
+                    visibility: Visibility::Visible,
+                    ..default()
+                },
+                DefaultFontSize {
+                    size: AXIS_TITLE_SIZE,
+                },
+                SigLabel,
+            ));
+        });
+    }
+    if weighted > 0 {
+        warn!("Significance not tested for {weighted} axes with weighted samples.");
     }
 }
 
+/// Distance between the error bars of the conditions sharing an axis.
+const ERROR_BAR_GAP: f32 = 14.;
+
 /// Plot histogram as numerical variable next to arrows.
 fn plot_side_hist(
     mut commands: Commands,
     mut z_eps: Local<f32>,
     mut aes_query: Query<
-        (&Distribution<f32>, &Aesthetics, &mut GeomHist, &AesFilter),
+        (
+            &Distribution<f32>,
+            Option<&Weights>,
+            &Aesthetics,
+            &mut GeomHist,
+            &AesFilter,
+            Option<&Layer>,
+        ),
         (With<Gy>, Without<PopUp>),
     >,
     query: Query<(&Transform, &Xaxis)>,
+    mut ui_state: ResMut<UiState>,
+    registry: Res<GeomRegistry>,
 ) {
-    'outer: for (dist, aes, mut geom, is_met) in aes_query.iter_mut() {
+    // the whiskers are plotted instead by plot_whiskers
+    if ui_state.whiskers {
+        return;
+    }
+    'outer: for (dist, weights, aes, mut geom, is_met, layer) in aes_query.iter_mut() {
         if geom.rendered {
             continue;
         }
@@ -467,43 +1418,88 @@ fn plot_side_hist(
             if let Some(index) = aes
                 .identifiers
                 .iter()
-                .position(|r| (r == &axis.id) & (geom.side == axis.side))
+                .position(|r| (r == &axis.id) & (geom.side == axis.side) & (is_met.met == axis.met))
             {
                 let this_dist = match dist.0.get(index) {
                     Some(d) => d,
                     None => continue,
                 };
+                let this_weights = weights.and_then(|w| w.0.get(index)).map(|w| w.as_slice());
                 let line = match geom.plot {
-                    HistPlot::Hist => plot_hist(this_dist, 160, axis.arrow_size, axis.xlimits),
-                    HistPlot::Kde => plot_kde(this_dist, 100, axis.arrow_size, axis.xlimits),
+                    HistPlot::Hist => {
+                        let (samples, weights) =
+                            stats::subsample(this_dist, this_weights, ui_state.max_samples);
+                        plot_hist(
+                            &samples,
+                            weights.as_deref(),
+                            ui_state.hist_bins,
+                            ui_state.axis_length(axis.arrow_size),
+                            axis.xlimits,
+                        )
+                    }
+                    HistPlot::Kde => {
+                        let (samples, weights) =
+                            stats::subsample(this_dist, this_weights, ui_state.max_samples);
+                        plot_kde(
+                            &samples,
+                            weights.as_deref(),
+                            100,
+                            ui_state.axis_length(axis.arrow_size),
+                            axis.xlimits,
+                            ui_state.kde_kernel,
+                            ui_state.kde_bandwidth,
+                        )
+                    }
                     HistPlot::BoxPoint => {
                         warn!("Tried to plot a BoxPoint from a Distributions. Not Implemented! Consider using a Point as input");
                         None
                     }
-                };
-                let Some(line) = line else { continue 'outer };
-                let hex = match geom.side {
-                    // the color is updated by another system given the settings
-                    Side::Right => "7dce9688",
-                    Side::Left => "DA968788",
-                    _ => {
-                        warn!("Tried to plot Up direction for non-popup '{}'", axis.id);
-                        continue;
+                    HistPlot::ErrorBar => {
+                        // stack the conditions sharing the axis
+                        let row = aes
+                            .condition
+                            .as_ref()
+                            .and_then(|c| axis.conditions.iter().position(|x| x == c))
+                            .unwrap_or(0);
+                        plot_error_bar(
+                            this_dist,
+                            this_weights,
+                            ui_state.error_bar,
+                            ui_state.axis_length(axis.arrow_size),
+                            axis.xlimits,
+                            ERROR_BAR_GAP * (row + 1) as f32,
+                        )
                     }
+                    HistPlot::Custom(name) => plot_custom(
+                        &registry,
+                        name,
+                        this_dist,
+                        this_weights,
+                        ui_state.axis_length(axis.arrow_size),
+                        axis.xlimits,
+                    ),
                 };
+                let Some(line) = line else { continue 'outer };
+                if geom.side == Side::Up {
+                    warn!("Tried to plot Up direction for non-popup '{}'", axis.id);
+                    continue;
+                }
+                // kept in sync with the settings by normalize_histogram_height
+                let color = ui_state.side_color(&geom.side, aes.condition.as_deref());
 
-                commands.spawn((
+                let mut hist = commands.spawn((
                     ShapeBundle {
                         path: GeometryBuilder::build_as(&line),
                         // increment z to avoid flickering problems
                         spatial: SpatialBundle {
-                            transform: trans
-                                .with_translation(trans.translation + Vec3::new(0., 0., *z_eps)),
+                            transform: trans.with_translation(
+                                trans.translation + Vec3::new(0., 0., *z_eps + layer_z(layer)),
+                            ),
                             ..default()
                         },
                         ..default()
                     },
-                    Fill::color(Color::hex(hex).unwrap()),
+                    Fill::color(color),
                     VisCondition {
                         condition: aes.condition.clone(),
                     },
@@ -514,43 +1510,159 @@ fn plot_side_hist(
                     },
                     (*is_met).clone(),
                 ));
+                if matches!(geom.plot, HistPlot::ErrorBar) {
+                    hist.insert(ErrorBarTag);
+                }
             }
             geom.rendered = true;
         }
     }
 }
 
+/// Length of the whiskers spanning all the values of a side.
+const WHISKER_SIZE: f32 = 60.;
+/// Distance along the arrow between the whiskers of both sides and its center.
+const WHISKER_GAP: f32 = 5.;
+
+/// Plot the 95% interval and the mean of the side distributions as whiskers
+/// across the center of the arrows, instead of [`plot_side_hist`].
+///
+/// All the whiskers of a side share the scale so that they can be compared.
+fn plot_whiskers(
+    mut commands: Commands,
+    mut ui_state: ResMut<UiState>,
+    mut aes_query: Query<
+        (&Distribution<f32>, &Aesthetics, &mut GeomHist, &AesFilter),
+        (With<Gy>, Without<PopUp>),
+    >,
+    arrows: Query<(&Transform, &ArrowTag), With<Path>>,
+) {
+    if !ui_state.whiskers || aes_query.iter().all(|(_, _, geom, _)| geom.rendered) {
+        return;
+    }
+    let mut limits: HashMap<Side, (f32, f32)> = HashMap::new();
+    for (dist, _, geom, _) in aes_query.iter() {
+        let entry = limits
+            .entry(geom.side.clone())
+            .or_insert((f32::INFINITY, f32::NEG_INFINITY));
+        for samples in dist.0.iter() {
+            entry.0 = entry.0.min(min_f32(samples));
+            entry.1 = entry.1.max(max_f32(samples));
+        }
+    }
+    for limits in limits.values_mut() {
+        widen_degenerate(limits);
+    }
+    for (dist, aes, mut geom, filter) in aes_query.iter_mut() {
+        if geom.rendered {
+            continue;
+        }
+        geom.rendered = true;
+        let gap = match geom.side {
+            Side::Right => WHISKER_GAP,
+            Side::Left => -WHISKER_GAP,
+            _ => continue,
+        };
+        let color = ui_state
+            .side_color(&geom.side, aes.condition.as_deref())
+            .with_a(1.);
+        if filter.met {
+            continue;
+        }
+        let (min, max) = limits[&geom.side];
+        let x = |value: f32| lerp(value, min, max, -WHISKER_SIZE / 2., WHISKER_SIZE / 2.);
+        for (trans, arrow) in arrows.iter() {
+            let Some(samples) = aes
+                .identifiers
+                .iter()
+                .position(|r| r == &arrow.id)
+                .and_then(|i| dist.0.get(i))
+            else {
+                continue;
+            };
+            let pos = trans.translation.truncate() + arrow.direction * gap;
+            let rotation = Vec2::X.angle_between(arrow.direction.perp());
+            commands.spawn((
+                ShapeBundle {
+                    path: plot_whisker(
+                        x(stats::quantile(samples, 0.025)),
+                        x(stats::mean(samples)),
+                        x(stats::quantile(samples, 0.975)),
+                    ),
+                    spatial: SpatialBundle::from_transform(
+                        Transform::from_xyz(pos.x, pos.y, 5.)
+                            .with_rotation(Quat::from_rotation_z(rotation)),
+                    ),
+                    ..default()
+                },
+                Stroke::new(color, 3.),
+                VisCondition {
+                    condition: aes.condition.clone(),
+                },
+                WhiskerTag {
+                    node_id: arrow.node_id,
+                    side: geom.side.clone(),
+                },
+            ));
+        }
+    }
+}
+
+/// Plot a geom of the [`GeomRegistry`], warning if it was never registered.
+fn plot_custom(
+    registry: &GeomRegistry,
+    name: &str,
+    samples: &[f32],
+    weights: Option<&[f32]>,
+    size: f32,
+    xlimits: (f32, f32),
+) -> Option<Path> {
+    let Some(plot) = registry.get(name) else {
+        warn!("Tried to plot the custom geom '{name}', which is not registered.");
+        return None;
+    };
+    plot(samples, weights, size, xlimits)
+}
+
 fn plot_side_box(
     mut commands: Commands,
     ui_state: Res<UiState>,
     mut aes_query: Query<
-        (&Point<f32>, &Aesthetics, &mut GeomHist, &AesFilter),
+        (
+            &Point<f32>,
+            &Aesthetics,
+            &mut GeomHist,
+            &AesFilter,
+            Option<&Layer>,
+        ),
         (With<Gy>, Without<PopUp>),
     >,
     mut query: Query<(&mut Transform, &Xaxis), With<Unscale>>,
 ) {
-    for (colors, aes, mut geom, is_box) in aes_query.iter_mut() {
+    for (colors, aes, mut geom, is_box, layer) in aes_query.iter_mut() {
         if geom.rendered {
             continue;
         }
         let min_val = min_f32(&colors.0);
         let max_val = max_f32(&colors.0);
+        let geom_name = if is_box.met { "Metabolite" } else { "Reaction" };
+        let (min_color, max_color) = ui_state.color_scale(geom_name, aes.condition.as_deref());
         let grad = build_grad(
             ui_state.zero_white,
             min_val,
             max_val,
-            &ui_state.min_reaction_color,
-            &ui_state.max_reaction_color,
+            &min_color,
+            &max_color,
         );
 
         for (mut trans, axis) in query.iter_mut() {
             if let Some(index) = aes
                 .identifiers
                 .iter()
-                .position(|r| (r == &axis.id) & (geom.side == axis.side))
+                .position(|r| (r == &axis.id) & (geom.side == axis.side) & (is_box.met == axis.met))
             {
                 match geom.plot {
-                    HistPlot::Hist | HistPlot::Kde => {
+                    HistPlot::Hist | HistPlot::Kde | HistPlot::ErrorBar | HistPlot::Custom(_) => {
                         warn!(
                             "Tried to plot a distribution from one point. Coercing to a Box Point!"
                         );
@@ -560,6 +1672,9 @@ fn plot_side_box(
                 let color = from_grad_clamped(&grad, colors.0[index], min_val, max_val);
 
                 trans.translation.z += 10.;
+                let box_trans = trans
+                    .with_scale(Vec3::new(1., 1., 1.))
+                    .with_translation(trans.translation + Vec3::new(0., 0., layer_z(layer)));
                 let shape = if f32::abs(colors.0[index]) > 1e-7 {
                     let line_box = plot_box_point(
                         axis.conditions.len(),
@@ -572,7 +1687,7 @@ fn plot_side_box(
                         ShapeBundle {
                             path: GeometryBuilder::build_as(&line_box),
                             spatial: SpatialBundle {
-                                transform: trans.with_scale(Vec3::new(1., 1., 1.)),
+                                transform: box_trans,
                                 ..default()
                             },
                             ..default()
@@ -601,7 +1716,7 @@ fn plot_side_box(
                         ShapeBundle {
                             path: GeometryBuilder::build_as(&shape),
                             spatial: SpatialBundle {
-                                transform: trans.with_scale(Vec3::new(1., 1., 1.)),
+                                transform: box_trans,
                                 ..default()
                             },
                             ..default()
@@ -634,25 +1749,246 @@ fn plot_side_box(
     }
 }
 
+/// Distance between the gauges of ΔG and the center of their arrows.
+const THERMO_AWAY: f32 = 70.;
+/// Length of the gauges of ΔG.
+const THERMO_SIZE: f32 = 80.;
+
+/// Plot the ΔG of the reactions as gauges next to their arrows, colored by
+/// whether the reaction is favorable, near equilibrium or unfavorable.
+///
+/// All gauges of the same entity share the same scale, centered at zero.
+fn plot_thermo(
+    mut commands: Commands,
+    ui_state: Res<UiState>,
+    mut last_threshold: Local<Option<f32>>,
+    mut aes_query: Query<(&Point<f32>, &Uncertainty, &Aesthetics, &mut GeomThermo)>,
+    arrows: Query<(&Transform, &ArrowTag), With<Path>>,
+    gauges: Query<Entity, With<ThermoTag>>,
+) {
+    if *last_threshold != Some(ui_state.thermo_threshold) {
+        for e in gauges.iter() {
+            commands.entity(e).despawn_recursive();
+        }
+        for (_, _, _, mut geom) in aes_query.iter_mut() {
+            geom.plotted = false;
+        }
+        *last_threshold = Some(ui_state.thermo_threshold);
+    }
+    for (dgs, stds, aes, mut geom) in aes_query.iter_mut() {
+        if geom.plotted {
+            continue;
+        }
+        let range = dgs
+            .0
+            .iter()
+            .zip(stds.0.iter())
+            .map(|(dg, std)| dg.abs() + 2. * std)
+            .fold(ui_state.thermo_threshold, f32::max)
+            .max(f32::EPSILON);
+        for (trans, arrow) in arrows.iter() {
+            let Some(index) = aes.identifiers.iter().position(|r| r == &arrow.id) else {
+                continue;
+            };
+            let (dg, std) = (dgs.0[index], stds.0[index]);
+            let (track, marker) = plot_gauge(dg, std, range, THERMO_SIZE);
+            let color = Favorability::classify(dg, std, ui_state.thermo_threshold).color();
+            let pos = trans.translation.truncate() + arrow.direction.perp() * THERMO_AWAY;
+            let transform = Transform::from_xyz(pos.x, pos.y, 5.);
+            for (path, fill, stroke) in [
+                (track, None, Some(Stroke::new(Color::GRAY, 2.))),
+                (marker, Some(Fill::color(color)), None),
+            ] {
+                let mut gauge = commands.spawn((
+                    ShapeBundle {
+                        path: GeometryBuilder::build_as(&path),
+                        spatial: SpatialBundle::from_transform(transform),
+                        ..default()
+                    },
+                    VisCondition {
+                        condition: aes.condition.clone(),
+                    },
+                    ThermoTag {
+                        node_id: arrow.node_id,
+                    },
+                ));
+                if let Some(fill) = fill {
+                    gauge.insert(fill);
+                }
+                if let Some(stroke) = stroke {
+                    gauge.insert(stroke);
+                }
+            }
+        }
+        geom.plotted = true;
+    }
+}
+
+/// Distance between the stacked bars and the center of their arrows, on the
+/// opposite side of the gauges of ΔG.
+const STACK_AWAY: f32 = 70.;
+/// Length of the stacked bar with the largest total.
+const STACK_SIZE: f32 = 80.;
+const STACK_HEIGHT: f32 = 12.;
+
+/// Plot the [`Parts`] of the reactions as bars next to their arrows, stacked
+/// and colored by category. Negative parts are not shown.
+fn plot_stack(
+    mut commands: Commands,
+    mut aes_query: Query<(&Parts, &Aesthetics, &mut GeomStack)>,
+    all_parts: Query<&Parts>,
+    arrows: Query<(&Transform, &ArrowTag), With<Path>>,
+) {
+    if aes_query.iter().all(|(_, _, geom)| geom.plotted) {
+        return;
+    }
+    // shared with the pies so that the legend applies to both
+    let categories = categories(all_parts.iter());
+    for (parts, aes, mut geom) in aes_query.iter_mut() {
+        if geom.plotted {
+            continue;
+        }
+        geom.plotted = true;
+        let max_total = parts
+            .0
+            .iter()
+            .map(|parts| parts.iter().map(|(_, v)| v.max(0.)).sum::<f32>())
+            .fold(0f32, f32::max);
+        if max_total <= 0. {
+            continue;
+        }
+        for (trans, arrow) in arrows.iter() {
+            let Some(index) = aes.identifiers.iter().position(|r| r == &arrow.id) else {
+                continue;
+            };
+            let pos = trans.translation.truncate() - arrow.direction.perp() * STACK_AWAY;
+            let mut start = pos.x - STACK_SIZE / 2.;
+            for (name, value) in parts.0[index].iter() {
+                let width = value.max(0.) / max_total * STACK_SIZE;
+                if width <= 0. {
+                    continue;
+                }
+                let color = category_color(categories.iter().position(|c| c == name).unwrap_or(0));
+                commands.spawn((
+                    ShapeBundle {
+                        path: GeometryBuilder::build_as(&shapes::Rectangle {
+                            extents: Vec2::new(width, STACK_HEIGHT),
+                            origin: shapes::RectangleOrigin::BottomLeft,
+                        }),
+                        spatial: SpatialBundle::from_transform(Transform::from_xyz(
+                            start, pos.y, 5.,
+                        )),
+                        ..default()
+                    },
+                    Fill::color(color),
+                    Stroke::new(Color::WHITE, 1.),
+                    VisCondition {
+                        condition: aes.condition.clone(),
+                    },
+                    StackTag {
+                        node_id: arrow.node_id,
+                    },
+                ));
+                start += width;
+            }
+        }
+    }
+}
+
+/// Distance between the center of the pies and the center of their circles.
+const PIE_AWAY: f32 = 40.;
+const PIE_RADIUS: f32 = 15.;
+
+/// Plot the [`Parts`] of the metabolites as pies next to their circles,
+/// starting at 12 o'clock and colored by category. Negative parts are not shown.
+fn plot_pie(
+    mut commands: Commands,
+    mut aes_query: Query<(&Parts, &Aesthetics, &mut GeomPie)>,
+    all_parts: Query<&Parts>,
+    circles: Query<(&Transform, &CircleTag)>,
+) {
+    if aes_query.iter().all(|(_, _, geom)| geom.plotted) {
+        return;
+    }
+    let categories = categories(all_parts.iter());
+    for (parts, aes, mut geom) in aes_query.iter_mut() {
+        if geom.plotted {
+            continue;
+        }
+        geom.plotted = true;
+        for (trans, circle) in circles.iter() {
+            let Some(index) = aes.identifiers.iter().position(|r| r == &circle.id) else {
+                continue;
+            };
+            let total = parts.0[index].iter().map(|(_, v)| v.max(0.)).sum::<f32>();
+            if total <= 0. {
+                continue;
+            }
+            let pos = trans.translation.truncate() + Vec2::ONE.normalize() * PIE_AWAY;
+            let mut start = std::f32::consts::FRAC_PI_2;
+            for (name, value) in parts.0[index].iter() {
+                let sweep = value.max(0.) / total * std::f32::consts::TAU;
+                if sweep <= 0. {
+                    continue;
+                }
+                let color = category_color(categories.iter().position(|c| c == name).unwrap_or(0));
+                commands.spawn((
+                    ShapeBundle {
+                        path: plot_wedge(start, sweep, PIE_RADIUS),
+                        spatial: SpatialBundle::from_transform(Transform::from_xyz(
+                            pos.x, pos.y, 5.,
+                        )),
+                        ..default()
+                    },
+                    Fill::color(color),
+                    Stroke::new(Color::WHITE, 1.),
+                    VisCondition {
+                        condition: aes.condition.clone(),
+                    },
+                    PieTag {
+                        id: circle.id.clone(),
+                    },
+                ));
+                start -= sweep;
+            }
+        }
+    }
+}
+
 /// Plot hovered histograms of both metabolites and reactions.
 fn plot_hover_hist(
     mut commands: Commands,
+    mut ui_state: ResMut<UiState>,
+    units: Res<Units>,
     asset_server: Res<AssetServer>,
+    map_font: Res<MapFont>,
+    registry: Res<GeomRegistry>,
     mut z_eps: Local<f32>,
     mut query: Query<(&Transform, &Hover)>,
     mut aes_query: Query<
-        (&Distribution<f32>, &Aesthetics, &mut GeomHist, &AesFilter),
+        (
+            &Distribution<f32>,
+            Option<&Weights>,
+            Option<&Untransformed<Vec<f32>>>,
+            &Aesthetics,
+            &mut GeomHist,
+            &AesFilter,
+            Option<&Layer>,
+            Option<&ColumnName>,
+        ),
         (With<Gy>, With<PopUp>),
     >,
 ) {
-    'outer: for (dist, aes, mut geom, is_met) in aes_query.iter_mut() {
+    'outer: for (dist, weights, original, aes, mut geom, is_met, layer, column) in
+        aes_query.iter_mut()
+    {
         if geom.rendered {
             continue;
         }
         // we only need to differentiate the z-index between aes with different
         // conditions that could appear in the same axis
         *z_eps += 1e-6;
-        let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+        let font = map_font.handle.clone();
         for (trans, hover) in query.iter_mut() {
             if hover.xlimits.is_none() {
                 continue;
@@ -663,19 +1999,60 @@ fn plot_hover_hist(
                     None => continue,
                 };
                 let xlimits = hover.xlimits.as_ref().unwrap();
+                let this_weights = weights.and_then(|w| w.0.get(index)).map(|w| w.as_slice());
                 let line = match geom.plot {
-                    HistPlot::Hist => plot_hist(this_dist, 55, 600., *xlimits),
-                    HistPlot::Kde => plot_kde(this_dist, 80, 600., *xlimits),
+                    HistPlot::Hist => {
+                        let (samples, weights) =
+                            stats::subsample(this_dist, this_weights, ui_state.max_samples);
+                        plot_hist(
+                            &samples,
+                            weights.as_deref(),
+                            ui_state.hist_bins,
+                            600.,
+                            *xlimits,
+                        )
+                    }
+                    HistPlot::Kde => {
+                        let (samples, weights) =
+                            stats::subsample(this_dist, this_weights, ui_state.max_samples);
+                        plot_kde(
+                            &samples,
+                            weights.as_deref(),
+                            80,
+                            600.,
+                            *xlimits,
+                            ui_state.kde_kernel,
+                            ui_state.kde_bandwidth,
+                        )
+                    }
                     HistPlot::BoxPoint => {
                         warn!("Tried to plot a BoxPoint from a Distributions. Not Implemented! Consider using a Point as input");
                         None
                     }
+                    HistPlot::ErrorBar => {
+                        let row = aes
+                            .condition
+                            .as_ref()
+                            .and_then(|c| ui_state.conditions.iter().position(|x| x == c))
+                            .unwrap_or(0);
+                        plot_error_bar(
+                            this_dist,
+                            this_weights,
+                            ui_state.error_bar,
+                            600.,
+                            *xlimits,
+                            ERROR_BAR_GAP * (row + 1) as f32,
+                        )
+                    }
+                    HistPlot::Custom(name) => {
+                        plot_custom(&registry, name, this_dist, this_weights, 600., *xlimits)
+                    }
                 };
                 let Some(line) = line else { continue 'outer };
                 let transform = Transform::from_xyz(
                     trans.translation.x + 150.,
                     trans.translation.y + 150.,
-                    40. + *z_eps,
+                    40. + *z_eps + layer_z(layer),
                 );
                 let geometry = ShapeBundle {
                     path: GeometryBuilder::build_as(&line),
@@ -686,9 +2063,35 @@ fn plot_hover_hist(
                     },
                     ..default()
                 };
-                let fill = Fill::color(Color::hex("ffb73388").unwrap());
-                let scales = plot_scales(this_dist, 600., font.clone(), 12.);
-                commands
+                let fill = Fill::color(ui_state.side_color(&Side::Up, aes.condition.as_deref()));
+                let scale_samples = original
+                    .filter(|_| ui_state.show_original)
+                    .and_then(|o| o.0.get(index))
+                    .unwrap_or(this_dist);
+                let unit = column.map(|c| units.of(&c.0)).unwrap_or_default();
+                let scales = plot_scales(
+                    scale_samples,
+                    this_weights,
+                    600.,
+                    unit,
+                    ui_state.number_format,
+                    font.clone(),
+                    12. * ui_state.text_scale,
+                );
+                let key = [column.map(|c| c.0.as_str()), aes.condition.as_deref()]
+                    .into_iter()
+                    .flatten()
+                    .join(" · ");
+                // keys of different conditions in the same popup are stacked
+                let key_row = aes
+                    .condition
+                    .as_ref()
+                    .and_then(|c| ui_state.conditions.iter().position(|x| x == c))
+                    .unwrap_or(0);
+                let key_color = fill.color.with_a(1.);
+                let key_size = POPUP_KEY_SIZE * ui_state.text_scale;
+                let key_y = 140. - key_row as f32 * key_size * 1.2;
+                let popup = commands
                     .spawn((
                         HistTag {
                             side: geom.side.clone(),
@@ -701,32 +2104,175 @@ fn plot_hover_hist(
                     ))
                     .insert((geometry, fill))
                     .with_children(|p| {
-                        p.spawn(SpriteBundle {
-                            texture: asset_server.load("hover.png"),
-                            transform: Transform::from_xyz(0., 0., -0.4),
-                            ..default()
-                        });
+                        p.spawn((
+                            SpriteBundle {
+                                texture: asset_server.load("hover.png"),
+                                transform: Transform::from_xyz(0., 0., -0.4),
+                                ..default()
+                            },
+                            PopUpFrame,
+                        ));
+                    })
+                    .with_children(|parent| {
+                        parent.spawn((scales.x_0, IgnoreSave, PopUpFrame));
                     })
                     .with_children(|parent| {
-                        parent.spawn((scales.x_0, IgnoreSave));
+                        parent.spawn((scales.x_n, IgnoreSave, PopUpFrame));
                     })
                     .with_children(|parent| {
-                        parent.spawn((scales.x_n, IgnoreSave));
+                        parent.spawn((scales.y, IgnoreSave, PopUpFrame));
                     })
                     .with_children(|parent| {
-                        parent.spawn((scales.y, IgnoreSave));
+                        parent.spawn((
+                            Text2dBundle {
+                                text: Text::from_section(
+                                    key,
+                                    TextStyle {
+                                        font: font.clone(),
+                                        font_size: key_size,
+                                        color: key_color,
+                                    },
+                                ),
+                                text_anchor: Anchor::TopLeft,
+                                transform: Transform::from_xyz(-330., 0., 0.2),
+                                ..default()
+                            },
+                            PopUpKey { y: key_y },
+                            IgnoreSave,
+                        ));
                     })
-                    .insert((AnyTag { id: hover.node_id }, (*is_met).clone()));
+                    .insert((AnyTag { id: hover.node_id }, (*is_met).clone()))
+                    .insert(PopUpBase {
+                        y: transform.translation.y,
+                        key_y,
+                    })
+                    .id();
+                if matches!(geom.plot, HistPlot::ErrorBar) {
+                    commands.entity(popup).insert(ErrorBarTag);
+                }
             }
             geom.rendered = true;
         }
     }
 }
 
+/// Remove the plotted distributions when the KDE or histogram settings (or the
+/// values shown in the popups) change so that [`plot_side_hist`] and [`plot_hover_hist`] plot them again.
+fn replot_distributions(
+    mut commands: Commands,
+    ui_state: Res<UiState>,
+    mut last: Local<
+        Option<(
+            Kernel,
+            f32,
+            u32,
+            usize,
+            bool,
+            bool,
+            f32,
+            bool,
+            ErrorBar,
+            NumberFormat,
+            f32,
+        )>,
+    >,
+    mut aes_query: Query<&mut GeomHist, (With<Gy>, With<Distribution<f32>>)>,
+    hist_query: Query<Entity, Or<((With<HistTag>, Without<Unscale>), With<WhiskerTag>)>>,
+    mut axis_query: Query<(&mut Path, &Xaxis)>,
+) {
+    let current = (
+        ui_state.kde_kernel,
+        ui_state.kde_bandwidth,
+        ui_state.hist_bins,
+        ui_state.max_samples,
+        ui_state.show_original,
+        ui_state.fixed_hist_length,
+        ui_state.hist_length,
+        ui_state.whiskers,
+        ui_state.error_bar,
+        // the scales of the popups are written with them
+        ui_state.number_format,
+        ui_state.text_scale,
+    );
+    let Some(previous) = last.replace(current) else {
+        return;
+    };
+    if previous == current {
+        return;
+    }
+    for mut geom in aes_query.iter_mut() {
+        geom.rendered = false;
+    }
+    for entity in hist_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (mut path, axis) in axis_query.iter_mut() {
+        let (line, _) = plot_line(ui_state.axis_length(axis.arrow_size), Transform::default());
+        *path = line.path;
+    }
+}
+
+/// Set the x-limits of the histogram axes to the range of all the axes of their
+/// side if [`UiState::global_xlimits`] or back to their own values otherwise,
+/// replotting the side histograms if any limit changed.
+pub fn share_xlimits(
+    mut commands: Commands,
+    ui_state: Res<UiState>,
+    mut axis_query: Query<&mut Xaxis>,
+    mut aes_query: Query<&mut GeomHist, (With<Gy>, With<Distribution<f32>>, Without<PopUp>)>,
+    hist_query: Query<(Entity, &HistTag), Without<Unscale>>,
+) {
+    // the axes of the metabolites are shared apart from those of the reactions
+    let mut sides: HashMap<(Side, bool), (f32, f32)> = HashMap::new();
+    if ui_state.global_xlimits {
+        for axis in axis_query
+            .iter()
+            .filter(|axis| !matches!(axis.plot, HistPlot::BoxPoint))
+        {
+            let limits = sides
+                .entry((axis.side.clone(), axis.met))
+                .or_insert(axis.own_xlimits);
+            *limits = (
+                f32::min(limits.0, axis.own_xlimits.0),
+                f32::max(limits.1, axis.own_xlimits.1),
+            );
+        }
+    }
+    let mut changed = false;
+    for mut axis in axis_query.iter_mut() {
+        if matches!(axis.plot, HistPlot::BoxPoint) {
+            continue;
+        }
+        let xlimits = sides
+            .get(&(axis.side.clone(), axis.met))
+            .copied()
+            .unwrap_or(axis.own_xlimits);
+        if axis.xlimits != xlimits {
+            axis.xlimits = xlimits;
+            changed = true;
+        }
+    }
+    // whiskers do not depend on the axes, the histograms are replotted with
+    // the current limits once they are shown again
+    if !changed || ui_state.whiskers {
+        return;
+    }
+    for mut geom in aes_query.iter_mut() {
+        geom.rendered = false;
+    }
+    for (entity, hist) in hist_query.iter() {
+        if hist.side != Side::Up {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
 /// Normalize the height of histograms to be comparable with each other.
 /// It treats the two sides independently.
 fn normalize_histogram_height(
     mut ui_state: ResMut<UiState>,
+    axes: Query<&Xaxis>,
+    arrows: Query<(&ArrowTag, &Stroke), Without<HistTag>>,
     mut query: Query<
         (
             &mut Transform,
@@ -734,29 +2280,58 @@ fn normalize_histogram_height(
             &mut Fill,
             &HistTag,
             &VisCondition,
+            Has<ErrorBarTag>,
         ),
         Without<Unscale>,
     >,
 ) {
-    for (mut trans, path, mut fill, hist, condition) in query.iter_mut() {
+    let heights: HashMap<(u64, Side), f32> = axes
+        .iter()
+        .map(|axis| ((axis.node_id, axis.side.clone()), axis.height))
+        .collect();
+    let arrow_colors: HashMap<u64, Color> = if ui_state.hist_color_by_data {
+        arrows
+            .iter()
+            .map(|(arrow, stroke)| (arrow.node_id, stroke.color))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    for (mut trans, path, mut fill, hist, condition, error_bar) in query.iter_mut() {
         let height = max_f32(&path.0.iter().map(|ev| ev.to().y).collect::<Vec<f32>>());
-        trans.scale.y = match hist.side {
-            Side::Left => ui_state.max_left / height,
-            Side::Right => ui_state.max_right / height,
-            Side::Up => ui_state.max_top / height,
+        let factor = heights
+            .get(&(hist.node_id, hist.side.clone()))
+            .copied()
+            .unwrap_or(1.);
+        // flat paths would be scaled to infinity
+        if (height > 0.) & !error_bar {
+            trans.scale.y = factor
+                * match hist.side {
+                    Side::Left => ui_state.max_left / height,
+                    Side::Right => ui_state.max_right / height,
+                    Side::Up => ui_state.max_top / height,
+                };
+        }
+        let color = ui_state.side_color(&hist.side, condition.condition.as_deref());
+        fill.color = match arrow_colors.get(&hist.node_id) {
+            // the side histograms take the color of their arrow, keeping the transparency
+            Some(arrow_color) if hist.side != Side::Up => arrow_color.with_a(color.a()),
+            _ => color,
         };
-        let ui_condition = ui_state.condition.clone();
-        fill.color = {
-            let color_ref = match hist.side {
-                Side::Left => &mut ui_state.color_left,
-                Side::Right => &mut ui_state.color_right,
-                Side::Up => &mut ui_state.color_top,
-            };
-            let color = match condition.condition.as_ref() {
-                Some(cond) => or_color(cond, color_ref, true),
-                None => or_color(&ui_condition, color_ref, false),
-            };
-            Color::rgba_linear(color.r(), color.g(), color.b(), color.a())
+    }
+}
+
+/// Color the whiskers with the settings of their side and condition.
+fn color_whiskers(
+    mut ui_state: ResMut<UiState>,
+    mut whiskers: Query<(&mut Stroke, &WhiskerTag, &VisCondition)>,
+) {
+    for (mut stroke, whisker, condition) in whiskers.iter_mut() {
+        let color = ui_state
+            .side_color(&whisker.side, condition.condition.as_deref())
+            .with_a(1.);
+        if stroke.color != color {
+            stroke.color = color;
         }
     }
 }
@@ -764,37 +2339,150 @@ fn normalize_histogram_height(
 /// Propagate color from Ui to color component.
 fn change_color(
     ui_state: Res<UiState>,
-    mut query: Query<(&mut Fill, &HistTag, &ColorListener), With<Stroke>>,
+    mut query: Query<
+        (
+            &mut Fill,
+            &HistTag,
+            &ColorListener,
+            &AesFilter,
+            Option<&VisCondition>,
+        ),
+        With<Stroke>,
+    >,
 ) {
-    let mut gradients: HashMap<Side, colorgrad::Gradient> = HashMap::new();
+    let mut gradients: HashMap<(Side, Option<String>, bool), colorgrad::Gradient> = HashMap::new();
     if ui_state.is_changed() {
-        for (mut fill, hist, color) in query.iter_mut() {
-            let grad = gradients.entry(hist.side.clone()).or_insert(build_grad(
-                ui_state.zero_white,
-                color.min_val,
-                color.max_val,
-                &ui_state.min_reaction_color,
-                &ui_state.max_reaction_color,
-            ));
+        for (mut fill, hist, color, filter, vis) in query.iter_mut() {
+            let condition = vis.and_then(|vis| vis.condition.clone());
+            let geom_name = if filter.met { "Metabolite" } else { "Reaction" };
+            let (min_color, max_color) = ui_state.color_scale(geom_name, condition.as_deref());
+            let grad = gradients
+                .entry((hist.side.clone(), condition, filter.met))
+                .or_insert(build_grad(
+                    ui_state.zero_white,
+                    color.min_val,
+                    color.max_val,
+                    &min_color,
+                    &max_color,
+                ));
             fill.color = from_grad_clamped(grad, color.value, color.min_val, color.max_val);
         }
     }
 }
 
+/// When all conditions are shown, stack the popups of the conditions of a
+/// reaction or metabolite in rows, in the order of the conditions, so that
+/// they fit in the height of one popup instead of overlapping. Each row keeps
+/// the color of its condition and its key; the background and the scales are
+/// only drawn once, under the bottom row.
+fn stack_popups(
+    ui_state: Res<UiState>,
+    mut popups: Query<
+        (
+            &mut Transform,
+            &Path,
+            &PopUpBase,
+            &VisCondition,
+            &AnyTag,
+            &Children,
+        ),
+        (With<HistTag>, Without<ErrorBarTag>),
+    >,
+    mut keys: Query<&mut PopUpKey>,
+    mut frames: Query<&mut Visibility, With<PopUpFrame>>,
+) {
+    let stacked = ui_state.condition == "ALL";
+    let position = |condition: &Option<String>| {
+        condition
+            .as_ref()
+            .and_then(|c| ui_state.conditions.iter().position(|x| x == c))
+            .unwrap_or(0)
+    };
+    let mut rows: HashMap<u64, Vec<usize>> = HashMap::new();
+    if stacked {
+        for (_, _, _, condition, tag, _) in popups.iter() {
+            rows.entry(tag.id)
+                .or_default()
+                .push(position(&condition.condition));
+        }
+        for positions in rows.values_mut() {
+            positions.sort();
+        }
+    }
+    for (mut trans, path, base, condition, tag, children) in popups.iter_mut() {
+        let positions = rows.get(&tag.id).filter(|positions| positions.len() > 1);
+        // the first condition goes on top
+        let (row, n) = positions
+            .map(|positions| {
+                let i = positions
+                    .iter()
+                    .position(|p| *p == position(&condition.condition))
+                    .unwrap_or(0);
+                (positions.len() - 1 - i, positions.len())
+            })
+            .unwrap_or((0, 1));
+        let height = ui_state.max_top / n as f32;
+        let y = base.y + row as f32 * height;
+        if trans.translation.y != y {
+            trans.translation.y = y;
+        }
+        // the height of the histogram is set every frame by normalize_histogram_height,
+        // unless it is flat
+        if (n > 1) && path.0.iter().any(|ev| ev.to().y > 0.) {
+            trans.scale.y /= n as f32;
+        }
+        let key_y = if n > 1 { height } else { base.key_y };
+        let mut row_keys = keys.iter_many_mut(children);
+        while let Some(mut key) = row_keys.fetch_next() {
+            if key.y != key_y {
+                key.y = key_y;
+            }
+        }
+        let visibility = if row > 0 {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+        let mut row_frames = frames.iter_many_mut(children);
+        while let Some(mut frame) = row_frames.fetch_next() {
+            if *frame != visibility {
+                *frame = visibility;
+            }
+        }
+    }
+}
+
 /// Unscale up children of scaled histograms.
 fn unscale_histogram_children(
     parents: Query<(Entity, &Children), (With<HistTag>, Without<Unscale>)>,
-    mut query: Query<&mut Transform>,
+    mut query: Query<(&mut Transform, Option<&PopUpKey>)>,
 ) {
     for (parent, children) in parents.iter() {
-        let Ok(scale) = query.get_mut(parent).map(|trans| trans.scale.y) else {
+        let Ok(scale) = query.get(parent).map(|(trans, _)| trans.scale.y) else {
             continue;
         };
         for child in children {
-            let Ok(mut trans) = query.get_mut(*child) else {
+            let Ok((mut trans, key)) = query.get_mut(*child) else {
                 continue;
             };
             trans.scale.y = 1. / scale;
+            if let Some(key) = key {
+                trans.translation.y = key.y / scale;
+            }
+        }
+    }
+}
+
+/// Keep the keys of the popups in the color of their histogram.
+fn color_popup_keys(
+    popups: Query<(&Fill, &Children), (With<AnyTag>, Changed<Fill>)>,
+    mut keys: Query<&mut Text, With<PopUpKey>>,
+) {
+    for (fill, children) in popups.iter() {
+        let mut keys = keys.iter_many_mut(children);
+        while let Some(mut text) = keys.fetch_next() {
+            // the fill is transparent, but the text should be readable
+            text.sections[0].style.color = fill.color.with_a(1.);
         }
     }
 }
@@ -823,7 +2511,8 @@ fn fill_conditions(mut ui_state: ResMut<UiState>, aesthetics: Query<&Aesthetics>
             ui_state.conditions = vec![String::from("")];
             ui_state.condition = String::from("");
         }
-        if ui_state.condition.is_empty() {
+        // the condition may have been given before the data was loaded
+        if !ui_state.conditions.contains(&ui_state.condition) {
             ui_state.condition = ui_state.conditions[0].clone();
         }
     }
@@ -856,6 +2545,7 @@ fn follow_the_axes(
                 // z has to be maintained per element in the axis to avoid flickering
                 trans.translation.x = axis_trans.translation.x;
                 trans.translation.y = axis_trans.translation.y;
+                trans.rotation = axis_trans.rotation;
                 if hist.follow_scale {
                     trans.scale.x = axis_trans.scale.x;
                 }
@@ -864,15 +2554,112 @@ fn follow_the_axes(
     }
 }
 
+/// Distance between a histogram axis and the middle of its reaction above
+/// which the axis is connected to the reaction with a [`LeaderLine`].
+const LEADER_DISTANCE: f32 = 150.;
+const LEADER_WIDTH: f32 = 1.5;
+
+/// Connect the histograms moved far away from their reaction to its middle,
+/// so that they can still be attributed after decluttering the figure. The
+/// lines are regular shapes, so they are also exported.
+fn draw_leader_lines(
+    mut commands: Commands,
+    ui_state: Res<UiState>,
+    theme: Res<Theme>,
+    axes: Query<(&Transform, &Xaxis)>,
+    arrows: Query<(&Transform, &ArrowTag)>,
+    mut lines: Query<(Entity, &mut LeaderLine, &mut Path)>,
+) {
+    let arrow_pos: HashMap<u64, Vec2> = arrows
+        .iter()
+        .map(|(trans, arrow)| (arrow.node_id, trans.translation.truncate()))
+        .collect();
+    let mut wanted: HashMap<(u64, Side), (Vec2, Vec2)> = HashMap::new();
+    if ui_state.leader_lines {
+        for (trans, axis) in axes.iter().filter(|(_, axis)| !axis.met) {
+            let Some(to) = arrow_pos.get(&axis.node_id) else {
+                continue;
+            };
+            let from = trans.translation.truncate();
+            if from.distance(*to) > LEADER_DISTANCE {
+                wanted.insert((axis.node_id, axis.side.clone()), (from, *to));
+            }
+        }
+    }
+    for (entity, mut line, mut path) in lines.iter_mut() {
+        match wanted.remove(&(line.node_id, line.side.clone())) {
+            Some(ends) if ends != line.ends => {
+                *path = GeometryBuilder::build_as(&shapes::Line(ends.0, ends.1));
+                line.ends = ends;
+            }
+            Some(_) => (),
+            None => commands.entity(entity).despawn_recursive(),
+        }
+    }
+    for ((node_id, side), ends) in wanted {
+        commands.spawn((
+            ShapeBundle {
+                path: GeometryBuilder::build_as(&shapes::Line(ends.0, ends.1)),
+                spatial: SpatialBundle::from_transform(Transform::from_xyz(0., 0., 0.3)),
+                ..default()
+            },
+            Stroke::new(theme.text().with_a(0.6), LEADER_WIDTH),
+            LeaderLine {
+                node_id,
+                side,
+                ends,
+            },
+        ));
+    }
+}
+
 /// Set which data is actively plotted in the screen to show its corresponding
 /// settings.
 fn activate_settings(
-    ui_state: ResMut<UiState>,
+    mut ui_state: ResMut<UiState>,
     mut active_data: ResMut<ActiveData>,
+    groups: Res<Groups>,
+    thermo: Query<(), With<GeomThermo>>,
     arrows_or_boxes: Query<(&Aesthetics, &Point<f32>), Or<(With<GeomArrow>, With<GeomHist>)>>,
     circles: Query<(&Aesthetics, &Point<f32>), With<GeomMetabolite>>,
     hists: Query<(&Aesthetics, &GeomHist), With<Distribution<f32>>>,
+    missing: Query<(&Aesthetics, &Missing)>,
+    axes: Query<&Xaxis>,
+    summarized: Query<(), With<Summarized>>,
+    columns: Query<&ColumnName, Added<ColumnName>>,
+    mut removed_columns: RemovedComponents<ColumnName>,
+    all_columns: Query<&ColumnName>,
 ) {
+    if !columns.is_empty() || removed_columns.read().count() > 0 {
+        active_data.columns = all_columns
+            .iter()
+            .map(|column| column.0.clone())
+            .unique()
+            .sorted()
+            .collect();
+    }
+    if groups.is_changed() {
+        active_data.groups = groups.names().into_iter().cloned().collect();
+        if let Some(group) = ui_state.group.as_ref() {
+            if !active_data.groups.contains(group) {
+                ui_state.group = None;
+            }
+        }
+    }
+    active_data.flat = axes.iter().filter(|axis| axis.flat).count();
+    active_data.thermo = !thermo.is_empty();
+    active_data.summarized = !summarized.is_empty();
+    active_data.missing = missing
+        .iter()
+        .filter(|(aes, _)| {
+            aes.condition
+                .as_ref()
+                .map(|c| c == &ui_state.condition)
+                .unwrap_or(true)
+        })
+        .flat_map(|(_, missing)| missing.0.iter())
+        .unique()
+        .count();
     active_data.arrow = arrows_or_boxes
         .iter()
         // this works because data without a condition should always be shown
@@ -0,0 +1,345 @@
+//! Text boxes, arrows and rectangles placed freely on the map as callouts.
+//!
+//! They are dragged like the histograms and saved with the map.
+use crate::font::MapFont;
+use crate::geom::{Drag, Xaxis};
+use crate::gui::{DragBindings, MapCursor, UiState};
+use bevy::prelude::*;
+use bevy::text::TextLayoutInfo;
+use bevy_egui::{egui, EguiContexts};
+use bevy_pancam::PanCam;
+use bevy_prototype_lyon::prelude::{
+    shapes, GeometryBuilder, Path, PathBuilder, ShapeBundle, Stroke,
+};
+use serde::{Deserialize, Serialize};
+
+/// Above the labels of the map (z=4).
+const ANNOTATION_Z: f32 = 5.;
+
+pub struct AnnotationPlugin;
+
+impl Plugin for AnnotationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_annotations);
+    }
+}
+
+/// Annotation of the map. While the app runs, its position is the one of its
+/// [`Transform`]; `position` is only updated when it is stored in the map.
+#[derive(Component, Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub kind: AnnotationKind,
+    /// Position in the world, relative to the center of the map.
+    pub position: Vec2,
+    /// sRGBA color.
+    pub color: [f32; 4],
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnnotationKind {
+    Text {
+        text: String,
+        font_size: f32,
+    },
+    /// Arrow from the position of the annotation to its `tip`.
+    Arrow {
+        tip: Vec2,
+        width: f32,
+    },
+    /// Outline of a rectangle centered at the position of the annotation.
+    Rect {
+        size: Vec2,
+        width: f32,
+    },
+}
+
+impl AnnotationKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnnotationKind::Text { .. } => "text",
+            AnnotationKind::Arrow { .. } => "arrow",
+            AnnotationKind::Rect { .. } => "rectangle",
+        }
+    }
+}
+
+impl Annotation {
+    pub fn new(kind: AnnotationKind, position: Vec2) -> Self {
+        Self {
+            kind,
+            position,
+            color: [0.2, 0.2, 0.2, 1.0],
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        let [r, g, b, a] = self.color;
+        Color::rgba(r, g, b, a)
+    }
+
+    /// Whether a point, relative to the position of the annotation, is on it.
+    /// Texts are measured by their `text_size` once they are laid out.
+    pub fn contains(&self, point: Vec2, text_size: Option<Vec2>) -> bool {
+        match &self.kind {
+            AnnotationKind::Text { font_size, .. } => {
+                let half = text_size.unwrap_or(Vec2::splat(*font_size)) / 2.;
+                point.abs().cmple(half).all()
+            }
+            AnnotationKind::Arrow { tip, width } => {
+                let t = (point.dot(*tip) / tip.length_squared().max(1e-6)).clamp(0., 1.);
+                point.distance(*tip * t) < width.max(10.)
+            }
+            AnnotationKind::Rect { size, width } => {
+                point.abs().cmple(*size / 2. + width.max(10.)).all()
+            }
+        }
+    }
+}
+
+/// Spawn an annotation, drawn by [`draw_annotations`].
+pub fn spawn_annotation(commands: &mut Commands, annotation: Annotation, font: Handle<Font>) {
+    let transform = Transform::from_xyz(annotation.position.x, annotation.position.y, ANNOTATION_Z);
+    let mut entity = match annotation.kind {
+        AnnotationKind::Text { .. } => commands.spawn(Text2dBundle {
+            text: Text::from_section("", TextStyle { font, ..default() })
+                .with_justify(JustifyText::Center),
+            transform,
+            ..default()
+        }),
+        _ => commands.spawn((
+            ShapeBundle {
+                spatial: SpatialBundle::from_transform(transform),
+                ..default()
+            },
+            Stroke::new(annotation.color(), 1.),
+        )),
+    };
+    entity.insert((annotation, Drag::default()));
+}
+
+/// Arrow along `tip` with a head proportional to its `width`.
+fn arrow_path(tip: Vec2, width: f32) -> Path {
+    let mut builder = PathBuilder::new();
+    builder.move_to(Vec2::ZERO);
+    builder.line_to(tip);
+    let back = -tip.normalize_or_zero() * (4. * width + 10.);
+    for angle in [-0.45f32, 0.45] {
+        builder.move_to(tip);
+        builder.line_to(tip + Vec2::from_angle(angle).rotate(back));
+    }
+    builder.build()
+}
+
+/// Redraw the annotations that were added or edited.
+fn draw_annotations(
+    mut query: Query<
+        (
+            &Annotation,
+            Option<&mut Text>,
+            Option<&mut Path>,
+            Option<&mut Stroke>,
+        ),
+        Changed<Annotation>,
+    >,
+) {
+    for (annotation, text, path, stroke) in query.iter_mut() {
+        let color = annotation.color();
+        match (&annotation.kind, text, path, stroke) {
+            (
+                AnnotationKind::Text {
+                    text: value,
+                    font_size,
+                },
+                Some(mut text),
+                _,
+                _,
+            ) => {
+                let section = &mut text.sections[0];
+                section.value.clone_from(value);
+                section.style.font_size = *font_size;
+                section.style.color = color;
+            }
+            (AnnotationKind::Arrow { tip, width }, _, Some(mut path), Some(mut stroke)) => {
+                *path = arrow_path(*tip, *width);
+                stroke.color = color;
+                stroke.options.line_width = *width;
+            }
+            (AnnotationKind::Rect { size, width }, _, Some(mut path), Some(mut stroke)) => {
+                *path = GeometryBuilder::build_as(&shapes::Rectangle {
+                    extents: *size,
+                    origin: shapes::RectangleOrigin::Center,
+                });
+                stroke.color = color;
+                stroke.options.line_width = *width;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Grab the annotations with the button that moves the histograms, unless a
+/// histogram was grabbed.
+pub fn grab_annotations(
+    mut egui_context: EguiContexts,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    bindings: Res<DragBindings>,
+    cursor: MapCursor,
+    axes: Query<&Drag, (With<Xaxis>, Without<Annotation>)>,
+    mut annotations: Query<
+        (&Transform, &mut Drag, &Annotation, Option<&TextLayoutInfo>),
+        Without<Xaxis>,
+    >,
+) {
+    if mouse_button_input.just_released(bindings.hist_move) {
+        for (_, mut drag, _, _) in annotations.iter_mut() {
+            drag.dragged = false;
+        }
+    }
+    if !mouse_button_input.just_pressed(bindings.hist_move)
+        || egui_context.ctx_mut().is_pointer_over_area()
+        || axes.iter().any(|drag| drag.dragged)
+    {
+        return;
+    }
+    let Some(world_pos) = cursor.world_pos() else {
+        return;
+    };
+    let grabbed = annotations
+        .iter_mut()
+        .filter(|(trans, _, annotation, layout)| {
+            annotation.contains(
+                world_pos - trans.translation.truncate(),
+                layout.map(|layout| layout.logical_size),
+            )
+        })
+        .last();
+    if let Some((trans, mut drag, _, _)) = grabbed {
+        drag.dragged = true;
+        drag.offset = trans.translation.truncate() - world_pos;
+    }
+}
+
+/// Window to add annotations at the center of the view and edit them.
+pub fn ui_annotations(
+    mut commands: Commands,
+    mut egui_context: EguiContexts,
+    mut state: ResMut<UiState>,
+    map_font: Res<MapFont>,
+    cameras: Query<&GlobalTransform, With<PanCam>>,
+    mut annotations: Query<(Entity, &mut Annotation)>,
+) {
+    if state.hide || !state.show_annotations {
+        return;
+    }
+    let mut added = None;
+    egui::Window::new("Annotations")
+        .open(&mut state.show_annotations)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                for kind in [
+                    AnnotationKind::Text {
+                        text: String::from("Text"),
+                        font_size: 40.,
+                    },
+                    AnnotationKind::Arrow {
+                        tip: Vec2::new(150., 0.),
+                        width: 6.,
+                    },
+                    AnnotationKind::Rect {
+                        size: Vec2::new(300., 200.),
+                        width: 6.,
+                    },
+                ] {
+                    if ui.button(format!("+ {}", kind.name())).clicked() {
+                        added = Some(kind);
+                    }
+                }
+            });
+            for (entity, mut annotation) in annotations.iter_mut() {
+                ui.push_id(entity, |ui| {
+                    ui.horizontal(|ui| {
+                        // only flag real edits so that the annotation is not redrawn every frame
+                        let edited = annotation.bypass_change_detection();
+                        let mut changed = false;
+                        match &mut edited.kind {
+                            AnnotationKind::Text { text, font_size } => {
+                                changed |= ui
+                                    .add(egui::TextEdit::singleline(text).desired_width(120.))
+                                    .changed();
+                                changed |= ui
+                                    .add(
+                                        egui::DragValue::new(font_size)
+                                            .clamp_range(5.0..=500.0)
+                                            .prefix("size: "),
+                                    )
+                                    .changed();
+                            }
+                            AnnotationKind::Arrow { tip, width } => {
+                                ui.label("arrow");
+                                changed |= ui
+                                    .add(egui::DragValue::new(&mut tip.x).prefix("dx: "))
+                                    .changed();
+                                changed |= ui
+                                    .add(egui::DragValue::new(&mut tip.y).prefix("dy: "))
+                                    .changed();
+                                changed |= ui
+                                    .add(
+                                        egui::DragValue::new(width)
+                                            .clamp_range(1.0..=100.0)
+                                            .prefix("width: "),
+                                    )
+                                    .changed();
+                            }
+                            AnnotationKind::Rect { size, width } => {
+                                ui.label("rectangle");
+                                changed |= ui
+                                    .add(
+                                        egui::DragValue::new(&mut size.x)
+                                            .clamp_range(1.0..=20000.0)
+                                            .prefix("w: "),
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .add(
+                                        egui::DragValue::new(&mut size.y)
+                                            .clamp_range(1.0..=20000.0)
+                                            .prefix("h: "),
+                                    )
+                                    .changed();
+                                changed |= ui
+                                    .add(
+                                        egui::DragValue::new(width)
+                                            .clamp_range(1.0..=100.0)
+                                            .prefix("width: "),
+                                    )
+                                    .changed();
+                            }
+                        }
+                        changed |= ui
+                            .color_edit_button_rgba_unmultiplied(&mut edited.color)
+                            .changed();
+                        if changed {
+                            annotation.set_changed();
+                        }
+                        if ui.button("🗑").on_hover_text("Remove").clicked() {
+                            commands.entity(entity).despawn_recursive();
+                        }
+                    });
+                });
+            }
+            ui.label("Move them with the button that moves the histograms.");
+        });
+    if let Some(kind) = added {
+        let center = cameras
+            .iter()
+            .next()
+            .map(|trans| trans.translation().truncate())
+            .unwrap_or_default();
+        spawn_annotation(
+            &mut commands,
+            Annotation::new(kind, center),
+            map_font.handle.clone(),
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Raster image displayed behind the map (e.g., a cell schematic).
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct BackgroundPlugin;
+
+impl Plugin for BackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MapBackground::default())
+            .add_systems(Update, update_background);
+    }
+}
+
+/// Settings of the background image. They are saved with the map.
+#[derive(Resource, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MapBackground {
+    /// Path to the image; empty for no background.
+    pub path: String,
+    pub opacity: f32,
+    pub scale: f32,
+    /// Offset of the center of the image from the center of the map.
+    pub offset: Vec2,
+    /// Whether the path has to be (re)loaded.
+    #[serde(skip, default = "default_dirty")]
+    pub dirty: bool,
+}
+
+fn default_dirty() -> bool {
+    true
+}
+
+impl Default for MapBackground {
+    fn default() -> Self {
+        Self {
+            path: String::from(""),
+            opacity: 0.5,
+            scale: 1.0,
+            offset: Vec2::ZERO,
+            dirty: false,
+        }
+    }
+}
+
+/// Marker for the background sprite.
+#[derive(Component)]
+pub struct BackgroundTag;
+
+/// Spawn, replace or update the background sprite when its settings change.
+fn update_background(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut background: ResMut<MapBackground>,
+    mut sprite_query: Query<(Entity, &mut Sprite, &mut Transform), With<BackgroundTag>>,
+) {
+    if !background.is_changed() {
+        return;
+    }
+    if background.dirty {
+        background.dirty = false;
+        for (entity, _, _) in sprite_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        if background.path.is_empty() {
+            return;
+        }
+        commands.spawn((
+            SpriteBundle {
+                texture: asset_server.load(background.path.clone()),
+                sprite: Sprite {
+                    color: Color::rgba(1., 1., 1., background.opacity),
+                    ..default()
+                },
+                // below arrows (z=1) and metabolites (z=2)
+                transform: Transform::from_xyz(background.offset.x, background.offset.y, 0.)
+                    .with_scale(Vec3::new(background.scale, background.scale, 1.)),
+                ..default()
+            },
+            BackgroundTag,
+        ));
+        return;
+    }
+    for (_, mut sprite, mut trans) in sprite_query.iter_mut() {
+        sprite.color.set_a(background.opacity);
+        trans.translation.x = background.offset.x;
+        trans.translation.y = background.offset.y;
+        trans.scale = Vec3::new(background.scale, background.scale, 1.);
+    }
+}
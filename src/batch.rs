@@ -0,0 +1,325 @@
+//! Non-interactive mode that renders the figures described in a spec file,
+//! called with `shu batch spec.json` (or `spec.yaml`).
+use crate::data::{DataTransform, ReactionState};
+use crate::escher::{EscherMap, MapState, SecondaryStyle};
+use crate::funcplot::{ArrowStyle, ErrorBar, Kernel, NumberFormat};
+use crate::gui::{rgba_from_hex, UiState};
+use crate::screenshot::{ScreenshotEvent, TiledExport, TiledScreenshotEvent, SETTLE_FRAMES};
+use crate::stats::Summary;
+use crate::theme::Theme;
+use bevy::app::AppExit;
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy_egui::egui::Rgba;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Frames to wait after the last export so that the images are written to disk.
+const EXIT_FRAMES: u32 = 60;
+
+pub struct BatchPlugin;
+
+impl Plugin for BatchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BatchRun>()
+            .add_systems(PostStartup, start_batch)
+            .add_systems(Update, run_batch);
+    }
+}
+
+/// Figure described in a JSON or YAML file: the map, the data and how it
+/// looks, and the images to export. Relative paths are relative to the spec file.
+#[derive(Resource, Deserialize)]
+pub struct BatchSpec {
+    /// Escher map, the default map is used if not given.
+    pub map: Option<String>,
+    #[serde(default)]
+    pub datasets: Vec<DatasetSpec>,
+    #[serde(default)]
+    pub style: StyleSpec,
+    /// Theme file saved from the settings, the light theme is used if not given.
+    pub theme: Option<String>,
+    pub exports: Vec<ExportSpec>,
+    /// Size of the window in logical pixels, which is the size of the raster images.
+    pub size: Option<(f32, f32)>,
+}
+
+#[derive(Deserialize)]
+pub struct DatasetSpec {
+    pub path: String,
+    #[serde(default)]
+    pub transform: DataTransform,
+    pub metric: Option<String>,
+    /// Drawing layer, plots of higher layers are drawn on top.
+    #[serde(default)]
+    pub layer: i32,
+    /// How the sizes of the reactions are drawn.
+    #[serde(default)]
+    pub arrow_style: ArrowStyle,
+}
+
+/// Settings to apply over the defaults of [`UiState`]. Colors are hex strings.
+#[derive(Deserialize, Default)]
+pub struct StyleSpec {
+    pub min_reaction: Option<f32>,
+    pub max_reaction: Option<f32>,
+    pub min_metabolite: Option<f32>,
+    pub max_metabolite: Option<f32>,
+    pub min_reaction_color: Option<String>,
+    pub max_reaction_color: Option<String>,
+    pub min_metabolite_color: Option<String>,
+    pub max_metabolite_color: Option<String>,
+    pub zero_white: Option<bool>,
+    pub global_scale: Option<bool>,
+    pub kde_kernel: Option<Kernel>,
+    pub kde_bandwidth: Option<f32>,
+    pub hist_bins: Option<u32>,
+    pub max_samples: Option<usize>,
+    pub error_bar: Option<ErrorBar>,
+    pub summary: Option<Summary>,
+    pub summary_quantile: Option<f32>,
+    pub fixed_hist_length: Option<bool>,
+    pub hist_length: Option<f32>,
+    pub labels: Option<bool>,
+    pub label_size: Option<f32>,
+    pub text_scale: Option<f32>,
+    /// Path of a TTF file, relative to the assets or absolute.
+    pub font: Option<String>,
+    pub secondary: Option<SecondaryStyle>,
+    pub reversibility: Option<bool>,
+    pub number_format: Option<NumberFormat>,
+}
+
+#[derive(Deserialize)]
+pub struct ExportSpec {
+    /// Output image, the extension decides the format (svg, png, jpeg, bmp or tga).
+    pub path: String,
+    /// Condition to show, the current one if not given.
+    pub condition: Option<String>,
+    /// Write one panel per condition instead (raster only).
+    #[serde(default)]
+    pub tiles: bool,
+}
+
+impl BatchSpec {
+    /// Read the spec from the command line arguments if shu is called as
+    /// `shu batch spec.json`.
+    pub fn from_args() -> anyhow::Result<Option<Self>> {
+        let mut args = std::env::args().skip(1);
+        if args.next().as_deref() != Some("batch") {
+            return Ok(None);
+        }
+        let Some(path) = args.next() else {
+            anyhow::bail!("usage: shu batch <spec.json|spec.yaml>");
+        };
+        Self::from_file(path).map(Some)
+    }
+
+    /// Read a spec, as YAML if the extension is `yaml` or `yml` and as JSON otherwise.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let is_yaml = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"));
+        let mut spec: Self = if is_yaml {
+            serde_yaml::from_str(&content)?
+        } else {
+            serde_json::from_str(&content)?
+        };
+        let root = path.parent().unwrap_or(Path::new("."));
+        let resolve = |p: &mut String| {
+            if Path::new(p.as_str()).is_relative() {
+                *p = root.join(p.as_str()).to_string_lossy().to_string();
+            }
+        };
+        if let Some(map) = spec.map.as_mut() {
+            resolve(map);
+        }
+        if let Some(theme) = spec.theme.as_mut() {
+            resolve(theme);
+        }
+        spec.datasets.iter_mut().for_each(|d| resolve(&mut d.path));
+        spec.exports.iter_mut().for_each(|e| resolve(&mut e.path));
+        Ok(spec)
+    }
+}
+
+impl StyleSpec {
+    fn apply(&self, ui_state: &mut UiState) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+        fn set_color(target: &mut Rgba, hex: &Option<String>) {
+            let Some(hex) = hex else {
+                return;
+            };
+            match rgba_from_hex(hex) {
+                Ok(color) => *target = color,
+                Err(e) => warn!("Invalid color '{hex}' in batch spec: {e}"),
+            }
+        }
+        set(&mut ui_state.min_reaction, &self.min_reaction);
+        set(&mut ui_state.max_reaction, &self.max_reaction);
+        set(&mut ui_state.min_metabolite, &self.min_metabolite);
+        set(&mut ui_state.max_metabolite, &self.max_metabolite);
+        // the color scales are the same for all conditions
+        for (scale, hex) in [
+            (&mut ui_state.min_reaction_color, &self.min_reaction_color),
+            (&mut ui_state.max_reaction_color, &self.max_reaction_color),
+            (
+                &mut ui_state.min_metabolite_color,
+                &self.min_metabolite_color,
+            ),
+            (
+                &mut ui_state.max_metabolite_color,
+                &self.max_metabolite_color,
+            ),
+        ] {
+            set_color(scale.entry(String::new()).or_default(), hex);
+        }
+        set(&mut ui_state.zero_white, &self.zero_white);
+        set(&mut ui_state.global_scale, &self.global_scale);
+        set(&mut ui_state.kde_kernel, &self.kde_kernel);
+        set(&mut ui_state.kde_bandwidth, &self.kde_bandwidth);
+        set(&mut ui_state.hist_bins, &self.hist_bins);
+        set(&mut ui_state.max_samples, &self.max_samples);
+        set(&mut ui_state.error_bar, &self.error_bar);
+        set(&mut ui_state.summary, &self.summary);
+        set(&mut ui_state.summary_quantile, &self.summary_quantile);
+        set(&mut ui_state.fixed_hist_length, &self.fixed_hist_length);
+        set(&mut ui_state.hist_length, &self.hist_length);
+        set(&mut ui_state.labels, &self.labels);
+        set(&mut ui_state.label_size, &self.label_size);
+        set(&mut ui_state.text_scale, &self.text_scale);
+        set(&mut ui_state.font, &self.font);
+        set(&mut ui_state.secondary, &self.secondary);
+        set(&mut ui_state.reversibility, &self.reversibility);
+        set(&mut ui_state.number_format, &self.number_format);
+    }
+}
+
+/// Progress through the [`BatchSpec::exports`].
+#[derive(Resource, Default)]
+struct BatchRun {
+    next: usize,
+    prepared: bool,
+    wait: u32,
+}
+
+/// Load the map and datasets of the spec and apply its style.
+fn start_batch(
+    spec: Res<BatchSpec>,
+    asset_server: Res<AssetServer>,
+    mut ui_state: ResMut<UiState>,
+    mut theme: ResMut<Theme>,
+    mut map_state: ResMut<MapState>,
+    mut reaction_state: ResMut<ReactionState>,
+) {
+    if let Some(map) = spec.map.as_ref() {
+        let handle: Handle<EscherMap> = asset_server.load(map.clone());
+        map_state.open(map.clone(), handle);
+    }
+    for dataset in spec.datasets.iter() {
+        reaction_state.add(
+            dataset.path.clone(),
+            asset_server.load(dataset.path.clone()),
+        );
+        if let Some(added) = reaction_state
+            .datasets
+            .iter_mut()
+            .find(|d| d.name == dataset.path)
+        {
+            added.transform = dataset.transform;
+            added.metric.clone_from(&dataset.metric);
+            added.layer = dataset.layer;
+            added.arrow_style = dataset.arrow_style;
+        }
+    }
+    spec.style.apply(&mut ui_state);
+    // the settings windows are never part of the figures
+    ui_state.hide = true;
+    if let Some(path) = spec.theme.as_ref() {
+        match Theme::from_file(path) {
+            Ok(loaded) => *theme = loaded,
+            Err(e) => warn!("Could not load the theme '{path}': {e}"),
+        }
+    }
+}
+
+/// Export the images of the spec one at a time, once everything is plotted,
+/// and close the app afterwards.
+fn run_batch(
+    spec: Res<BatchSpec>,
+    mut run: ResMut<BatchRun>,
+    mut ui_state: ResMut<UiState>,
+    map_state: Res<MapState>,
+    reaction_state: Res<ReactionState>,
+    asset_server: Res<AssetServer>,
+    tiled_export: Option<Res<TiledExport>>,
+    mut screen_events: EventWriter<ScreenshotEvent>,
+    mut tiled_events: EventWriter<TiledScreenshotEvent>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if !map_state.loaded || !reaction_state.loaded {
+        // nothing would be plotted, so stop instead of waiting forever
+        let map = &map_state.maps[map_state.current];
+        let failed = std::iter::once((map.name.as_str(), map.handle.id().untyped()))
+            .chain(
+                reaction_state
+                    .datasets
+                    .iter()
+                    .map(|dataset| (dataset.name.as_str(), dataset.handle.id().untyped())),
+            )
+            .find(|(_, id)| matches!(asset_server.get_load_state(*id), Some(LoadState::Failed)));
+        if let Some((path, _)) = failed {
+            error!("Could not load '{path}'.");
+            exit.send(AppExit);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if tiled_export.is_some() {
+        // the condition is restored at the end of the tiles
+        run.wait = run.wait.max(SETTLE_FRAMES);
+        return;
+    }
+    if run.wait > 0 {
+        run.wait -= 1;
+        return;
+    }
+    let Some(export) = spec.exports.get(run.next) else {
+        exit.send(AppExit);
+        return;
+    };
+    if !run.prepared {
+        if let Some(condition) = export.condition.as_ref() {
+            if ui_state.conditions.contains(condition) {
+                ui_state.condition.clone_from(condition);
+            } else {
+                warn!("Condition '{condition}' not found in the data.");
+            }
+        }
+        run.prepared = true;
+        run.wait = SETTLE_FRAMES;
+        return;
+    }
+    info!("Exporting {}", export.path);
+    if export.tiles {
+        tiled_events.send(TiledScreenshotEvent {
+            path: export.path.clone(),
+        });
+    } else {
+        screen_events.send(ScreenshotEvent {
+            path: export.path.clone(),
+        });
+    }
+    run.next += 1;
+    run.prepared = false;
+    if run.next == spec.exports.len() {
+        run.wait = EXIT_FRAMES;
+    }
+}
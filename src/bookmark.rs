@@ -0,0 +1,163 @@
+//! Named views of the map (position, zoom and condition) to jump between with
+//! the number keys, e.g., from one pathway to another when presenting.
+//!
+//! They are saved with the map.
+use crate::escher::{EscherMap, MapState};
+use crate::gui::UiState;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_pancam::PanCam;
+use serde::{Deserialize, Serialize};
+
+/// Keys that jump to the first bookmarks, in order.
+pub const BOOKMARK_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+pub struct BookmarkPlugin;
+
+impl Plugin for BookmarkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<JumpToBookmark>()
+            .add_systems(Update, (jump_on_key, jump_to_bookmark.after(jump_on_key)));
+    }
+}
+
+/// View of the map stored under a name.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Bookmark {
+    pub name: String,
+    /// Center of the view in the world, relative to the center of the map.
+    pub position: Vec2,
+    /// Scale of the projection of the camera; higher is zoomed out.
+    pub scale: f32,
+    /// Condition shown, kept as is if it is not in the data.
+    #[serde(default)]
+    pub condition: String,
+}
+
+/// Move the camera to the bookmark at this index of the current map.
+#[derive(Event)]
+pub struct JumpToBookmark(pub usize);
+
+fn jump_on_key(
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut egui_context: EguiContexts,
+    mut jump_events: EventWriter<JumpToBookmark>,
+) {
+    // the digits are typed in the settings
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    if let Some(i) = BOOKMARK_KEYS
+        .iter()
+        .position(|key| key_input.just_pressed(*key))
+    {
+        jump_events.send(JumpToBookmark(i));
+    }
+}
+
+/// Apply the view of a bookmark to the camera and the condition, within the
+/// zoom limits of the camera.
+pub fn jump_to_bookmark(
+    mut jump_events: EventReader<JumpToBookmark>,
+    mut ui_state: ResMut<UiState>,
+    map_state: Option<Res<MapState>>,
+    maps: Res<Assets<EscherMap>>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection, &PanCam)>,
+) {
+    let Some(JumpToBookmark(i)) = jump_events.read().last() else {
+        return;
+    };
+    let Some(bookmark) = map_state
+        .and_then(|state| maps.get(state.escher_map()))
+        .and_then(|map| map.bookmarks.get(*i))
+    else {
+        return;
+    };
+    for (mut trans, mut proj, pancam) in cameras.iter_mut() {
+        trans.translation = bookmark.position.extend(trans.translation.z);
+        proj.scale = bookmark
+            .scale
+            .clamp(pancam.min_scale, pancam.max_scale.unwrap_or(f32::INFINITY));
+    }
+    if (ui_state.condition != bookmark.condition)
+        && ui_state.conditions.contains(&bookmark.condition)
+    {
+        ui_state.condition.clone_from(&bookmark.condition);
+    }
+}
+
+/// Window to bookmark the current view and to rename, reorder, jump to or
+/// remove the bookmarks of the map.
+pub fn ui_bookmarks(
+    mut egui_context: EguiContexts,
+    mut state: ResMut<UiState>,
+    map_state: Option<Res<MapState>>,
+    mut maps: ResMut<Assets<EscherMap>>,
+    cameras: Query<(&Transform, &OrthographicProjection), With<PanCam>>,
+    mut jump_events: EventWriter<JumpToBookmark>,
+) {
+    if state.hide || !state.show_bookmarks {
+        return;
+    }
+    let Some(map) = map_state.and_then(|map_state| maps.get_mut(map_state.escher_map())) else {
+        return;
+    };
+    let condition = state.condition.clone();
+    egui::Window::new("Bookmarks")
+        .open(&mut state.show_bookmarks)
+        .show(egui_context.ctx_mut(), |ui| {
+            if ui.button("+ current view").clicked() {
+                if let Some((trans, proj)) = cameras.iter().next() {
+                    map.bookmarks.push(Bookmark {
+                        name: format!("View {}", map.bookmarks.len() + 1),
+                        position: trans.translation.truncate(),
+                        scale: proj.scale,
+                        condition,
+                    });
+                }
+            }
+            let mut removed = None;
+            let mut raised = None;
+            for (i, bookmark) in map.bookmarks.iter_mut().enumerate() {
+                ui.push_id(i, |ui| {
+                    ui.horizontal(|ui| {
+                        let key = if i < BOOKMARK_KEYS.len() {
+                            format!("{}", i + 1)
+                        } else {
+                            String::from("-")
+                        };
+                        if ui.button(key).on_hover_text("Jump to this view").clicked() {
+                            jump_events.send(JumpToBookmark(i));
+                        }
+                        ui.add(egui::TextEdit::singleline(&mut bookmark.name).desired_width(120.));
+                        if !bookmark.condition.is_empty() {
+                            ui.label(&bookmark.condition);
+                        }
+                        if (i > 0) && ui.small_button("⬆").on_hover_text("Move up").clicked() {
+                            raised = Some(i);
+                        }
+                        if ui.button("🗑").on_hover_text("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                });
+            }
+            if let Some(i) = raised {
+                map.bookmarks.swap(i - 1, i);
+            }
+            if let Some(i) = removed {
+                map.bookmarks.remove(i);
+            }
+            ui.label("Jump to the first nine with the number keys.");
+        });
+}
@@ -0,0 +1,173 @@
+//! Keyboard and touch navigation of the map and zoom to fit. Dragging and
+//! zooming with the mouse are handled by [`PanCam`].
+use crate::escher::CircleTag;
+use crate::geom::Drag;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::EguiContexts;
+use bevy_pancam::PanCam;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FitMapEvent>().add_systems(
+            Update,
+            (
+                pan_with_keys,
+                touch_gestures,
+                fit_on_key,
+                fit_map.after(fit_on_key),
+            ),
+        );
+    }
+}
+
+/// Move and zoom the camera so that the whole map fits in the window.
+#[derive(Event)]
+pub struct FitMapEvent;
+
+/// Speed of the panning with the arrow keys, in screen pixels per second.
+const PAN_SPEED: f32 = 800.;
+/// Space left around the map when fitting it to the window.
+const FIT_MARGIN: f32 = 1.1;
+
+/// Pan the camera with the arrow keys.
+fn pan_with_keys(
+    time: Res<Time>,
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut egui_context: EguiContexts,
+    mut cameras: Query<(&mut Transform, &OrthographicProjection), With<PanCam>>,
+) {
+    let direction = [
+        (KeyCode::ArrowLeft, Vec2::NEG_X),
+        (KeyCode::ArrowRight, Vec2::X),
+        (KeyCode::ArrowUp, Vec2::Y),
+        (KeyCode::ArrowDown, Vec2::NEG_Y),
+    ]
+    .into_iter()
+    .filter(|(key, _)| key_input.pressed(*key))
+    .map(|(_, dir)| dir)
+    .sum::<Vec2>();
+    // the keys move the cursor when typing in the settings
+    if (direction == Vec2::ZERO) || egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    for (mut trans, proj) in cameras.iter_mut() {
+        // scaled by the zoom so that the speed on the screen is constant
+        let step = direction.normalize() * PAN_SPEED * proj.scale * time.delta_seconds();
+        trans.translation += step.extend(0.);
+    }
+}
+
+/// Pan the camera with one or two fingers and zoom it by pinching. Fingers
+/// dragging a histogram (see [`crate::gui::LongPress`]) do not move the map.
+fn touch_gestures(
+    touches: Res<Touches>,
+    mut egui_context: EguiContexts,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    drags: Query<&Drag>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection, &PanCam)>,
+) {
+    let (previous, current): (Vec<Vec2>, Vec<Vec2>) = touches
+        .iter()
+        .take(2)
+        .map(|touch| (touch.previous_position(), touch.position()))
+        .unzip();
+    if (previous == current)
+        || drags.iter().any(|drag| drag.dragged)
+        || egui_context.ctx_mut().is_pointer_over_area()
+    {
+        return;
+    }
+    let Ok(win) = windows.get_single() else {
+        return;
+    };
+    let window = Vec2::new(win.width(), win.height());
+    for (mut trans, mut proj, pancam) in cameras.iter_mut() {
+        let limits = (pancam.min_scale, pancam.max_scale.unwrap_or(f32::INFINITY));
+        let (center, scale) = follow_fingers(
+            trans.translation.truncate(),
+            proj.scale,
+            &previous,
+            &current,
+            window,
+            limits,
+        );
+        trans.translation = center.extend(trans.translation.z);
+        proj.scale = scale;
+    }
+}
+
+/// Camera position and scale that keep the point of the map under the fingers
+/// (their middle when pinching) under them after they moved from `previous`
+/// to `current`, in window coordinates. The scale follows the distance between
+/// two fingers, within `limits`.
+pub fn follow_fingers(
+    camera: Vec2,
+    scale: f32,
+    previous: &[Vec2],
+    current: &[Vec2],
+    window: Vec2,
+    limits: (f32, f32),
+) -> (Vec2, f32) {
+    let middle = |points: &[Vec2]| points.iter().sum::<Vec2>() / points.len() as f32;
+    let spread = |points: &[Vec2]| match points {
+        [a, b] => a.distance(*b),
+        _ => 0.,
+    };
+    let new_scale = if spread(previous) > 0. && spread(current) > 0. {
+        (scale * spread(previous) / spread(current)).clamp(limits.0, limits.1)
+    } else {
+        scale
+    };
+    // from the center of the window, with the y axis of the map pointing up
+    let offset = |point: Vec2| {
+        let from_center = point - window / 2.;
+        Vec2::new(from_center.x, -from_center.y)
+    };
+    (
+        camera + offset(middle(previous)) * scale - offset(middle(current)) * new_scale,
+        new_scale,
+    )
+}
+
+fn fit_on_key(key_input: Res<ButtonInput<KeyCode>>, mut fit_events: EventWriter<FitMapEvent>) {
+    if key_input.just_pressed(KeyCode::Home) {
+        fit_events.send(FitMapEvent);
+    }
+}
+
+/// Center the camera on the bounding box of the metabolites and zoom to show it all.
+fn fit_map(
+    mut fit_events: EventReader<FitMapEvent>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    circles: Query<&Transform, (With<CircleTag>, Without<PanCam>)>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection, &PanCam)>,
+) {
+    if fit_events.read().count() == 0 {
+        return;
+    }
+    let Ok(win) = windows.get_single() else {
+        return;
+    };
+    let (min, max) = circles
+        .iter()
+        .map(|trans| trans.translation.truncate())
+        .fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), pos| (min.min(pos), max.max(pos)),
+        );
+    // no map loaded
+    if !(min.is_finite() && max.is_finite()) {
+        return;
+    }
+    let size = (max - min) * FIT_MARGIN;
+    let scale = f32::max(size.x / win.width(), size.y / win.height());
+    let center = (min + max) / 2.;
+    for (mut trans, mut proj, pancam) in cameras.iter_mut() {
+        trans.translation.x = center.x;
+        trans.translation.y = center.y;
+        proj.scale = scale.clamp(pancam.min_scale, pancam.max_scale.unwrap_or(f32::INFINITY));
+    }
+}
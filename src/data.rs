@@ -1,12 +1,15 @@
 //! Input data logic.
 
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 
 use crate::aesthetics;
 use crate::escher::EscherMap;
+use crate::funcplot::ArrowStyle;
 use crate::geom::{self, HistTag, Xaxis};
 use crate::geom::{AesFilter, GeomHist, HistPlot};
 use crate::info::Info;
+use crate::stats::Summary;
 use bevy::asset::io::Reader;
 use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
 use bevy::prelude::*;
@@ -24,7 +27,11 @@ impl Plugin for DataPlugin {
             .init_asset::<Data>()
             .register_asset_loader(CustomAssetLoader::<EscherMap>::new(vec!["json"]))
             .register_asset_loader(CustomAssetLoader::<Data>::new(vec!["metabolism.json"]))
+            .init_resource::<aesthetics::Groups>()
+            .init_resource::<aesthetics::Units>()
             .add_systems(PostUpdate, load_data);
+        #[cfg(feature = "parquet")]
+        app.register_asset_loader(LongTableLoader);
     }
 }
 
@@ -44,11 +51,23 @@ pub enum CustomJsonLoaderError {
     /// A [RON](ron) Error
     #[error("Could not parse JSON: {0}")]
     JsonSpannedError(#[from] serde_json::Error),
+    #[error("Invalid file: {0}")]
+    Invalid(String),
 }
 
+/// Checks of a file that go beyond its JSON structure.
+pub trait Validate {
+    /// Describe what is wrong with the file so that it can be fixed.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl Validate for EscherMap {}
+
 impl<A> AssetLoader for CustomAssetLoader<A>
 where
-    for<'de> A: serde::Deserialize<'de> + bevy::asset::Asset,
+    for<'de> A: serde::Deserialize<'de> + bevy::asset::Asset + Validate,
 {
     type Asset = A;
     type Settings = ();
@@ -63,6 +82,9 @@ where
             let mut bytes = Vec::new();
             reader.read_to_end(&mut bytes).await?;
             let custom_asset = serde_json::from_slice::<A>(&bytes)?;
+            custom_asset
+                .validate()
+                .map_err(CustomJsonLoaderError::Invalid)?;
             Ok(custom_asset)
         })
     }
@@ -81,11 +103,93 @@ impl<A> CustomAssetLoader<A> {
     }
 }
 
-#[derive(Deserialize)]
+/// Extensions of the tables loaded by [`LongTableLoader`].
+#[cfg(feature = "parquet")]
+pub const LONG_TABLE_EXTENSIONS: [&str; 3] = ["parquet", "arrow", "feather"];
+
+/// Columns of the long tables that hold values, named after the distribution
+/// fields of [`Data`]; `value` is plotted as `y`.
+#[cfg(feature = "parquet")]
+const LONG_VALUE_COLUMNS: [&str; 9] = [
+    "value",
+    "y",
+    "left_y",
+    "hover_y",
+    "kde_y",
+    "kde_left_y",
+    "kde_hover_y",
+    "bar_y",
+    "bar_left_y",
+];
+
+/// Loader of samples stored as Parquet or Arrow IPC tables in long format,
+/// which are much smaller and faster to read than JSON for sampling runs.
+#[cfg(feature = "parquet")]
+pub struct LongTableLoader;
+
+/// Possible errors that can be produced by [`LongTableLoader`]
+#[cfg(feature = "parquet")]
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error)]
+pub enum LongTableError {
+    /// An [IO](std::io) Error
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not read Parquet: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("Could not read Arrow: {0}")]
+    Arrow(#[from] arrow::error::ArrowError),
+    #[error("Missing column `{0}`")]
+    Column(&'static str),
+}
+
+#[cfg(feature = "parquet")]
+impl AssetLoader for LongTableLoader {
+    type Asset = Data;
+    type Settings = ();
+    type Error = LongTableError;
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let is_parquet = load_context
+                .path()
+                .extension()
+                .is_some_and(|ext| ext == "parquet");
+            let batches = if is_parquet {
+                parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+                    bytes::Bytes::from(bytes),
+                )?
+                .build()?
+                .collect::<Result<Vec<_>, _>>()?
+            } else {
+                arrow::ipc::reader::FileReader::try_new(std::io::Cursor::new(bytes), None)?
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+            Data::from_long_table(&batches)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &LONG_TABLE_EXTENSIONS
+    }
+}
+
+#[derive(Deserialize, Clone)]
 #[serde(untagged)]
 /// Enum to represent floats that may be NaN or Inf.
+///
+/// A sample in a distribution may also come as a `[value, weight]` pair.
+/// Nulls and strings are treated as missing values.
 enum Number {
     Num(f32),
+    Weighted(f32, f32),
+    Null,
     #[allow(dead_code)]
     // some libraries may use "NaN" or "Inf" as null in JSON we don't care about
     // those values but still has to be as is since serde(other) is not possible
@@ -96,7 +200,7 @@ enum Number {
 impl From<Number> for Option<f32> {
     fn from(value: Number) -> Self {
         match value {
-            Number::Num(num) => Some(num),
+            Number::Num(num) | Number::Weighted(num, _) => Some(num),
             _ => None,
         }
     }
@@ -105,22 +209,154 @@ impl From<Number> for Option<f32> {
 impl Number {
     fn as_ref(&self) -> Option<&f32> {
         match self {
-            Number::Num(num) => Some(num),
+            Number::Num(num) | Number::Weighted(num, _) => Some(num),
             _ => None,
         }
     }
+
+    /// Value and weight of a sample; unweighted samples have a weight of 1.
+    fn weighted(&self) -> Option<(f32, f32)> {
+        match *self {
+            Number::Num(num) => Some((num, 1.)),
+            Number::Weighted(num, weight) if weight >= 0. => Some((num, weight)),
+            _ => None,
+        }
+    }
+
+    fn is_weighted(&self) -> bool {
+        matches!(self, Number::Weighted(..))
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+/// Value of an identifier in a color or size column, which may also be given
+/// as the samples of a distribution, e.g. `[1.2, 0.9, 1.4]`.
+enum Value {
+    Samples(Vec<Number>),
+    One(Number),
+}
+
+impl Value {
+    fn as_ref(&self) -> Option<&f32> {
+        match self {
+            Value::One(num) => num.as_ref(),
+            Value::Samples(_) => None,
+        }
+    }
+
+    fn is_samples(&self) -> bool {
+        matches!(self, Value::Samples(_))
+    }
+
+    /// Samples of the value, without the missing ones; a single value is one sample.
+    fn samples(&self) -> Vec<f32> {
+        match self {
+            Value::One(num) => num.as_ref().into_iter().copied().collect(),
+            Value::Samples(samples) => samples.iter().filter_map(Number::as_ref).copied().collect(),
+        }
+    }
+}
+
+/// A JSON file recognized by its content instead of by its name.
+// read once per file, so the size of the variants does not matter
+#[allow(clippy::large_enum_variant)]
+pub enum JsonFile {
+    Map(EscherMap),
+    Data(Data),
+}
+
+/// Possible errors of [`sniff_json`].
+#[derive(Debug, thiserror::Error)]
+pub enum JsonFileError {
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Invalid(String),
+}
+
+/// Tell escher maps from data by their structure: a map is an array whose
+/// parts have `reactions` or `nodes`, while the data is an object of columns.
+pub fn sniff_json(bytes: &[u8]) -> Result<JsonFile, JsonFileError> {
+    let value: serde_json::Value = serde_json::from_slice(bytes)?;
+    let is_map = value.as_array().is_some_and(|parts| {
+        parts
+            .iter()
+            .any(|part| part.get("reactions").is_some() || part.get("nodes").is_some())
+    });
+    if is_map {
+        Ok(JsonFile::Map(serde_json::from_value(value)?))
+    } else {
+        let data: Data = serde_json::from_value(value)?;
+        data.validate().map_err(JsonFileError::Invalid)?;
+        Ok(JsonFile::Data(data))
+    }
+}
+
+/// File that could not be loaded and why, shown to the user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadError {
+    pub path: String,
+    pub message: String,
+    /// Line and column of the JSON where reading failed.
+    pub position: Option<(usize, usize)>,
+}
+
+impl LoadError {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+            position: None,
+        }
+    }
+
+    pub fn from_json(path: impl Into<String>, error: &JsonFileError) -> Self {
+        use serde_json::error::Category;
+        let JsonFileError::Json(e) = error else {
+            return Self::new(path, error.to_string());
+        };
+        // the position is shown on its own
+        let full = e.to_string();
+        let message = full
+            .trim_end_matches(&format!(" at line {} column {}", e.line(), e.column()))
+            .to_string();
+        let message = match e.classify() {
+            Category::Syntax => format!("The file is not valid JSON: {message}."),
+            Category::Eof => format!("The file ended too early, is it complete? {message}."),
+            Category::Data => {
+                format!("Unexpected content: {message}. Check the names and types of the fields.")
+            }
+            Category::Io => message,
+        };
+        Self {
+            path: path.into(),
+            message,
+            position: (e.line() > 0).then(|| (e.line(), e.column())),
+        }
+    }
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some((line, column)) = self.position {
+            write!(f, " (line {line}, column {column})")?;
+        }
+        Ok(())
+    }
 }
 
 /// Metabolic data from the user that can be read from a `file.metabolism.json`.
-#[derive(Deserialize, Asset, Default, TypePath)]
+#[derive(Deserialize, Asset, Default, TypePath, Clone)]
 pub struct Data {
     /// Vector of reactions' identifiers
     reactions: Option<Vec<String>>,
     // TODO: generalize this for any Data Type and use them (from escher.rs)
     /// Numeric values to plot as reaction arrow colors.
-    colors: Option<Vec<Number>>,
+    colors: Option<Vec<Value>>,
     /// Numeric values to plot as reaction arrow sizes.
-    sizes: Option<Vec<Number>>,
+    sizes: Option<Vec<Value>>,
     /// Numeric values to plot as KDE.
     y: Option<Vec<Vec<Number>>>,
     /// Numeric values to plot as KDE.
@@ -133,25 +369,232 @@ pub struct Data {
     kde_left_y: Option<Vec<Vec<Number>>>,
     /// Numeric values to plot on a hovered popup.
     kde_hover_y: Option<Vec<Vec<Number>>>,
+    /// Numeric values to plot as mean and error bars.
+    bar_y: Option<Vec<Vec<Number>>>,
+    /// Numeric values to plot as mean and error bars.
+    bar_left_y: Option<Vec<Vec<Number>>>,
     /// Numeric values to plot as KDE.
     box_y: Option<Vec<Number>>,
     /// Numeric values to plot as KDE.
     box_left_y: Option<Vec<Number>>,
+    /// Gibbs free energy of the reactions (kJ/mol), plotted as gauges.
+    dg: Option<Vec<Number>>,
+    /// Standard deviation of `dg`.
+    dg_std: Option<Vec<Number>>,
+    /// Usage of each enzyme (e.g., isozymes) of the reactions, plotted as stacked bars.
+    enzymes: Option<Vec<HashMap<String, Number>>>,
     /// Categorical values to be associated with conditions.
     conditions: Option<Vec<String>>,
     /// Categorical values to be associated with conditions.
     met_conditions: Option<Vec<String>>,
+    /// Group (e.g., subsystem) of each reaction.
+    groups: Option<Vec<String>>,
+    /// Group (e.g., compartment) of each metabolite.
+    met_groups: Option<Vec<String>>,
     /// Vector of metabolites' identifiers
     metabolites: Option<Vec<String>>,
     // TODO: generalize this for any Data Type and use them (from escher.rs)
     /// Numeric values to plot as metabolite circle colors.
-    met_colors: Option<Vec<Number>>,
+    met_colors: Option<Vec<Value>>,
     /// Numeric values to plot as metabolite circle sizes.
-    met_sizes: Option<Vec<Number>>,
+    met_sizes: Option<Vec<Value>>,
+    /// Mass isotopomer distribution (fractions of M+0, M+1, ...) of the
+    /// metabolites, plotted as pies.
+    mids: Option<Vec<Vec<Number>>>,
     /// Numeric values to plot as histogram on hover.
     met_y: Option<Vec<Vec<Number>>>,
     /// Numeric values to plot as density on hover.
     kde_met_y: Option<Vec<Vec<Number>>>,
+    /// Numeric values to plot as histogram next to the metabolite circles.
+    side_met_y: Option<Vec<Vec<Number>>>,
+    /// Numeric values to plot as density next to the metabolite circles.
+    kde_side_met_y: Option<Vec<Vec<Number>>>,
+    /// Numeric values to plot as box next to the metabolite circles.
+    box_met_y: Option<Vec<Number>>,
+    /// Titles of the axes of the numeric columns (e.g., `{"y": "kcat [1/s]"}`).
+    /// Columns without a title are named after themselves.
+    labels: Option<HashMap<String, String>>,
+    /// Units of the numeric columns (e.g., `{"y": "mmol/gDW/h"}`), written
+    /// after the titles of their axes and in the legend.
+    units: Option<HashMap<String, String>>,
+    /// Named sets of values (e.g., fluxes, enzyme usage) for the same
+    /// identifiers. The selected metric replaces the fields that it defines.
+    metrics: Option<HashMap<String, Data>>,
+}
+
+impl Data {
+    /// Names of the [`Data::metrics`], sorted.
+    pub fn metric_names(&self) -> Vec<&String> {
+        self.metrics
+            .as_ref()
+            .map(|metrics| metrics.keys().sorted().collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether there are values to plot without selecting a metric.
+    pub fn has_values(&self) -> bool {
+        !self.is_empty()
+    }
+
+    /// Data with the values of the `metric` in place of the top-level ones.
+    fn with_metric(&self, metric: Option<&str>) -> Cow<'_, Data> {
+        let Some(metric) = metric.and_then(|m| self.metrics.as_ref()?.get(m)) else {
+            return Cow::Borrowed(self);
+        };
+        fn or<T: Clone>(metric: &Option<T>, data: &Option<T>) -> Option<T> {
+            metric.as_ref().or(data.as_ref()).cloned()
+        }
+        Cow::Owned(Data {
+            reactions: or(&metric.reactions, &self.reactions),
+            colors: or(&metric.colors, &self.colors),
+            sizes: or(&metric.sizes, &self.sizes),
+            y: or(&metric.y, &self.y),
+            left_y: or(&metric.left_y, &self.left_y),
+            hover_y: or(&metric.hover_y, &self.hover_y),
+            kde_y: or(&metric.kde_y, &self.kde_y),
+            kde_left_y: or(&metric.kde_left_y, &self.kde_left_y),
+            kde_hover_y: or(&metric.kde_hover_y, &self.kde_hover_y),
+            bar_y: or(&metric.bar_y, &self.bar_y),
+            bar_left_y: or(&metric.bar_left_y, &self.bar_left_y),
+            box_y: or(&metric.box_y, &self.box_y),
+            box_left_y: or(&metric.box_left_y, &self.box_left_y),
+            dg: or(&metric.dg, &self.dg),
+            dg_std: or(&metric.dg_std, &self.dg_std),
+            enzymes: or(&metric.enzymes, &self.enzymes),
+            conditions: or(&metric.conditions, &self.conditions),
+            met_conditions: or(&metric.met_conditions, &self.met_conditions),
+            groups: or(&metric.groups, &self.groups),
+            met_groups: or(&metric.met_groups, &self.met_groups),
+            metabolites: or(&metric.metabolites, &self.metabolites),
+            met_colors: or(&metric.met_colors, &self.met_colors),
+            met_sizes: or(&metric.met_sizes, &self.met_sizes),
+            mids: or(&metric.mids, &self.mids),
+            met_y: or(&metric.met_y, &self.met_y),
+            kde_met_y: or(&metric.kde_met_y, &self.kde_met_y),
+            side_met_y: or(&metric.side_met_y, &self.side_met_y),
+            kde_side_met_y: or(&metric.kde_side_met_y, &self.kde_side_met_y),
+            box_met_y: or(&metric.box_met_y, &self.box_met_y),
+            labels: or(&metric.labels, &self.labels),
+            units: or(&metric.units, &self.units),
+            metrics: None,
+        })
+    }
+
+    /// Group of each reaction and of each metabolite, by identifier.
+    pub fn groups(&self) -> (HashMap<String, String>, HashMap<String, String>) {
+        fn by_id(
+            ids: &Option<Vec<String>>,
+            groups: &Option<Vec<String>>,
+        ) -> HashMap<String, String> {
+            match (ids, groups) {
+                (Some(ids), Some(groups)) => {
+                    ids.iter().cloned().zip(groups.iter().cloned()).collect()
+                }
+                _ => HashMap::new(),
+            }
+        }
+        (
+            by_id(&self.reactions, &self.groups),
+            by_id(&self.metabolites, &self.met_groups),
+        )
+    }
+
+    /// Unit of each column that has one, by the title of the column.
+    pub fn units(&self) -> HashMap<String, String> {
+        self.units
+            .iter()
+            .flatten()
+            .map(|(column, unit)| (self.title(column), unit.clone()))
+            .collect()
+    }
+
+    /// Title of the axis where `column` is plotted.
+    fn title(&self, column: &str) -> String {
+        self.labels
+            .as_ref()
+            .and_then(|labels| labels.get(column))
+            .cloned()
+            .unwrap_or_else(|| column.to_string())
+    }
+}
+
+impl Validate for Data {
+    /// Every column needs one value per identifier, otherwise the values
+    /// cannot be told apart. Metrics are checked with the columns they inherit.
+    fn validate(&self) -> Result<(), String> {
+        self.validate_columns()?;
+        for name in self.metric_names() {
+            self.with_metric(Some(name))
+                .validate_columns()
+                .map_err(|e| format!("Metric `{name}`: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Data {
+    fn validate_columns(&self) -> Result<(), String> {
+        fn len<T>(column: &Option<Vec<T>>) -> Option<usize> {
+            column.as_ref().map(Vec::len)
+        }
+        let reaction_columns = [
+            ("colors", len(&self.colors)),
+            ("sizes", len(&self.sizes)),
+            ("y", len(&self.y)),
+            ("left_y", len(&self.left_y)),
+            ("hover_y", len(&self.hover_y)),
+            ("kde_y", len(&self.kde_y)),
+            ("kde_left_y", len(&self.kde_left_y)),
+            ("kde_hover_y", len(&self.kde_hover_y)),
+            ("bar_y", len(&self.bar_y)),
+            ("bar_left_y", len(&self.bar_left_y)),
+            ("box_y", len(&self.box_y)),
+            ("box_left_y", len(&self.box_left_y)),
+            ("dg", len(&self.dg)),
+            ("dg_std", len(&self.dg_std)),
+            ("enzymes", len(&self.enzymes)),
+            ("conditions", len(&self.conditions)),
+            ("groups", len(&self.groups)),
+        ];
+        let metabolite_columns = [
+            ("met_colors", len(&self.met_colors)),
+            ("met_sizes", len(&self.met_sizes)),
+            ("mids", len(&self.mids)),
+            ("met_y", len(&self.met_y)),
+            ("kde_met_y", len(&self.kde_met_y)),
+            ("side_met_y", len(&self.side_met_y)),
+            ("kde_side_met_y", len(&self.kde_side_met_y)),
+            ("box_met_y", len(&self.box_met_y)),
+            ("met_conditions", len(&self.met_conditions)),
+            ("met_groups", len(&self.met_groups)),
+        ];
+        for (ids, n_ids, columns) in [
+            ("reactions", len(&self.reactions), &reaction_columns[..]),
+            (
+                "metabolites",
+                len(&self.metabolites),
+                &metabolite_columns[..],
+            ),
+        ] {
+            for (column, n) in columns.iter().filter_map(|(c, n)| Some((c, (*n)?))) {
+                match n_ids {
+                    None => {
+                        return Err(format!(
+                            "`{column}` is given but there are no `{ids}` to plot it on."
+                        ))
+                    }
+                    Some(n_ids) if n != n_ids => {
+                        return Err(format!(
+                            "`{column}` has {n} values but `{ids}` has {n_ids} identifiers. \
+                            Give one value per identifier (null if it is missing)."
+                        ))
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 trait IsEmpty {
@@ -164,6 +607,124 @@ impl<T> IsEmpty for Option<Vec<T>> {
     }
 }
 
+#[cfg(feature = "parquet")]
+impl Data {
+    /// Build the data from tables in long format, with one row per sample and
+    /// the columns `reaction`, `condition` (optional), `sample` (optional, to
+    /// order the samples) and any of [`LONG_VALUE_COLUMNS`].
+    pub fn from_long_table(
+        batches: &[arrow::record_batch::RecordBatch],
+    ) -> Result<Data, LongTableError> {
+        use arrow::array::{Array, AsArray};
+        use arrow::compute::cast;
+        use arrow::datatypes::{DataType, Float32Type, Int64Type};
+
+        let value_columns: Vec<&str> = LONG_VALUE_COLUMNS
+            .into_iter()
+            .filter(|name| batches.iter().any(|b| b.column_by_name(name).is_some()))
+            .collect();
+        if value_columns.is_empty() {
+            return Err(LongTableError::Column("value"));
+        }
+        let has_conditions = batches
+            .iter()
+            .any(|b| b.column_by_name("condition").is_some());
+        // samples of each reaction and condition, with their order and a value per column
+        let mut groups: Vec<(String, String, Vec<(i64, Vec<Number>)>)> = Vec::new();
+        let mut index: HashMap<String, HashMap<String, usize>> = HashMap::new();
+        let mut order = 0;
+        for batch in batches {
+            let reactions = batch
+                .column_by_name("reaction")
+                .ok_or(LongTableError::Column("reaction"))?;
+            let reactions = cast(reactions, &DataType::Utf8)?;
+            let reactions = reactions.as_string::<i32>();
+            let conditions = batch
+                .column_by_name("condition")
+                .map(|c| cast(c, &DataType::Utf8))
+                .transpose()?;
+            let conditions = conditions.as_ref().map(|c| c.as_string::<i32>());
+            let samples = batch
+                .column_by_name("sample")
+                .map(|c| cast(c, &DataType::Int64))
+                .transpose()?;
+            let samples = samples.as_ref().map(|c| c.as_primitive::<Int64Type>());
+            let values = value_columns
+                .iter()
+                .map(|name| {
+                    batch
+                        .column_by_name(name)
+                        .map(|c| cast(c, &DataType::Float32))
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let values = values
+                .iter()
+                .map(|c| c.as_ref().map(|c| c.as_primitive::<Float32Type>()))
+                .collect::<Vec<_>>();
+            for row in 0..batch.num_rows() {
+                order += 1;
+                if reactions.is_null(row) {
+                    continue;
+                }
+                let reaction = reactions.value(row);
+                let condition = conditions
+                    .filter(|c| c.is_valid(row))
+                    .map_or("", |c| c.value(row));
+                // most rows belong to a known group, so the keys are only allocated once
+                let group = match index.get(reaction).and_then(|conds| conds.get(condition)) {
+                    Some(group) => *group,
+                    None => {
+                        groups.push((reaction.to_string(), condition.to_string(), Vec::new()));
+                        index
+                            .entry(reaction.to_string())
+                            .or_default()
+                            .insert(condition.to_string(), groups.len() - 1);
+                        groups.len() - 1
+                    }
+                };
+                let sample = samples
+                    .filter(|s| s.is_valid(row))
+                    .map_or(order, |s| s.value(row));
+                let row_values = values
+                    .iter()
+                    .map(|c| {
+                        c.filter(|c| c.is_valid(row))
+                            .map_or(Number::Null, |c| Number::Num(c.value(row)))
+                    })
+                    .collect();
+                groups[group].2.push((sample, row_values));
+            }
+        }
+        for (_, _, rows) in groups.iter_mut() {
+            rows.sort_by_key(|(sample, _)| *sample);
+        }
+        let mut data = Data {
+            reactions: Some(groups.iter().map(|(r, _, _)| r.clone()).collect()),
+            conditions: has_conditions.then(|| groups.iter().map(|(_, c, _)| c.clone()).collect()),
+            ..Default::default()
+        };
+        for (i, name) in value_columns.into_iter().enumerate() {
+            let column = groups
+                .iter()
+                .map(|(_, _, rows)| rows.iter().map(|(_, values)| values[i].clone()).collect())
+                .collect();
+            let field = match name {
+                "left_y" => &mut data.left_y,
+                "hover_y" => &mut data.hover_y,
+                "kde_y" => &mut data.kde_y,
+                "kde_left_y" => &mut data.kde_left_y,
+                "kde_hover_y" => &mut data.kde_hover_y,
+                "bar_y" => &mut data.bar_y,
+                "bar_left_y" => &mut data.bar_left_y,
+                _ => &mut data.y,
+            };
+            *field = Some(column);
+        }
+        Ok(data)
+    }
+}
+
 impl IsEmpty for Data {
     #[rustfmt::skip]
     /// [`Data`] is empty if no identifiers are passed or no numeric data is passed.
@@ -174,19 +735,128 @@ impl IsEmpty for Data {
         }
         self.colors.is_empty() & self.sizes.is_empty() & self.y.is_empty() &
         self.left_y.is_empty() & self.hover_y.is_empty() & self.kde_y.is_empty() &
-        self.kde_left_y.is_empty() & self.kde_hover_y.is_empty() & self.box_y.is_empty() &
-        self.box_left_y.is_empty() & self.conditions.is_empty() & self.met_conditions.is_empty() &
-        self.met_colors.is_empty() & self.met_sizes.is_empty() & self.met_y.is_empty() & self.kde_met_y.is_empty()
+        self.kde_left_y.is_empty() & self.kde_hover_y.is_empty() & self.bar_y.is_empty() &
+        self.bar_left_y.is_empty() & self.box_y.is_empty() &
+        self.box_left_y.is_empty() & self.dg.is_empty() & self.enzymes.is_empty() & self.conditions.is_empty() & self.met_conditions.is_empty() &
+        self.met_colors.is_empty() & self.met_sizes.is_empty() & self.mids.is_empty() & self.met_y.is_empty() & self.kde_met_y.is_empty() &
+        self.side_met_y.is_empty() & self.kde_side_met_y.is_empty() & self.box_met_y.is_empty()
     }
 }
 
-/// Resource that contains a [`Handle`] to user data. Modified when new datas comes in.
-#[derive(Resource)]
+/// Transformation applied to the numeric values of a [`Dataset`] before plotting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataTransform {
+    #[default]
+    Identity,
+    Log10,
+    Abs,
+    /// Standardize with the mean and standard deviation across identifiers.
+    ZScore,
+    /// Rescale to `[0, 1]` with the minimum and maximum across identifiers.
+    MinMax,
+}
+
+impl DataTransform {
+    pub const ALL: [DataTransform; 5] = [
+        DataTransform::Identity,
+        DataTransform::Log10,
+        DataTransform::Abs,
+        DataTransform::ZScore,
+        DataTransform::MinMax,
+    ];
+
+    /// Transform the `values` in place. The statistics of [`DataTransform::ZScore`]
+    /// and [`DataTransform::MinMax`] are computed over all of them.
+    pub fn apply<'a>(self, values: impl IntoIterator<Item = &'a mut f32>) {
+        let mut values: Vec<&mut f32> = values.into_iter().collect();
+        if values.is_empty() {
+            return;
+        }
+        match self {
+            DataTransform::Identity => (),
+            DataTransform::Log10 => values.iter_mut().for_each(|x| **x = x.log10()),
+            DataTransform::Abs => values.iter_mut().for_each(|x| **x = x.abs()),
+            DataTransform::ZScore => {
+                let n = values.len() as f32;
+                let mean = values.iter().map(|x| **x).sum::<f32>() / n;
+                let std = (values.iter().map(|x| (**x - mean).powi(2)).sum::<f32>() / n).sqrt();
+                // constant data is only centered
+                let std = if std > 0. { std } else { 1. };
+                values.iter_mut().for_each(|x| **x = (**x - mean) / std);
+            }
+            DataTransform::MinMax => {
+                let min = values.iter().map(|x| **x).fold(f32::INFINITY, f32::min);
+                let max = values.iter().map(|x| **x).fold(f32::NEG_INFINITY, f32::max);
+                let range = if max > min { max - min } else { 1. };
+                values.iter_mut().for_each(|x| **x = (**x - min) / range);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for DataTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                DataTransform::Identity => "none",
+                DataTransform::Log10 => "log10",
+                DataTransform::Abs => "|x|",
+                DataTransform::ZScore => "z-score",
+                DataTransform::MinMax => "min-max",
+            }
+        )
+    }
+}
+
+/// A user data file loaded in the app.
+pub struct Dataset {
+    pub name: String,
+    pub handle: Handle<Data>,
+    /// Inactive datasets are kept but not plotted.
+    pub active: bool,
+    pub transform: DataTransform,
+    /// Selected metric of [`Data::metrics`], `None` for the top-level values.
+    pub metric: Option<String>,
+    /// Drawing layer of the plots, see [`aesthetics::Layer`].
+    pub layer: i32,
+    /// How the sizes of the reactions are drawn.
+    pub arrow_style: ArrowStyle,
+}
+
+/// Resource that contains the [`Handle`]s to user data. Modified when new datas comes in.
+///
+/// All active datasets are plotted in order, so that later datasets are drawn
+/// on top of the previous ones.
+#[derive(Resource, Default)]
 pub struct ReactionState {
-    pub reaction_data: Option<Handle<Data>>,
+    pub datasets: Vec<Dataset>,
     pub loaded: bool,
 }
 
+impl ReactionState {
+    /// Add a dataset or replace the data of the dataset with the same name.
+    pub fn add(&mut self, name: impl Into<String>, handle: Handle<Data>) {
+        let name = name.into();
+        if let Some(dataset) = self.datasets.iter_mut().find(|d| d.name == name) {
+            dataset.handle = handle;
+        } else {
+            self.datasets.push(Dataset {
+                name,
+                handle,
+                active: true,
+                transform: DataTransform::default(),
+                metric: None,
+                layer: 0,
+                arrow_style: ArrowStyle::default(),
+            });
+        }
+        self.loaded = false;
+    }
+}
+
 struct GgPair<'a, Aes, Geom> {
     aes_component: Aes,
     geom_component: Geom,
@@ -200,37 +870,93 @@ fn load_data(
     mut commands: Commands,
     mut state: ResMut<ReactionState>,
     mut info_state: ResMut<Info>,
-    mut custom_assets: ResMut<Assets<Data>>,
+    custom_assets: Res<Assets<Data>>,
     asset_server: Res<AssetServer>,
     mut restore_event: EventWriter<aesthetics::RestoreEvent>,
+    mut groups: ResMut<aesthetics::Groups>,
+    mut units: ResMut<aesthetics::Units>,
     // remove data to be plotted, axes and histograms
-    to_remove: Query<Entity, Or<(With<aesthetics::Aesthetics>, With<HistTag>, With<Xaxis>)>>,
+    to_remove: Query<
+        Entity,
+        Or<(
+            With<aesthetics::Aesthetics>,
+            With<HistTag>,
+            With<Xaxis>,
+            With<geom::ThermoTag>,
+            With<geom::StackTag>,
+            With<geom::PieTag>,
+            With<geom::WhiskerTag>,
+        )>,
+    >,
 ) {
-    let custom_asset = if let Some(reac_handle) = &state.reaction_data {
-        if let Some(bevy::asset::LoadState::Failed) = asset_server.get_load_state(reac_handle) {
-            info_state
-                .notify("Failed loading data! Check if your metabolism.json is in correct format.");
-            state.reaction_data = None;
-            return;
-        }
-        custom_assets.get_mut(reac_handle.id())
-    } else {
+    if state.loaded {
         return;
-    };
-    if state.loaded || custom_asset.is_none() {
+    }
+    let before = state.datasets.len();
+    state.datasets.retain(|dataset| {
+        !matches!(
+            asset_server.get_load_state(&dataset.handle),
+            Some(bevy::asset::LoadState::Failed)
+        )
+    });
+    if state.datasets.len() < before {
+        info_state
+            .notify("Failed loading data! Check if your metabolism.json is in correct format.");
         return;
     }
-
-    let data = custom_asset.unwrap();
-    if data.is_empty() {
+    // wait until all datasets are available
+    if state
+        .datasets
+        .iter()
+        .any(|dataset| !custom_assets.contains(&dataset.handle))
+    {
         return;
     }
+
     info_state.notify("Loading data...");
     // remove all previous plotted data
     for e in to_remove.iter() {
         commands.entity(e).despawn_recursive();
     }
     restore_event.send(aesthetics::RestoreEvent {});
+    *groups = aesthetics::Groups::default();
+    for dataset in state.datasets.iter_mut().filter(|dataset| dataset.active) {
+        let data = custom_assets.get(&dataset.handle).unwrap();
+        if dataset.metric.is_none() && data.is_empty() {
+            // files with only metrics start with the first one
+            dataset.metric = data.metric_names().first().map(|m| m.to_string());
+        }
+        let data = data.with_metric(dataset.metric.as_deref());
+        let (reactions, metabolites) = data.groups();
+        groups.reactions.extend(reactions);
+        groups.metabolites.extend(metabolites);
+        for (title, unit) in data.units() {
+            // units edited in the settings are kept when the data is reloaded
+            units.0.entry(title).or_insert(unit);
+        }
+        if data.is_empty() {
+            continue;
+        }
+        spawn_data(
+            &mut commands,
+            &data,
+            dataset.transform,
+            dataset.layer,
+            dataset.arrow_style,
+        );
+    }
+    state.loaded = true;
+    info_state.close()
+}
+
+/// Spawn the aesthetics of one [`Data`] file.
+fn spawn_data(
+    commands: &mut Commands,
+    data: &Data,
+    transform: DataTransform,
+    layer: i32,
+    arrow_style: ArrowStyle,
+) {
     let conditions = data
         .conditions
         .clone()
@@ -256,15 +982,20 @@ fn load_data(
                 .iter()
                 .map(|i| reactions[*i].clone())
                 .collect::<Vec<String>>();
-            if let Some(ref mut point_data) = &mut data.colors {
+            if let Some(point_data) = &data.colors {
                 insert_geom_map(
-                    &mut commands,
+                    commands,
                     &indices,
                     point_data,
                     &identifiers,
+                    transform,
+                    data.title("colors"),
                     GgPair {
                         aes_component: aesthetics::Gcolor {},
-                        geom_component: geom::GeomArrow { plotted: false },
+                        geom_component: geom::GeomArrow {
+                            plotted: false,
+                            style: arrow_style,
+                        },
                         cond,
                         hover: false,
                         met: false,
@@ -272,16 +1003,21 @@ fn load_data(
                 );
             }
 
-            if let Some(ref mut point_data) = &mut data.sizes {
+            if let Some(point_data) = &data.sizes {
                 {
                     insert_geom_map(
-                        &mut commands,
+                        commands,
                         &indices,
                         point_data,
                         &identifiers,
+                        transform,
+                        data.title("sizes"),
                         GgPair {
                             aes_component: aesthetics::Gsize {},
-                            geom_component: geom::GeomArrow { plotted: false },
+                            geom_component: geom::GeomArrow {
+                                plotted: false,
+                                style: arrow_style,
+                            },
                             cond,
                             hover: false,
                             met: false,
@@ -289,73 +1025,113 @@ fn load_data(
                     );
                 };
             }
-            for (i, (aes, geom_component)) in [
-                (&mut data.y, GeomHist::right(HistPlot::Hist)),
-                (&mut data.left_y, GeomHist::left(HistPlot::Hist)),
-                (&mut data.kde_y, GeomHist::right(HistPlot::Kde)),
-                (&mut data.kde_left_y, GeomHist::left(HistPlot::Kde)),
-                (&mut data.hover_y, GeomHist::up(HistPlot::Hist)),
-                (&mut data.kde_hover_y, GeomHist::up(HistPlot::Kde)),
+            for (i, (column, aes, geom_component)) in [
+                ("y", &data.y, GeomHist::right(HistPlot::Hist)),
+                ("left_y", &data.left_y, GeomHist::left(HistPlot::Hist)),
+                ("kde_y", &data.kde_y, GeomHist::right(HistPlot::Kde)),
+                (
+                    "kde_left_y",
+                    &data.kde_left_y,
+                    GeomHist::left(HistPlot::Kde),
+                ),
+                ("bar_y", &data.bar_y, GeomHist::right(HistPlot::ErrorBar)),
+                (
+                    "bar_left_y",
+                    &data.bar_left_y,
+                    GeomHist::left(HistPlot::ErrorBar),
+                ),
+                ("hover_y", &data.hover_y, GeomHist::up(HistPlot::Hist)),
+                (
+                    "kde_hover_y",
+                    &data.kde_hover_y,
+                    GeomHist::up(HistPlot::Kde),
+                ),
             ]
             .into_iter()
             .enumerate()
             {
-                if let Some(dist_data) = aes.as_mut() {
+                if let Some(dist_data) = aes.as_ref() {
                     insert_geom_hist(
-                        &mut commands,
+                        commands,
                         dist_data,
                         &indices,
                         &identifiers,
+                        transform,
+                        layer,
+                        data.title(column),
                         GgPair {
                             aes_component: aesthetics::Gy {},
                             geom_component,
                             cond,
-                            hover: i > 3,
+                            hover: i > 5,
                             met: false,
                         },
                     );
                 }
             }
-            for (var, geom) in [
-                (&mut data.box_y, GeomHist::right(HistPlot::BoxPoint)),
-                (&mut data.box_left_y, GeomHist::left(HistPlot::BoxPoint)),
+            if let Some(dg_data) = &data.dg {
+                insert_geom_thermo(
+                    commands,
+                    &indices,
+                    dg_data,
+                    data.dg_std.as_deref(),
+                    &identifiers,
+                    cond,
+                    data.title("dg"),
+                );
+            }
+            if let Some(enzyme_data) = &data.enzymes {
+                insert_geom_parts(
+                    commands,
+                    &indices,
+                    |i| {
+                        enzyme_data
+                            .get(i)
+                            .into_iter()
+                            .flatten()
+                            // filter values that are NaN
+                            .filter_map(|(name, value)| value.as_ref().map(|v| (name.clone(), *v)))
+                            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+                            .collect()
+                    },
+                    &identifiers,
+                    cond,
+                    data.title("enzymes"),
+                    geom::GeomStack { plotted: false },
+                );
+            }
+            for (column, var, geom) in [
+                ("box_y", &data.box_y, GeomHist::right(HistPlot::BoxPoint)),
+                (
+                    "box_left_y",
+                    &data.box_left_y,
+                    GeomHist::left(HistPlot::BoxPoint),
+                ),
             ]
             .into_iter()
             {
                 if let Some(point_data) = var {
-                    let (mut data, ids): (Vec<f32>, Vec<String>) = indices
-                        .iter()
-                        .map(|i| &point_data[*i])
-                        .zip(identifiers.iter())
-                        // filter values that are NaN
-                        .filter_map(|(col, id)| col.as_ref().map(|x| (*x, id.clone())))
-                        .unzip();
-                    if data.is_empty() {
-                        continue;
-                    }
-                    commands.spawn((
-                        aesthetics::Gy {},
-                        aesthetics::Point(std::mem::take(&mut data)),
-                        geom,
-                        AesFilter {
+                    insert_geom_box(
+                        commands,
+                        point_data,
+                        &indices,
+                        &identifiers,
+                        transform,
+                        layer,
+                        data.title(column),
+                        GgPair {
+                            aes_component: aesthetics::Gy {},
+                            geom_component: geom,
+                            cond,
+                            hover: false,
                             met: false,
-                            pbox: true,
-                        },
-                        aesthetics::Aesthetics {
-                            identifiers: ids,
-                            condition: if cond.is_empty() {
-                                None
-                            } else {
-                                Some(cond.to_string())
-                            },
                         },
-                    ));
+                    );
                 }
             }
         }
     }
 
-    info_state.notify("Loading Metabolite data!");
     let conditions = data
         .met_conditions
         .clone()
@@ -381,12 +1157,14 @@ fn load_data(
                 .iter()
                 .map(|i| metabolites[*i].clone())
                 .collect::<Vec<String>>();
-            if let Some(color_data) = &mut data.met_colors {
+            if let Some(color_data) = &data.met_colors {
                 insert_geom_map(
-                    &mut commands,
+                    commands,
                     &indices,
                     color_data,
                     &identifiers,
+                    transform,
+                    data.title("met_colors"),
                     GgPair {
                         aes_component: aesthetics::Gcolor {},
                         geom_component: geom::GeomMetabolite { plotted: false },
@@ -396,12 +1174,14 @@ fn load_data(
                     },
                 );
             }
-            if let Some(size_data) = &mut data.met_sizes {
+            if let Some(size_data) = &data.met_sizes {
                 insert_geom_map(
-                    &mut commands,
+                    commands,
                     &indices,
                     size_data,
                     &identifiers,
+                    transform,
+                    data.title("met_sizes"),
                     GgPair {
                         aes_component: aesthetics::Gsize {},
                         geom_component: geom::GeomMetabolite { plotted: false },
@@ -411,92 +1191,274 @@ fn load_data(
                     },
                 );
             }
-            for (aes, geom_component) in [
-                (&mut data.met_y, GeomHist::up(HistPlot::Hist)),
-                (&mut data.kde_met_y, GeomHist::up(HistPlot::Kde)),
+            if let Some(mid_data) = &data.mids {
+                insert_geom_parts(
+                    commands,
+                    &indices,
+                    |i| {
+                        mid_data
+                            .get(i)
+                            .into_iter()
+                            .flatten()
+                            .enumerate()
+                            // filter values that are NaN
+                            .filter_map(|(m, value)| value.as_ref().map(|v| (format!("M+{m}"), *v)))
+                            .collect()
+                    },
+                    &identifiers,
+                    cond,
+                    data.title("mids"),
+                    geom::GeomPie { plotted: false },
+                );
+            }
+            for (i, (column, aes, geom_component)) in [
+                ("met_y", &data.met_y, GeomHist::up(HistPlot::Hist)),
+                ("kde_met_y", &data.kde_met_y, GeomHist::up(HistPlot::Kde)),
+                (
+                    "side_met_y",
+                    &data.side_met_y,
+                    GeomHist::right(HistPlot::Hist),
+                ),
+                (
+                    "kde_side_met_y",
+                    &data.kde_side_met_y,
+                    GeomHist::right(HistPlot::Kde),
+                ),
             ]
             .into_iter()
+            .enumerate()
             {
                 if let Some(dist_data) = aes {
                     insert_geom_hist(
-                        &mut commands,
+                        commands,
                         dist_data,
                         &indices,
                         &identifiers,
+                        transform,
+                        layer,
+                        data.title(column),
                         GgPair {
                             aes_component: aesthetics::Gy {},
                             geom_component,
                             cond,
-                            hover: true,
+                            hover: i < 2,
                             met: true,
                         },
                     );
                 }
             }
+            if let Some(point_data) = &data.box_met_y {
+                insert_geom_box(
+                    commands,
+                    point_data,
+                    &indices,
+                    &identifiers,
+                    transform,
+                    layer,
+                    data.title("box_met_y"),
+                    GgPair {
+                        aes_component: aesthetics::Gy {},
+                        geom_component: GeomHist::right(HistPlot::BoxPoint),
+                        cond,
+                        hover: false,
+                        met: true,
+                    },
+                );
+            }
         }
     }
-
-    state.loaded = true;
-    info_state.close()
 }
 
+/// Spawn the values of a color or size column. Identifiers given as
+/// distributions are summarized into a point, see [`aesthetics::Summarized`].
 fn insert_geom_map<Aes: Component, Geom: Component>(
     commands: &mut Commands,
     indices: &HashSet<usize>,
-    aes_data: &[Number],
+    aes_data: &[Value],
     identifiers: &[String],
+    transform: DataTransform,
+    title: String,
     ggcomp: GgPair<Aes, Geom>,
 ) {
-    let (mut data, ids): (Vec<f32>, Vec<String>) = indices
+    let (mut data, ids, original, summarized) = if aes_data.iter().any(Value::is_samples) {
+        let (mut samples, mut ids): (Vec<(Vec<f32>, Vec<f32>)>, Vec<String>) = indices
+            .iter()
+            .map(|i| aes_data[*i].samples())
+            .zip(identifiers.iter())
+            // filter values that are NaN
+            .filter(|(samples, _)| !samples.is_empty())
+            .map(|(samples, id)| {
+                let weights = vec![1.; samples.len()];
+                ((samples, weights), id.clone())
+            })
+            .unzip();
+        let original = transform_samples(transform, &mut samples, &mut ids);
+        let summarized = aesthetics::Summarized {
+            samples: samples.into_iter().map(|(samples, _)| samples).collect(),
+            original,
+        };
+        let (data, original) = summarized.summarize(Summary::default(), 0.5);
+        (data, ids, original, Some(summarized))
+    } else {
+        let (mut data, mut ids): (Vec<f32>, Vec<String>) = indices
+            .iter()
+            .map(|i| &aes_data[*i])
+            .zip(identifiers.iter())
+            // filter values that are NaN
+            .filter_map(|(col, id)| col.as_ref().map(|x| (*x, id.clone())))
+            .unzip();
+        let original = transform_points(transform, &mut data, &mut ids);
+        (data, ids, original, None)
+    };
+    if data.is_empty() {
+        return;
+    }
+    let missing = missing_ids(identifiers, &ids);
+    let mut ent_commands = commands.spawn(aesthetics::Aesthetics {
+        identifiers: ids,
+        condition: if ggcomp.cond.is_empty() {
+            None
+        } else {
+            Some(ggcomp.cond.to_string())
+        },
+    });
+    ent_commands
+        .insert(ggcomp.aes_component)
+        .insert(aesthetics::Point(std::mem::take(&mut data)))
+        .insert(aesthetics::ColumnName(title))
+        .insert(ggcomp.geom_component);
+    if let Some(original) = original {
+        ent_commands.insert(aesthetics::Untransformed(original));
+    }
+    if let Some(summarized) = summarized {
+        ent_commands.insert(summarized);
+    }
+    if !missing.is_empty() {
+        ent_commands.insert(aesthetics::Missing(missing));
+    }
+}
+
+/// Spawn the ΔG of the reactions with their standard deviations, if any.
+///
+/// The transform of the dataset is not applied, since the sign of ΔG is
+/// what the gauges show.
+fn insert_geom_thermo(
+    commands: &mut Commands,
+    indices: &HashSet<usize>,
+    dg_data: &[Number],
+    std_data: Option<&[Number]>,
+    identifiers: &[String],
+    cond: &str,
+    title: String,
+) {
+    let ((data, stds), ids): ((Vec<f32>, Vec<f32>), Vec<String>) = indices
         .iter()
-        .map(|i| &aes_data[*i])
         .zip(identifiers.iter())
         // filter values that are NaN
-        .filter_map(|(col, id)| col.as_ref().map(|x| (*x, id.clone())))
+        .filter_map(|(i, id)| {
+            let dg = *dg_data[*i].as_ref()?;
+            let std = std_data
+                .and_then(|stds| stds.get(*i))
+                .and_then(|std| std.as_ref().copied())
+                .unwrap_or(0.);
+            Some(((dg, std), id.clone()))
+        })
         .unzip();
     if data.is_empty() {
         return;
     }
-    commands
-        .spawn(aesthetics::Aesthetics {
+    let missing = missing_ids(identifiers, &ids);
+    let mut ent_commands = commands.spawn((
+        aesthetics::Aesthetics {
             identifiers: ids,
-            condition: if ggcomp.cond.is_empty() {
+            condition: if cond.is_empty() {
                 None
             } else {
-                Some(ggcomp.cond.to_string())
+                Some(cond.to_string())
             },
+        },
+        aesthetics::Gy {},
+        aesthetics::Point(data),
+        aesthetics::Uncertainty(stds),
+        aesthetics::ColumnName(title),
+        geom::GeomThermo { plotted: false },
+    ));
+    if !missing.is_empty() {
+        ent_commands.insert(aesthetics::Missing(missing));
+    }
+}
+
+/// Spawn the named parts of the elements, as given by `parts_of` for each
+/// index of the data. Elements without parts are missing.
+fn insert_geom_parts<Geom: Component>(
+    commands: &mut Commands,
+    indices: &HashSet<usize>,
+    parts_of: impl Fn(usize) -> Vec<(String, f32)>,
+    identifiers: &[String],
+    cond: &str,
+    title: String,
+    geom_component: Geom,
+) {
+    let (parts, ids): (Vec<Vec<(String, f32)>>, Vec<String>) = indices
+        .iter()
+        .zip(identifiers.iter())
+        .filter_map(|(i, id)| {
+            let parts = parts_of(*i);
+            (!parts.is_empty()).then(|| (parts, id.clone()))
         })
-        .insert(ggcomp.aes_component)
-        .insert(aesthetics::Point(std::mem::take(&mut data)))
-        .insert(ggcomp.geom_component);
+        .unzip();
+    if parts.is_empty() {
+        return;
+    }
+    let missing = missing_ids(identifiers, &ids);
+    let mut ent_commands = commands.spawn((
+        aesthetics::Aesthetics {
+            identifiers: ids,
+            condition: if cond.is_empty() {
+                None
+            } else {
+                Some(cond.to_string())
+            },
+        },
+        aesthetics::Gy {},
+        aesthetics::Parts(parts),
+        aesthetics::ColumnName(title),
+        geom_component,
+    ));
+    if !missing.is_empty() {
+        ent_commands.insert(aesthetics::Missing(missing));
+    }
 }
 
 fn insert_geom_hist<Aes: Component, Geom: Component>(
     commands: &mut Commands,
-    dist_data: &mut [Vec<Number>],
+    dist_data: &[Vec<Number>],
     indices: &HashSet<usize>,
     identifiers: &[String],
+    transform: DataTransform,
+    layer: i32,
+    title: String,
     ggcomp: GgPair<Aes, Geom>,
 ) {
-    let (mut data, ids): (Vec<Vec<f32>>, Vec<String>) = indices
+    let weighted = indices
         .iter()
-        .map(|i| std::mem::take(&mut dist_data[*i]))
+        .any(|i| dist_data[*i].iter().any(Number::is_weighted));
+    let (mut data, mut ids): (Vec<(Vec<f32>, Vec<f32>)>, Vec<String>) = indices
+        .iter()
+        .map(|i| &dist_data[*i])
         // also filter values that are NaN
         .zip(identifiers.iter())
         .map(|(col, id)| {
-            (
-                std::mem::take(
-                    &mut col
-                        .into_iter()
-                        .filter_map(|c| c.into())
-                        .collect::<Vec<f32>>(),
-                ),
-                id.clone(),
-            )
+            let samples: (Vec<f32>, Vec<f32>) = col.iter().filter_map(Number::weighted).unzip();
+            (samples, id.clone())
         })
-        .filter(|(c, _)| !c.is_empty())
+        .filter(|((c, _), _)| !c.is_empty())
         .unzip();
+    let original = transform_samples(transform, &mut data, &mut ids);
     if !data.is_empty() {
+        let missing = missing_ids(identifiers, &ids);
+        let (mut data, mut weights): (Vec<Vec<f32>>, Vec<Vec<f32>>) =
+            std::mem::take(&mut data).into_iter().unzip();
         let mut ent_commands = commands.spawn(ggcomp.geom_component);
         ent_commands
             .insert(aesthetics::Aesthetics {
@@ -514,9 +1476,143 @@ fn insert_geom_hist<Aes: Component, Geom: Component>(
                     met: ggcomp.met,
                     pbox: false,
                 },
+                aesthetics::ColumnName(title),
+                aesthetics::Layer(layer),
             ));
+        if weighted {
+            ent_commands.insert(aesthetics::Weights(std::mem::take(&mut weights)));
+        }
+        if let Some(original) = original {
+            ent_commands.insert(aesthetics::Untransformed(original));
+        }
+        if !missing.is_empty() {
+            ent_commands.insert(aesthetics::Missing(missing));
+        }
         if ggcomp.hover {
             ent_commands.insert(geom::PopUp {});
         }
     }
 }
+
+/// Spawn one value per identifier to be plotted as a box on the side of its axis.
+fn insert_geom_box<Aes: Component, Geom: Component>(
+    commands: &mut Commands,
+    point_data: &[Number],
+    indices: &HashSet<usize>,
+    identifiers: &[String],
+    transform: DataTransform,
+    layer: i32,
+    title: String,
+    ggcomp: GgPair<Aes, Geom>,
+) {
+    let (mut data, mut ids): (Vec<f32>, Vec<String>) = indices
+        .iter()
+        .map(|i| &point_data[*i])
+        .zip(identifiers.iter())
+        // filter values that are NaN
+        .filter_map(|(col, id)| col.as_ref().map(|x| (*x, id.clone())))
+        .unzip();
+    let original = transform_points(transform, &mut data, &mut ids);
+    if data.is_empty() {
+        return;
+    }
+    let missing = missing_ids(identifiers, &ids);
+    let mut ent_commands = commands.spawn((
+        ggcomp.aes_component,
+        aesthetics::Point(std::mem::take(&mut data)),
+        ggcomp.geom_component,
+        AesFilter {
+            met: ggcomp.met,
+            pbox: true,
+        },
+        aesthetics::Aesthetics {
+            identifiers: ids,
+            condition: if ggcomp.cond.is_empty() {
+                None
+            } else {
+                Some(ggcomp.cond.to_string())
+            },
+        },
+        aesthetics::ColumnName(title),
+        aesthetics::Layer(layer),
+    ));
+    if let Some(original) = original {
+        ent_commands.insert(aesthetics::Untransformed(original));
+    }
+    if !missing.is_empty() {
+        ent_commands.insert(aesthetics::Missing(missing));
+    }
+}
+
+/// Identifiers that were dropped from `identifiers` because their values are missing.
+fn missing_ids(identifiers: &[String], kept: &[String]) -> Vec<String> {
+    let kept: HashSet<&String> = kept.iter().collect();
+    identifiers
+        .iter()
+        .filter(|id| !kept.contains(id))
+        .cloned()
+        .collect()
+}
+
+/// Apply `transform` to the values of each identifier, dropping those that are
+/// not finite afterwards (e.g., log10 of non-positive numbers).
+///
+/// Returns the original values that were kept, if the data was transformed.
+fn transform_points(
+    transform: DataTransform,
+    data: &mut Vec<f32>,
+    ids: &mut Vec<String>,
+) -> Option<Vec<f32>> {
+    if transform == DataTransform::Identity {
+        return None;
+    }
+    let original = data.clone();
+    transform.apply(data.iter_mut());
+    let (kept, original, kept_ids): (Vec<f32>, Vec<f32>, Vec<String>) = std::mem::take(data)
+        .into_iter()
+        .zip(original)
+        .zip(std::mem::take(ids))
+        .filter(|((x, _), _)| x.is_finite())
+        .map(|((x, o), id)| (x, o, id))
+        .multiunzip();
+    *data = kept;
+    *ids = kept_ids;
+    Some(original)
+}
+
+/// Apply `transform` to the samples of all identifiers, dropping the samples
+/// that are not finite afterwards and the identifiers left without samples.
+///
+/// Returns the original samples that were kept, if the data was transformed.
+fn transform_samples(
+    transform: DataTransform,
+    data: &mut Vec<(Vec<f32>, Vec<f32>)>,
+    ids: &mut Vec<String>,
+) -> Option<Vec<Vec<f32>>> {
+    if transform == DataTransform::Identity {
+        return None;
+    }
+    let original: Vec<Vec<f32>> = data.iter().map(|(samples, _)| samples.clone()).collect();
+    transform.apply(data.iter_mut().flat_map(|(samples, _)| samples.iter_mut()));
+    let mut kept_original = Vec::new();
+    for (((samples, weights), original), id) in std::mem::take(data)
+        .into_iter()
+        .zip(original)
+        .zip(std::mem::take(ids))
+    {
+        let (samples, weights, original): (Vec<f32>, Vec<f32>, Vec<f32>) = samples
+            .into_iter()
+            .zip(weights)
+            .zip(original)
+            .filter(|((x, _), _)| x.is_finite())
+            .map(|((x, w), o)| (x, w, o))
+            .multiunzip();
+        if samples.is_empty() {
+            continue;
+        }
+        data.push((samples, weights));
+        ids.push(id);
+        kept_original.push(original);
+    }
+    Some(kept_original)
+}
@@ -1,19 +1,22 @@
 //! Data model of escher JSON maps
 //! TODO: borrow strings
-use crate::funcplot::draw_arrow;
-use crate::geom::{GeomHist, HistTag, Side, Xaxis};
+use crate::annotation::{spawn_annotation, Annotation};
+use crate::background::MapBackground;
+use crate::bookmark::Bookmark;
+use crate::font::MapFont;
+use crate::funcplot::{draw_arrow, hexagon};
+use crate::geom::{GeomHist, HistTag, RibbonTag, Side, Xaxis};
+use crate::gui::UiState;
 use crate::info::Info;
+use crate::provenance::Provenance;
 use crate::scale::DefaultFontSize;
+use crate::theme::Theme;
 use bevy::prelude::*;
 use bevy::reflect::TypePath;
 use bevy_prototype_lyon::prelude::*;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, collections::HashMap};
-
-pub const ARROW_COLOR: Color = Color::rgba(95. / 255., 94. / 255., 95. / 255., 1.0);
-pub const MET_COLOR: Color = Color::rgb(190. / 255., 185. / 255., 185. / 255.);
-pub const MET_STROK: Color = Color::rgb(95. / 255., 94. / 255., 95. / 255.);
+use std::collections::{HashMap, HashSet};
 
 pub struct EscherPlugin;
 
@@ -21,27 +24,138 @@ impl Plugin for EscherPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(NodeToText::default())
             .insert_resource(MapDimensions::default())
-            .add_systems(Update, load_map);
+            .init_resource::<Adjacency>()
+            .init_resource::<MapFont>()
+            .add_systems(Update, (load_map, show_reversibility));
     }
 }
 
+/// A map opened in the app, shown as a tab.
+pub struct MapTab {
+    pub name: String,
+    pub handle: Handle<EscherMap>,
+}
+
+/// Resource that contains the opened maps. Only the current one is spawned.
 #[derive(Resource)]
 pub struct MapState {
-    pub escher_map: Handle<EscherMap>,
+    pub maps: Vec<MapTab>,
+    pub current: usize,
     pub loaded: bool,
 }
 
+impl MapState {
+    pub fn new(name: impl Into<String>, handle: Handle<EscherMap>) -> Self {
+        Self {
+            maps: vec![MapTab {
+                name: name.into(),
+                handle,
+            }],
+            current: 0,
+            loaded: false,
+        }
+    }
+
+    /// Handle to the map that is currently displayed.
+    pub fn escher_map(&self) -> &Handle<EscherMap> {
+        &self.maps[self.current].handle
+    }
+
+    /// Open a map in a new tab (or replace the map with the same name) and display it.
+    pub fn open(&mut self, name: impl Into<String>, handle: Handle<EscherMap>) {
+        let name = name.into();
+        if let Some(i) = self.maps.iter().position(|tab| tab.name == name) {
+            self.maps[i].handle = handle;
+            self.current = i;
+        } else {
+            self.maps.push(MapTab { name, handle });
+            self.current = self.maps.len() - 1;
+        }
+        self.loaded = false;
+    }
+
+    /// Replace the map with the same name without changing the displayed tab.
+    pub fn replace(&mut self, name: &str, handle: Handle<EscherMap>) {
+        if let Some(i) = self.maps.iter().position(|tab| tab.name == name) {
+            self.maps[i].handle = handle;
+            self.loaded &= i != self.current;
+        }
+    }
+}
+
 /// Resource to map arrow ids to their [`Entity`] for hovering purposes.
 #[derive(Resource, Default)]
 pub struct NodeToText {
     pub inner: HashMap<u64, Entity>,
 }
 
+/// Resource that links the reactions to the metabolites that they consume or
+/// produce and back, by their bigg ids. Built when the map is loaded.
+#[derive(Resource, Default)]
+pub struct Adjacency {
+    pub reactions: HashMap<String, HashSet<String>>,
+    pub metabolites: HashMap<String, HashSet<String>>,
+}
+
+impl Adjacency {
+    pub fn new(metabolism: &Metabolism) -> Self {
+        let mut adjacency = Self::default();
+        for reac in metabolism.reactions.values() {
+            for met in reac.metabolites.iter() {
+                adjacency
+                    .reactions
+                    .entry(reac.bigg_id.clone())
+                    .or_default()
+                    .insert(met.bigg_id.clone());
+                adjacency
+                    .metabolites
+                    .entry(met.bigg_id.clone())
+                    .or_default()
+                    .insert(reac.bigg_id.clone());
+            }
+        }
+        adjacency
+    }
+
+    /// Reactions and metabolites linked to the element `id` (a metabolite if
+    /// `met`), the element included.
+    pub fn linked<'a>(&'a self, id: &'a str, met: bool) -> (HashSet<&'a str>, HashSet<&'a str>) {
+        let neighbors = if met {
+            &self.metabolites
+        } else {
+            &self.reactions
+        };
+        let neighbors: HashSet<&'a str> = neighbors
+            .get(id)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        let itself = HashSet::from([id]);
+        if met {
+            (neighbors, itself)
+        } else {
+            (itself, neighbors)
+        }
+    }
+}
+
 #[derive(Deserialize, Asset, Default, Serialize, TypePath)]
 pub struct EscherMap {
-    #[allow(dead_code)]
-    info: EscherInfo,
+    pub info: EscherInfo,
     pub metabolism: Metabolism,
+    /// Background image settings, saved by shu (not part of escher).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<MapBackground>,
+    /// Annotations placed on the map, saved by shu (not part of escher).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+    /// Views of the map to jump to, saved by shu (not part of escher).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bookmarks: Vec<Bookmark>,
+    /// Map and data that were shown when it was saved by shu (not part of escher).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
 }
 
 impl EscherMap {
@@ -62,62 +176,188 @@ impl EscherMap {
 
     /// Get the coordinates of a metabolite given a node id
     pub fn met_coords(&self, met_id: &str) -> Option<Vec2> {
-        let met = self.metabolism.nodes.get(&met_id.parse().unwrap())?;
-        match met {
-            Node::Metabolite(Metabolite { x, y, .. })
-            | Node::Multimarker { x, y }
-            | Node::Midmarker { x, y } => Some(Vec2::new(*x, *y)),
-        }
+        self.metabolism
+            .nodes
+            .get(&met_id.parse().unwrap())
+            .map(Node::pos)
     }
 
-    /// Reaction direction as defined by the vector that follows the longest segment.
-    /// This is needed to calculate rotation angles for elements at the side of the
-    /// reactions.
-    pub fn main_direction(&self, reac: &Reaction) -> Vec2 {
-        reac.segments
+    /// Mean position of the metabolites, which is placed at the origin of the world.
+    pub fn center(&self) -> Vec2 {
+        let (total, n) = self
+            .metabolism
+            .nodes
             .values()
-            .filter_map(|seg| {
-                self.metabolism
-                    .nodes
-                    .get(&seg.from_node_id.parse().unwrap())
-            })
-            .chain(
-                reac.segments
-                    .values()
-                    .filter_map(|seg| self.metabolism.nodes.get(&seg.to_node_id.parse().unwrap())),
-            )
             .filter_map(|node| match node {
-                Node::Metabolite(Metabolite {
-                    x,
-                    y,
-                    node_is_primary,
-                    ..
-                }) if *node_is_primary => Some(Vec2::new(*x, *y)),
+                Node::Metabolite(met) => Some(Vec2::new(met.x, met.y)),
                 _ => None,
             })
-            .combinations(2)
-            .map(|vec| vec[1] - vec[0])
-            // avoid zero vectors
-            .filter(|vec| vec.max_element() > 1e-5)
-            .max_by(|x, y| {
-                if x.length() - y.length() > 1e-5 {
-                    Ordering::Less
-                } else {
-                    Ordering::Greater
+            .fold((Vec2::ZERO, 0.), |(total, n), pos| (total + pos, n + 1.));
+        total / n
+    }
+
+    /// Move an element of the map to `pos`, in the coordinates of the map.
+    ///
+    /// A metabolite takes its label along. Since it also shifts the center of
+    /// the map, the stored histograms, annotations and bookmarks are moved the
+    /// other way so that they stay in place once the map is spawned again.
+    pub fn move_element(&mut self, element: Editable, pos: Vec2) {
+        match element {
+            Editable::Metabolite(node_id) => {
+                let old_center = self.center();
+                let Some(Node::Metabolite(met)) = self.metabolism.nodes.get_mut(&node_id) else {
+                    return;
+                };
+                let delta = pos - Vec2::new(met.x, met.y);
+                (met.x, met.y) = (pos.x, pos.y);
+                met.label_x += delta.x;
+                met.label_y += delta.y;
+                // escher and bevy defines "y" in the opposite direction
+                let shift = self.center() - old_center;
+                let shift = Vec2::new(-shift.x, shift.y);
+                for trans in self
+                    .metabolism
+                    .reactions
+                    .values_mut()
+                    .filter_map(|reac| reac.hist_position.as_mut())
+                    .flat_map(|hists| hists.values_mut())
+                {
+                    trans.translation += shift.extend(0.);
+                }
+                for annotation in self.annotations.iter_mut() {
+                    annotation.position += shift;
                 }
+                for bookmark in self.bookmarks.iter_mut() {
+                    bookmark.position += shift;
+                }
+            }
+            Editable::MetaboliteLabel(node_id) => {
+                if let Some(Node::Metabolite(met)) = self.metabolism.nodes.get_mut(&node_id) {
+                    (met.label_x, met.label_y) = (pos.x, pos.y);
+                }
+            }
+            Editable::ReactionLabel(node_id) => {
+                if let Some(reac) = self.metabolism.reactions.get_mut(&node_id) {
+                    (reac.label_x, reac.label_y) = (pos.x, pos.y);
+                }
+            }
+        }
+    }
+
+    /// Whether the node is a secondary metabolite (cofactor).
+    fn is_secondary(&self, node_id: &str) -> bool {
+        matches!(
+            node_id
+                .parse()
+                .ok()
+                .and_then(|id| self.metabolism.nodes.get(&id)),
+            Some(Node::Metabolite(Metabolite {
+                node_is_primary: false,
+                ..
+            }))
+        )
+    }
+
+    /// Reaction direction as defined by the vector that follows the longest segment
+    /// between primary metabolites. If the reaction does not have two primary
+    /// metabolites, the nodes of its segments (including markers) are used instead.
+    /// This is needed to calculate rotation angles for elements at the side of the
+    /// reactions.
+    pub fn main_direction(&self, reac: &Reaction) -> Vec2 {
+        let nodes: Vec<&Node> = reac
+            .segments
+            .values()
+            .flat_map(|seg| [&seg.from_node_id, &seg.to_node_id])
+            .unique()
+            .filter_map(|id| {
+                id.parse::<u64>()
+                    .ok()
+                    .and_then(|id| self.metabolism.nodes.get(&id))
             })
+            .collect();
+        let primary = nodes.iter().filter_map(|node| match node {
+            Node::Metabolite(Metabolite {
+                x,
+                y,
+                node_is_primary: true,
+                ..
+            }) => Some(Vec2::new(*x, *y)),
+            _ => None,
+        });
+        longest_vector(primary)
+            .or_else(|| longest_vector(nodes.iter().map(|node| node.pos())))
             .unwrap_or(Vec2::Y)
             .normalize()
     }
 }
 
-#[derive(Deserialize, Serialize, Default)]
-struct EscherInfo {
-    map_name: String,
-    map_id: String,
-    map_description: String,
-    homepage: String,
-    schema: String,
+/// Distance (in map units) under which the ends of segments are considered the
+/// same when looking for reactions drawn on top of each other.
+const BUNDLE_TOLERANCE: f32 = 5.;
+/// Separation between the arrows of reactions drawn on top of each other.
+const BUNDLE_SPACING: f32 = 12.;
+
+impl EscherMap {
+    /// Reactions drawn on top of each other (e.g., isozymes drawn as separate
+    /// reactions), as their position in the bundle and the size of the bundle.
+    pub fn duplicate_bundles(
+        &self,
+        reactions: &HashMap<u64, Reaction>,
+    ) -> HashMap<u64, (usize, usize)> {
+        let snap = |pos: Vec2| {
+            let pos = (pos / BUNDLE_TOLERANCE).round();
+            (pos.x as i32, pos.y as i32)
+        };
+        let mut bundles: HashMap<Vec<((i32, i32), (i32, i32))>, Vec<u64>> = HashMap::new();
+        for (node_id, reac) in reactions {
+            let mut shape: Vec<_> = reac
+                .segments
+                .values()
+                .filter_map(|seg| {
+                    let from = snap(self.met_coords(&seg.from_node_id)?);
+                    let to = snap(self.met_coords(&seg.to_node_id)?);
+                    // the same segment may be drawn in the other direction
+                    Some(if from < to { (from, to) } else { (to, from) })
+                })
+                .collect();
+            if shape.is_empty() {
+                continue;
+            }
+            shape.sort();
+            bundles.entry(shape).or_default().push(*node_id);
+        }
+        bundles
+            .into_values()
+            .filter(|ids| ids.len() > 1)
+            .flat_map(|mut ids| {
+                ids.sort();
+                let size = ids.len();
+                ids.into_iter()
+                    .enumerate()
+                    .map(move |(i, node_id)| (node_id, (i, size)))
+            })
+            .collect()
+    }
+}
+
+/// Longest vector between any two `points`, if they are not all in the same place.
+fn longest_vector(points: impl Iterator<Item = Vec2>) -> Option<Vec2> {
+    points
+        .combinations(2)
+        .map(|pair| pair[1] - pair[0])
+        // avoid zero vectors
+        .filter(|vec| vec.length() > 1e-5)
+        .max_by(|x, y| x.length().total_cmp(&y.length()))
+}
+
+/// Metadata of an escher map.
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+pub struct EscherInfo {
+    pub map_name: String,
+    pub map_id: String,
+    pub map_description: String,
+    pub homepage: String,
+    pub schema: String,
 }
 
 #[derive(Deserialize, Serialize, Default)]
@@ -165,6 +405,9 @@ pub struct Reaction {
     label_y: f32,
     gene_reaction_rule: String,
     pub hist_position: Option<HashMap<Side, SerTransform>>,
+    /// Height of the histograms relative to the global height of their side.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hist_height: Option<HashMap<Side, f32>>,
     // genes: Vec<HashMap<String, String>>,
     metabolites: Vec<MetRef>,
     pub segments: HashMap<u32, Segment>,
@@ -178,10 +421,23 @@ enum MetImportance {
 
 impl Reaction {
     fn get_products(&self, metab: &Metabolism) -> HashMap<String, (bool, MetImportance)> {
+        self.get_nodes(metab, |coefficient| coefficient > 1e-6)
+    }
+
+    fn get_reactants(&self, metab: &Metabolism) -> HashMap<String, (bool, MetImportance)> {
+        self.get_nodes(metab, |coefficient| coefficient < -1e-6)
+    }
+
+    /// Node ids of the metabolites whose stoichiometric coefficient passes `filter`.
+    fn get_nodes(
+        &self,
+        metab: &Metabolism,
+        filter: impl Fn(f32) -> bool,
+    ) -> HashMap<String, (bool, MetImportance)> {
         let met_to_node_id: HashMap<&str, (&str, MetImportance)> = self
             .segments
-            .iter()
-            .flat_map(|(_, seg)| [&seg.from_node_id, &seg.to_node_id])
+            .values()
+            .flat_map(|seg| [&seg.from_node_id, &seg.to_node_id])
             .filter_map(|node| metab.nodes.get(&node.parse().unwrap()).map(|x| (x, node)))
             .filter_map(|(met, x)| match met {
                 Node::Metabolite(Metabolite {
@@ -204,7 +460,7 @@ impl Reaction {
             .collect();
         self.metabolites
             .iter()
-            .filter(|met| met.coefficient > 1e-6)
+            .filter(|met| filter(met.coefficient))
             .map(|met| {
                 (
                     met_to_node_id[met.bigg_id.as_str()].0.to_string(),
@@ -243,6 +499,16 @@ enum Node {
     Midmarker { x: f32, y: f32 },
 }
 
+impl Node {
+    fn pos(&self) -> Vec2 {
+        match self {
+            Node::Metabolite(Metabolite { x, y, .. })
+            | Node::Multimarker { x, y }
+            | Node::Midmarker { x, y } => Vec2::new(*x, *y),
+        }
+    }
+}
+
 /// Component for Bevy that will be rendered on screen.
 /// Rendered as circles.
 #[derive(Component, Deserialize, Clone, Serialize)]
@@ -261,6 +527,63 @@ pub struct Metabolite {
 pub struct CircleTag {
     pub id: String,
 }
+/// Marker for the circles and labels of secondary metabolites (cofactors).
+#[derive(Component)]
+pub struct Secondary;
+/// Part of the arrow of a reaction drawn as an entity of its own (the segments
+/// of secondary metabolites or the heads of reversible reactions), styled as the arrow.
+#[derive(Component)]
+pub struct ArrowPart {
+    pub arrow: Entity,
+}
+/// Marker for the heads on the reactants of reversible reactions.
+#[derive(Component)]
+pub struct ReverseHeads;
+
+/// How secondary metabolites and their segments are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecondaryStyle {
+    #[default]
+    Show,
+    Shrink,
+    Hide,
+}
+
+impl SecondaryStyle {
+    pub const ALL: [SecondaryStyle; 3] = [
+        SecondaryStyle::Show,
+        SecondaryStyle::Shrink,
+        SecondaryStyle::Hide,
+    ];
+}
+
+impl std::fmt::Display for SecondaryStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                SecondaryStyle::Show => "show",
+                SecondaryStyle::Shrink => "shrink",
+                SecondaryStyle::Hide => "hide",
+            }
+        )
+    }
+}
+
+/// Element of the map that can be moved in the edit mode, with the id of its node.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub enum Editable {
+    Metabolite(u64),
+    MetaboliteLabel(u64),
+    ReactionLabel(u64),
+}
+
+/// Marker for the text labels of reactions and metabolites.
+#[derive(Component)]
+pub struct MapLabel;
+
 /// Component to differentiate arrows via identifier (bigg_id in [`Reaction`]).
 #[derive(Component, Deserialize, Clone)]
 pub struct ArrowTag {
@@ -268,12 +591,13 @@ pub struct ArrowTag {
     pub direction: Vec2,
     pub node_id: u64,
     pub hists: Option<HashMap<Side, SerTransform>>,
+    pub hist_heights: Option<HashMap<Side, f32>>,
 }
 
 pub trait Tag: Component {
     fn id(&self) -> &str;
-    fn default_color() -> Color {
-        ARROW_COLOR
+    fn default_color(theme: &Theme) -> Color {
+        theme.arrow()
     }
 }
 
@@ -281,8 +605,8 @@ impl Tag for CircleTag {
     fn id(&self) -> &str {
         &self.id
     }
-    fn default_color() -> Color {
-        MET_COLOR
+    fn default_color(theme: &Theme) -> Color {
+        theme.metabolite()
     }
 }
 
@@ -303,14 +627,15 @@ fn build_text_tag(
     center_x: f32,
     center_y: f32,
     font_size: f32,
-) -> (Text2dBundle, DefaultFontSize) {
+    color: Color,
+) -> (Text2dBundle, DefaultFontSize, MapLabel) {
     let pos = node.label_position();
     let text = Text::from_section(
         node.id(),
         TextStyle {
             font,
             font_size,
-            color: ARROW_COLOR,
+            color,
         },
     )
     .with_justify(JustifyText::Center);
@@ -322,6 +647,7 @@ fn build_text_tag(
             ..default()
         },
         DefaultFontSize { size: font_size },
+        MapLabel,
     )
 }
 
@@ -345,6 +671,25 @@ impl Labelled for Reaction {
     }
 }
 
+/// Show the heads on the reactants of reversible reactions if enabled.
+fn show_reversibility(
+    ui_state: Res<UiState>,
+    new_heads: Query<(), Added<ReverseHeads>>,
+    mut heads: Query<&mut Visibility, With<ReverseHeads>>,
+) {
+    if !ui_state.is_changed() && new_heads.is_empty() {
+        return;
+    }
+    let vis = if ui_state.reversibility {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    for mut visibility in heads.iter_mut() {
+        *visibility = vis;
+    }
+}
+
 /// Mark an entity as hoverable.
 #[derive(Component)]
 pub struct Hover {
@@ -367,15 +712,30 @@ pub fn load_map(
     mut info_state: ResMut<Info>,
     mut map_dims: ResMut<MapDimensions>,
     mut node_to_text: ResMut<NodeToText>,
+    mut adjacency: ResMut<Adjacency>,
     asset_server: Res<AssetServer>,
+    map_font: Res<MapFont>,
+    theme: Res<Theme>,
     mut custom_assets: ResMut<Assets<EscherMap>>,
-    existing_map: Query<Entity, Or<(With<CircleTag>, With<ArrowTag>, With<HistTag>, With<Xaxis>)>>,
+    existing_map: Query<
+        Entity,
+        Or<(
+            With<CircleTag>,
+            With<ArrowTag>,
+            With<ArrowPart>,
+            With<HistTag>,
+            With<Xaxis>,
+            With<Annotation>,
+            With<RibbonTag>,
+        )>,
+    >,
     mut existing_geom_hist: Query<&mut GeomHist>,
 ) {
-    let custom_asset = custom_assets.get_mut(&state.escher_map);
-    if let (Some(bevy::asset::LoadState::Failed), false) =
-        (asset_server.get_load_state(&state.escher_map), state.loaded)
-    {
+    let custom_asset = custom_assets.get_mut(state.escher_map());
+    if let (Some(bevy::asset::LoadState::Failed), false) = (
+        asset_server.get_load_state(state.escher_map()),
+        state.loaded,
+    ) {
         info_state.notify("Failed loading map! Check that you JSON is correct.");
         state.loaded = true;
         return;
@@ -393,31 +753,33 @@ pub fn load_map(
     }
 
     let my_map = custom_asset.unwrap();
-    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    // each map keeps its own background, reset it if there is none
+    commands.insert_resource(my_map.background.clone().unwrap_or(MapBackground {
+        dirty: true,
+        ..default()
+    }));
+    let font = map_font.handle.clone();
+    *adjacency = Adjacency::new(&my_map.metabolism);
     let (reactions, metabolites) = my_map.get_components();
     // center all metabolites positions
-    let (total_x, total_y) = metabolites
-        .values()
-        .map(|met| (met.x, met.y))
-        .fold((0., 0.), |(acc_x, acc_y), (x, y)| (acc_x + x, acc_y + y));
-    let (center_x, center_y) = (
-        total_x / metabolites.len() as f32,
-        total_y / metabolites.len() as f32,
-    );
+    let Vec2 {
+        x: center_x,
+        y: center_y,
+    } = my_map.center();
     map_dims.x = center_x;
     map_dims.y = center_y;
     // add infinitesimal epsilon to each arrow so they don't flicker because of z-ordering
     // metabolites are not expected to occupy the same space, but better to be safe
     let mut z_eps = 1e-6;
+    // the two hexagons are built once and copied to every metabolite, which is
+    // cheaper than building them again; each metabolite still gets its own mesh
+    // since its color, size and hover come from the data
+    let (primary_hexagon, secondary_hexagon) = (hexagon(20.0), hexagon(10.0));
     for (node_id, mut met) in metabolites {
-        let shape = shapes::RegularPolygon {
-            sides: 6,
-            feature: shapes::RegularPolygonFeature::Radius(if met.node_is_primary {
-                20.0
-            } else {
-                10.0
-            }),
-            ..shapes::RegularPolygon::default()
+        let shape = if met.node_is_primary {
+            &primary_hexagon
+        } else {
+            &secondary_hexagon
         };
         let circle = CircleTag {
             id: met.bigg_id.clone(),
@@ -428,29 +790,57 @@ pub fn load_map(
             xlimits: None,
         };
         z_eps += 1e-6;
-        commands.spawn((
+        let is_primary = met.node_is_primary;
+        let mut circle_ent = commands.spawn((
             ShapeBundle {
-                path: GeometryBuilder::build_as(&shape),
+                path: Path(shape.0.clone()),
                 spatial: SpatialBundle {
                     transform: Transform::from_xyz(met.x - center_x, -met.y + center_y, 2. + z_eps),
                     ..default()
                 },
                 ..Default::default()
             },
-            Fill::color(MET_COLOR),
-            Stroke::new(MET_STROK, 4.0),
+            Fill::color(theme.metabolite()),
+            Stroke::new(theme.metabolite_stroke(), 4.0),
             circle.clone(),
+            Editable::Metabolite(node_id),
         ));
-        commands.spawn((
-            build_text_tag(&mut met, font.clone(), center_x, center_y, 25.),
+        if !is_primary {
+            circle_ent.insert(Secondary);
+        }
+        let mut text_ent = commands.spawn((
+            build_text_tag(
+                &mut met,
+                font.clone(),
+                center_x,
+                center_y,
+                25.,
+                theme.text(),
+            ),
             hover,
             circle,
+            Editable::MetaboliteLabel(node_id),
         ));
+        if !is_primary {
+            text_ent.insert(Secondary);
+        }
+    }
+    let bundles = my_map.duplicate_bundles(&reactions);
+    if !bundles.is_empty() {
+        info!("{} reactions are drawn on top of others", bundles.len());
     }
     // add infinitesimal epsilon to each arrow so they don't flicker because of z-ordering
     let mut z_eps = 1e-6;
     for (node_id, mut reac) in reactions {
         let mut path_builder = PathBuilder::new();
+        // segments of secondary metabolites are drawn apart so that they can be hidden,
+        // unless the reaction only involves secondary metabolites (e.g., ATP maintenance)
+        let touches_secondary = |seg: &Segment| {
+            my_map.is_secondary(&seg.from_node_id) | my_map.is_secondary(&seg.to_node_id)
+        };
+        let split = reac.segments.values().any(|seg| !touches_secondary(seg));
+        let mut secondary_builder = PathBuilder::new();
+        let mut secondary_heads = ShapePath::new();
         // origin of the figure as the center of mass
         let ori: Vec2 = reac
             .segments
@@ -471,34 +861,50 @@ pub fn load_map(
         let ori: Vec2 = Vec2::new(ori.x, -ori.y);
         let direction = my_map.main_direction(&reac);
         let mut products = reac.get_products(&my_map.metabolism);
+        let mut reactants = if reac.reversibility {
+            reac.get_reactants(&my_map.metabolism)
+        } else {
+            HashMap::new()
+        };
         let mut arrow_heads = ShapePath::new();
-        for (_, segment) in reac.segments.iter_mut() {
+        let mut reverse_heads = ShapePath::new();
+        for segment in reac.segments.values_mut() {
             if let (Some(from), Some(to)) = (
                 my_map.met_coords(&segment.from_node_id),
                 my_map.met_coords(&segment.to_node_id),
             ) {
+                let secondary = split && touches_secondary(segment);
+                let builder = if secondary {
+                    &mut secondary_builder
+                } else {
+                    &mut path_builder
+                };
                 let re_from = Vec2::new(from.x, -from.y);
                 let re_to = Vec2::new(to.x, -to.y);
                 // to draw the arrows
                 let mut last_from = Vec2::new(from.x, -from.y);
-                path_builder.move_to(re_from - ori);
+                // to draw the arrows of reversible reactions on the from side
+                let mut first_to = re_to;
+                builder.move_to(re_from - ori);
                 match (
                     std::mem::take(&mut segment.b1),
                     std::mem::take(&mut segment.b2),
                 ) {
                     (Some(BezierHandle { x, y }), None) | (None, Some(BezierHandle { x, y })) => {
                         last_from = Vec2::new(x, -y);
-                        path_builder.quadratic_bezier_to(last_from - ori, re_to - ori);
+                        first_to = last_from;
+                        builder.quadratic_bezier_to(last_from - ori, re_to - ori);
                         last_from -= (re_to - re_from) / 2.;
                     }
                     (Some(BezierHandle { x: x1, y: y1 }), Some(BezierHandle { x: x2, y: y2 })) => {
                         let prev_from = Vec2::new(x1, -y1);
+                        first_to = prev_from;
                         last_from = Vec2::new(x2, -y2);
-                        path_builder.cubic_bezier_to(prev_from - ori, last_from - ori, re_to - ori);
+                        builder.cubic_bezier_to(prev_from - ori, last_from - ori, re_to - ori);
                         last_from -= (re_to - prev_from) / 2.;
                     }
                     (None, None) => {
-                        path_builder.line_to(re_to - ori);
+                        builder.line_to(re_to - ori);
                     }
                 }
                 if let Some((drawn, importance)) = products.get_mut(segment.to_node_id.as_str()) {
@@ -507,17 +913,42 @@ pub fn load_map(
                             MetImportance::Primary => 22.0,
                             MetImportance::Secondary => 14.0,
                         };
-                        arrow_heads =
-                            arrow_heads.add(&draw_arrow(last_from - ori, re_to - ori, offset));
+                        let head = draw_arrow(last_from - ori, re_to - ori, offset);
+                        if secondary {
+                            secondary_heads = secondary_heads.add(&head);
+                        } else {
+                            arrow_heads = arrow_heads.add(&head);
+                        }
                         *drawn = true;
                     }
                 }
+                // heads of secondary reactants are not drawn to declutter the map
+                if !secondary {
+                    let (key, from, to) = if reactants.contains_key(segment.to_node_id.as_str()) {
+                        (segment.to_node_id.as_str(), last_from, re_to)
+                    } else {
+                        (segment.from_node_id.as_str(), first_to, re_from)
+                    };
+                    let reactant = reactants.get_mut(key).map(|met| (met, from, to));
+                    if let Some(((drawn, importance), from, to)) = reactant {
+                        if !*drawn {
+                            let offset = match importance {
+                                MetImportance::Primary => 22.0,
+                                MetImportance::Secondary => 14.0,
+                            };
+                            reverse_heads =
+                                reverse_heads.add(&draw_arrow(from - ori, to - ori, offset));
+                            *drawn = true;
+                        }
+                    }
+                }
             }
         }
         let line = path_builder.build();
         let arrow = ArrowTag {
             id: reac.bigg_id.clone(),
             hists: reac.hist_position.clone(),
+            hist_heights: reac.hist_height.clone(),
             node_id,
             direction,
         };
@@ -530,30 +961,88 @@ pub fn load_map(
         builder = builder.add(&line);
         builder = builder.add(&arrow_heads.build());
         z_eps += 1e-6;
-        commands.spawn((
-            ShapeBundle {
-                path: builder.build(),
-                spatial: SpatialBundle {
-                    transform: Transform::from_xyz(ori.x - center_x, ori.y + center_y, 1. + z_eps),
+        // duplicated arrows are spread side by side so that all their colors are seen
+        let shift = bundles
+            .get(&node_id)
+            .map(|(i, size)| {
+                direction.perp() * (*i as f32 - (size - 1) as f32 / 2.) * BUNDLE_SPACING
+            })
+            .unwrap_or(Vec2::ZERO);
+        let transform = Transform::from_xyz(
+            ori.x - center_x + shift.x,
+            ori.y + center_y + shift.y,
+            1. + z_eps,
+        );
+        let arrow_ent = commands
+            .spawn((
+                ShapeBundle {
+                    path: builder.build(),
+                    spatial: SpatialBundle {
+                        transform,
+                        ..Default::default()
+                    },
                     ..Default::default()
                 },
-                ..Default::default()
-            },
-            Stroke::new(ARROW_COLOR, 10.0),
-            arrow.clone(),
-        ));
+                Stroke::new(theme.arrow(), 10.0),
+                arrow.clone(),
+            ))
+            .id();
+        if split {
+            let secondary_path = GeometryBuilder::new()
+                .add(&secondary_builder.build())
+                .add(&secondary_heads.build());
+            commands.spawn((
+                ShapeBundle {
+                    path: secondary_path.build(),
+                    spatial: SpatialBundle {
+                        transform,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                Stroke::new(theme.arrow(), 10.0),
+                ArrowPart { arrow: arrow_ent },
+                Secondary,
+            ));
+        }
+        if reac.reversibility {
+            commands.spawn((
+                ShapeBundle {
+                    path: GeometryBuilder::build_as(&reverse_heads.build()),
+                    spatial: SpatialBundle {
+                        transform,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                Stroke::new(theme.arrow(), 10.0),
+                ArrowPart { arrow: arrow_ent },
+                ReverseHeads,
+            ));
+        }
         // spawn the text and collect its id in the hashmap for hovering.
         node_to_text.insert(
             node_id,
             commands
                 .spawn((
-                    build_text_tag(&mut reac, font.clone(), center_x, center_y, 35.),
+                    build_text_tag(
+                        &mut reac,
+                        font.clone(),
+                        center_x,
+                        center_y,
+                        35.,
+                        theme.text(),
+                    ),
                     arrow,
                     hover,
+                    Editable::ReactionLabel(node_id),
                 ))
                 .id(),
         );
     }
+    for annotation in my_map.annotations.iter() {
+        spawn_annotation(&mut commands, annotation.clone(), font.clone());
+    }
     // Send signal to repaint histograms.
     for mut geom in existing_geom_hist.iter_mut() {
         geom.rendered = false;
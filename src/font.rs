@@ -0,0 +1,73 @@
+//! Font of the texts drawn on the map, which can be changed in the settings
+//! (e.g., to the one mandated by a journal).
+use crate::gui::UiState;
+use crate::info::Info;
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+
+/// Font of the map unless another one is chosen.
+pub const DEFAULT_FONT: &str = "fonts/FiraSans-Bold.ttf";
+
+/// Fonts shipped with shu, by name.
+pub const BUNDLED_FONTS: [(&str, &str); 3] = [
+    ("Fira Sans Bold", DEFAULT_FONT),
+    ("Fira Mono", "fonts/FiraMono-Medium.ttf"),
+    ("Assistant", "fonts/Assistant-Regular.ttf"),
+];
+
+pub struct MapFontPlugin;
+
+impl Plugin for MapFontPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapFont>()
+            .add_systems(Update, switch_map_font);
+    }
+}
+
+/// Font of the labels, axis titles, scales and annotations of the map, loaded
+/// from [`UiState::font`].
+#[derive(Resource)]
+pub struct MapFont {
+    pub handle: Handle<Font>,
+    path: String,
+}
+
+impl FromWorld for MapFont {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            handle: world.resource::<AssetServer>().load(DEFAULT_FONT),
+            path: String::from(DEFAULT_FONT),
+        }
+    }
+}
+
+/// Load the font chosen in the settings and replace the previous one in the
+/// texts of the map. The UI (legend, info box) keeps its own font.
+fn switch_map_font(
+    mut ui_state: ResMut<UiState>,
+    mut info_state: ResMut<Info>,
+    asset_server: Res<AssetServer>,
+    mut map_font: ResMut<MapFont>,
+    mut texts: Query<&mut Text, Without<Node>>,
+) {
+    let failed = matches!(
+        asset_server.get_load_state(&map_font.handle),
+        Some(LoadState::Failed)
+    );
+    if failed && map_font.path != DEFAULT_FONT {
+        info_state.notify("Failed loading font! Check the path of the TTF file.");
+        ui_state.font = String::from(DEFAULT_FONT);
+    }
+    if ui_state.font == map_font.path {
+        return;
+    }
+    let previous = std::mem::replace(&mut map_font.handle, asset_server.load(&ui_state.font));
+    map_font.path.clone_from(&ui_state.font);
+    for mut text in texts.iter_mut() {
+        for section in text.sections.iter_mut() {
+            if section.style.font == previous {
+                section.style.font = map_font.handle.clone();
+            }
+        }
+    }
+}
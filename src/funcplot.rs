@@ -1,30 +1,37 @@
 //! Functions for plotting data.
 
 use bevy::prelude::{
-    Color, Component, Font, Handle, SpatialBundle, Text, Text2dBundle, TextStyle, Transform, Vec2,
+    Color, Component, Font, Handle, SpatialBundle, Text, Text2dBundle, TextSection, TextStyle,
+    Transform, Vec2,
 };
 use bevy_prototype_lyon::{
     entity::ShapeBundle,
-    prelude::{GeometryBuilder, Path, PathBuilder, Stroke},
+    prelude::{
+        tess::path::{iterator::PathIterator, PathEvent},
+        GeometryBuilder, Path, PathBuilder, Stroke,
+    },
     shapes,
 };
 use colorgrad::{Color as GradColor, CustomGradient, Gradient};
+use serde::Deserialize;
 
 #[derive(Component)]
 /// Marker trait to avoid outputting an [`Entity`] to the screen.
 pub struct IgnoreSave;
 
-/// Maximum of a slice.
+/// Maximum of a slice, skipping NaNs.
 pub fn max_f32(slice: &[f32]) -> f32 {
     slice
         .iter()
+        .filter(|x| !x.is_nan())
         .fold(0f32, |acc, x| if x - acc > 1e-8 { *x } else { acc })
 }
 
-/// Minimum of a slice.
+/// Minimum of a slice, skipping NaNs.
 pub fn min_f32(slice: &[f32]) -> f32 {
     slice
         .iter()
+        .filter(|x| !x.is_nan())
         .fold(0f32, |acc, x| if x - acc <= 1e-8 { *x } else { acc })
 }
 
@@ -32,25 +39,336 @@ fn std_normal(x: f32) -> f32 {
     std::f32::consts::E.powf(-x.powi(2) / 2.) / (2. * std::f32::consts::PI).sqrt()
 }
 
-fn kde(x: f32, samples: &[f32], h: f32) -> f32 {
+/// Kernel used to estimate the density in [`plot_kde`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kernel {
+    #[default]
+    Gaussian,
+    Epanechnikov,
+    Uniform,
+}
+
+impl Kernel {
+    pub const ALL: [Kernel; 3] = [Kernel::Gaussian, Kernel::Epanechnikov, Kernel::Uniform];
+
+    fn eval(self, u: f32) -> f32 {
+        match self {
+            Kernel::Gaussian => std_normal(u),
+            Kernel::Epanechnikov if u.abs() <= 1. => 0.75 * (1. - u.powi(2)),
+            Kernel::Uniform if u.abs() <= 1. => 0.5,
+            _ => 0.,
+        }
+    }
+}
+
+impl std::fmt::Display for Kernel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Kernel::Gaussian => "gaussian",
+                Kernel::Epanechnikov => "epanechnikov",
+                Kernel::Uniform => "uniform",
+            }
+        )
+    }
+}
+
+/// Spread drawn around the mean by [`plot_error_bar`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorBar {
+    /// Standard deviation.
+    #[default]
+    Sd,
+    /// Standard error of the mean.
+    Sem,
+    /// 95% interval between the 2.5% and 97.5% quantiles.
+    Interval,
+}
+
+impl ErrorBar {
+    pub const ALL: [ErrorBar; 3] = [ErrorBar::Sd, ErrorBar::Sem, ErrorBar::Interval];
+}
+
+impl std::fmt::Display for ErrorBar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorBar::Sd => "SD",
+                ErrorBar::Sem => "SEM",
+                ErrorBar::Interval => "95% interval",
+            }
+        )
+    }
+}
+
+/// How the size of the reactions is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArrowStyle {
+    /// Width of the line of the arrow.
+    #[default]
+    Line,
+    /// Tapered ribbon (see [`ribbon_path`]) as wide as the magnitude of the
+    /// value, narrowing towards the products if it is positive and towards the
+    /// reactants if it is negative.
+    Ribbon,
+}
+
+impl ArrowStyle {
+    pub const ALL: [ArrowStyle; 2] = [ArrowStyle::Line, ArrowStyle::Ribbon];
+}
+
+impl std::fmt::Display for ArrowStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ArrowStyle::Line => "line width",
+                ArrowStyle::Ribbon => "flux ribbon",
+            }
+        )
+    }
+}
+
+/// Languages whose locales write a comma as decimal separator.
+const DECIMAL_COMMA_LANGUAGES: [&str; 20] = [
+    "bg", "ca", "cs", "da", "de", "el", "es", "fi", "fr", "hr", "hu", "id", "it", "nb", "nl", "pl",
+    "pt", "ro", "ru", "sv",
+];
+
+/// Notation of the numbers of the legend and the scales.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Notation {
+    /// "5.20e-1"
+    #[default]
+    Scientific,
+    /// "0.52"
+    Plain,
+    /// With a prefix of the International System of Units, "520.00m".
+    Si,
+}
+
+impl Notation {
+    pub const ALL: [Notation; 3] = [Notation::Scientific, Notation::Plain, Notation::Si];
+}
+
+impl std::fmt::Display for Notation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Notation::Scientific => write!(f, "scientific"),
+            Notation::Plain => write!(f, "plain"),
+            Notation::Si => write!(f, "SI prefixes"),
+        }
+    }
+}
+
+/// Prefixes of the International System of Units from 1e-12 to 1e12.
+const SI_PREFIXES: [&str; 9] = ["p", "n", "µ", "m", "", "k", "M", "G", "T"];
+
+/// How numbers are written in the legend, the scales and the exported CSVs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct NumberFormat {
+    /// Write a comma as decimal separator, e.g., "1,50e3".
+    pub decimal_comma: bool,
+    /// Group the thousands of the numbers that are not in scientific notation.
+    pub grouping: bool,
+    /// Notation of the labels of the legend and the scales.
+    pub notation: Notation,
+    /// Decimals of the labels of the legend and the scales.
+    pub decimals: usize,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            decimal_comma: false,
+            grouping: false,
+            notation: Notation::default(),
+            decimals: 2,
+        }
+    }
+}
+
+impl NumberFormat {
+    /// Format of the locale of the system, from the `LC_ALL`, `LC_NUMERIC` or
+    /// `LANG` environment variables.
+    pub fn from_env() -> Self {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()));
+        Self::for_locale(locale.as_deref().unwrap_or_default())
+    }
+
+    /// Format of a `locale` like "de_DE.UTF-8".
+    pub fn for_locale(locale: &str) -> Self {
+        let language = locale.split(['_', '-', '.']).next().unwrap_or_default();
+        Self {
+            decimal_comma: DECIMAL_COMMA_LANGUAGES.contains(&language),
+            ..Self::default()
+        }
+    }
+
+    /// Label of the legend or the scales in the chosen [`Notation`].
+    pub fn label(&self, value: f32) -> String {
+        let decimals = self.decimals;
+        match self.notation {
+            Notation::Scientific => self.sci(value, decimals),
+            Notation::Plain => self.group(&format!("{value:.decimals$}")),
+            Notation::Si => {
+                // powers of a thousand, the largest that leaves an integer part
+                let power = if value.is_finite() && value != 0. {
+                    (value.abs().log10() / 3.).floor().clamp(-4., 4.) as i32
+                } else {
+                    0
+                };
+                let mantissa = value / 1000f32.powi(power);
+                let prefix = SI_PREFIXES[(power + 4) as usize];
+                format!("{}{prefix}", self.group(&format!("{mantissa:.decimals$}")))
+            }
+        }
+    }
+
+    /// Like [`NumberFormat::label`], with the sign also for positive numbers.
+    pub fn signed_label(&self, value: f32) -> String {
+        let label = self.label(value);
+        if label.starts_with('-') {
+            label
+        } else {
+            format!("+{label}")
+        }
+    }
+
+    /// Scientific notation with `precision` decimals, e.g., "1.50e3".
+    pub fn sci(&self, value: f32, precision: usize) -> String {
+        self.localize(format!("{value:.precision$e}"))
+    }
+
+    /// Scientific notation with `precision` decimals and the sign, e.g., "+1.50e3".
+    pub fn signed_sci(&self, value: f32, precision: usize) -> String {
+        self.localize(format!("{value:+.precision$e}"))
+    }
+
+    /// Shortest representation of the value, with the thousands grouped if set.
+    pub fn plain(&self, value: f32) -> String {
+        self.group(&value.to_string())
+    }
+
+    /// Group the thousands of a formatted `number` if set and use the decimal separator.
+    fn group(&self, number: &str) -> String {
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+        let (sign, digits) = integer.split_at(usize::from(integer.starts_with('-')));
+        let integer = if self.grouping && digits.bytes().all(|b| b.is_ascii_digit()) {
+            let separator = if self.decimal_comma { '.' } else { ',' };
+            let mut grouped = sign.to_string();
+            for (i, digit) in digits.chars().enumerate() {
+                if i > 0 && (digits.len() - i) % 3 == 0 {
+                    grouped.push(separator);
+                }
+                grouped.push(digit);
+            }
+            grouped
+        } else {
+            integer.to_string()
+        };
+        if fraction.is_empty() {
+            integer
+        } else {
+            format!("{integer}{}{fraction}", self.decimal_separator())
+        }
+    }
+
+    /// Character between the integer and the fractional digits.
+    pub fn decimal_separator(&self) -> char {
+        if self.decimal_comma {
+            ','
+        } else {
+            '.'
+        }
+    }
+
+    /// Separator of the fields of a CSV, which cannot be the decimal separator.
+    pub fn csv_separator(&self) -> char {
+        if self.decimal_comma {
+            ';'
+        } else {
+            ','
+        }
+    }
+
+    fn localize(&self, number: String) -> String {
+        if self.decimal_comma {
+            number.replace('.', ",")
+        } else {
+            number
+        }
+    }
+}
+
+fn kde(x: f32, samples: &[f32], h: f32, kernel: Kernel) -> f32 {
     1. / (h * samples.len() as f32)
         * samples
             .iter()
-            .map(|x_i| std_normal((x - x_i) / h))
+            .map(|x_i| kernel.eval((x - x_i) / h))
             .sum::<f32>()
 }
 
+/// Kernel density estimate where each sample contributes proportionally to its weight.
+fn weighted_kde(x: f32, samples: &[f32], weights: &[f32], h: f32, kernel: Kernel) -> f32 {
+    1. / (h * weights.iter().sum::<f32>())
+        * samples
+            .iter()
+            .zip(weights.iter())
+            .map(|(x_i, w_i)| w_i * kernel.eval((x - x_i) / h))
+            .sum::<f32>()
+}
+
+/// Mean of the samples, weighted if `weights` are provided.
+pub fn weighted_mean(samples: &[f32], weights: Option<&[f32]>) -> f32 {
+    match weights {
+        Some(weights) => {
+            samples
+                .iter()
+                .zip(weights.iter())
+                .map(|(x, w)| x * w)
+                .sum::<f32>()
+                / weights.iter().sum::<f32>()
+        }
+        None => samples.iter().sum::<f32>() / samples.len() as f32,
+    }
+}
+
 pub fn linspace(start: f32, stop: f32, nstep: u32) -> Vec<f32> {
     let delta: f32 = (stop - start) / (nstep as f32 - 1.);
     (0..(nstep)).map(|i| start + i as f32 * delta).collect()
 }
 
+/// Widen `xlimits` around their value if they collapse to a point (e.g., all the
+/// values of a distribution are zero) so that the distribution can still be plotted,
+/// centered on the axis. Returns whether the limits were degenerate.
+pub fn widen_degenerate(xlimits: &mut (f32, f32)) -> bool {
+    if xlimits.1 - xlimits.0 > 1e-8 {
+        return false;
+    }
+    let pad = f32::max(xlimits.0.abs() * 0.1, 0.5);
+    *xlimits = (xlimits.0 - pad, xlimits.1 + pad);
+    true
+}
+
 enum PlottingState {
     Zero,
     Over { last_x: f32 },
 }
 
-/// Plot a density with a normal kernel using [`Paths`].
+/// Plot a density with the given `kernel` and `bandwidth` using [`Paths`].
 ///
 /// The path defines a set of positive curves starting when `y_0 > 0` at `[x_0, y_0]`
 /// to n consecutive `[x_n, y]` KDE evaluations until `y == 0` again. The last line
@@ -58,7 +376,19 @@ enum PlottingState {
 ///
 /// This way, artifacts produced when tesselating infinitesimal areas or when the
 /// path is not closed are avoided.
-pub fn plot_kde(samples: &[f32], n: u32, size: f32, xlimits: (f32, f32)) -> Option<Path> {
+///
+/// If `weights` are provided, each sample contributes proportionally to its weight.
+/// If the density is zero everywhere (e.g., a too small `bandwidth`), a spike at
+/// the mean is plotted instead of an empty path.
+pub fn plot_kde(
+    samples: &[f32],
+    weights: Option<&[f32]>,
+    n: u32,
+    size: f32,
+    xlimits: (f32, f32),
+    kernel: Kernel,
+    bandwidth: f32,
+) -> Option<Path> {
     let center = size / 2.;
     let anchors = linspace(-center, center, n);
     if center.is_nan() {
@@ -68,18 +398,25 @@ pub fn plot_kde(samples: &[f32], n: u32, size: f32, xlimits: (f32, f32)) -> Opti
         return None;
     }
     let mut path_builder = PathBuilder::new();
+    let mut drawn = false;
     if samples.len() == 1 {
         path_builder = plot_spike(path_builder, samples[0], xlimits, center);
+        drawn = true;
     } else {
         let mut state = PlottingState::Zero;
         path_builder.move_to(Vec2::new(anchors[0], 0.));
         for (point_x, anchor_x) in linspace(xlimits.0, xlimits.1, n).iter().zip(anchors.iter()) {
-            let y = f32::max(kde(*point_x, samples, 1.06), 0.);
+            let density = match weights {
+                Some(weights) => weighted_kde(*point_x, samples, weights, bandwidth, kernel),
+                None => kde(*point_x, samples, bandwidth, kernel),
+            };
+            let y = f32::max(density, 0.);
             match state {
                 PlottingState::Zero => {
                     if y > 0. {
                         path_builder.move_to(Vec2::new(*anchor_x, y));
                         state = PlottingState::Over { last_x: *anchor_x };
+                        drawn = true;
                     }
                 }
                 PlottingState::Over { last_x } => {
@@ -96,14 +433,32 @@ pub fn plot_kde(samples: &[f32], n: u32, size: f32, xlimits: (f32, f32)) -> Opti
             path_builder.line_to(Vec2::new(last_x, 0.));
         }
     }
+    if !drawn {
+        path_builder = plot_spike(
+            path_builder,
+            weighted_mean(samples, weights),
+            xlimits,
+            center,
+        );
+    }
     Some(path_builder.build())
 }
 
 /// Histogram plotting with n bins.
-pub fn plot_hist(samples: &[f32], bins: u32, size: f32, xlimits: (f32, f32)) -> Option<Path> {
+///
+/// If `weights` are provided, the height of each bin is the sum of the weights
+/// of its samples instead of the count. If no bin has a positive height, a spike
+/// at the mean is plotted instead of an empty path.
+pub fn plot_hist(
+    samples: &[f32],
+    weights: Option<&[f32]>,
+    bins: u32,
+    size: f32,
+    xlimits: (f32, f32),
+) -> Option<Path> {
     let center = size / 2.;
-    // a bin should not be less than a data point
-    let bins = u32::min(samples.len() as u32 / 2, bins);
+    // a bin should not be less than a data point, but at least one bin is needed
+    let bins = u32::max(u32::min(samples.len() as u32 / 2, bins), 2);
     // actual x points to be mapped to the KDE
     let points = linspace(xlimits.0, xlimits.1, bins);
     // calculated x positions in the graph
@@ -116,8 +471,10 @@ pub fn plot_hist(samples: &[f32], bins: u32, size: f32, xlimits: (f32, f32)) ->
     }
 
     let mut path_builder = PathBuilder::new();
+    let mut drawn = false;
     if samples.len() == 1 {
         path_builder = plot_spike(path_builder, samples[0], xlimits, center);
+        drawn = true;
     } else {
         for ((anchor_a, anchor_b), (point_a, point_b)) in anchors.clone()[0..(anchors.len() - 1)]
             .iter()
@@ -129,19 +486,34 @@ pub fn plot_hist(samples: &[f32], bins: u32, size: f32, xlimits: (f32, f32)) ->
             )
         {
             // TODO: sort first this and operate over indices
-            let y = samples
-                .iter()
-                .filter(|&&x| (x >= *point_a) & (x < *point_b))
-                .count();
-            if y == 0 {
+            let in_bin = |x: &f32| (*x >= *point_a) & (*x < *point_b);
+            let y = match weights {
+                Some(weights) => samples
+                    .iter()
+                    .zip(weights.iter())
+                    .filter(|(x, _)| in_bin(x))
+                    .map(|(_, w)| w)
+                    .sum::<f32>(),
+                None => samples.iter().filter(|x| in_bin(x)).count() as f32,
+            };
+            if y <= 0. {
                 continue;
             }
             path_builder.move_to(Vec2::new(*anchor_a, 0.));
-            path_builder.line_to(Vec2::new(*anchor_a, y as f32));
-            path_builder.line_to(Vec2::new(*anchor_b, y as f32));
+            path_builder.line_to(Vec2::new(*anchor_a, y));
+            path_builder.line_to(Vec2::new(*anchor_b, y));
             path_builder.line_to(Vec2::new(*anchor_b, 0.));
+            drawn = true;
         }
     }
+    if !drawn {
+        path_builder = plot_spike(
+            path_builder,
+            weighted_mean(samples, weights),
+            xlimits,
+            center,
+        );
+    }
     Some(path_builder.build())
 }
 
@@ -180,6 +552,159 @@ pub fn plot_box_point(n_cond: usize, cond_index: usize) -> Path {
     path_builder.build()
 }
 
+/// Colors of categorical data, repeated if there are more categories.
+const CATEGORY_PALETTE: [&str; 10] = [
+    "4e79a7", "f28e2b", "e15759", "76b7b2", "59a14f", "edc948", "b07aa1", "ff9da7", "9c755f",
+    "bab0ac",
+];
+
+/// Color of the category at `index` of a sorted list of categories.
+pub fn category_color(index: usize) -> Color {
+    Color::hex(CATEGORY_PALETTE[index % CATEGORY_PALETTE.len()]).unwrap()
+}
+
+/// Thermodynamic favorability of a reaction in the direction of its arrow.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Favorability {
+    Forward,
+    /// The ±2σ interval of ΔG contains zero or |ΔG| is below the threshold.
+    Equilibrium,
+    Reverse,
+}
+
+impl Favorability {
+    pub fn classify(dg: f32, std: f32, threshold: f32) -> Self {
+        if (dg.abs() < threshold) | (dg.abs() < 2. * std) {
+            Favorability::Equilibrium
+        } else if dg < 0. {
+            Favorability::Forward
+        } else {
+            Favorability::Reverse
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Favorability::Forward => Color::rgb_u8(46, 139, 87),
+            Favorability::Equilibrium => Color::rgb_u8(218, 165, 32),
+            Favorability::Reverse => Color::rgb_u8(178, 34, 34),
+        }
+    }
+}
+
+/// Build a gauge of ΔG of length `size` that maps `[-range, range]` to it.
+///
+/// Returns the track, a line with a tick at zero, and the marker, the ±2σ
+/// interval with a triangle pointing at the estimate.
+pub fn plot_gauge(dg: f32, std: f32, range: f32, size: f32) -> (Path, Path) {
+    let half = size / 2.;
+    let x = |value: f32| lerp(value, -range, range, -half, half);
+    let mut track = PathBuilder::new();
+    track.move_to(Vec2::new(-half, 0.));
+    track.line_to(Vec2::new(half, 0.));
+    track.move_to(Vec2::new(0., -6.));
+    track.line_to(Vec2::new(0., 6.));
+    let mut marker = PathBuilder::new();
+    let (low, high) = (x(dg - 2. * std), x(dg + 2. * std));
+    if high - low > 1. {
+        marker.move_to(Vec2::new(low, -3.));
+        marker.line_to(Vec2::new(high, -3.));
+        marker.line_to(Vec2::new(high, 3.));
+        marker.line_to(Vec2::new(low, 3.));
+        marker.close();
+    }
+    let center = x(dg);
+    marker.move_to(Vec2::new(center, 4.));
+    marker.line_to(Vec2::new(center + 6., 14.));
+    marker.line_to(Vec2::new(center - 6., 14.));
+    marker.close();
+    (track.build(), marker.build())
+}
+
+/// Build a horizontal whisker from `low` to `high` with caps at both ends and
+/// a longer tick at `center`.
+pub fn plot_whisker(low: f32, center: f32, high: f32) -> Path {
+    let mut whisker = PathBuilder::new();
+    whisker.move_to(Vec2::new(low, 0.));
+    whisker.line_to(Vec2::new(high, 0.));
+    for (x, half) in [(low, 3.), (high, 3.), (center, 6.)] {
+        whisker.move_to(Vec2::new(x, -half));
+        whisker.line_to(Vec2::new(x, half));
+    }
+    whisker.build()
+}
+
+/// Half height of the caps of [`plot_error_bar`] and radius of its mean.
+const ERROR_BAR_CAP: f32 = 5.;
+
+/// Build a dot at the mean of the samples with a bar spanning the `kind` of
+/// error, `y` units above the axis. The bar is filled so that it takes the
+/// color of the condition like the histograms.
+pub fn plot_error_bar(
+    samples: &[f32],
+    weights: Option<&[f32]>,
+    kind: ErrorBar,
+    size: f32,
+    xlimits: (f32, f32),
+    y: f32,
+) -> Option<Path> {
+    if samples.is_empty() || size.is_nan() {
+        return None;
+    }
+    let (mean, sd) = match weights {
+        Some(w) => crate::stats::weighted_mean_std(samples, w),
+        None => (crate::stats::mean(samples), crate::stats::std_dev(samples)),
+    };
+    let (low, high) = match kind {
+        ErrorBar::Sd => (mean - sd, mean + sd),
+        ErrorBar::Sem => {
+            let sem = sd / (samples.len() as f32).sqrt();
+            (mean - sem, mean + sem)
+        }
+        ErrorBar::Interval => (
+            crate::stats::quantile(samples, 0.025),
+            crate::stats::quantile(samples, 0.975),
+        ),
+    };
+    let center = size / 2.;
+    let x = |t: f32| lerp(t, xlimits.0, xlimits.1, -center, center);
+    let (low, mean, high) = (x(low), x(mean), x(high));
+    let mut path_builder = PathBuilder::new();
+    // bar and caps as thin rectangles
+    for (x_0, x_1, half) in [
+        (low, high, 1.),
+        (low - 1., low + 1., ERROR_BAR_CAP),
+        (high - 1., high + 1., ERROR_BAR_CAP),
+    ] {
+        path_builder.move_to(Vec2::new(x_0, y - half));
+        path_builder.line_to(Vec2::new(x_1, y - half));
+        path_builder.line_to(Vec2::new(x_1, y + half));
+        path_builder.line_to(Vec2::new(x_0, y + half));
+        path_builder.close();
+    }
+    path_builder.move_to(Vec2::new(mean + ERROR_BAR_CAP, y));
+    path_builder.arc(
+        Vec2::new(mean, y),
+        Vec2::splat(ERROR_BAR_CAP),
+        std::f32::consts::TAU,
+        0.,
+    );
+    path_builder.close();
+    Some(path_builder.build())
+}
+
+/// Build a wedge of a pie of `radius` centered at the origin, starting at
+/// `start` radians (counterclockwise from the x-axis) and spanning `sweep`
+/// radians clockwise.
+pub fn plot_wedge(start: f32, sweep: f32, radius: f32) -> Path {
+    let mut wedge = PathBuilder::new();
+    wedge.move_to(Vec2::ZERO);
+    wedge.line_to(Vec2::from_angle(start) * radius);
+    wedge.arc(Vec2::ZERO, Vec2::splat(radius), -sweep, 0.);
+    wedge.close();
+    wedge.build()
+}
+
 /// Bundle for text that goes into plot scales.
 #[derive(Clone)]
 pub struct ScaleBundle {
@@ -190,12 +715,16 @@ pub struct ScaleBundle {
 
 impl ScaleBundle {
     /// Build text components from minimum, maximum and mean values.
+    ///
+    /// The `unit` is written after the maximum, in a second section of its text.
     pub fn new(
         minimum: f32,
         maximum: f32,
         mean: f32,
         mean_pos: f32,
         size: f32,
+        unit: &str,
+        number_format: NumberFormat,
         font: Handle<Font>,
         font_size: f32,
         color: Color,
@@ -203,7 +732,7 @@ impl ScaleBundle {
         // build x component
         let x_0 = Text2dBundle {
             text: Text::from_section(
-                format!("{:+.3e}", minimum),
+                number_format.signed_label(minimum),
                 TextStyle {
                     font: font.clone(),
                     font_size,
@@ -214,21 +743,22 @@ impl ScaleBundle {
             transform: Transform::from_xyz(-size / 2. - font_size * 2., 0., 0.2),
             ..Default::default()
         };
+        let style = TextStyle {
+            font: font.clone(),
+            font_size,
+            color,
+        };
         let x_n = Text2dBundle {
-            text: Text::from_section(
-                format!("{:+.3e}", maximum),
-                TextStyle {
-                    font: font.clone(),
-                    font_size,
-                    color,
-                },
-            ),
+            text: Text::from_sections([
+                TextSection::new(number_format.signed_label(maximum), style.clone()),
+                TextSection::new(unit_suffix(unit), style),
+            ]),
             transform: Transform::from_xyz(size / 2., 0., 0.2),
             ..Default::default()
         };
         let y = Text2dBundle {
             text: Text::from_section(
-                format!("{:+.3e}", mean),
+                number_format.signed_label(mean),
                 TextStyle {
                     font,
                     font_size,
@@ -242,6 +772,15 @@ impl ScaleBundle {
     }
 }
 
+/// Text written after a value for its `unit`, empty if there is none.
+pub fn unit_suffix(unit: &str) -> String {
+    if unit.is_empty() {
+        String::new()
+    } else {
+        format!(" {unit}")
+    }
+}
+
 pub fn plot_line(size: f32, transform: Transform) -> (ShapeBundle, Stroke) {
     let mut path_builder = PathBuilder::new();
     path_builder.move_to(Vec2::new(-size / 2., 0.));
@@ -261,17 +800,32 @@ pub fn plot_line(size: f32, transform: Transform) -> (ShapeBundle, Stroke) {
 }
 
 /// Build and position text tags to indicate the scale of thethe  x-axis.
-pub fn plot_scales(samples: &[f32], size: f32, font: Handle<Font>, font_size: f32) -> ScaleBundle {
-    let mean: f32 = samples.iter().sum::<f32>() / samples.len() as f32;
+pub fn plot_scales(
+    samples: &[f32],
+    weights: Option<&[f32]>,
+    size: f32,
+    unit: &str,
+    number_format: NumberFormat,
+    font: Handle<Font>,
+    font_size: f32,
+) -> ScaleBundle {
+    let mean: f32 = weighted_mean(samples, weights);
     let min = min_f32(samples);
     let max = max_f32(samples);
-    let mean_pos = lerp(mean, min, max, -size / 2., size / 2.);
+    let mean_pos = if max - min > 1e-8 {
+        lerp(mean, min, max, -size / 2., size / 2.)
+    } else {
+        // degenerate distributions are centered, see [`widen_degenerate`]
+        0.
+    };
     ScaleBundle::new(
         min,
         max,
         mean,
         mean_pos,
         size,
+        unit,
+        number_format,
         font,
         font_size,
         Color::rgb(51. / 255., 78. / 255., 107. / 255.),
@@ -309,6 +863,127 @@ pub fn path_to_vec(path: &Path) -> Vec2 {
     last_point - first_point
 }
 
+/// Hexagon of a metabolite, centered at the origin.
+pub fn hexagon(radius: f32) -> Path {
+    GeometryBuilder::build_as(&shapes::RegularPolygon {
+        sides: 6,
+        feature: shapes::RegularPolygonFeature::Radius(radius),
+        ..shapes::RegularPolygon::default()
+    })
+}
+
+/// Dashed copy of a `path`, with its curves flattened into lines.
+///
+/// The pattern restarts at every subpath, so that short arrow heads are not
+/// swallowed by a gap.
+pub fn dash_path(path: &Path, dash: f32, gap: f32) -> Path {
+    let mut builder = PathBuilder::new();
+    // whether the pen is down and how much is left of the current dash or gap
+    let (mut on, mut left) = (true, dash);
+    for event in path.0.iter().flattened(0.5) {
+        let (from, to) = match event {
+            PathEvent::Begin { .. } => {
+                (on, left) = (true, dash);
+                continue;
+            }
+            PathEvent::Line { from, to } => (from, to),
+            PathEvent::End {
+                last,
+                first,
+                close: true,
+            } => (last, first),
+            _ => continue,
+        };
+        let (from, to) = (Vec2::new(from.x, from.y), Vec2::new(to.x, to.y));
+        let length = from.distance(to);
+        let mut done = 0.;
+        while done < length {
+            let step = left.min(length - done);
+            if on {
+                builder.move_to(from.lerp(to, done / length));
+                builder.line_to(from.lerp(to, (done + step) / length));
+            }
+            done += step;
+            left -= step;
+            if left <= 0. {
+                on = !on;
+                left = if on { dash } else { gap };
+            }
+        }
+    }
+    builder.build()
+}
+
+/// Width of the narrow end of a ribbon relative to its wide end.
+const RIBBON_TIP: f32 = 0.2;
+
+/// Tapered ribbon over the segments (open subpaths) of the `path` of an arrow,
+/// `width` wide at the reactants and narrowing towards the products, or the
+/// other way around if `reverse`.
+///
+/// The products are found from the heads of the arrow (closed subpaths), or
+/// else are placed along `direction`. The segments are flattened and all of
+/// them are wound the same way, so they should be filled with a non-zero rule.
+pub fn ribbon_path(path: &Path, direction: Vec2, width: f32, reverse: bool) -> Path {
+    let mut segments: Vec<Vec<Vec2>> = Vec::new();
+    let mut heads: Vec<Vec2> = Vec::new();
+    let mut current: Vec<Vec2> = Vec::new();
+    for event in path.0.iter().flattened(0.5) {
+        match event {
+            PathEvent::Begin { at } => current = vec![Vec2::new(at.x, at.y)],
+            PathEvent::Line { to, .. } => current.push(Vec2::new(to.x, to.y)),
+            PathEvent::End { close: true, .. } => heads.append(&mut current),
+            PathEvent::End { close: false, .. } => segments.push(std::mem::take(&mut current)),
+            _ => {}
+        }
+    }
+    let points = segments.iter().flatten();
+    let n = points.clone().count().max(1) as f32;
+    let center = points.clone().sum::<Vec2>() / n;
+    let towards_products = if heads.is_empty() {
+        direction
+    } else {
+        heads.iter().sum::<Vec2>() / heads.len() as f32 - center
+    };
+    let direction = towards_products.try_normalize().unwrap_or(Vec2::X);
+    let (low, high) = points.fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), p| {
+        (low.min(p.dot(direction)), high.max(p.dot(direction)))
+    });
+    let half_width = |p: Vec2| {
+        let t = if high - low > 1e-3 {
+            (p.dot(direction) - low) / (high - low)
+        } else {
+            0.5
+        };
+        let t = if reverse { 1. - t } else { t };
+        width / 2. * (1. - (1. - RIBBON_TIP) * t)
+    };
+    let mut builder = PathBuilder::new();
+    for segment in segments.iter().filter(|segment| segment.len() > 1) {
+        let last = segment.len() - 1;
+        // normal at each point, averaged between its two lines
+        let normals: Vec<Vec2> = (0..segment.len())
+            .map(|i| {
+                let before = segment[i] - segment[i.saturating_sub(1)];
+                let after = segment[(i + 1).min(last)] - segment[i];
+                (before.normalize_or_zero() + after.normalize_or_zero())
+                    .perp()
+                    .normalize_or_zero()
+            })
+            .collect();
+        let side = |i: usize, sign: f32| segment[i] + normals[i] * sign * half_width(segment[i]);
+        builder.move_to(side(0, 1.));
+        for i in 1..=last {
+            builder.line_to(side(i, 1.));
+        }
+        for i in (0..=last).rev() {
+            builder.line_to(side(i, -1.));
+        }
+        builder.close();
+    }
+    builder.build()
+}
+
 /// Interpolate a value `t` in domain `[min_1, max_1]` to `[min_2, max_2]`.
 pub fn lerp(t: f32, min_1: f32, max_1: f32, min_2: f32, max_2: f32) -> f32 {
     // clamp min and max to avoid explosion with low values on the first domain
@@ -1,11 +1,16 @@
-use bevy::prelude::Component;
+use crate::funcplot::ArrowStyle;
+use bevy::prelude::{App, Component, Quat, Resource, Vec2};
+use bevy_prototype_lyon::prelude::Path;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// When in a Entity with `Aesthetics`, it will plot whatever aes to
 /// the arrows in the map.
 #[derive(Component)]
 pub struct GeomArrow {
     pub plotted: bool,
+    /// How the size of the reactions is drawn, chosen per dataset.
+    pub style: ArrowStyle,
 }
 
 /// Side of the arrow a plot (box point, histogram or legend) is referring to.
@@ -24,6 +29,53 @@ pub enum HistPlot {
     Kde,
     // Point estimate.
     BoxPoint,
+    /// Mean with error bars, one per condition along the height of the axis.
+    ErrorBar,
+    /// Geom registered in the [`GeomRegistry`] under this name.
+    Custom(&'static str),
+}
+
+/// Build the shape of a custom geom from the samples (and weights) of one
+/// identifier, given the length of the axis and its limits.
+pub type GeomFn = fn(&[f32], Option<&[f32]>, f32, (f32, f32)) -> Option<Path>;
+
+/// Geoms added by extensions with [`RegisterGeom::register_geom`].
+///
+/// Entities with `Aesthetics`, `Gy`, a `Distribution` and a [`GeomHist`]
+/// whose plot is [`HistPlot::Custom`] go through the same pipeline as the
+/// histograms: axis building, filtering by condition, hovering and legend.
+#[derive(Resource, Default)]
+pub struct GeomRegistry {
+    geoms: HashMap<&'static str, GeomFn>,
+}
+
+impl GeomRegistry {
+    pub fn get(&self, name: &str) -> Option<GeomFn> {
+        self.geoms.get(name).copied()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &&'static str> {
+        self.geoms.keys()
+    }
+}
+
+/// Extension point of the [`App`] to add custom geoms, e.g. from a plugin:
+///
+/// ```ignore
+/// app.register_geom("dg", plot_dg_glyphs);
+/// ```
+pub trait RegisterGeom {
+    fn register_geom(&mut self, name: &'static str, plot: GeomFn) -> &mut Self;
+}
+
+impl RegisterGeom for App {
+    fn register_geom(&mut self, name: &'static str, plot: GeomFn) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(GeomRegistry::default)
+            .geoms
+            .insert(name, plot);
+        self
+    }
 }
 
 /// When in a Entity with `Aesthetics`, it will plot whatever aes to
@@ -75,6 +127,60 @@ pub struct GeomMetabolite {
     pub plotted: bool,
 }
 
+/// When in a Entity with `Aesthetics`, it will plot the ΔG of the reactions
+/// as gauges next to the arrows in the map.
+#[derive(Component)]
+pub struct GeomThermo {
+    pub plotted: bool,
+}
+
+/// Component of the gauges spawned by a [`GeomThermo`].
+#[derive(Component)]
+pub struct ThermoTag {
+    pub node_id: u64,
+}
+
+/// When in a Entity with `Aesthetics` and `Parts`, it will plot the parts of
+/// each reaction as stacked bars next to the arrows in the map.
+#[derive(Component)]
+pub struct GeomStack {
+    pub plotted: bool,
+}
+
+/// Component of the bars spawned by a [`GeomStack`].
+#[derive(Component)]
+pub struct StackTag {
+    pub node_id: u64,
+}
+
+/// Component of the whiskers plotted instead of the side distributions
+/// when [`crate::gui::UiState::whiskers`] is set.
+#[derive(Component)]
+pub struct WhiskerTag {
+    pub node_id: u64,
+    pub side: Side,
+}
+
+/// Component of the ribbons drawn over the arrows when the style of the
+/// [`GeomArrow`] of the sizes is a ribbon.
+#[derive(Component)]
+pub struct RibbonTag {
+    pub arrow: bevy::prelude::Entity,
+}
+
+/// When in a Entity with `Aesthetics` and `Parts`, it will plot the parts of
+/// each metabolite (e.g., its mass isotopomers) as pies next to the circles.
+#[derive(Component)]
+pub struct GeomPie {
+    pub plotted: bool,
+}
+
+/// Component of the wedges spawned by a [`GeomPie`].
+#[derive(Component)]
+pub struct PieTag {
+    pub id: String,
+}
+
 /// Component applied to all Hist-like entities (spawned by a GeomKde, GeomHist, etc. aesthetic)
 /// This allow us to query for systems like normalize or drag.
 #[derive(Component)]
@@ -84,6 +190,11 @@ pub struct HistTag {
     pub follow_scale: bool,
 }
 
+/// Marker of the [`HistPlot::ErrorBar`] shapes, which keep their height
+/// instead of being normalized like the histograms.
+#[derive(Component)]
+pub struct ErrorBarTag;
+
 #[derive(Component)]
 pub struct VisCondition {
     pub condition: Option<String>,
@@ -95,18 +206,58 @@ pub struct Xaxis {
     pub id: String,
     pub arrow_size: f32,
     pub xlimits: (f32, f32),
+    /// Limits of the values of this axis alone, [`Xaxis::xlimits`] are set to
+    /// the range of the side instead when [`crate::gui::UiState::global_xlimits`].
+    pub own_xlimits: (f32, f32),
     pub side: Side,
     pub plot: HistPlot,
     pub node_id: u64,
     pub conditions: Vec<String>,
+    /// Names of the data columns plotted in this axis.
+    pub titles: Vec<String>,
+    /// All the values of the axis are equal, so its limits were widened.
+    pub flat: bool,
+    /// Height of the histograms of this axis relative to the global height of its side.
+    pub height: f32,
+    /// The axis is anchored to a metabolite circle instead of a reaction arrow.
+    pub met: bool,
+}
+
+impl Xaxis {
+    /// Register the column `title` as being plotted in this axis.
+    pub fn add_title(&mut self, title: &str) {
+        if !self.titles.iter().any(|t| t == title) {
+            self.titles.push(title.to_string());
+        }
+    }
 }
 
+/// Text with the titles of an [`Xaxis`], spawned as its child.
+#[derive(Component)]
+pub struct AxisTitle;
+
+/// Text with the significance of the difference between two conditions,
+/// spawned as a child of an [`Xaxis`].
+#[derive(Component)]
+pub struct SigLabel;
+
 /// Component that marks something susceptible of being dragged/rotated.
 #[derive(Debug, Component, Default)]
 pub struct Drag {
     pub dragged: bool,
     pub rotating: bool,
     pub scaling: bool,
+    /// Part of the multi-selection, moved, rotated and deleted together.
+    pub selected: bool,
+    /// Position relative to the cursor while being dragged.
+    pub offset: Vec2,
+    /// Position the entity moves to when the motion is smoothed; it keeps
+    /// moving after being released until it gets there.
+    pub target: Option<Vec2>,
+    /// Rotation when the current rotation started and the angle accumulated since then.
+    pub rotation_start: Option<(Quat, f32)>,
+    /// Rotation the entity turns to when the motion is smoothed.
+    pub target_rotation: Option<Quat>,
 }
 
 impl std::fmt::Display for Side {
@@ -129,6 +280,15 @@ impl std::fmt::Display for Xaxis {
     }
 }
 
+/// Line from a displaced histogram axis to the middle of its reaction.
+#[derive(Component)]
+pub struct LeaderLine {
+    pub node_id: u64,
+    pub side: Side,
+    /// Ends of the line, to only rebuild it when they move.
+    pub ends: (Vec2, Vec2),
+}
+
 /// Component of all popups.
 #[derive(Component)]
 pub struct PopUp;
@@ -0,0 +1,121 @@
+//! Grid lines and rulers in map coordinates to aid manual layout.
+use crate::escher::MapDimensions;
+use crate::gui::UiState;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiSettings};
+
+pub struct GridPlugin;
+
+impl Plugin for GridPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_grid);
+    }
+}
+
+/// Maximum number of lines drawn per axis; the spacing is doubled until it fits.
+const MAX_LINES: f32 = 100.;
+const RULER_WIDTH: f32 = 18.;
+
+/// Map coordinates (as in escher, y grows downwards) of a world position.
+pub fn world_to_map(world: Vec2, map_dims: &MapDimensions) -> Vec2 {
+    Vec2::new(world.x + map_dims.x, -world.y + map_dims.y)
+}
+
+/// World position of some map coordinates.
+pub fn map_to_world(map: Vec2, map_dims: &MapDimensions) -> Vec2 {
+    Vec2::new(map.x - map_dims.x, -map.y + map_dims.y)
+}
+
+/// Snap a world position to the closest intersection of the grid in map coordinates.
+pub fn snap_to_grid(world: Vec2, spacing: f32, map_dims: &MapDimensions) -> Vec2 {
+    let map = world_to_map(world, map_dims);
+    map_to_world((map / spacing).round() * spacing, map_dims)
+}
+
+/// Draw the grid and the rulers on top of the map with egui so that they
+/// are never part of the exported images.
+fn draw_grid(
+    mut egui_context: EguiContexts,
+    ui_state: Res<UiState>,
+    map_dims: Res<MapDimensions>,
+    egui_settings: Res<EguiSettings>,
+    q_camera: Query<(&Camera, &GlobalTransform)>,
+) {
+    if !ui_state.grid || ui_state.hide || (ui_state.grid_spacing <= 0.) {
+        return;
+    }
+    let Ok((camera, camera_transform)) = q_camera.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    let (Some(top_left), Some(bottom_right)) = (
+        camera.viewport_to_world_2d(camera_transform, Vec2::ZERO),
+        camera.viewport_to_world_2d(camera_transform, viewport),
+    ) else {
+        return;
+    };
+    let (map_min, map_max) = (
+        world_to_map(top_left, &map_dims),
+        world_to_map(bottom_right, &map_dims),
+    );
+    let mut spacing = ui_state.grid_spacing;
+    while ((map_max - map_min) / spacing).max_element() > MAX_LINES {
+        spacing *= 2.;
+    }
+    // egui works in points, which are the logical pixels scaled by egui
+    let to_screen = |world: Vec2| {
+        camera
+            .world_to_viewport(camera_transform, world.extend(0.))
+            .map(|pos| {
+                let pos = pos / egui_settings.scale_factor;
+                egui::pos2(pos.x, pos.y)
+            })
+    };
+    let ctx = egui_context.ctx_mut();
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    let screen = ctx.screen_rect();
+    let line = egui::Stroke::new(1.0f32, egui::Color32::from_black_alpha(40));
+    let ruler_color = egui::Color32::from_rgba_unmultiplied(240, 240, 240, 220);
+    let text_color = egui::Color32::from_gray(60);
+    let font = egui::FontId::monospace(10.);
+    painter.rect_filled(
+        egui::Rect::from_min_max(screen.min, egui::pos2(screen.max.x, RULER_WIDTH)),
+        0.,
+        ruler_color,
+    );
+    painter.rect_filled(
+        egui::Rect::from_min_max(screen.min, egui::pos2(RULER_WIDTH, screen.max.y)),
+        0.,
+        ruler_color,
+    );
+    let mut x = (map_min.x / spacing).ceil() * spacing;
+    while x <= map_max.x {
+        if let Some(pos) = to_screen(map_to_world(Vec2::new(x, map_min.y), &map_dims)) {
+            painter.vline(pos.x, screen.y_range(), line);
+            painter.text(
+                egui::pos2(pos.x + 2., 2.),
+                egui::Align2::LEFT_TOP,
+                format!("{x:.0}"),
+                font.clone(),
+                text_color,
+            );
+        }
+        x += spacing;
+    }
+    let mut y = (map_min.y / spacing).ceil() * spacing;
+    while y <= map_max.y {
+        if let Some(pos) = to_screen(map_to_world(Vec2::new(map_min.x, y), &map_dims)) {
+            painter.hline(screen.x_range(), pos.y, line);
+            painter.text(
+                egui::pos2(2., pos.y + 2.),
+                egui::Align2::LEFT_TOP,
+                format!("{y:.0}"),
+                font.clone(),
+                text_color,
+            );
+        }
+        y += spacing;
+    }
+}
@@ -1,20 +1,58 @@
 //! Gui (windows and panels) to upload data and hover.
 
-use crate::data::{Data, ReactionState};
-use crate::escher::{ArrowTag, EscherMap, Hover, MapState, NodeToText, ARROW_COLOR};
+use crate::aesthetics::{
+    Aesthetics, ColumnName, Distribution, Parts, Point, Uncertainty, Units, Weights,
+};
+use crate::annotation::{grab_annotations, Annotation};
+use crate::background::MapBackground;
+use crate::camera::FitMapEvent;
+use crate::data::{sniff_json, Data, JsonFile, LoadError, ReactionState};
+use crate::escher::{
+    ArrowTag, CircleTag, Editable, EscherMap, Hover, MapDimensions, MapState, NodeToText,
+    SecondaryStyle,
+};
 use crate::extra_egui::NewTabHyperlink;
-use crate::geom::{AnyTag, Drag, HistTag, VisCondition, Xaxis};
+use crate::font::BUNDLED_FONTS;
+use crate::funcplot::{category_color, ErrorBar, IgnoreSave, Kernel, Notation, NumberFormat};
+use crate::geom::{AesFilter, AnyTag, Drag, GeomMetabolite, HistTag, Side, VisCondition, Xaxis};
+use crate::grid::snap_to_grid;
 use crate::info::Info;
-use crate::screenshot::ScreenshotEvent;
+use crate::onboarding::Onboarding;
+use crate::provenance::Provenance;
+use crate::screenshot::{
+    AnimationExportEvent, LegendExportEvent, ScreenshotEvent, TiledScreenshotEvent,
+};
+use crate::stats::{SigTest, Summary};
+use crate::theme::Theme;
+// only needed by the windows
+#[cfg(feature = "gui")]
+use crate::{
+    aesthetics::Gy,
+    annotation::ui_annotations,
+    data::DataTransform,
+    escher::Adjacency,
+    funcplot::ArrowStyle,
+    geom::{GeomHist, PopUp},
+    stats::Comparison,
+};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::render::camera::NormalizedRenderTarget;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
 use bevy::window::PrimaryWindow;
 use bevy_egui::egui::color_picker::{color_edit_button_rgba, Alpha};
 use bevy_egui::egui::epaint::Rgba;
 use bevy_egui::{egui, EguiContexts, EguiPlugin, EguiSettings};
-use bevy_prototype_lyon::prelude::Path;
+use bevy_pancam::PanCam;
+use bevy_prototype_lyon::prelude::{shapes, GeometryBuilder, Path, ShapeBundle, Stroke};
 use chrono::offset::Utc;
 use itertools::Itertools;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+#[cfg(feature = "gui")]
+use std::collections::BTreeMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct GuiPlugin;
 
@@ -25,23 +63,66 @@ impl Plugin for GuiPlugin {
             .insert_resource(UiState::default())
             .insert_resource(AxisMode::Hide)
             .insert_resource(ActiveData::default())
+            .init_resource::<Coverage>()
+            .insert_resource(HoverState::default())
+            .insert_resource(Selection::default())
+            .insert_resource(DragBindings::default())
+            .init_resource::<LongPress>()
+            .insert_resource(GuiScale::load())
+            .insert_resource(Onboarding::load())
             .add_event::<SaveEvent>()
-            .add_systems(Update, ui_settings)
+            .add_event::<DataExportEvent>()
+            .add_event::<FileDialogEvent>()
             .add_systems(Update, show_hover)
+            .add_systems(Update, copy_hovered.after(show_hover))
+            .add_systems(Update, select_on_click.after(show_hover))
             .add_systems(Update, follow_mouse_on_drag)
             .add_systems(Update, follow_mouse_on_drag_ui)
             .add_systems(Update, follow_mouse_on_rotate)
             .add_systems(Update, follow_mouse_on_scale)
-            .add_systems(Update, scale_ui)
+            .add_systems(Update, (scale_ui, apply_gui_scale.after(scale_ui)))
             .add_systems(Update, show_axes)
-            .add_systems(Update, (mouse_click_system, mouse_click_ui_system));
+            .add_systems(Update, (mouse_click_system, mouse_click_ui_system))
+            .add_systems(Update, grab_on_long_press)
+            .add_systems(Update, (grab_map_elements, drop_map_elements))
+            .add_systems(Update, grab_annotations.after(mouse_click_system))
+            .add_systems(Update, (select_in_rect, edit_selection))
+            .add_systems(Update, (highlight_listed, compute_coverage));
+
+        // the windows can be left out when embedding the map in other apps
+        #[cfg(feature = "gui")]
+        building
+            .add_systems(Update, (ui_settings, ui_datasets, ui_map_tabs))
+            .add_systems(Update, show_comparison.after(show_hover))
+            .add_systems(
+                Update,
+                (ui_selection, ui_arrange, ui_inspect_axis, ui_coverage),
+            )
+            .add_systems(
+                Update,
+                (
+                    crate::onboarding::ui_news,
+                    ui_annotations,
+                    crate::bookmark::ui_bookmarks,
+                    ui_load_error,
+                    crate::provenance::ui_about,
+                ),
+            );
 
         // file drop and file system does not work in WASM
         #[cfg(not(target_arch = "wasm32"))]
-        building.add_systems(Update, (file_drop, save_file));
+        building.add_systems(Update, (file_drop, file_dialog, save_file, export_data));
 
         #[cfg(target_arch = "wasm32")]
-        building.add_systems(Update, (listen_js_escher, listen_js_data, listen_js_info));
+        building.add_systems(
+            Update,
+            (
+                listen_js_escher,
+                listen_js_data,
+                listen_js_info,
+                listen_js_commands,
+            ),
+        );
     }
 }
 const HIGH_COLOR: Color = Color::rgb(183. / 255., 210. / 255., 255.);
@@ -70,18 +151,72 @@ pub fn or_color<'m>(key: &str, map: &'m mut HashMap<String, Rgba>, random: bool)
     }
 }
 
+/// Action applied to the reactions pasted in the settings.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ListAction {
+    None,
+    /// Hide all the reactions that are not in the list.
+    Only,
+    Hide,
+    Highlight,
+}
+
+impl ListAction {
+    pub const ALL: [ListAction; 4] = [
+        ListAction::None,
+        ListAction::Only,
+        ListAction::Hide,
+        ListAction::Highlight,
+    ];
+}
+
+impl std::fmt::Display for ListAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ListAction::None => "none",
+                ListAction::Only => "show only",
+                ListAction::Hide => "hide",
+                ListAction::Highlight => "highlight",
+            }
+        )
+    }
+}
+
+/// Parse a hex color (e.g., `"7dce96"`) into the color type of the settings.
+pub fn rgba_from_hex(hex: &str) -> Result<Rgba, bevy::render::color::HexColorError> {
+    let [r, g, b, a] = Color::hex(hex)?.as_rgba_u8();
+    Ok(Rgba::from_srgba_unmultiplied(r, g, b, a))
+}
+
 /// Global appeareance settings.
 #[derive(Resource)]
 pub struct UiState {
     pub min_reaction: f32,
     pub max_reaction: f32,
     pub zero_white: bool,
-    pub min_reaction_color: Rgba,
-    pub max_reaction_color: Rgba,
+    /// Scale the colors and sizes with the range of all conditions instead
+    /// of the one of the condition shown.
+    pub global_scale: bool,
+    /// Colors of the lowest values of the reactions per condition, the empty
+    /// string holds those of the conditions that were not set.
+    pub min_reaction_color: HashMap<String, Rgba>,
+    pub max_reaction_color: HashMap<String, Rgba>,
     pub min_metabolite: f32,
     pub max_metabolite: f32,
-    pub min_metabolite_color: Rgba,
-    pub max_metabolite_color: Rgba,
+    pub min_metabolite_color: HashMap<String, Rgba>,
+    pub max_metabolite_color: HashMap<String, Rgba>,
+    /// Color of the arrows and circles whose values are missing.
+    pub missing_color: Rgba,
+    /// Draw the reactions with a value of (about) zero as thin dashed lines.
+    pub knockouts: bool,
+    /// Largest absolute value of a reaction that is drawn as knocked out.
+    pub knockout_threshold: f32,
+    pub knockout_color: Rgba,
+    /// Show the names of the data columns under the axes.
+    pub axis_titles: bool,
     pub max_left: f32,
     pub max_right: f32,
     pub max_top: f32,
@@ -90,11 +225,136 @@ pub struct UiState {
     pub color_top: HashMap<String, Rgba>,
     pub condition: String,
     pub conditions: Vec<String>,
+    /// Condition to compare the hovered element against.
+    pub reference: String,
+    /// Test of the difference between `sig_conditions`, written under the axes.
+    pub sig_test: Option<SigTest>,
+    pub sig_conditions: (String, String),
+    /// Write the p-values instead of asterisks.
+    pub sig_pvalues: bool,
     pub save_path: String,
     pub map_path: String,
     pub data_path: String,
     pub screen_path: String,
+    /// Show the fraction of the map with data in each condition.
+    pub show_coverage: bool,
+    /// Path of the CSV with the mapped values.
+    pub csv_path: String,
+    /// Only export the values of the selected elements to the CSV.
+    pub csv_selected: bool,
+    /// Decimal separator and grouping of the numbers in the legend, the
+    /// scales and the CSV.
+    pub number_format: NumberFormat,
+    /// Files dropped or imported in this session with their kind or why they
+    /// could not be loaded.
+    pub dropped: Vec<(String, Result<&'static str, LoadError>)>,
+    /// Failed file shown in a dialog until it is dismissed.
+    pub load_error: Option<LoadError>,
+    /// Path of the grid of screenshots, one per condition.
+    pub tiles_path: String,
+    /// Path of the animation cycling through the conditions.
+    pub animation_path: String,
+    /// Frames per second of the animation.
+    pub animation_fps: f32,
+    /// Width in pixels of the frames of the animation, 0 for the window size.
+    pub animation_width: u32,
+    /// Path of the legend exported alone.
+    pub legend_path: String,
+    /// Size of the raster legend relative to the one in the window.
+    pub legend_scale: f32,
+    /// Path to save or load the [`Theme`].
+    pub theme_path: String,
     pub hide: bool,
+    /// Reload the map and data files when they change on disk.
+    pub watch: bool,
+    /// Show grid lines and rulers in map coordinates.
+    pub grid: bool,
+    /// Distance between grid lines in map units.
+    pub grid_spacing: f32,
+    /// Snap dragged histograms to the grid.
+    pub snap: bool,
+    /// |ΔG| (kJ/mol) under which reactions are considered near equilibrium.
+    pub thermo_threshold: f32,
+    /// Snap dragged and rotated histograms to the positions and angles of the others.
+    pub snap_axes: bool,
+    /// Keep dragged histograms within `leash_length` of their arrow.
+    pub leash: bool,
+    pub leash_length: f32,
+    /// Connect the histograms moved away from their reaction to its middle.
+    pub leader_lines: bool,
+    /// Move the metabolites and labels of the map instead of the histograms.
+    pub edit_map: bool,
+    /// Show the window to add and edit annotations.
+    pub show_annotations: bool,
+    /// Show the window to bookmark views of the map.
+    pub show_bookmarks: bool,
+    /// Show the window with the [`crate::provenance::Provenance`] of the map.
+    pub show_about: bool,
+    /// Reduce detail of the map when zoomed out.
+    pub lod: bool,
+    /// Camera scale above which the map is rendered with less detail.
+    pub lod_threshold: f32,
+    /// Show the labels of reactions and metabolites.
+    pub labels: bool,
+    /// Factor applied to the font size of the labels.
+    pub label_size: f32,
+    /// Factor applied to the font size of all the texts of the map.
+    pub text_scale: f32,
+    /// Path of the font of the map, see [`crate::font::MapFont`].
+    pub font: String,
+    /// Path to a TTF file written in the settings, used as font once loaded.
+    pub font_path: String,
+    /// Display of the secondary metabolites (cofactors) and their segments.
+    pub secondary: SecondaryStyle,
+    /// Draw arrow heads on both sides of reversible reactions.
+    pub reversibility: bool,
+    /// Reaction identifiers pasted by the user, separated by spaces, commas,
+    /// semicolons or new lines.
+    pub id_list: String,
+    /// What to do with the reactions of `id_list`.
+    pub list_action: ListAction,
+    /// Group whose reactions and metabolites are highlighted, dimming the rest.
+    pub group: Option<String>,
+    /// Color the reactions and metabolites by their group.
+    pub group_colors: bool,
+    /// Range of the values (of the color, or else of the size) of the reactions
+    /// drawn in full in the current condition; the rest is dimmed.
+    pub reaction_filter: Option<(f32, f32)>,
+    /// Like [`UiState::reaction_filter`], for the metabolites.
+    pub metabolite_filter: Option<(f32, f32)>,
+    /// Hide the elements filtered out by value instead of dimming them.
+    pub hide_filtered: bool,
+    /// Kernel of the density estimates.
+    pub kde_kernel: Kernel,
+    /// Bandwidth of the density estimates, in data units.
+    pub kde_bandwidth: f32,
+    /// Number of bins of the histograms.
+    pub hist_bins: u32,
+    /// Samples of a distribution drawn as a histogram or density at most, 0
+    /// to draw all of them.
+    pub max_samples: usize,
+    /// Spread drawn around the mean by the error bar geoms.
+    pub error_bar: ErrorBar,
+    /// Statistic of the distributions mapped to colors and sizes.
+    pub summary: Summary,
+    /// Quantile used by [`Summary::Quantile`].
+    pub summary_quantile: f32,
+    /// Use the same length for all the histogram axes instead of the length of their arrows.
+    pub fixed_hist_length: bool,
+    /// Length of the histogram axes when fixed, in map units.
+    pub hist_length: f32,
+    /// Share the x-range of the histograms of a side across all reactions
+    /// instead of fitting each axis to its own values.
+    pub global_xlimits: bool,
+    /// Fill the side histograms with the color of their arrow instead of the
+    /// color of their side and condition.
+    pub hist_color_by_data: bool,
+    /// Plot the 95% interval of the side distributions as whiskers across
+    /// the arrows instead of histograms or densities.
+    pub whiskers: bool,
+    /// Show the values before the transformation of the datasets in the legend
+    /// of points and in the popups.
+    pub show_original: bool,
     // since this type and field are private, Self has to be initialized
     // with Default::default(), ensuring that the fallbacks for colors (empty string) are set.
     _init: Init,
@@ -105,11 +365,29 @@ struct Init;
 impl Default for UiState {
     fn default() -> Self {
         Self {
-            min_reaction_color: Rgba::from_srgba_unmultiplied(178, 74, 74, 255),
-            max_reaction_color: Rgba::from_srgba_unmultiplied(64, 169, 127, 255),
-            min_metabolite_color: Rgba::from_srgba_unmultiplied(222, 208, 167, 255),
-            max_metabolite_color: Rgba::from_srgba_unmultiplied(189, 143, 120, 255),
+            min_reaction_color: HashMap::from([(
+                String::new(),
+                Rgba::from_srgba_unmultiplied(178, 74, 74, 255),
+            )]),
+            max_reaction_color: HashMap::from([(
+                String::new(),
+                Rgba::from_srgba_unmultiplied(64, 169, 127, 255),
+            )]),
+            min_metabolite_color: HashMap::from([(
+                String::new(),
+                Rgba::from_srgba_unmultiplied(222, 208, 167, 255),
+            )]),
+            max_metabolite_color: HashMap::from([(
+                String::new(),
+                Rgba::from_srgba_unmultiplied(189, 143, 120, 255),
+            )]),
+            missing_color: Rgba::from_srgba_unmultiplied(110, 110, 110, 255),
+            knockouts: false,
+            knockout_threshold: 0.,
+            knockout_color: Rgba::from_srgba_unmultiplied(214, 39, 40, 255),
+            axis_titles: true,
             zero_white: false,
+            global_scale: false,
             min_reaction: 20.,
             max_reaction: 60.,
             min_metabolite: 15.,
@@ -143,23 +421,165 @@ impl Default for UiState {
             },
             condition: String::from(""),
             conditions: vec![String::from("")],
+            reference: String::from(""),
+            sig_test: None,
+            sig_conditions: (String::from(""), String::from("")),
+            sig_pvalues: false,
             save_path: format!("this_map-{}.json", Utc::now().format("%T-%Y")),
             screen_path: format!("screenshot-{}.svg", Utc::now().format("%T-%Y")),
+            tiles_path: format!("tiles-{}.png", Utc::now().format("%T-%Y")),
+            animation_path: format!("conditions-{}.gif", Utc::now().format("%T-%Y")),
+            animation_fps: 1.,
+            animation_width: 0,
+            legend_path: format!("legend-{}.png", Utc::now().format("%T-%Y")),
+            legend_scale: 4.,
+            csv_path: format!("data-{}.csv", Utc::now().format("%T-%Y")),
+            csv_selected: false,
+            number_format: NumberFormat::from_env(),
+            show_coverage: false,
+            dropped: Vec::new(),
+            load_error: None,
+            theme_path: String::from("theme.json"),
             map_path: String::from("my_map.json"),
             data_path: String::from("my_data.metabolism.json"),
             hide: false,
+            watch: false,
+            grid: false,
+            grid_spacing: 100.,
+            snap: false,
+            snap_axes: false,
+            leash: false,
+            leash_length: 300.,
+            leader_lines: true,
+            edit_map: false,
+            show_annotations: false,
+            show_bookmarks: false,
+            show_about: false,
+            thermo_threshold: 1.,
+            lod: true,
+            lod_threshold: 8.,
+            labels: true,
+            label_size: 1.,
+            text_scale: 1.,
+            font: String::from(crate::font::DEFAULT_FONT),
+            font_path: String::new(),
+            secondary: SecondaryStyle::default(),
+            reversibility: false,
+            id_list: String::new(),
+            list_action: ListAction::None,
+            group: None,
+            group_colors: false,
+            reaction_filter: None,
+            metabolite_filter: None,
+            hide_filtered: false,
+            kde_kernel: Kernel::default(),
+            error_bar: ErrorBar::default(),
+            summary: Summary::default(),
+            summary_quantile: 0.5,
+            kde_bandwidth: 1.06,
+            hist_bins: 160,
+            max_samples: 20_000,
+            fixed_hist_length: false,
+            whiskers: false,
+            hist_color_by_data: false,
+            hist_length: 200.,
+            global_xlimits: false,
+            show_original: false,
             _init: Init,
         }
     }
 }
 
 impl UiState {
-    fn get_geom_params_mut(&mut self, extreme: &str, geom: &str) -> (&mut Rgba, &mut f32) {
+    /// Length of the histogram axis of an arrow of size `arrow_size`.
+    pub fn axis_length(&self, arrow_size: f32) -> f32 {
+        if self.fixed_hist_length {
+            self.hist_length
+        } else {
+            arrow_size
+        }
+    }
+
+    /// Whether the reaction `id` is in the pasted [`UiState::id_list`].
+    pub fn listed(&self, id: &str) -> bool {
+        self.id_list
+            .split(|c: char| c.is_whitespace() || (c == ',') || (c == ';'))
+            .any(|listed| listed == id)
+    }
+
+    /// Whether the reaction `id` is hidden by the [`UiState::list_action`].
+    pub fn hides_reaction(&self, id: &str) -> bool {
+        match self.list_action {
+            ListAction::Only => !self.listed(id),
+            ListAction::Hide => self.listed(id),
+            ListAction::None | ListAction::Highlight => false,
+        }
+    }
+
+    /// Whether data of `condition` is shown in the current condition.
+    /// Data without condition is always shown.
+    pub fn shows(&self, condition: Option<&str>) -> bool {
+        condition
+            .map(|c| (self.condition == "ALL") || (c == self.condition))
+            .unwrap_or(true)
+    }
+
+    fn get_scale_params_mut(
+        &mut self,
+        extreme: &str,
+        geom: &str,
+    ) -> (&mut HashMap<String, Rgba>, &mut f32) {
         match (extreme, geom) {
             ("min", "Reaction") => (&mut self.min_reaction_color, &mut self.min_reaction),
             ("max", "Reaction") => (&mut self.max_reaction_color, &mut self.max_reaction),
             ("min", "Metabolite") => (&mut self.min_metabolite_color, &mut self.min_metabolite),
             ("max", "Metabolite") => (&mut self.max_metabolite_color, &mut self.max_metabolite),
+            _ => panic!("Unknown geom"),
+        }
+    }
+
+    /// Condition whose color scales are edited in the settings, the empty
+    /// string for all of them.
+    fn scale_condition(&self) -> String {
+        if self.condition == "ALL" {
+            String::new()
+        } else {
+            self.condition.clone()
+        }
+    }
+
+    /// Colors of the lowest and highest values of the `geom` ("Reaction" or
+    /// "Metabolite") in `condition`, those of all conditions if it was not set.
+    pub fn color_scale(&self, geom: &str, condition: Option<&str>) -> (Rgba, Rgba) {
+        let (min, max) = match geom {
+            "Metabolite" => (&self.min_metabolite_color, &self.max_metabolite_color),
+            _ => (&self.min_reaction_color, &self.max_reaction_color),
+        };
+        let get = |colors: &HashMap<String, Rgba>| {
+            *condition
+                .and_then(|cond| colors.get(cond))
+                .unwrap_or(&colors[""])
+        };
+        (get(min), get(max))
+    }
+
+    /// Whether the `value` of a metabolite (`met`) or a reaction passes the
+    /// filter by value; elements without a value only pass if there is no filter.
+    pub fn passes_filter(&self, met: bool, value: Option<f32>) -> bool {
+        let filter = if met {
+            self.metabolite_filter
+        } else {
+            self.reaction_filter
+        };
+        match (filter, value) {
+            (None, _) => true,
+            (Some((min, max)), Some(value)) => (min..=max).contains(&value),
+            (Some(_), None) => false,
+        }
+    }
+
+    fn get_geom_params_mut(&mut self, extreme: &str, geom: &str) -> (&mut Rgba, &mut f32) {
+        match (extreme, geom) {
             ("left", _) => (
                 or_color(geom, &mut self.color_left, true),
                 &mut self.max_left,
@@ -173,6 +593,22 @@ impl UiState {
         }
     }
 
+    /// Color of the distributions of a `side` in a `condition`, as set in the
+    /// settings. Conditions get a random color the first time that they are seen.
+    pub fn side_color(&mut self, side: &Side, condition: Option<&str>) -> Color {
+        let ui_condition = self.condition.clone();
+        let colors = match side {
+            Side::Left => &mut self.color_left,
+            Side::Right => &mut self.color_right,
+            Side::Up => &mut self.color_top,
+        };
+        let color = match condition {
+            Some(cond) => or_color(cond, colors, true),
+            None => or_color(&ui_condition, colors, false),
+        };
+        Color::rgba_linear(color.r(), color.g(), color.b(), color.a())
+    }
+
     fn get_mut_paths(&mut self, label: &str) -> &mut String {
         match label {
             "Map" => &mut self.map_path,
@@ -196,6 +632,18 @@ pub struct ActiveData {
     pub arrow: bool,
     pub circle: bool,
     pub histogram: ActiveHists,
+    /// Number of elements with missing values in the displayed condition.
+    pub missing: usize,
+    /// Number of histogram axes whose values are all equal.
+    pub flat: usize,
+    /// ΔG of reactions is plotted.
+    pub thermo: bool,
+    /// Some color or size is given as distributions, see [`UiState::summary`].
+    pub summarized: bool,
+    /// Names of the groups of the reactions and metabolites in the data.
+    pub groups: Vec<String>,
+    /// Titles of the plotted columns, sorted.
+    pub columns: Vec<String>,
 }
 
 impl ActiveData {
@@ -218,22 +666,118 @@ impl ActiveData {
 #[derive(Event)]
 pub struct SaveEvent(String);
 
+/// Native file dialogs launched from the Import and Export sections.
+#[derive(Event, Clone, Copy)]
+pub enum FileDialogEvent {
+    OpenMap,
+    OpenData,
+    SaveMap,
+}
+
+/// Write the values mapped in the current condition to a CSV file.
+#[derive(Event)]
+pub struct DataExportEvent {
+    pub path: String,
+    /// Only export the selected reaction and histograms.
+    pub selected_only: bool,
+}
+
+/// Mouse buttons used to move and rotate the histograms and the legend.
+///
+/// Both are configured independently so that interacting with one does not
+/// trigger the other.
+#[derive(Resource)]
+pub struct DragBindings {
+    pub hist_move: MouseButton,
+    pub hist_rotate: MouseButton,
+    /// Speed of rotation and scaling of histograms.
+    pub hist_sensitivity: f32,
+    pub legend_move: MouseButton,
+    pub legend_rotate: MouseButton,
+    /// Speed of rotation of the legend.
+    pub legend_sensitivity: f32,
+    /// Time (in seconds) that moved and rotated entities take to (mostly)
+    /// catch up with the mouse; 0 follows it immediately.
+    pub smoothing: f32,
+    /// Distance (in screen pixels) from the cursor at which histograms and
+    /// hovered elements are picked, the same at any zoom.
+    pub pick_radius: f32,
+}
+
+impl Default for DragBindings {
+    fn default() -> Self {
+        Self {
+            hist_move: MouseButton::Middle,
+            hist_rotate: MouseButton::Right,
+            hist_sensitivity: 1.0,
+            legend_move: MouseButton::Middle,
+            legend_rotate: MouseButton::Right,
+            legend_sensitivity: 1.0,
+            smoothing: 0.0,
+            pick_radius: 25.0,
+        }
+    }
+}
+
+/// Buttons that can be bound to drag actions; left is reserved for panning.
+const DRAG_BUTTONS: [(MouseButton, &str); 2] = [
+    (MouseButton::Middle, "middle"),
+    (MouseButton::Right, "right"),
+];
+
+fn button_combo(ui: &mut egui::Ui, id: &str, button: &mut MouseButton) {
+    let selected = DRAG_BUTTONS
+        .iter()
+        .find(|(b, _)| b == button)
+        .map(|(_, name)| *name)
+        .unwrap_or("-");
+    // labels are repeated for histograms and legend so the id is passed
+    let label = id.rsplit('_').next().unwrap_or(id);
+    egui::ComboBox::new(id, label)
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            for (b, name) in DRAG_BUTTONS {
+                ui.selectable_value(button, b, name);
+            }
+        });
+}
+
+/// Events sent by the buttons of the settings window.
+#[derive(SystemParam)]
+pub struct SettingsEvents<'w> {
+    save: EventWriter<'w, SaveEvent>,
+    load: EventWriter<'w, FileDragAndDrop>,
+    screen: EventWriter<'w, ScreenshotEvent>,
+    tile: EventWriter<'w, TiledScreenshotEvent>,
+    data: EventWriter<'w, DataExportEvent>,
+    dialog: EventWriter<'w, FileDialogEvent>,
+    fit: EventWriter<'w, FitMapEvent>,
+    legend: EventWriter<'w, LegendExportEvent>,
+    animation: EventWriter<'w, AnimationExportEvent>,
+}
+
 /// Settings for appearance of map and plots.
 /// This is managed by [`bevy_egui`] and it is separate from the rest of the GUI.
 pub fn ui_settings(
     mut egui_context: EguiContexts,
     mut state: ResMut<UiState>,
+    mut background: ResMut<MapBackground>,
+    mut bindings: ResMut<DragBindings>,
+    mut gui_scale: ResMut<GuiScale>,
+    mut theme: ResMut<Theme>,
     active_set: Res<ActiveData>,
-    mut save_events: EventWriter<SaveEvent>,
-    mut load_events: EventWriter<FileDragAndDrop>,
-    mut screen_events: EventWriter<ScreenshotEvent>,
-    windows: Query<(Entity, &Window), With<PrimaryWindow>>,
+    mut events: SettingsEvents,
+    mut units: ResMut<Units>,
+    cursor: MapCursor,
+    mut onboarding: ResMut<Onboarding>,
 ) {
     if state.hide {
         return;
     }
     egui::Window::new("Settings").show(egui_context.ctx_mut(), |ui| {
-        ui.visuals_mut().override_text_color = Some(egui::Color32::WHITE);
+        if theme.dark_ui {
+            ui.visuals_mut().override_text_color = Some(egui::Color32::WHITE);
+        }
         for (geom, ext) in ["Reaction", "Metabolite"]
             .into_iter()
             .cartesian_product(["min", "max"])
@@ -241,12 +785,37 @@ pub fn ui_settings(
             if !active_set.get(geom) {
                 continue;
             }
+            let scale_condition = state.scale_condition();
             if "min" == ext {
-                ui.label(format!("{geom} scale"));
+                ui.horizontal(|ui| {
+                    if scale_condition.is_empty() {
+                        ui.label(format!("{geom} scale"));
+                    } else {
+                        ui.label(format!("{geom} scale ({scale_condition})"));
+                    }
+                    let (min_colors, _) = state.get_scale_params_mut("min", geom);
+                    if min_colors.contains_key(&scale_condition)
+                        && !scale_condition.is_empty()
+                        && ui
+                            .small_button("↺")
+                            .on_hover_text("Use the colors of all conditions")
+                            .clicked()
+                    {
+                        min_colors.remove(&scale_condition);
+                        state
+                            .get_scale_params_mut("max", geom)
+                            .0
+                            .remove(&scale_condition);
+                    }
+                });
             }
-            let (color, value) = state.get_geom_params_mut(ext, geom);
+            let (colors, value) = state.get_scale_params_mut(ext, geom);
+            // a condition follows the colors of all conditions until it is edited
+            let mut color = *colors.get(&scale_condition).unwrap_or(&colors[""]);
             ui.horizontal(|ui| {
-                color_edit_button_rgba(ui, color, Alpha::Opaque);
+                if color_edit_button_rgba(ui, &mut color, Alpha::Opaque).changed() {
+                    colors.insert(scale_condition, color);
+                }
                 ui.add(egui::Slider::new(value, 5.0..=90.0).text(ext));
             });
         }
@@ -264,12 +833,298 @@ pub fn ui_settings(
                     ui.add(egui::Slider::new(value, 1.0..=300.0).text(side));
                 });
             }
+            if active_set.flat > 0 {
+                ui.colored_label(
+                    egui::Color32::from_rgb(200, 130, 0),
+                    format!(
+                        "⚠ {} histogram axes have no spread, their values are centered",
+                        active_set.flat
+                    ),
+                );
+            }
         }
 
         if active_set.get("Reaction") | active_set.get("Metabolite") {
             ui.checkbox(&mut state.zero_white, "Zero as white");
+            if state.conditions.len() > 1 {
+                ui.checkbox(&mut state.global_scale, "Same scale for all conditions")
+                    .on_hover_text("Range of the colors and sizes over all conditions");
+            }
+        }
+        if active_set.get("Reaction") {
+            ui.horizontal(|ui| {
+                let knockouts = ui.checkbox(&mut state.knockouts, "Knockouts");
+                onboarding.hint(knockouts, "knockouts").on_hover_text(
+                    "Dash the reactions whose absolute value is under the threshold",
+                );
+                color_edit_button_rgba(ui, &mut state.knockout_color, Alpha::Opaque);
+                ui.add(
+                    egui::DragValue::new(&mut state.knockout_threshold)
+                        .speed(0.01)
+                        .clamp_range(0.0..=f32::MAX)
+                        .prefix("≤ "),
+                );
+            });
+        }
+        if active_set.missing > 0 {
+            ui.horizontal(|ui| {
+                color_edit_button_rgba(ui, &mut state.missing_color, Alpha::Opaque);
+                ui.label(format!("{} missing values", active_set.missing));
+            });
+        }
+
+        if active_set.arrow || active_set.circle {
+            ui.collapsing("Filter by value", |ui| {
+                // borrow both filters at once
+                let filters = &mut *state;
+                for (geom, active, filter) in [
+                    ("Reaction", active_set.arrow, &mut filters.reaction_filter),
+                    (
+                        "Metabolite",
+                        active_set.circle,
+                        &mut filters.metabolite_filter,
+                    ),
+                ] {
+                    if !active {
+                        continue;
+                    }
+                    ui.horizontal(|ui| {
+                        let mut enabled = filter.is_some();
+                        if ui.checkbox(&mut enabled, geom).changed() {
+                            *filter = enabled.then_some((f32::NEG_INFINITY, f32::INFINITY));
+                        }
+                        if let Some((min, max)) = filter {
+                            ui.add(egui::DragValue::new(min).speed(0.1).prefix("≥ "));
+                            ui.add(egui::DragValue::new(max).speed(0.1).prefix("≤ "));
+                        }
+                    });
+                }
+                ui.checkbox(&mut state.hide_filtered, "Hide instead of dimming");
+            });
+        }
+
+        if !active_set.groups.is_empty() {
+            let groups = ui.collapsing("Groups", |ui| {
+                ui.checkbox(&mut state.group_colors, "Color by group");
+                egui::ScrollArea::vertical()
+                    .max_height(200.)
+                    .show(ui, |ui| {
+                        let selected = &mut state.group;
+                        ui.selectable_value(selected, None, "All");
+                        for (i, group) in active_set.groups.iter().enumerate() {
+                            let [r, g, b, _] = category_color(i).as_rgba_u8();
+                            ui.selectable_value(
+                                selected,
+                                Some(group.clone()),
+                                egui::RichText::new(group).color(egui::Color32::from_rgb(r, g, b)),
+                            );
+                        }
+                    });
+            });
+            onboarding.hint(groups.header_response, "groups");
+        }
+
+        if !active_set.columns.is_empty() {
+            let units_header = ui.collapsing("Units", |ui| {
+                egui::Grid::new("units").show(ui, |ui| {
+                    for column in active_set.columns.iter() {
+                        ui.label(column);
+                        let mut unit = units.of(column).to_string();
+                        // only written when edited, to not trigger a relabel every frame
+                        if ui.text_edit_singleline(&mut unit).changed() {
+                            units.0.insert(column.clone(), unit);
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+            onboarding.hint(units_header.header_response, "units");
+        }
+
+        if active_set.thermo {
+            ui.add(
+                egui::Slider::new(&mut state.thermo_threshold, 0.0..=20.0)
+                    .text("ΔG equilibrium (kJ/mol)"),
+            );
+        }
+
+        if active_set.summarized {
+            ui.horizontal(|ui| {
+                let summary = &mut state.summary;
+                egui::ComboBox::from_label("Summary")
+                    .selected_text(summary.to_string())
+                    .show_ui(ui, |ui| {
+                        for sm in Summary::ALL {
+                            ui.selectable_value(summary, sm, sm.to_string());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Statistic of the distributions mapped to colors and sizes");
+                if state.summary == Summary::Quantile {
+                    ui.add(
+                        egui::DragValue::new(&mut state.summary_quantile)
+                            .speed(0.01)
+                            .clamp_range(0.0..=1.0),
+                    );
+                }
+            });
+        }
+
+        if active_set.any_hist() {
+            ui.collapsing("Distributions", |ui| {
+                let kernel = &mut state.kde_kernel;
+                egui::ComboBox::from_label("Kernel")
+                    .selected_text(kernel.to_string())
+                    .show_ui(ui, |ui| {
+                        for k in Kernel::ALL {
+                            ui.selectable_value(kernel, k, k.to_string());
+                        }
+                    });
+                ui.add(
+                    egui::Slider::new(&mut state.kde_bandwidth, 0.001..=100.0)
+                        .logarithmic(true)
+                        .text("bandwidth"),
+                );
+                ui.add(egui::Slider::new(&mut state.hist_bins, 5..=300).text("bins"));
+                ui.add(
+                    egui::DragValue::new(&mut state.max_samples)
+                        .speed(100.)
+                        .clamp_range(0..=10_000_000)
+                        .prefix("max samples: "),
+                )
+                .on_hover_text("Subsample longer distributions to draw them, 0 to draw all");
+                let error_bar = &mut state.error_bar;
+                let error_bars = egui::ComboBox::from_label("Error bars")
+                    .selected_text(error_bar.to_string())
+                    .show_ui(ui, |ui| {
+                        for e in ErrorBar::ALL {
+                            ui.selectable_value(error_bar, e, e.to_string());
+                        }
+                    });
+                onboarding.hint(error_bars.response, "error_bars");
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut state.fixed_hist_length, "Fixed length");
+                    if state.fixed_hist_length {
+                        ui.add(
+                            egui::DragValue::new(&mut state.hist_length)
+                                .clamp_range(20.0..=2000.0)
+                                .suffix(" u"),
+                        );
+                    }
+                });
+                ui.checkbox(&mut state.global_xlimits, "Shared x-range")
+                    .on_hover_text("Use the same x-range for all the histograms of a side");
+                ui.checkbox(&mut state.whiskers, "95% whiskers on arrows");
+                ui.checkbox(&mut state.hist_color_by_data, "Color by arrow")
+                    .on_hover_text("Fill the side histograms with the color of their reaction");
+                ui.checkbox(&mut state.axis_titles, "Axis titles");
+            });
         }
 
+        ui.horizontal(|ui| {
+            if ui.button("Fit map to window (Home)").clicked() {
+                events.fit.send(FitMapEvent);
+            }
+            if ui.button("What's new").clicked() {
+                onboarding.show_news = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut state.grid, "Grid");
+            if state.grid {
+                ui.add(
+                    egui::DragValue::new(&mut state.grid_spacing)
+                        .clamp_range(5.0..=5000.0)
+                        .prefix("spacing: "),
+                );
+            }
+            ui.checkbox(&mut state.snap, "Snap");
+            ui.checkbox(&mut state.snap_axes, "Align")
+                .on_hover_text("Snap histograms to the positions and angles of the others");
+            ui.checkbox(&mut state.edit_map, "Edit map").on_hover_text(
+                "Drag metabolites and labels with the button that moves the histograms; \
+                save the map to keep the changes",
+            );
+            ui.checkbox(&mut state.show_annotations, "Annotations")
+                .on_hover_text("Texts, arrows and rectangles on the map");
+            ui.checkbox(&mut state.show_bookmarks, "Bookmarks")
+                .on_hover_text("Views of the map to jump to with the number keys");
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut state.leash, "Leash")
+                .on_hover_text("Keep dragged histograms near their reaction");
+            if state.leash {
+                ui.add(
+                    egui::DragValue::new(&mut state.leash_length)
+                        .clamp_range(50.0..=5000.0)
+                        .prefix("distance: "),
+                );
+            }
+            ui.checkbox(&mut state.leader_lines, "Leader lines")
+                .on_hover_text("Connect the histograms moved away to their reaction");
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut state.lod, "Level of detail");
+            if state.lod {
+                ui.add(egui::Slider::new(&mut state.lod_threshold, 1.0..=40.0).text("zoom"));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut state.labels, "Labels");
+            if state.labels {
+                ui.add(egui::Slider::new(&mut state.label_size, 0.25..=4.0).text("size"));
+            }
+        });
+        ui.horizontal(|ui| {
+            let bundled = BUNDLED_FONTS.iter().find(|(_, path)| *path == state.font);
+            egui::ComboBox::from_label("Font")
+                .selected_text(bundled.map_or("Custom", |(name, _)| *name))
+                .show_ui(ui, |ui| {
+                    for (name, path) in BUNDLED_FONTS {
+                        if ui.selectable_label(state.font == path, name).clicked() {
+                            state.font = String::from(path);
+                        }
+                    }
+                });
+            ui.add(egui::Slider::new(&mut state.text_scale, 0.5..=3.0).text("text size"))
+                .on_hover_text("Size of all the texts of the map");
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut state.font_path)
+                    .hint_text("font.ttf")
+                    .desired_width(150.),
+            );
+            if ui
+                .add_enabled(!state.font_path.is_empty(), egui::Button::new("Load font"))
+                .clicked()
+            {
+                state.font = state.font_path.clone();
+            }
+        });
+        let secondary = &mut state.secondary;
+        egui::ComboBox::from_label("Secondary metabolites")
+            .selected_text(secondary.to_string())
+            .show_ui(ui, |ui| {
+                for style in SecondaryStyle::ALL {
+                    ui.selectable_value(secondary, style, style.to_string());
+                }
+            });
+        ui.checkbox(&mut state.reversibility, "Reversible reactions");
+        ui.collapsing("Reaction list", |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut state.id_list)
+                    .hint_text("Paste reaction identifiers")
+                    .desired_rows(3),
+            );
+            ui.horizontal(|ui| {
+                let action = &mut state.list_action;
+                for choice in ListAction::ALL {
+                    ui.selectable_value(action, choice, choice.to_string());
+                }
+            });
+        });
+
         if let Some(first_cond) = state.conditions.first() {
             if !((first_cond.is_empty()) & (state.conditions.len() == 1)) {
                 let conditions = state.conditions.clone();
@@ -281,6 +1136,41 @@ pub fn ui_settings(
                             ui.selectable_value(condition, cond.clone(), cond.clone());
                         }
                     });
+                let reference = &mut state.reference;
+                egui::ComboBox::from_label("Reference")
+                    .selected_text(reference.clone())
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(reference, String::from(""), "None");
+                        for cond in conditions.iter().filter(|c| c.as_str() != "ALL") {
+                            ui.selectable_value(reference, cond.clone(), cond.clone());
+                        }
+                    });
+                let significance = ui.collapsing("Significance", |ui| {
+                    let test = &mut state.sig_test;
+                    egui::ComboBox::from_label("Test")
+                        .selected_text(test.map_or(String::from("None"), |t| t.to_string()))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(test, None, "None");
+                            for choice in SigTest::ALL {
+                                ui.selectable_value(test, Some(choice), choice.to_string());
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        let (cond_a, cond_b) = &mut state.sig_conditions;
+                        for (id, cond) in [("sig_a", cond_a), ("sig_b", cond_b)] {
+                            egui::ComboBox::from_id_source(id)
+                                .selected_text(cond.clone())
+                                .show_ui(ui, |ui| {
+                                    for c in conditions.iter().filter(|c| c.as_str() != "ALL") {
+                                        ui.selectable_value(cond, c.clone(), c.clone());
+                                    }
+                                });
+                        }
+                    });
+                    ui.checkbox(&mut state.sig_pvalues, "p-values")
+                        .on_hover_text("Write the p-values instead of asterisks");
+                });
+                onboarding.hint(significance.header_response, "significance");
             }
         }
         // direct interactions with the file system are not supported in WASM
@@ -289,24 +1179,121 @@ pub fn ui_settings(
             #[cfg(not(target_arch = "wasm32"))]
             ui.horizontal(|ui| {
                 if ui.button("Save map").clicked() {
-                    save_events.send(SaveEvent(state.save_path.clone()));
+                    events.save.send(SaveEvent(state.save_path.clone()));
                 }
                 ui.text_edit_singleline(&mut state.save_path);
+                if ui.button("…").on_hover_text("Choose file").clicked() {
+                    events.dialog.send(FileDialogEvent::SaveMap);
+                }
             });
 
             ui.horizontal(|ui| {
                 if ui.button("Image").clicked() {
-                    screen_events.send(ScreenshotEvent {
+                    events.screen.send(ScreenshotEvent {
                         path: state.screen_path.clone(),
                     });
                     state.hide = true;
                 }
                 ui.text_edit_singleline(&mut state.screen_path);
-            })
+            });
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if state.conditions.len() > 1 {
+                ui.horizontal(|ui| {
+                    if ui.button("Tiles").clicked() {
+                        events.tile.send(TiledScreenshotEvent {
+                            path: state.tiles_path.clone(),
+                        });
+                    }
+                    ui.text_edit_singleline(&mut state.tiles_path);
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Animation").clicked() {
+                        events.animation.send(AnimationExportEvent {
+                            path: state.animation_path.clone(),
+                            fps: state.animation_fps,
+                            width: state.animation_width,
+                        });
+                    }
+                    ui.text_edit_singleline(&mut state.animation_path);
+                });
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut state.animation_fps)
+                            .speed(0.1)
+                            .clamp_range(0.1..=30.0)
+                            .suffix(" fps"),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut state.animation_width)
+                            .clamp_range(0..=7680)
+                            .suffix(" px"),
+                    )
+                    .on_hover_text("Width of the frames, 0 keeps the size of the window");
+                })
+                .response
+                .on_hover_text("GIF, or numbered PNG frames for other extensions");
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.horizontal(|ui| {
+                if ui.button("Legend").clicked() {
+                    events.legend.send(LegendExportEvent {
+                        path: state.legend_path.clone(),
+                        scale: state.legend_scale,
+                    });
+                }
+                ui.text_edit_singleline(&mut state.legend_path);
+                ui.add(
+                    egui::DragValue::new(&mut state.legend_scale)
+                        .speed(0.1)
+                        .clamp_range(1.0..=10.0)
+                        .suffix("×"),
+                )
+                .on_hover_text("Resolution of raster images relative to the window");
+            });
+
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.horizontal(|ui| {
+                if ui.button("Data").clicked() {
+                    events.data.send(DataExportEvent {
+                        path: state.csv_path.clone(),
+                        selected_only: state.csv_selected,
+                    });
+                }
+                ui.text_edit_singleline(&mut state.csv_path);
+                ui.checkbox(&mut state.csv_selected, "selected");
+            });
+            ui.horizontal(|ui| {
+                let decimal_comma =
+                    ui.checkbox(&mut state.number_format.decimal_comma, "Decimal comma");
+                onboarding
+                    .hint(decimal_comma, "number_format")
+                    .on_hover_text("Also in the legend; the CSV is then separated by semicolons");
+                ui.checkbox(&mut state.number_format.grouping, "Group thousands");
+            });
+            ui.horizontal(|ui| {
+                let notation = &mut state.number_format.notation;
+                egui::ComboBox::from_label("Labels")
+                    .selected_text(notation.to_string())
+                    .show_ui(ui, |ui| {
+                        for n in Notation::ALL {
+                            ui.selectable_value(notation, n, n.to_string());
+                        }
+                    });
+                ui.add(
+                    egui::DragValue::new(&mut state.number_format.decimals)
+                        .clamp_range(0..=6)
+                        .suffix(" decimals"),
+                )
+                .on_hover_text("Of the numbers in the legend and the scales of the popups");
+            });
+            ui.checkbox(&mut state.show_about, "About this map")
+                .on_hover_text("Sources of the map and the data, stored in saved maps and SVGs");
         });
         #[cfg(not(target_arch = "wasm32"))]
         ui.collapsing("Import", |ui| {
-            let Ok((win, _)) = windows.get_single() else {
+            let Some(win) = cursor.window() else {
                 return;
             };
             for label in ["Map", "Data"] {
@@ -314,14 +1301,163 @@ pub fn ui_settings(
                 ui.horizontal(|ui| {
                     if ui.button(label).clicked() {
                         // piggyback on file_drop()
-                        load_events.send(FileDragAndDrop::DroppedFile {
+                        events.load.send(FileDragAndDrop::DroppedFile {
                             window: win,
                             path_buf: path.clone().into(),
                         });
                     }
                     ui.text_edit_singleline(path);
+                    if ui.button("…").on_hover_text("Choose file").clicked() {
+                        events.dialog.send(if label == "Map" {
+                            FileDialogEvent::OpenMap
+                        } else {
+                            FileDialogEvent::OpenData
+                        });
+                    }
+                });
+            }
+            ui.checkbox(&mut state.watch, "Reload on file changes");
+            if !state.dropped.is_empty() {
+                ui.separator();
+                let mut reopened = None;
+                for (path, result) in state.dropped.iter() {
+                    let name = std::path::Path::new(path)
+                        .file_name()
+                        .map_or(path.clone(), |name| name.to_string_lossy().to_string());
+                    match result {
+                        Ok(kind) => ui.label(format!("✔ {name} ({kind})")).on_hover_text(path),
+                        Err(e) => {
+                            let label = ui
+                                .add(
+                                    egui::Label::new(
+                                        egui::RichText::new(format!("✖ {name}"))
+                                            .color(egui::Color32::LIGHT_RED),
+                                    )
+                                    .sense(egui::Sense::click()),
+                                )
+                                .on_hover_text(format!("{path}: {e}\nClick for details"));
+                            if label.clicked() {
+                                reopened = Some(e.clone());
+                            }
+                            label
+                        }
+                    };
+                }
+                if reopened.is_some() {
+                    state.load_error = reopened;
+                }
+                if ui.button("Clear").clicked() {
+                    state.dropped.clear();
+                }
+            }
+        });
+        ui.collapsing("Mouse", |ui| {
+            ui.label("Histograms");
+            button_combo(ui, "hist_move", &mut bindings.hist_move);
+            button_combo(ui, "hist_rotate", &mut bindings.hist_rotate);
+            ui.add(
+                egui::Slider::new(&mut bindings.hist_sensitivity, 0.1..=5.0).text("sensitivity"),
+            );
+            ui.label("Legend");
+            button_combo(ui, "legend_move", &mut bindings.legend_move);
+            button_combo(ui, "legend_rotate", &mut bindings.legend_rotate);
+            ui.add(
+                egui::Slider::new(&mut bindings.legend_sensitivity, 0.1..=5.0).text("sensitivity"),
+            );
+            ui.add(egui::Slider::new(&mut bindings.smoothing, 0.0..=0.5).text("smoothing (s)"))
+                .on_hover_text("Ease moved and rotated elements towards the mouse");
+            ui.add(
+                egui::Slider::new(&mut bindings.pick_radius, 5.0..=100.0)
+                    .text("picking radius (px)"),
+            )
+            .on_hover_text("How close to the cursor histograms and hovered elements are picked");
+        });
+        ui.collapsing("Interface", |ui| {
+            ui.label("Settings size (Ctrl + scroll)");
+            ui.horizontal_wrapped(|ui| {
+                for preset in GUI_SCALE_PRESETS {
+                    let selected = (gui_scale.egui - preset).abs() < 1e-3;
+                    if ui
+                        .selectable_label(selected, format!("{:.0}%", preset * 100.))
+                        .clicked()
+                    {
+                        gui_scale.egui = preset;
+                    }
+                }
+            });
+            let mut legend = gui_scale.legend;
+            ui.add(
+                egui::Slider::new(&mut legend, MIN_GUI_SCALE..=MAX_GUI_SCALE).text("legend size"),
+            );
+            if legend != gui_scale.legend {
+                gui_scale.legend = legend;
+            }
+        });
+        ui.collapsing("Background", |ui| {
+            // bypass change detection so that the sprite is only updated on edits
+            let bg = background.bypass_change_detection();
+            let before = bg.clone();
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.horizontal(|ui| {
+                if ui.button("Image").clicked() {
+                    bg.dirty = true;
+                }
+                ui.text_edit_singleline(&mut bg.path);
+            });
+            ui.add(egui::Slider::new(&mut bg.opacity, 0.0..=1.0).text("opacity"));
+            ui.add(egui::Slider::new(&mut bg.scale, 0.05..=20.0).text("scale"));
+            ui.horizontal(|ui| {
+                ui.label("offset");
+                ui.add(egui::DragValue::new(&mut bg.offset.x).prefix("x: "));
+                ui.add(egui::DragValue::new(&mut bg.offset.y).prefix("y: "));
+            });
+            if *bg != before {
+                background.set_changed();
+            }
+        });
+        ui.collapsing("Theme", |ui| {
+            // bypass change detection so that the map is only recolored on edits
+            let th = theme.bypass_change_detection();
+            let before = th.clone();
+            ui.horizontal(|ui| {
+                if ui.button("Light").clicked() {
+                    *th = Theme::light();
+                }
+                if ui.button("Dark").clicked() {
+                    *th = Theme::dark();
+                }
+            });
+            for (color, label) in [
+                (&mut th.background, "background"),
+                (&mut th.arrow, "reactions"),
+                (&mut th.metabolite, "metabolites"),
+                (&mut th.metabolite_stroke, "metabolite border"),
+                (&mut th.text, "labels"),
+            ] {
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_srgb(color);
+                    ui.label(label);
                 });
             }
+            ui.checkbox(&mut th.dark_ui, "Dark settings");
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    if let Err(e) = safe_json_write(&state.theme_path, &*th) {
+                        warn!("Could not save the theme: {e}");
+                    }
+                }
+                if ui.button("Load").clicked() {
+                    match Theme::from_file(&state.theme_path) {
+                        Ok(loaded) => *th = loaded,
+                        Err(e) => warn!("Could not load the theme: {e}"),
+                    }
+                }
+                ui.text_edit_singleline(&mut state.theme_path);
+            });
+            if *th != before {
+                theme.set_changed();
+            }
         });
 
         ui.add(NewTabHyperlink::from_label_and_url(
@@ -331,59 +1467,509 @@ pub fn ui_settings(
     });
 }
 
-/// Open `.metabolism.json` and `.reactions.json` files when dropped on the window.
-pub fn file_drop(
-    mut info_state: ResMut<Info>,
-    asset_server: Res<AssetServer>,
+/// List the loaded datasets so that they can be toggled, re-ordered or removed.
+#[cfg(feature = "gui")]
+fn ui_datasets(
+    mut egui_context: EguiContexts,
+    mut state: ResMut<UiState>,
     mut reaction_resource: ResMut<ReactionState>,
-    mut escher_resource: ResMut<MapState>,
-    mut events: EventReader<FileDragAndDrop>,
+    data_assets: Res<Assets<Data>>,
+    mut onboarding: ResMut<Onboarding>,
 ) {
-    for event in events.read() {
-        if let FileDragAndDrop::DroppedFile { path_buf, .. } = event {
-            println!("Dropped file with path: {:?}", path_buf);
-
-            let path_string = path_buf.to_str().unwrap().to_string();
-            if path_buf.to_str().unwrap().ends_with("metabolism.json") {
-                let reaction_handle: Handle<Data> = asset_server.load(path_string);
-                reaction_resource.reaction_data = Some(reaction_handle);
-                reaction_resource.loaded = false;
-                info_state.notify("(gui) Loading data...");
-            } else {
-                //an escher map
-                let escher_handle: Handle<EscherMap> = asset_server.load(path_string);
-                escher_resource.escher_map = escher_handle;
-                escher_resource.loaded = false;
-                info_state.notify("Loading map...");
-            }
-        }
+    if state.hide || reaction_resource.datasets.is_empty() {
+        return;
     }
-}
-
-/// Cursor to mouse position. Adapted from bevy cheatbook.
-fn get_pos(win: &Window, camera: &Camera, camera_transform: &GlobalTransform) -> Option<Vec2> {
-    win.cursor_position()
-        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
-        .map(|ray| ray.origin.truncate())
-}
-
-/// Show hovered data on cursor enter.
-fn show_hover(
-    ui_state: Res<UiState>,
-    windows: Query<&Window, With<PrimaryWindow>>,
-    hover_query: Query<(&Transform, &Hover)>,
-    mut popup_query: Query<(&mut Visibility, &AnyTag, &VisCondition), With<HistTag>>,
-    q_camera: Query<(&Camera, &GlobalTransform)>,
+    let n = reaction_resource.datasets.len();
+    let mut changed = false;
+    let mut swap: Option<(usize, usize)> = None;
+    let mut remove: Option<usize> = None;
+    egui::Window::new("Datasets").show(egui_context.ctx_mut(), |ui| {
+        for (i, dataset) in reaction_resource
+            .bypass_change_detection()
+            .datasets
+            .iter_mut()
+            .enumerate()
+        {
+            ui.horizontal(|ui| {
+                let name = std::path::Path::new(&dataset.name)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or(&dataset.name)
+                    .to_string();
+                changed |= ui
+                    .checkbox(&mut dataset.active, name)
+                    .on_hover_text(&dataset.name)
+                    .changed();
+                if ui.add_enabled(i > 0, egui::Button::new("⏶")).clicked() {
+                    swap = Some((i - 1, i));
+                }
+                if ui.add_enabled(i + 1 < n, egui::Button::new("⏷")).clicked() {
+                    swap = Some((i, i + 1));
+                }
+                if ui.button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                let transform = dataset.transform;
+                egui::ComboBox::from_id_source(("transform", i))
+                    .selected_text(transform.to_string())
+                    .show_ui(ui, |ui| {
+                        for t in DataTransform::ALL {
+                            ui.selectable_value(&mut dataset.transform, t, t.to_string());
+                        }
+                    });
+                changed |= transform != dataset.transform;
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut dataset.layer)
+                            .clamp_range(-10..=10)
+                            .speed(0.05)
+                            .prefix("z: "),
+                    )
+                    .on_hover_text("Plots of higher layers are drawn on top")
+                    .changed();
+                let arrow_style = dataset.arrow_style;
+                egui::ComboBox::from_id_source(("arrow_style", i))
+                    .selected_text(arrow_style.to_string())
+                    .show_ui(ui, |ui| {
+                        for style in ArrowStyle::ALL {
+                            ui.selectable_value(&mut dataset.arrow_style, style, style.to_string());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Size of the reactions; ribbons narrow towards the products");
+                changed |= arrow_style != dataset.arrow_style;
+                let Some(data) = data_assets.get(&dataset.handle) else {
+                    return;
+                };
+                let metrics = data.metric_names();
+                if metrics.is_empty() {
+                    return;
+                }
+                let metric = dataset.metric.clone();
+                egui::ComboBox::from_id_source(("metric", i))
+                    .selected_text(metric.as_deref().unwrap_or("default"))
+                    .show_ui(ui, |ui| {
+                        if data.has_values() {
+                            ui.selectable_value(&mut dataset.metric, None, "default");
+                        }
+                        for m in metrics {
+                            ui.selectable_value(&mut dataset.metric, Some(m.clone()), m.as_str());
+                        }
+                    });
+                changed |= metric != dataset.metric;
+            });
+        }
+        if reaction_resource
+            .datasets
+            .iter()
+            .any(|d| d.transform != DataTransform::Identity)
+        {
+            ui.checkbox(&mut state.show_original, "Show original values");
+        }
+        let coverage = ui.checkbox(&mut state.show_coverage, "Coverage");
+        onboarding
+            .hint(coverage, "coverage")
+            .on_hover_text("Fraction of the map with data in each condition");
+    });
+    if let Some((a, b)) = swap {
+        reaction_resource.datasets.swap(a, b);
+        changed = true;
+    }
+    if let Some(i) = remove {
+        reaction_resource.datasets.remove(i);
+        changed = true;
+    }
+    if changed {
+        reaction_resource.loaded = false;
+    }
+}
+
+/// Fraction of the reactions and metabolites of the map with data in each
+/// condition, as `(condition, reactions, metabolites)`.
+#[derive(Resource, Default)]
+pub struct Coverage(pub Vec<(String, f32, f32)>);
+
+/// Conditions where less than this fraction of the map has data are flagged.
+#[cfg(feature = "gui")]
+const SPARSE_COVERAGE: f32 = 0.5;
+
+/// Compute the [`Coverage`] when the data or the map change.
+pub fn compute_coverage(
+    mut coverage: ResMut<Coverage>,
+    new_elements: Query<(), Or<(Added<Aesthetics>, Added<ArrowTag>, Added<CircleTag>)>>,
+    mut removed: RemovedComponents<Aesthetics>,
+    data: Query<&Aesthetics>,
+    arrows: Query<&ArrowTag>,
+    circles: Query<&CircleTag>,
 ) {
-    let (camera, camera_transform) = q_camera.single();
-    let Ok(win) = windows.get_single() else {
+    let removed_any = removed.read().count() > 0;
+    if new_elements.is_empty() && !removed_any {
         return;
+    }
+    coverage.0.clear();
+    if data.is_empty() {
+        return;
+    }
+    let reactions: HashSet<&str> = arrows.iter().map(|arrow| arrow.id.as_str()).collect();
+    let metabolites: HashSet<&str> = circles.iter().map(|circle| circle.id.as_str()).collect();
+    let fraction = |ids: &HashSet<&str>, of: &HashSet<&str>| {
+        if of.is_empty() {
+            0.
+        } else {
+            ids.intersection(of).count() as f32 / of.len() as f32
+        }
     };
-    if let Some(world_pos) = get_pos(win, camera, camera_transform) {
-        for (trans, hover) in hover_query.iter() {
-            if (world_pos - Vec2::new(trans.translation.x, trans.translation.y)).length_squared()
-                < 5000.
+    let mut conditions: Vec<String> = data
+        .iter()
+        .filter_map(|aes| aes.condition.clone())
+        .unique()
+        .sorted()
+        .collect();
+    if conditions.is_empty() {
+        conditions.push(String::new());
+    }
+    for condition in conditions {
+        // data without condition is shown in every condition
+        let ids: HashSet<&str> = data
+            .iter()
+            .filter(|aes| aes.condition.as_ref().is_none_or(|c| c == &condition))
+            .flat_map(|aes| aes.identifiers.iter().map(|id| id.as_str()))
+            .collect();
+        let (with_reactions, with_metabolites) =
+            (fraction(&ids, &reactions), fraction(&ids, &metabolites));
+        coverage
+            .0
+            .push((condition, with_reactions, with_metabolites));
+    }
+}
+
+/// Show the [`Coverage`] as a heatmap, flagging the sparse conditions.
+#[cfg(feature = "gui")]
+fn ui_coverage(
+    mut egui_context: EguiContexts,
+    mut state: ResMut<UiState>,
+    coverage: Res<Coverage>,
+) {
+    if state.hide || !state.show_coverage || coverage.0.is_empty() {
+        return;
+    }
+    let cell = |ui: &mut egui::Ui, fraction: f32| {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(70., 18.), egui::Sense::hover());
+        let color = egui::Color32::from_rgb(
+            (200. * (1. - fraction)) as u8 + 40,
+            (160. * fraction) as u8 + 60,
+            70,
+        );
+        ui.painter().rect_filled(rect, 2., color);
+        ui.painter().text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            format!("{:.0}%", fraction * 100.),
+            egui::FontId::default(),
+            egui::Color32::WHITE,
+        );
+    };
+    egui::Window::new("Coverage")
+        .open(&mut state.show_coverage)
+        .show(egui_context.ctx_mut(), |ui| {
+            egui::Grid::new("coverage_grid").show(ui, |ui| {
+                ui.label("condition");
+                ui.label("reactions");
+                ui.label("metabolites");
+                ui.end_row();
+                for (condition, reactions, metabolites) in coverage.0.iter() {
+                    let name = if condition.is_empty() {
+                        "all data"
+                    } else {
+                        condition.as_str()
+                    };
+                    if reactions.max(*metabolites) < SPARSE_COVERAGE {
+                        ui.colored_label(egui::Color32::from_rgb(200, 130, 0), format!("⚠ {name}"))
+                            .on_hover_text("Most of the map has no data in this condition");
+                    } else {
+                        ui.label(name);
+                    }
+                    cell(ui, *reactions);
+                    cell(ui, *metabolites);
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+/// Open `.metabolism.json` and `.reactions.json` files when dropped on the window.
+pub fn file_drop(
+    mut info_state: ResMut<Info>,
+    mut ui_state: ResMut<UiState>,
+    asset_server: Res<AssetServer>,
+    mut reaction_resource: ResMut<ReactionState>,
+    mut escher_resource: ResMut<MapState>,
+    mut data_assets: ResMut<Assets<Data>>,
+    mut escher_assets: ResMut<Assets<EscherMap>>,
+    mut events: EventReader<FileDragAndDrop>,
+) {
+    for event in events.read() {
+        if let FileDragAndDrop::DroppedFile { path_buf, .. } = event {
+            println!("Dropped file with path: {:?}", path_buf);
+
+            let path_string = path_buf.to_string_lossy().to_string();
+            #[cfg(feature = "parquet")]
+            if crate::data::LONG_TABLE_EXTENSIONS
+                .iter()
+                .any(|ext| path_string.ends_with(&format!(".{ext}")))
             {
+                ui_state.data_path.clone_from(&path_string);
+                reaction_resource.add(path_string.clone(), asset_server.load(path_string.clone()));
+                info_state.notify("(gui) Loading data...");
+                ui_state.dropped.push((path_string, Ok("table")));
+                continue;
+            }
+            let sniffed = std::fs::read(path_buf)
+                .map_err(|e| LoadError::new(&path_string, e.to_string()))
+                .and_then(|bytes| {
+                    sniff_json(&bytes).map_err(|e| LoadError::from_json(&path_string, &e))
+                });
+            // the asset loaders are chosen by extension, so files with other names
+            // are added directly (without hot reloading)
+            let data_by_name = path_string.ends_with("metabolism.json");
+            let map_by_name = path_string.ends_with(".json") && !data_by_name;
+            let result = match sniffed {
+                Ok(JsonFile::Data(data)) => {
+                    ui_state.data_path.clone_from(&path_string);
+                    let reaction_handle: Handle<Data> = if data_by_name {
+                        asset_server.load(path_string.clone())
+                    } else {
+                        data_assets.add(data)
+                    };
+                    reaction_resource.add(path_string.clone(), reaction_handle);
+                    info_state.notify("(gui) Loading data...");
+                    Ok("data")
+                }
+                Ok(JsonFile::Map(escher_map)) => {
+                    ui_state.map_path.clone_from(&path_string);
+                    let escher_handle: Handle<EscherMap> = if map_by_name {
+                        asset_server.load(path_string.clone())
+                    } else {
+                        escher_assets.add(escher_map)
+                    };
+                    escher_resource.open(path_string.clone(), escher_handle);
+                    info_state.notify("Loading map...");
+                    Ok("map")
+                }
+                Err(e) => {
+                    warn!("Could not load {path_string}: {e}");
+                    info_state.notify("Failed loading file! See Import for details.");
+                    ui_state.load_error = Some(e.clone());
+                    Err(e)
+                }
+            };
+            ui_state.dropped.push((path_string, result));
+        }
+    }
+}
+
+/// Dialog with why the last file could not be loaded.
+#[cfg(feature = "gui")]
+fn ui_load_error(mut egui_context: EguiContexts, mut state: ResMut<UiState>) {
+    let Some(error) = state.load_error.as_ref() else {
+        return;
+    };
+    let mut open = true;
+    let mut dismissed = false;
+    egui::Window::new("Could not load file")
+        .open(&mut open)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.monospace(&error.path);
+            if let Some((line, column)) = error.position {
+                ui.label(format!("Line {line}, column {column}"));
+            }
+            ui.colored_label(egui::Color32::LIGHT_RED, &error.message);
+            dismissed = ui.button("OK").clicked();
+        });
+    if !open || dismissed {
+        state.load_error = None;
+    }
+}
+
+/// Open the native file dialog requested by a [`FileDialogEvent`] without
+/// blocking the app and act on the chosen path once it is closed, as if the
+/// path had been written in the text input.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn file_dialog(
+    mut ui_state: ResMut<UiState>,
+    mut events: EventReader<FileDialogEvent>,
+    mut load_events: EventWriter<FileDragAndDrop>,
+    mut save_events: EventWriter<SaveEvent>,
+    mut pending: Local<Option<(FileDialogEvent, Task<Option<std::path::PathBuf>>)>>,
+    cursor: MapCursor,
+) {
+    for event in events.read() {
+        // only one dialog at a time
+        if pending.is_some() {
+            continue;
+        }
+        let event = *event;
+        let save_name = std::path::Path::new(&ui_state.save_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            let dialog = rfd::AsyncFileDialog::new().add_filter("JSON", &["json"]);
+            #[cfg(feature = "parquet")]
+            let dialog = match event {
+                FileDialogEvent::OpenData => {
+                    dialog.add_filter("Tables", &crate::data::LONG_TABLE_EXTENSIONS)
+                }
+                _ => dialog,
+            };
+            let handle = match event {
+                FileDialogEvent::SaveMap => dialog.set_file_name(save_name).save_file().await,
+                _ => dialog.pick_file().await,
+            };
+            handle.map(|file| file.path().to_path_buf())
+        });
+        *pending = Some((event, task));
+    }
+    let Some((event, task)) = pending.as_mut() else {
+        return;
+    };
+    let Some(chosen) = block_on(future::poll_once(task)) else {
+        return;
+    };
+    let event = *event;
+    *pending = None;
+    let Some(path_buf) = chosen else {
+        // dialog was cancelled
+        return;
+    };
+    let path = path_buf.to_string_lossy().to_string();
+    match event {
+        FileDialogEvent::SaveMap => {
+            ui_state.save_path.clone_from(&path);
+            save_events.send(SaveEvent(path));
+        }
+        FileDialogEvent::OpenMap | FileDialogEvent::OpenData => {
+            let label = match event {
+                FileDialogEvent::OpenMap => "Map",
+                _ => "Data",
+            };
+            *ui_state.get_mut_paths(label) = path;
+            let Some(window) = cursor.window() else {
+                return;
+            };
+            // piggyback on file_drop()
+            load_events.send(FileDragAndDrop::DroppedFile { window, path_buf });
+        }
+    }
+}
+
+/// Windows and the cameras rendering to them, without assuming that there is
+/// exactly one of each: the primary window may be closed and recreated, and
+/// other windows or cameras may be spawned by apps that embed shu.
+#[derive(SystemParam)]
+pub struct MapCursor<'w, 's> {
+    windows: Query<'w, 's, (Entity, &'static Window, Has<PrimaryWindow>)>,
+    cameras: Query<
+        'w,
+        's,
+        (
+            &'static Camera,
+            &'static GlobalTransform,
+            Option<&'static OrthographicProjection>,
+        ),
+    >,
+    touches: Option<Res<'w, Touches>>,
+}
+
+impl MapCursor<'_, '_> {
+    /// The primary window or, if there is none, any other window.
+    pub fn window(&self) -> Option<Entity> {
+        self.windows
+            .iter()
+            .find(|(_, _, primary)| *primary)
+            .or_else(|| self.windows.iter().next())
+            .map(|(entity, _, _)| entity)
+    }
+
+    /// Cursor in world coordinates, through the active camera that renders to
+    /// the window that the cursor is on. Without a cursor, the first finger on
+    /// a touch screen is used instead, taken to be on the primary window.
+    pub fn world_pos(&self) -> Option<Vec2> {
+        self.world_pick(0.).map(|(pos, _)| pos)
+    }
+
+    /// Cursor in world coordinates, as in [`MapCursor::world_pos`], and the
+    /// distance in the world that spans `pixels` on the screen at the zoom of
+    /// the camera, to pick elements at the same apparent distance.
+    pub fn world_pick(&self, pixels: f32) -> Option<(Vec2, f32)> {
+        let primary = self
+            .windows
+            .iter()
+            .find(|(_, _, primary)| *primary)
+            .map(|(entity, _, _)| entity);
+        let finger = self
+            .touches
+            .as_ref()
+            .and_then(|touches| touches.iter().next())
+            .map(|touch| touch.position());
+        self.windows.iter().find_map(|(entity, win, is_primary)| {
+            let cursor = win.cursor_position().or(finger.filter(|_| is_primary))?;
+            self.cameras
+                .iter()
+                .filter(|(camera, _, _)| camera.is_active)
+                .find(|(camera, _, _)| {
+                    matches!(
+                        camera.target.normalize(primary),
+                        Some(NormalizedRenderTarget::Window(window)) if window.entity() == entity
+                    )
+                })
+                .and_then(|(camera, camera_transform, projection)| {
+                    let scale = projection.map(|proj| proj.scale).unwrap_or(1.);
+                    get_pos(cursor, camera, camera_transform)
+                        .map(|pos| (pos, pick_distance(pixels, scale)))
+                })
+        })
+    }
+}
+
+/// Distance in the world that spans `pixels` on the screen for a camera
+/// zoomed out by `scale`.
+pub fn pick_distance(pixels: f32, scale: f32) -> f32 {
+    pixels * scale
+}
+
+/// Cursor to mouse position. Adapted from bevy cheatbook.
+fn get_pos(cursor: Vec2, camera: &Camera, camera_transform: &GlobalTransform) -> Option<Vec2> {
+    camera
+        .viewport_to_world(camera_transform, cursor)
+        .map(|ray| ray.origin.truncate())
+}
+
+/// Element currently under the cursor, tracked by [`show_hover`].
+#[derive(Resource, Default)]
+pub struct HoverState {
+    pub node_id: Option<u64>,
+    pub id: Option<String>,
+    /// Whether the hovered element is a metabolite.
+    pub met: bool,
+}
+
+/// Show hovered data on cursor enter.
+fn show_hover(
+    ui_state: Res<UiState>,
+    mut hover_state: ResMut<HoverState>,
+    bindings: Res<DragBindings>,
+    cursor: MapCursor,
+    hover_query: Query<(&Transform, &Hover, Has<CircleTag>)>,
+    mut popup_query: Query<(&mut Visibility, &AnyTag, &VisCondition), With<HistTag>>,
+) {
+    if let Some((world_pos, radius)) = cursor.world_pick(bindings.pick_radius) {
+        let mut hovered = None;
+        for (trans, hover, is_met) in hover_query.iter() {
+            if world_pos.distance_squared(trans.translation.truncate()) < radius * radius {
+                hovered = Some((hover.node_id, &hover.id, is_met));
                 for (mut vis, tag, hist) in popup_query.iter_mut() {
                     let cond_if = hist
                         .condition
@@ -407,132 +1993,1116 @@ fn show_hover(
                 }
             }
         }
+        // only write on change to keep change detection meaningful
+        if hover_state.node_id != hovered.map(|(node_id, _, _)| node_id) {
+            *hover_state = match hovered {
+                Some((node_id, id, met)) => HoverState {
+                    node_id: Some(node_id),
+                    id: Some(id.clone()),
+                    met,
+                },
+                None => HoverState::default(),
+            };
+        }
+    } else if hover_state.node_id.is_some() {
+        // the cursor left the window
+        *hover_state = HoverState::default();
+    }
+}
+
+/// Name of the column of an aesthetic, empty if it has none.
+fn column_name(name: Option<&ColumnName>) -> &str {
+    name.map_or("", |c| c.0.as_str())
+}
+
+/// Copy the identifier and the values of the hovered element in the current
+/// condition to the clipboard on Ctrl+C, one tab-separated line per column.
+fn copy_hovered(
+    mut egui_context: EguiContexts,
+    mut info_state: ResMut<Info>,
+    key_input: Res<ButtonInput<KeyCode>>,
+    ui_state: Res<UiState>,
+    hover_state: Res<HoverState>,
+    points: Query<(
+        &Aesthetics,
+        &Point<f32>,
+        Option<&ColumnName>,
+        Option<&AesFilter>,
+        Has<GeomMetabolite>,
+    )>,
+    dists: Query<(
+        &Aesthetics,
+        &Distribution<f32>,
+        Option<&ColumnName>,
+        &AesFilter,
+    )>,
+) {
+    let ctrl = key_input.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ]);
+    if !(ctrl && key_input.just_pressed(KeyCode::KeyC)) {
+        return;
+    }
+    let Some(id) = hover_state.id.as_ref() else {
+        return;
+    };
+    // the text being edited in the settings is copied instead
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let mut lines = Vec::new();
+    for (aes, point, name, filter, is_met) in points.iter() {
+        if (filter.map(|f| f.met).unwrap_or(false) | is_met) != hover_state.met
+            || !ui_state.shows(aes.condition.as_deref())
+        {
+            continue;
+        }
+        if let Some(i) = aes.identifiers.iter().position(|r| r == id) {
+            lines.push(format!("{id}\t{}\t{}", column_name(name), point.0[i]));
+        }
+    }
+    for (aes, dist, name, filter) in dists.iter() {
+        if (filter.met != hover_state.met) || !ui_state.shows(aes.condition.as_deref()) {
+            continue;
+        }
+        if let Some(samples) = aes
+            .identifiers
+            .iter()
+            .position(|r| r == id)
+            .and_then(|i| dist.0.get(i))
+        {
+            lines.push(format!(
+                "{id}\t{}\t{} ± {}",
+                column_name(name),
+                crate::stats::mean(samples),
+                crate::stats::std_dev(samples)
+            ));
+        }
+    }
+    if lines.is_empty() {
+        lines.push(id.clone());
+    }
+    lines.sort();
+    egui_context
+        .ctx_mut()
+        .output_mut(|out| out.copied_text = lines.join("\n"));
+    info_state.notify("Copied to clipboard");
+}
+
+/// Show statistics comparing the distributions of the hovered element between
+/// the selected condition and the reference condition.
+#[cfg(feature = "gui")]
+fn show_comparison(
+    mut egui_context: EguiContexts,
+    ui_state: Res<UiState>,
+    hover_state: Res<HoverState>,
+    dist_query: Query<(&Distribution<f32>, &Aesthetics, &GeomHist, &AesFilter), With<Gy>>,
+) {
+    if ui_state.hide
+        || ui_state.reference.is_empty()
+        || (ui_state.reference == ui_state.condition)
+        || (ui_state.condition == "ALL")
+    {
+        return;
+    }
+    let Some(id) = hover_state.id.as_ref() else {
+        return;
+    };
+    let mut samples: HashMap<String, (Option<&Vec<f32>>, Option<&Vec<f32>>)> = HashMap::new();
+    for (dist, aes, geom, filter) in dist_query.iter() {
+        if filter.met != hover_state.met {
+            continue;
+        }
+        let Some(condition) = aes.condition.as_ref() else {
+            continue;
+        };
+        let Some(index) = aes.identifiers.iter().position(|r| r == id) else {
+            continue;
+        };
+        let entry = samples.entry(geom.side.to_string()).or_default();
+        if condition == &ui_state.condition {
+            entry.0 = dist.0.get(index);
+        } else if condition == &ui_state.reference {
+            entry.1 = dist.0.get(index);
+        }
+    }
+    let comparisons = samples
+        .into_iter()
+        .filter_map(|(side, pair)| match pair {
+            (Some(current), Some(reference)) => Some((side, Comparison::new(current, reference))),
+            _ => None,
+        })
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .collect::<Vec<_>>();
+    if comparisons.is_empty() {
+        return;
+    }
+    egui::Window::new("Comparison").show(egui_context.ctx_mut(), |ui| {
+        ui.label(format!(
+            "{id}: {} vs {}",
+            ui_state.condition, ui_state.reference
+        ));
+        egui::Grid::new("comparison_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("side");
+                ui.label("Δ mean");
+                ui.label("Cohen's d");
+                ui.label("P(direction)");
+                ui.end_row();
+                for (side, comp) in comparisons.iter() {
+                    ui.label(side);
+                    ui.label(format!("{:+.3e}", comp.mean_diff));
+                    ui.label(
+                        comp.cohen_d
+                            .map(|d| format!("{d:+.2}"))
+                            .unwrap_or(String::from("-")),
+                    );
+                    ui.label(format!("{:.2}", comp.prob_direction));
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+/// Reaction or metabolite clicked on the map, shown in [`ui_selection`].
+#[derive(Resource, Default)]
+pub struct Selection {
+    pub node_id: Option<u64>,
+    pub id: Option<String>,
+    /// Whether the selected element is a metabolite.
+    pub met: bool,
+}
+
+/// Select the hovered reaction or metabolite on click.
+fn select_on_click(
+    mut egui_context: EguiContexts,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    hover_state: Res<HoverState>,
+    mut selection: ResMut<Selection>,
+) {
+    if !mouse_button_input.just_pressed(MouseButton::Left)
+        || egui_context.ctx_mut().is_pointer_over_area()
+    {
+        return;
+    }
+    if let (Some(node_id), Some(id)) = (hover_state.node_id, hover_state.id.as_ref()) {
+        selection.node_id = Some(node_id);
+        selection.id = Some(id.clone());
+        selection.met = hover_state.met;
+    }
+}
+
+/// Show the selected reaction with the direction used to place its histograms,
+/// which can be flipped when the heuristic gets it wrong, and its values in
+/// each condition, optionally ranked by value. A selected metabolite lists
+/// the reactions that consume or produce it.
+#[cfg(feature = "gui")]
+fn ui_selection(
+    mut egui_context: EguiContexts,
+    ui_state: Res<UiState>,
+    adjacency: Res<Adjacency>,
+    mut selection: ResMut<Selection>,
+    mut arrows: Query<(&Transform, &mut ArrowTag), (With<Path>, Without<Xaxis>, Without<HistTag>)>,
+    mut axes: Query<(&mut Transform, &mut Xaxis), (Without<ArrowTag>, Without<AnyTag>)>,
+    mut hists: Query<
+        (&mut Transform, &HistTag),
+        (Without<Xaxis>, Without<AnyTag>, Without<ArrowTag>),
+    >,
+    points: Query<
+        (&Aesthetics, &Point<f32>, Option<&ColumnName>),
+        (Without<GeomMetabolite>, Without<PopUp>),
+    >,
+    dists: Query<(
+        &Aesthetics,
+        &Distribution<f32>,
+        Option<&ColumnName>,
+        &AesFilter,
+    )>,
+    mut sort_by_value: Local<bool>,
+) {
+    if ui_state.hide {
+        return;
+    }
+    let (Some(node_id), Some(id)) = (selection.node_id, selection.id.clone()) else {
+        return;
+    };
+    if selection.met {
+        let mut open = true;
+        egui::Window::new("Selection")
+            .open(&mut open)
+            .show(egui_context.ctx_mut(), |ui| {
+                ui.label(&id);
+                let (reactions, _) = adjacency.linked(&id, true);
+                ui.label(format!(
+                    "Reactions: {}",
+                    reactions.iter().sorted().join(", ")
+                ));
+            });
+        if !open {
+            *selection = Selection::default();
+        }
+        return;
+    }
+    // value (mean of distributions) of the reaction in each condition, per column
+    let mut values: BTreeMap<&str, Vec<(&str, f32)>> = BTreeMap::new();
+    for (aes, point, name) in points.iter() {
+        let (Some(condition), Some(i)) = (
+            aes.condition.as_deref(),
+            aes.identifiers.iter().position(|r| r == &id),
+        ) else {
+            continue;
+        };
+        values
+            .entry(column_name(name))
+            .or_default()
+            .push((condition, point.0[i]));
+    }
+    for (aes, dist, name, filter) in dists.iter() {
+        let (false, Some(condition), Some(samples)) = (
+            filter.met,
+            aes.condition.as_deref(),
+            aes.identifiers
+                .iter()
+                .position(|r| r == &id)
+                .and_then(|i| dist.0.get(i)),
+        ) else {
+            continue;
+        };
+        values
+            .entry(column_name(name))
+            .or_default()
+            .push((condition, crate::stats::mean(samples)));
+    }
+    let Some((arrow_trans, mut arrow)) = arrows
+        .iter_mut()
+        .find(|(_, arrow)| arrow.node_id == node_id)
+    else {
+        return;
+    };
+    let mut open = true;
+    let mut flip = false;
+    egui::Window::new("Selection")
+        .open(&mut open)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label(id);
+            ui.horizontal(|ui| {
+                let angle = arrow.direction.y.atan2(arrow.direction.x).to_degrees();
+                ui.label(format!("direction: {angle:.0}°"));
+                flip = ui.button("Flip").clicked();
+            });
+            for (mut trans, mut axis) in axes
+                .iter_mut()
+                .filter(|(_, axis)| axis.node_id == node_id)
+                .sorted_by_key(|(_, axis)| axis.side.to_string())
+            {
+                ui.label(format!("{} histogram", axis.side));
+                // only write on edits to avoid triggering change detection every frame
+                let (mut length, mut height) = (trans.scale.x, axis.height);
+                ui.add(egui::Slider::new(&mut length, 0.1..=5.0).text("length"));
+                ui.add(egui::Slider::new(&mut height, 0.1..=5.0).text("height"));
+                if length != trans.scale.x {
+                    trans.scale.x = length;
+                }
+                if height != axis.height {
+                    axis.height = height;
+                }
+            }
+            if values.is_empty() {
+                return;
+            }
+            ui.separator();
+            ui.checkbox(&mut sort_by_value, "Rank conditions by value");
+            for (column, mut conditions) in values {
+                if *sort_by_value {
+                    conditions.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+                } else {
+                    conditions.sort_by_key(|(condition, _)| *condition);
+                }
+                ui.label(egui::RichText::new(column).strong());
+                egui::Grid::new(format!("selection_{column}"))
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (rank, (condition, value)) in conditions.into_iter().enumerate() {
+                            ui.label(format!("{}.", rank + 1));
+                            ui.label(condition);
+                            ui.label(format!("{value:.3e}"));
+                            ui.end_row();
+                        }
+                    });
+            }
+        });
+    if flip {
+        arrow.direction = -arrow.direction;
+        // the sides of the histograms are swapped by mirroring them through the arrow
+        let center = arrow_trans.translation.truncate();
+        for (mut trans, _) in axes.iter_mut().filter(|(_, axis)| axis.node_id == node_id) {
+            flip_around(&mut trans, center);
+        }
+        for (mut trans, _) in hists.iter_mut().filter(|(_, hist)| hist.node_id == node_id) {
+            flip_around(&mut trans, center);
+        }
+    }
+    if !open {
+        *selection = Selection::default();
+    }
+}
+
+/// Mirror a transform through `center` and turn it around.
+#[cfg(feature = "gui")]
+fn flip_around(trans: &mut Transform, center: Vec2) {
+    let mirrored = 2. * center - trans.translation.truncate();
+    trans.translation.x = mirrored.x;
+    trans.translation.y = mirrored.y;
+    trans.rotate_z(std::f32::consts::PI);
+}
+
+/// Halo drawn behind a reaction highlighted by the pasted list of reactions.
+#[derive(Component)]
+pub struct ListHalo;
+
+/// Draw a halo behind the arrows of the pasted reactions when highlighting them.
+fn highlight_listed(
+    mut commands: Commands,
+    ui_state: Res<UiState>,
+    mut last: Local<(String, Option<ListAction>)>,
+    new_map: Query<(), Added<ArrowTag>>,
+    arrows: Query<(&Transform, &Path, &ArrowTag), With<Stroke>>,
+    halos: Query<Entity, With<ListHalo>>,
+) {
+    // the settings mark the state as changed every frame they are shown
+    let current = (ui_state.id_list.clone(), Some(ui_state.list_action));
+    if (*last == current) && new_map.is_empty() {
+        return;
+    }
+    *last = current;
+    for halo in halos.iter() {
+        commands.entity(halo).despawn_recursive();
+    }
+    if ui_state.list_action != ListAction::Highlight {
+        return;
+    }
+    for (trans, path, _) in arrows
+        .iter()
+        .filter(|(_, _, arrow)| ui_state.listed(&arrow.id))
+    {
+        commands.spawn((
+            ShapeBundle {
+                path: Path(path.0.clone()),
+                spatial: SpatialBundle::from_transform(
+                    trans.with_translation(trans.translation - Vec3::Z * 0.05),
+                ),
+                ..default()
+            },
+            Stroke::new(HIGH_COLOR, 30.),
+            ListHalo,
+        ));
+    }
+}
+
+/// Highlight the label of the reaction of a histogram that is being interacted with.
+fn highlight_label(
+    node_to_text: &NodeToText,
+    text_query: &mut Query<&mut Text, With<ArrowTag>>,
+    node_id: u64,
+    on: bool,
+    theme: &Theme,
+) {
+    let Some(mut text) = node_to_text
+        .inner
+        .get(&node_id)
+        .and_then(|e| text_query.get_mut(*e).ok())
+    else {
+        return;
+    };
+    let (font_size, color) = if on {
+        (40., HIGH_COLOR)
+    } else {
+        (35., theme.text())
+    };
+    text.sections[0].style.font_size = font_size;
+    text.sections[0].style.color = color;
+}
+
+/// Seconds that a finger has to be held still on a histogram to grab it.
+const LONG_PRESS_TIME: f32 = 0.5;
+/// Distance in pixels that a held finger can move before it pans the map instead.
+const LONG_PRESS_SLOP: f32 = 10.;
+
+/// Finger on a touch screen that may grab a histogram if it is held still.
+#[derive(Resource, Default)]
+pub struct LongPress {
+    finger: Option<u64>,
+    held: f32,
+    /// Moved or held without grabbing anything, so it pans the map.
+    moved: bool,
+    grabbed: bool,
+}
+
+/// Grab the histogram under a finger held still, as [`mouse_click_system`]
+/// does with the mouse, and drop it when the finger is lifted or a second one
+/// starts a pinch.
+fn grab_on_long_press(
+    time: Res<Time>,
+    touches: Res<Touches>,
+    mut egui_context: EguiContexts,
+    mut long_press: ResMut<LongPress>,
+    bindings: Res<DragBindings>,
+    cursor: MapCursor,
+    mut drag_query: Query<(Entity, &Transform, &mut Drag), (With<Xaxis>, Without<Style>)>,
+) {
+    let mut fingers = touches.iter();
+    let (Some(touch), None) = (fingers.next(), fingers.next()) else {
+        if long_press.grabbed {
+            for (_, _, mut drag) in drag_query.iter_mut() {
+                drag.dragged = false;
+            }
+        }
+        if long_press.finger.is_some() {
+            *long_press = LongPress::default();
+        }
+        return;
+    };
+    if long_press.finger != Some(touch.id()) {
+        *long_press = LongPress {
+            finger: Some(touch.id()),
+            ..default()
+        };
+    }
+    if long_press.grabbed || long_press.moved {
+        return;
+    }
+    if (touch.distance().length() > LONG_PRESS_SLOP)
+        || egui_context.ctx_mut().is_pointer_over_area()
+    {
+        long_press.moved = true;
+        return;
+    }
+    long_press.held += time.delta_seconds();
+    if long_press.held < LONG_PRESS_TIME {
+        return;
+    }
+    let Some((world_pos, radius)) = cursor.world_pick(bindings.pick_radius) else {
+        return;
+    };
+    let grabbed = drag_query
+        .iter()
+        .find(|(_, trans, _)| world_pos.distance(trans.translation.truncate()) < radius)
+        .map(|(e, trans, drag)| (e, trans.translation.truncate(), drag.selected));
+    // only once per press, even if there was nothing to grab
+    long_press.moved = true;
+    if let Some((grabbed, anchor, selected)) = grabbed {
+        long_press.grabbed = true;
+        for (e, trans, mut drag) in drag_query.iter_mut() {
+            if (e == grabbed) | (selected & drag.selected) {
+                drag.dragged = true;
+                drag.offset = trans.translation.truncate() - anchor;
+            }
+        }
     }
 }
 
-/// Register an non-UI entity (histogram) as being dragged by center or right button.
+/// Register an non-UI entity (histogram) as being dragged by the buttons in [`DragBindings`].
+///
+/// Grabbing a selected histogram grabs the whole selection.
 fn mouse_click_system(
+    ui_state: Res<UiState>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    bindings: Res<DragBindings>,
+    node_to_text: Res<NodeToText>,
+    axis_mode: Res<AxisMode>,
+    theme: Res<Theme>,
+    ui_drag_query: Query<&Interaction, With<Drag>>,
+    mut drag_query: Query<(Entity, &Transform, &mut Drag, &Xaxis), Without<Style>>,
+    mut text_query: Query<&mut Text, With<ArrowTag>>,
+    cursor: MapCursor,
+) {
+    // the legend takes precedence over the histograms behind it
+    let over_ui = ui_drag_query
+        .iter()
+        .any(|interaction| !matches!(interaction, Interaction::None));
+    let pick = cursor.world_pick(bindings.pick_radius);
+    let world_pos = pick.map(|(pos, _)| pos);
+    let radius = pick.map(|(_, radius)| radius).unwrap_or_default();
+    let near = |trans: &Transform, world_pos: Vec2| {
+        world_pos.distance(trans.translation.truncate()) < radius
+    };
+    // the map is edited with the same button, see [`grab_map_elements`]
+    if mouse_button_input.just_pressed(bindings.hist_move) && !over_ui && !ui_state.edit_map {
+        // do not move more than one component at the same time, unless selected
+        let grabbed = world_pos.and_then(|world_pos| {
+            drag_query
+                .iter()
+                .find(|(_, trans, _, _)| near(trans, world_pos))
+                .map(|(e, trans, drag, _)| (e, trans.translation.truncate(), drag.selected))
+        });
+        if let Some((grabbed, anchor, selected)) = grabbed {
+            for (e, trans, mut drag, axis) in drag_query.iter_mut() {
+                if (e == grabbed) | (selected & drag.selected) {
+                    drag.dragged = true;
+                    drag.offset = trans.translation.truncate() - anchor;
+                    highlight_label(&node_to_text, &mut text_query, axis.node_id, true, &theme);
+                }
+            }
+        }
+    }
+
+    if mouse_button_input.just_released(bindings.hist_move) {
+        for (_, _, mut drag, axis) in drag_query.iter_mut() {
+            drag.dragged = false;
+            if !drag.selected {
+                highlight_label(&node_to_text, &mut text_query, axis.node_id, false, &theme);
+            }
+        }
+    }
+    if let (true, false, Some(world_pos)) = (
+        mouse_button_input.just_pressed(bindings.hist_rotate),
+        over_ui,
+        world_pos,
+    ) {
+        let grab_selection = drag_query
+            .iter()
+            .any(|(_, trans, drag, _)| drag.selected & near(trans, world_pos));
+        for (_, trans, mut drag, axis) in drag_query.iter_mut() {
+            if near(trans, world_pos) | (grab_selection & drag.selected) {
+                if matches!(*axis_mode, AxisMode::Show) {
+                    drag.scaling = true;
+                } else {
+                    drag.rotating = true;
+                }
+                highlight_label(&node_to_text, &mut text_query, axis.node_id, true, &theme);
+            }
+        }
+    }
+
+    if mouse_button_input.just_released(bindings.hist_rotate) {
+        for (_, _, mut drag, axis) in drag_query.iter_mut() {
+            drag.rotating = false;
+            drag.scaling = false;
+            if !drag.selected {
+                highlight_label(&node_to_text, &mut text_query, axis.node_id, false, &theme);
+            }
+        }
+    }
+}
+
+/// Grab the closest metabolite or label of the map in the edit mode, with the
+/// button that moves the histograms. Metabolites take their label along.
+fn grab_map_elements(
+    mut commands: Commands,
+    mut egui_context: EguiContexts,
+    ui_state: Res<UiState>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    bindings: Res<DragBindings>,
+    cursor: MapCursor,
+    elements: Query<(
+        Entity,
+        &Transform,
+        &Editable,
+        &ViewVisibility,
+        Option<&bevy::text::TextLayoutInfo>,
+    )>,
+) {
+    if !ui_state.edit_map
+        || !mouse_button_input.just_pressed(bindings.hist_move)
+        || egui_context.ctx_mut().is_pointer_over_area()
+    {
+        return;
+    }
+    let Some(world_pos) = cursor.world_pos() else {
+        return;
+    };
+    let grabbed = elements
+        .iter()
+        .filter(|(_, _, _, visibility, _)| visibility.get())
+        .filter_map(|(entity, trans, element, _, layout)| {
+            let pos = trans.translation.truncate();
+            let distance = match layout {
+                // labels are anchored on their left
+                Some(layout) => {
+                    let half = layout.logical_size / 2.;
+                    let distance = (world_pos - pos - Vec2::new(half.x, 0.)).abs();
+                    distance.cmple(half).all().then_some(distance.length())
+                }
+                None => Some(world_pos.distance(pos)).filter(|d| *d < 25.),
+            }?;
+            Some((entity, *element, distance))
+        })
+        .min_by(|a, b| a.2.total_cmp(&b.2));
+    let Some((grabbed, element, _)) = grabbed else {
+        return;
+    };
+    for (entity, trans, other, _, _) in elements.iter() {
+        let moves = match element {
+            Editable::Metabolite(node_id) => *other == Editable::MetaboliteLabel(node_id),
+            _ => false,
+        };
+        if moves || (entity == grabbed) {
+            commands.entity(entity).insert(Drag {
+                dragged: true,
+                offset: trans.translation.truncate() - world_pos,
+                ..default()
+            });
+        }
+    }
+}
+
+/// Write the positions of the map elements dropped in the edit mode to the
+/// [`EscherMap`]. The map is spawned again when a metabolite was moved so that
+/// its reactions follow it.
+fn drop_map_elements(
+    mut commands: Commands,
     mouse_button_input: Res<ButtonInput<MouseButton>>,
+    bindings: Res<DragBindings>,
+    map_dims: Res<MapDimensions>,
+    background: Res<MapBackground>,
+    mut assets: ResMut<Assets<EscherMap>>,
+    mut state: ResMut<MapState>,
+    hist_query: Query<(&Transform, &Xaxis), Without<AnyTag>>,
+    annotation_query: Query<(&Transform, &Annotation)>,
+    mut dropped: Query<
+        (Entity, &mut Transform, &Drag, &Editable),
+        (Without<Xaxis>, Without<Annotation>),
+    >,
+) {
+    if !mouse_button_input.just_released(bindings.hist_move) || dropped.is_empty() {
+        return;
+    }
+    let Some(escher_map) = assets.get_mut(state.escher_map()) else {
+        return;
+    };
+    // the histograms are stored before the center of the map shifts
+    store_layout(escher_map, &background, &hist_query, &annotation_query);
+    let mut respawn = false;
+    for (entity, mut trans, drag, element) in dropped.iter_mut() {
+        if let Some(target) = drag.target {
+            trans.translation = target.extend(trans.translation.z);
+        }
+        // escher and bevy defines "y" in the opposite direction
+        let pos = Vec2::new(
+            trans.translation.x + map_dims.x,
+            map_dims.y - trans.translation.y,
+        );
+        escher_map.move_element(*element, pos);
+        respawn |= matches!(element, Editable::Metabolite(_));
+        commands.entity(entity).remove::<Drag>();
+    }
+    if respawn {
+        state.loaded = false;
+    }
+}
+
+/// Rectangle being drawn with Shift and the left button to select histograms.
+#[derive(Component)]
+struct RubberBand {
+    start: Vec2,
+    end: Vec2,
+}
+
+/// Select the histograms whose center is inside the rectangle drawn with Shift
+/// and the left button. A click without dragging clears the selection.
+fn select_in_rect(
+    mut commands: Commands,
+    mut egui_context: EguiContexts,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    key_input: Res<ButtonInput<KeyCode>>,
+    node_to_text: Res<NodeToText>,
+    theme: Res<Theme>,
+    cursor: MapCursor,
+    mut band_query: Query<(Entity, &mut RubberBand, &mut Path, &mut Transform), Without<Xaxis>>,
+    mut drag_query: Query<(&Transform, &mut Drag, &Xaxis), (Without<Style>, Without<RubberBand>)>,
+    mut text_query: Query<&mut Text, With<ArrowTag>>,
+) {
+    let world_pos = cursor.world_pos();
+    let shift = key_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if shift
+        && mouse_button_input.just_pressed(MouseButton::Left)
+        && !egui_context.ctx_mut().is_pointer_over_area()
+    {
+        if let Some(world_pos) = world_pos {
+            commands.spawn((
+                ShapeBundle {
+                    spatial: SpatialBundle::from_transform(Transform::from_xyz(
+                        world_pos.x,
+                        world_pos.y,
+                        50.,
+                    )),
+                    ..default()
+                },
+                Stroke::new(HIGH_COLOR, 2.),
+                RubberBand {
+                    start: world_pos,
+                    end: world_pos,
+                },
+                IgnoreSave,
+            ));
+        }
+        return;
+    }
+    let Ok((entity, mut band, mut path, mut trans)) = band_query.get_single_mut() else {
+        return;
+    };
+    if mouse_button_input.pressed(MouseButton::Left) {
+        if let Some(world_pos) = world_pos {
+            band.end = world_pos;
+        }
+        let min = band.start.min(band.end);
+        trans.translation.x = min.x;
+        trans.translation.y = min.y;
+        *path = GeometryBuilder::build_as(&shapes::Rectangle {
+            extents: (band.end - band.start).abs(),
+            origin: shapes::RectangleOrigin::BottomLeft,
+        });
+        return;
+    }
+    commands.entity(entity).despawn();
+    let (min, max) = (band.start.min(band.end), band.start.max(band.end));
+    for (trans, mut drag, axis) in drag_query.iter_mut() {
+        let pos = trans.translation.truncate();
+        let selected = pos.cmpge(min).all() & pos.cmple(max).all();
+        if selected != drag.selected {
+            drag.selected = selected;
+            highlight_label(
+                &node_to_text,
+                &mut text_query,
+                axis.node_id,
+                selected,
+                &theme,
+            );
+        }
+    }
+}
+
+/// Clear the selection of histograms with Escape or remove them with Delete.
+fn edit_selection(
+    mut commands: Commands,
+    mut egui_context: EguiContexts,
+    key_input: Res<ButtonInput<KeyCode>>,
     node_to_text: Res<NodeToText>,
-    axis_mode: Res<AxisMode>,
-    mut drag_query: Query<(&Transform, &mut Drag, &Xaxis), Without<Style>>,
+    theme: Res<Theme>,
+    mut drag_query: Query<(Entity, &mut Drag, &Xaxis), Without<Style>>,
+    hists: Query<(Entity, &HistTag)>,
     mut text_query: Query<&mut Text, With<ArrowTag>>,
-    windows: Query<(Entity, &Window), With<PrimaryWindow>>,
-    q_camera: Query<(&Camera, &GlobalTransform)>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Middle) {
-        for (trans, mut drag, axis) in drag_query.iter_mut() {
-            let (camera, camera_transform) = q_camera.single();
-            let Ok((_, win)) = windows.get_single() else {
-                return;
-            };
-            if let Some(world_pos) = get_pos(win, camera, camera_transform) {
-                if (world_pos - Vec2::new(trans.translation.x, trans.translation.y))
-                    .length_squared()
-                    < 5000.
-                {
-                    drag.dragged = true;
-                    node_to_text.inner.get(&axis.node_id).map(|e| {
-                        text_query.get_mut(*e).map(|mut text| {
-                            text.sections[0].style.font_size = 40.;
-                            text.sections[0].style.color = HIGH_COLOR;
-                        })
-                    });
-                    // do not move more than one component at the same time
-                    break;
-                }
-            }
+    if egui_context.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let delete = key_input.just_pressed(KeyCode::Delete);
+    if !delete && !key_input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    for (axis_entity, mut drag, axis) in drag_query.iter_mut().filter(|(_, d, _)| d.selected) {
+        drag.selected = false;
+        highlight_label(&node_to_text, &mut text_query, axis.node_id, false, &theme);
+        if !delete {
+            continue;
         }
+        for (e, _) in hists
+            .iter()
+            .filter(|(_, hist)| (hist.node_id == axis.node_id) & (hist.side == axis.side))
+        {
+            commands.entity(e).despawn_recursive();
+        }
+        commands.entity(axis_entity).despawn_recursive();
     }
+}
 
-    if mouse_button_input.just_released(MouseButton::Middle) {
-        for (_, mut drag, axis) in drag_query.iter_mut() {
-            drag.dragged = false;
-            node_to_text.inner.get(&axis.node_id).map(|e| {
-                text_query.get_mut(*e).map(|mut text| {
-                    text.sections[0].style.font_size = 35.;
-                    text.sections[0].style.color = ARROW_COLOR;
+/// Operations of the "Arrange" window on the selected histograms.
+#[derive(Clone, Copy, Debug)]
+pub enum Arrange {
+    /// Same vertical position.
+    AlignRow,
+    /// Same horizontal position.
+    AlignColumn,
+    /// Same angle as the leftmost one.
+    AlignAngle,
+    /// Equal horizontal spacing between the outermost ones.
+    DistributeX,
+    /// Equal vertical spacing between the outermost ones.
+    DistributeY,
+}
+
+/// Apply `action` to the `transforms` of the selected histograms.
+pub fn arrange(action: Arrange, transforms: &mut [Transform]) {
+    if transforms.len() < 2 {
+        return;
+    }
+    let n = transforms.len() as f32;
+    match action {
+        Arrange::AlignRow => {
+            let y = transforms.iter().map(|t| t.translation.y).sum::<f32>() / n;
+            transforms.iter_mut().for_each(|t| t.translation.y = y);
+        }
+        Arrange::AlignColumn => {
+            let x = transforms.iter().map(|t| t.translation.x).sum::<f32>() / n;
+            transforms.iter_mut().for_each(|t| t.translation.x = x);
+        }
+        Arrange::AlignAngle => {
+            let rotation = transforms
+                .iter()
+                .min_by(|a, b| {
+                    a.translation
+                        .x
+                        .partial_cmp(&b.translation.x)
+                        .unwrap_or(Ordering::Equal)
                 })
+                .map(|t| t.rotation)
+                .unwrap_or_default();
+            transforms.iter_mut().for_each(|t| t.rotation = rotation);
+        }
+        Arrange::DistributeX | Arrange::DistributeY => {
+            let axis = if matches!(action, Arrange::DistributeX) {
+                0
+            } else {
+                1
+            };
+            let mut order: Vec<usize> = (0..transforms.len()).collect();
+            order.sort_by(|a, b| {
+                transforms[*a].translation[axis]
+                    .partial_cmp(&transforms[*b].translation[axis])
+                    .unwrap_or(Ordering::Equal)
             });
+            let first = transforms[order[0]].translation[axis];
+            let step = (transforms[order[order.len() - 1]].translation[axis] - first) / (n - 1.);
+            for (i, j) in order.into_iter().enumerate() {
+                transforms[j].translation[axis] = first + step * i as f32;
+            }
         }
     }
-    if mouse_button_input.just_pressed(MouseButton::Right) {
-        for (trans, mut drag, axis) in drag_query.iter_mut() {
-            let (camera, camera_transform) = q_camera.single();
-            let Ok((_, win)) = windows.get_single() else {
-                return;
-            };
-            if let Some(world_pos) = get_pos(win, camera, camera_transform) {
-                if (world_pos - Vec2::new(trans.translation.x, trans.translation.y))
-                    .length_squared()
-                    < 5000.
-                {
-                    if matches!(*axis_mode, AxisMode::Show) {
-                        drag.scaling = true;
-                    } else {
-                        drag.rotating = true;
-                    }
-                    node_to_text.inner.get(&axis.node_id).map(|e| {
-                        text_query.get_mut(*e).map(|mut text| {
-                            text.sections[0].style.font_size = 40.;
-                            text.sections[0].style.color = HIGH_COLOR;
-                        })
-                    });
+}
+
+/// Window to align and distribute the histograms of the multi-selection.
+#[cfg(feature = "gui")]
+fn ui_arrange(
+    mut egui_context: EguiContexts,
+    ui_state: Res<UiState>,
+    mut drag_query: Query<(&mut Transform, &Drag), (With<Xaxis>, Without<Style>)>,
+) {
+    let selected = drag_query.iter().filter(|(_, drag)| drag.selected).count();
+    if ui_state.hide || (selected < 2) {
+        return;
+    }
+    let mut action = None;
+    egui::Window::new("Arrange").show(egui_context.ctx_mut(), |ui| {
+        ui.label(format!("{selected} histograms selected"));
+        ui.horizontal(|ui| {
+            ui.label("align");
+            for (label, a) in [
+                ("row", Arrange::AlignRow),
+                ("column", Arrange::AlignColumn),
+                ("angle", Arrange::AlignAngle),
+            ] {
+                if ui.button(label).clicked() {
+                    action = Some(a);
                 }
             }
-        }
+        });
+        ui.horizontal(|ui| {
+            ui.label("distribute");
+            for (label, a) in [
+                ("horizontally", Arrange::DistributeX),
+                ("vertically", Arrange::DistributeY),
+            ] {
+                if ui.button(label).clicked() {
+                    action = Some(a);
+                }
+            }
+        });
+    });
+    let Some(action) = action else {
+        return;
+    };
+    let mut selection: Vec<Mut<Transform>> = drag_query
+        .iter_mut()
+        .filter(|(_, drag)| drag.selected)
+        .map(|(trans, _)| trans)
+        .collect();
+    let mut transforms: Vec<Transform> = selection.iter().map(|trans| **trans).collect();
+    arrange(action, &mut transforms);
+    for (trans, arranged) in selection.iter_mut().zip(transforms) {
+        **trans = arranged;
     }
+}
 
-    if mouse_button_input.just_released(MouseButton::Right) {
-        for (_, mut drag, axis) in drag_query.iter_mut() {
-            drag.rotating = false;
-            drag.scaling = false;
-            node_to_text.inner.get(&axis.node_id).map(|e| {
-                text_query.get_mut(*e).map(|mut text| {
-                    text.sections[0].style.font_size = 35.;
-                    text.sections[0].style.color = ARROW_COLOR;
-                })
+/// Window to type the exact position, rotation and scale of the histogram axis
+/// when it is the only one selected.
+#[cfg(feature = "gui")]
+fn ui_inspect_axis(
+    mut egui_context: EguiContexts,
+    ui_state: Res<UiState>,
+    mut drag_query: Query<(&mut Transform, &mut Drag, &Xaxis), Without<Style>>,
+) {
+    if ui_state.hide {
+        return;
+    }
+    let mut selected = drag_query.iter_mut().filter(|(_, drag, _)| drag.selected);
+    let (Some((mut trans, mut drag, axis)), None) = (selected.next(), selected.next()) else {
+        return;
+    };
+    let mut pos = trans.translation.truncate();
+    let mut angle = trans.rotation.to_euler(EulerRot::XYZ).2.to_degrees();
+    let mut scale = trans.scale.x;
+    let mut changed = false;
+    egui::Window::new("Histogram axis").show(egui_context.ctx_mut(), |ui| {
+        ui.label(format!("{} ({})", axis.id, axis.side));
+        egui::Grid::new("axis_transform").show(ui, |ui| {
+            ui.label("position");
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .add(egui::DragValue::new(&mut pos.x).prefix("x: "))
+                    .changed();
+                changed |= ui
+                    .add(egui::DragValue::new(&mut pos.y).prefix("y: "))
+                    .changed();
             });
-        }
+            ui.end_row();
+            ui.label("rotation");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut angle)
+                        .clamp_range(-180.0..=180.0)
+                        .suffix("°"),
+                )
+                .changed();
+            ui.end_row();
+            ui.label("scale");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut scale)
+                        .speed(0.01)
+                        .clamp_range(0.01..=100.0),
+                )
+                .changed();
+            ui.end_row();
+        });
+    });
+    // only written when edited, so that the histograms do not follow every frame
+    if changed {
+        // the typed values win over a smoothed motion in progress
+        drag.target = None;
+        drag.target_rotation = None;
+        trans.translation = pos.extend(trans.translation.z);
+        trans.rotation = Quat::from_rotation_z(angle.to_radians());
+        trans.scale.x = scale;
     }
 }
 
-/// Register a UI Drag enity as being dragged by center or right button.
+/// Register a UI Drag enity (legend) as being dragged by the buttons in [`DragBindings`].
+///
+/// Dragging only starts when the button is pressed over the entity, so that
+/// sweeping over the legend while moving a histogram does not grab it.
 fn mouse_click_ui_system(
     mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut drag_query: Query<(&mut Drag, &Interaction, &mut BackgroundColor)>,
+    bindings: Res<DragBindings>,
+    map_drag_query: Query<&Drag, Without<Style>>,
+    mut drag_query: Query<(&mut Drag, &Interaction, &mut BackgroundColor), With<Style>>,
 ) {
+    let map_busy = map_drag_query
+        .iter()
+        .any(|drag| drag.dragged | drag.rotating | drag.scaling);
     for (mut drag, interaction, mut background_color) in drag_query.iter_mut() {
-        match interaction {
-            Interaction::Hovered | Interaction::Pressed => {
-                drag.dragged = mouse_button_input.pressed(MouseButton::Middle);
-                drag.rotating = mouse_button_input.pressed(MouseButton::Right);
-                *background_color = BackgroundColor(Color::rgba(0.9, 0.9, 0.9, 0.2));
-            }
-            _ => {
-                drag.dragged &= mouse_button_input.pressed(MouseButton::Middle);
-                *background_color = BackgroundColor(Color::rgba(1.0, 1.0, 1.0, 0.0));
-            }
+        let hovered = matches!(interaction, Interaction::Hovered | Interaction::Pressed);
+        if hovered & !map_busy {
+            drag.dragged |= mouse_button_input.just_pressed(bindings.legend_move);
+            drag.rotating |= mouse_button_input.just_pressed(bindings.legend_rotate);
         }
+        drag.dragged &= mouse_button_input.pressed(bindings.legend_move);
+        drag.rotating &= mouse_button_input.pressed(bindings.legend_rotate);
+        *background_color = if hovered | drag.dragged {
+            BackgroundColor(Color::rgba(0.9, 0.9, 0.9, 0.2))
+        } else {
+            BackgroundColor(Color::rgba(1.0, 1.0, 1.0, 0.0))
+        };
     }
 }
 
+/// Distance in map units under which histograms snap to each other.
+const ALIGN_TOL: f32 = 20.;
+
+/// Snap each coordinate of `pos` to the closest one of `others` within [`ALIGN_TOL`].
+fn snap_to_axes(pos: Vec2, others: &[Vec2]) -> Vec2 {
+    let closest = |coord: fn(Vec2) -> f32| {
+        others
+            .iter()
+            .map(|other| coord(*other))
+            .filter(|c| f32::abs(c - coord(pos)) < ALIGN_TOL)
+            .min_by(|a, b| {
+                f32::abs(a - coord(pos))
+                    .partial_cmp(&f32::abs(b - coord(pos)))
+                    .unwrap_or(Ordering::Equal)
+            })
+            .unwrap_or(coord(pos))
+    };
+    Vec2::new(closest(|v| v.x), closest(|v| v.y))
+}
+
 /// Move the center-dragged interactable non-UI entities (histograms).
 fn follow_mouse_on_drag(
-    windows: Query<(Entity, &Window), With<PrimaryWindow>>,
-    mut drag_query: Query<(&mut Transform, &Drag), Without<Style>>,
-    q_camera: Query<(&Camera, &GlobalTransform)>,
+    ui_state: Res<UiState>,
+    bindings: Res<DragBindings>,
+    time: Res<Time>,
+    map_dims: Res<MapDimensions>,
+    cursor: MapCursor,
+    mut drag_query: Query<(&mut Transform, &mut Drag, Option<&Xaxis>), Without<Style>>,
+    arrows: Query<(&Transform, &ArrowTag), Without<Drag>>,
 ) {
-    for (mut trans, drag) in drag_query.iter_mut() {
+    let others: Vec<Vec2> = if ui_state.snap_axes {
+        drag_query
+            .iter()
+            .filter(|(_, drag, _)| !drag.dragged)
+            .map(|(trans, _, _)| trans.translation.truncate())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let factor = smoothing_factor(bindings.smoothing, time.delta_seconds());
+    for (mut trans, mut drag, axis) in drag_query.iter_mut() {
         if drag.dragged {
-            let (camera, camera_transform) = q_camera.single();
-            let Ok((_, win)) = windows.get_single() else {
-                return;
-            };
-            if let Some(world_pos) = get_pos(win, camera, camera_transform) {
-                trans.translation = Vec3::new(world_pos.x, world_pos.y, trans.translation.z);
+            if let Some(mut world_pos) = cursor.world_pos() {
+                if ui_state.snap {
+                    world_pos = snap_to_grid(world_pos, ui_state.grid_spacing, &map_dims);
+                }
+                if ui_state.snap_axes {
+                    world_pos = snap_to_axes(world_pos, &others);
+                }
+                // the rest of the selection keeps its place relative to the grabbed one
+                world_pos += drag.offset;
+                if let Some(arrow_pos) = axis
+                    .filter(|_| ui_state.leash)
+                    .and_then(|axis| {
+                        arrows
+                            .iter()
+                            .find(|(_, arrow)| arrow.node_id == axis.node_id)
+                    })
+                    .map(|(arrow_trans, _)| arrow_trans.translation.truncate())
+                {
+                    world_pos =
+                        arrow_pos + (world_pos - arrow_pos).clamp_length_max(ui_state.leash_length);
+                }
+                drag.target = Some(world_pos);
             }
         }
+        let Some(target) = drag.target else {
+            continue;
+        };
+        let pos = trans.translation.truncate().lerp(target, factor);
+        trans.translation = pos.extend(trans.translation.z);
+        if !drag.dragged && pos.distance_squared(target) < 0.01 {
+            drag.target = None;
+        }
+    }
+}
+
+/// Fraction of the remaining distance to the target that is covered in a frame
+/// of `dt` seconds, so that smoothed motions take the same time at any frame rate.
+pub fn smoothing_factor(smoothing: f32, dt: f32) -> f32 {
+    if smoothing <= 0. {
+        1.
+    } else {
+        1. - (-dt / smoothing).exp()
     }
 }
 
@@ -558,34 +3128,80 @@ fn follow_mouse_on_drag_ui(
 }
 
 /// Rotate the right-dragged interactable (histograms and legend) entities.
+///
+/// The mouse motion is accumulated into an angle from the rotation where the
+/// drag started, so that snapping does not swallow small motions at high frame rates.
 fn follow_mouse_on_rotate(
-    mut drag_query: Query<(&mut Transform, &Drag)>,
+    bindings: Res<DragBindings>,
+    ui_state: Res<UiState>,
+    time: Res<Time>,
+    mut drag_query: Query<(&mut Transform, &mut Drag, Has<Style>)>,
     mut mouse_motion_events: EventReader<bevy::input::mouse::MouseMotion>,
 ) {
-    for ev in mouse_motion_events.read() {
-        for (mut trans, drag) in drag_query.iter_mut() {
-            let pos = trans.translation;
-            if drag.rotating {
-                trans.rotate_around(pos, Quat::from_axis_angle(Vec3::Z, -ev.delta.y * 0.05));
-                // clamping of angle to rect angles
-                let (_, angle) = trans.rotation.to_axis_angle();
-                const TOL: f32 = 0.06;
-                if f32::abs(angle) < TOL {
-                    trans.rotation = Quat::from_axis_angle(Vec3::Z, 0.);
-                } else if f32::abs(angle - std::f32::consts::PI) < TOL {
-                    trans.rotation = Quat::from_axis_angle(Vec3::Z, std::f32::consts::PI);
-                } else if f32::abs(angle - std::f32::consts::PI / 2.) < TOL {
-                    trans.rotation = Quat::from_axis_angle(Vec3::Z, std::f32::consts::PI / 2.);
-                } else if f32::abs(angle - 3. * std::f32::consts::PI / 2.) < TOL {
-                    trans.rotation = Quat::from_axis_angle(Vec3::Z, 3. * std::f32::consts::PI / 2.);
-                }
-            }
+    let others: Vec<Quat> = if ui_state.snap_axes {
+        drag_query
+            .iter()
+            .filter(|(_, drag, is_ui)| !drag.rotating & !is_ui)
+            .map(|(trans, _, _)| trans.rotation)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let delta: f32 = mouse_motion_events.read().map(|ev| ev.delta.y).sum();
+    let factor = smoothing_factor(bindings.smoothing, time.delta_seconds());
+    for (mut trans, mut drag, is_ui) in drag_query.iter_mut() {
+        if drag.rotating {
+            let sensitivity = if is_ui {
+                bindings.legend_sensitivity
+            } else {
+                bindings.hist_sensitivity
+            };
+            let (start, mut angle) = drag.rotation_start.unwrap_or((trans.rotation, 0.));
+            angle -= delta * 0.05 * sensitivity;
+            drag.rotation_start = Some((start, angle));
+            let others: &[Quat] = if is_ui { &[] } else { &others };
+            drag.target_rotation = Some(snap_rotation(
+                Quat::from_axis_angle(Vec3::Z, angle) * start,
+                others,
+            ));
+        } else if drag.rotation_start.is_some() {
+            drag.rotation_start = None;
+        }
+        let Some(target) = drag.target_rotation else {
+            continue;
+        };
+        trans.rotation = trans.rotation.slerp(target, factor);
+        if !drag.rotating && trans.rotation.angle_between(target) < 1e-3 {
+            trans.rotation = target;
+            drag.target_rotation = None;
         }
     }
 }
 
+/// Clamp a rotation to right angles and then to the rotations of `others`
+/// when it is close enough.
+fn snap_rotation(rotation: Quat, others: &[Quat]) -> Quat {
+    const TOL: f32 = 0.06;
+    let (_, angle) = rotation.to_axis_angle();
+    let rotation = [
+        0.,
+        std::f32::consts::PI / 2.,
+        std::f32::consts::PI,
+        3. * std::f32::consts::PI / 2.,
+    ]
+    .into_iter()
+    .find(|right| f32::abs(angle - right) < TOL)
+    .map_or(rotation, |right| Quat::from_axis_angle(Vec3::Z, right));
+    others
+        .iter()
+        .find(|other| rotation.angle_between(**other) < TOL)
+        .copied()
+        .unwrap_or(rotation)
+}
+
 /// Scale the right-dragged interactable (histograms and legend) entities on AxisMode::Show.
 fn follow_mouse_on_scale(
+    bindings: Res<DragBindings>,
     mut drag_query: Query<(&mut Transform, &Drag)>,
     mut mouse_motion_events: EventReader<bevy::input::mouse::MouseMotion>,
 ) {
@@ -593,28 +3209,161 @@ fn follow_mouse_on_scale(
         for (mut trans, drag) in drag_query.iter_mut() {
             if drag.scaling {
                 const FACTOR: f32 = 0.01;
-                let scale = ev.delta.x * FACTOR;
+                let scale = ev.delta.x * FACTOR * bindings.hist_sensitivity;
                 trans.scale.x += scale;
             }
         }
     }
 }
 
-/// Change size of UI on +/-.
+/// Preset sizes of the GUI offered in the settings.
+const GUI_SCALE_PRESETS: [f32; 6] = [0.75, 1.0, 1.25, 1.5, 1.75, 2.0];
+const MIN_GUI_SCALE: f32 = GUI_SCALE_PRESETS[0];
+const MAX_GUI_SCALE: f32 = GUI_SCALE_PRESETS[GUI_SCALE_PRESETS.len() - 1];
+
+/// Scale of the settings windows (egui) and of the legend (bevy UI).
+///
+/// It is bounded by the [`GUI_SCALE_PRESETS`] and, on native, persisted across sessions.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct GuiScale {
+    pub egui: f32,
+    pub legend: f32,
+}
+
+impl Default for GuiScale {
+    fn default() -> Self {
+        Self {
+            egui: 1.0,
+            legend: 1.0,
+        }
+    }
+}
+
+impl GuiScale {
+    fn clamp(value: f32) -> f32 {
+        value.clamp(MIN_GUI_SCALE, MAX_GUI_SCALE)
+    }
+
+    /// Location of the persisted scale in the configuration directory of the user.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn path() -> Option<std::path::PathBuf> {
+        config_path("gui_scale.json")
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice::<Self>(&bytes).ok())
+            .map(|scale| Self {
+                egui: Self::clamp(scale.egui),
+                legend: Self::clamp(scale.legend),
+            })
+            .unwrap_or_default()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn load() -> Self {
+        Self::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn store(&self) -> std::io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        safe_json_write(path, self)
+    }
+}
+
+/// Location of a file of shu in the configuration directory of the user.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn config_path(file: &str) -> Option<std::path::PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .or_else(|| std::env::var_os("APPDATA"))
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config"))
+        })?;
+    Some(config.join("shu").join(file))
+}
+
+/// Change size of the settings on Ctrl and +/- or Ctrl+scroll and of the legend on +/-.
 fn scale_ui(
     key_input: Res<ButtonInput<KeyCode>>,
+    mut wheel_events: EventReader<bevy::input::mouse::MouseWheel>,
+    mut gui_scale: ResMut<GuiScale>,
+    mut pancam_query: Query<&mut PanCam>,
+) {
+    let ctrl = key_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    // the map should not zoom while the settings are being scaled
+    // nor pan while histograms are being selected
+    let lock = ctrl || key_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    for mut pancam in pancam_query.iter_mut() {
+        if pancam.enabled == lock {
+            pancam.enabled = !lock;
+        }
+    }
+    let mut delta = 0.;
+    if key_input.just_pressed(KeyCode::NumpadAdd) {
+        delta += 0.1;
+    } else if key_input.just_pressed(KeyCode::Minus) {
+        delta -= 0.1;
+    }
+    for ev in wheel_events.read() {
+        if ctrl {
+            delta += match ev.unit {
+                bevy::input::mouse::MouseScrollUnit::Line => ev.y * 0.05,
+                bevy::input::mouse::MouseScrollUnit::Pixel => ev.y * 0.002,
+            };
+        }
+    }
+    if delta == 0. {
+        return;
+    }
+    let scale = if ctrl {
+        &mut gui_scale.egui
+    } else {
+        &mut gui_scale.legend
+    };
+    *scale = GuiScale::clamp(*scale + delta);
+}
+
+/// Time (in seconds) that the [`GuiScale`] has to stay unchanged before it is
+/// persisted, so that scrolling or dragging the slider writes it only once.
+const GUI_SCALE_SAVE_DELAY: f32 = 0.5;
+
+/// Propagate the [`GuiScale`] to egui and the legend and persist it once it
+/// stops changing.
+fn apply_gui_scale(
+    time: Res<Time>,
+    gui_scale: Res<GuiScale>,
     mut ui_scale: ResMut<UiScale>,
     mut egui_settings: ResMut<EguiSettings>,
+    mut unsaved_for: Local<Option<f32>>,
 ) {
-    let scale = if key_input.pressed(KeyCode::ControlLeft) {
-        &mut egui_settings.scale_factor
-    } else {
-        &mut ui_scale.0
+    if gui_scale.is_changed() {
+        egui_settings.scale_factor = gui_scale.egui;
+        ui_scale.0 = gui_scale.legend;
+        if !gui_scale.is_added() {
+            *unsaved_for = Some(0.);
+        }
+        return;
+    }
+    let Some(idle) = unsaved_for.as_mut() else {
+        return;
     };
-    if key_input.just_pressed(KeyCode::NumpadAdd) {
-        *scale += 0.1;
-    } else if key_input.just_pressed(KeyCode::Minus) {
-        *scale -= 0.1;
+    *idle += time.delta_seconds();
+    if *idle < GUI_SCALE_SAVE_DELAY {
+        return;
+    }
+    *unsaved_for = None;
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Err(e) = gui_scale.store() {
+        warn!("Could not save the GUI scale: {e}");
     }
 }
 
@@ -656,22 +3405,20 @@ fn save_file(
     mut assets: ResMut<Assets<EscherMap>>,
     mut info_state: ResMut<Info>,
     state: ResMut<MapState>,
+    background: Res<MapBackground>,
     mut save_events: EventReader<SaveEvent>,
     hist_query: Query<(&Transform, &Xaxis), Without<AnyTag>>,
+    annotation_query: Query<(&Transform, &Annotation)>,
+    provenance: Option<Res<Provenance>>,
 ) {
     for save_event in save_events.read() {
-        let custom_asset = assets.get_mut(&state.escher_map);
+        let custom_asset = assets.get_mut(state.escher_map());
         if custom_asset.is_none() {
             return;
         }
         let escher_map = custom_asset.unwrap();
-        for (trans, axis) in hist_query.iter() {
-            if let Some(reac) = escher_map.metabolism.reactions.get_mut(&axis.node_id) {
-                reac.hist_position
-                    .get_or_insert(HashMap::new())
-                    .insert(axis.side.clone(), (*trans).into());
-            }
-        }
+        store_layout(escher_map, &background, &hist_query, &annotation_query);
+        escher_map.provenance = provenance.as_deref().cloned();
         safe_json_write(&save_event.0, escher_map).unwrap_or_else(|e| {
             warn!("Could not write the file: {}.", e);
             info_state.notify("File could not be written!\nCheck that path exists.");
@@ -679,7 +3426,233 @@ fn save_file(
     }
 }
 
-fn safe_json_write<P, C>(path: P, contents: C) -> std::io::Result<()>
+/// Write the values of the elements mapped in the current condition, one row
+/// per identifier and column. Distributions are summarised by their (weighted)
+/// mean, standard deviation and number of samples.
+fn export_data(
+    mut info_state: ResMut<Info>,
+    mut data_events: EventReader<DataExportEvent>,
+    ui_state: Res<UiState>,
+    selection: Res<Selection>,
+    axes: Query<(&Xaxis, &Drag)>,
+    points: Query<(
+        &Aesthetics,
+        &Point<f32>,
+        Option<&Uncertainty>,
+        Option<&ColumnName>,
+    )>,
+    dists: Query<(
+        &Aesthetics,
+        &Distribution<f32>,
+        Option<&Weights>,
+        Option<&ColumnName>,
+    )>,
+    parts: Query<(&Aesthetics, &Parts, Option<&ColumnName>)>,
+) {
+    for event in data_events.read() {
+        let selected: HashSet<&str> = selection
+            .id
+            .iter()
+            .map(String::as_str)
+            .chain(
+                axes.iter()
+                    .filter(|(_, drag)| drag.selected)
+                    .map(|(axis, _)| axis.id.as_str()),
+            )
+            .collect();
+        if event.selected_only && selected.is_empty() {
+            info_state.notify("Nothing selected to export!");
+            continue;
+        }
+        let keep = |aes: &Aesthetics, id: &str| {
+            ui_state.shows(aes.condition.as_deref())
+                & (!event.selected_only | selected.contains(id))
+        };
+        let column = |name: Option<&ColumnName>| name.map(|c| c.0.clone()).unwrap_or_default();
+        let number = |value: f32| ui_state.number_format.plain(value);
+        let mut rows: Vec<[String; 6]> = Vec::new();
+        for (aes, point, std, name) in points.iter() {
+            for (i, (id, value)) in aes.identifiers.iter().zip(point.0.iter()).enumerate() {
+                if keep(aes, id) {
+                    rows.push([
+                        id.clone(),
+                        aes.condition.clone().unwrap_or_default(),
+                        column(name),
+                        number(*value),
+                        std.and_then(|s| s.0.get(i))
+                            .map(|sd| number(*sd))
+                            .unwrap_or_default(),
+                        String::new(),
+                    ]);
+                }
+            }
+        }
+        for (aes, dist, weights, name) in dists.iter() {
+            for (i, (id, samples)) in aes.identifiers.iter().zip(dist.0.iter()).enumerate() {
+                if !keep(aes, id) {
+                    continue;
+                }
+                let (mean, sd) = match weights.and_then(|w| w.0.get(i)) {
+                    Some(w) => crate::stats::weighted_mean_std(samples, w),
+                    None => (crate::stats::mean(samples), crate::stats::std_dev(samples)),
+                };
+                rows.push([
+                    id.clone(),
+                    aes.condition.clone().unwrap_or_default(),
+                    column(name),
+                    number(mean),
+                    number(sd),
+                    samples.len().to_string(),
+                ]);
+            }
+        }
+        for (aes, parts, name) in parts.iter() {
+            for (id, id_parts) in aes.identifiers.iter().zip(parts.0.iter()) {
+                if !keep(aes, id) {
+                    continue;
+                }
+                for (part, value) in id_parts {
+                    rows.push([
+                        id.clone(),
+                        aes.condition.clone().unwrap_or_default(),
+                        format!("{}.{part}", column(name)),
+                        number(*value),
+                        String::new(),
+                        String::new(),
+                    ]);
+                }
+            }
+        }
+        rows.sort();
+        let sep = ui_state.number_format.csv_separator();
+        let header = ["identifier", "condition", "column", "value", "sd", "n"];
+        let csv = std::iter::once(header.join(&sep.to_string()))
+            .chain(rows.iter().map(|row| {
+                row.iter()
+                    .map(|field| csv_field(field, sep))
+                    .join(&sep.to_string())
+            }))
+            .join("\n");
+        match std::fs::write(&event.path, csv + "\n") {
+            Ok(_) => info_state.notify("Data exported"),
+            Err(e) => {
+                warn!("Could not write the file: {}.", e);
+                info_state.notify("File could not be written!\nCheck that path exists.");
+            }
+        }
+    }
+}
+
+/// Quote a CSV field if it contains the separator, quotes or line breaks.
+fn csv_field(field: &str, sep: char) -> String {
+    if field.contains([sep, '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Write the current histogram positions, annotations and background into the map.
+fn store_layout(
+    escher_map: &mut EscherMap,
+    background: &MapBackground,
+    hist_query: &Query<(&Transform, &Xaxis), Without<AnyTag>>,
+    annotation_query: &Query<(&Transform, &Annotation)>,
+) {
+    escher_map.annotations = annotation_query
+        .iter()
+        .map(|(trans, annotation)| Annotation {
+            position: trans.translation.truncate(),
+            ..annotation.clone()
+        })
+        .collect();
+    escher_map.background = if background.path.is_empty() {
+        None
+    } else {
+        Some(background.clone())
+    };
+    // the axes of the metabolites are placed again next to their circles
+    for (trans, axis) in hist_query.iter().filter(|(_, axis)| !axis.met) {
+        if let Some(reac) = escher_map.metabolism.reactions.get_mut(&axis.node_id) {
+            reac.hist_position
+                .get_or_insert(HashMap::new())
+                .insert(axis.side.clone(), (*trans).into());
+            reac.hist_height
+                .get_or_insert(HashMap::new())
+                .insert(axis.side.clone(), axis.height);
+        }
+    }
+}
+
+/// Tabs to switch between the opened maps.
+///
+/// The layout of the map that is left is kept in its asset so that it is
+/// restored when coming back to it.
+#[cfg(feature = "gui")]
+fn ui_map_tabs(
+    mut egui_context: EguiContexts,
+    mut ui_state: ResMut<UiState>,
+    mut assets: ResMut<Assets<EscherMap>>,
+    mut state: ResMut<MapState>,
+    background: Res<MapBackground>,
+    hist_query: Query<(&Transform, &Xaxis), Without<AnyTag>>,
+    annotation_query: Query<(&Transform, &Annotation)>,
+) {
+    if ui_state.hide || state.maps.len() < 2 {
+        return;
+    }
+    let mut selected: Option<usize> = None;
+    let mut closed: Option<usize> = None;
+    egui::TopBottomPanel::top("map_tabs").show(egui_context.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            for (i, tab) in state.maps.iter().enumerate() {
+                let name = std::path::Path::new(&tab.name)
+                    .file_stem()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or(&tab.name);
+                if ui
+                    .selectable_label(i == state.current, name)
+                    .on_hover_text(&tab.name)
+                    .clicked()
+                {
+                    selected = Some(i);
+                }
+                if ui.small_button("x").clicked() {
+                    closed = Some(i);
+                }
+                ui.separator();
+            }
+        });
+    });
+    if selected.is_none() && closed.is_none() {
+        return;
+    }
+    if state.loaded {
+        if let Some(escher_map) = assets.get_mut(state.escher_map()) {
+            store_layout(escher_map, &background, &hist_query, &annotation_query);
+        }
+    }
+    if let Some(i) = closed {
+        state.maps.remove(i);
+        match i.cmp(&state.current) {
+            Ordering::Less => state.current -= 1,
+            Ordering::Equal => {
+                state.current = state.current.min(state.maps.len() - 1);
+                state.loaded = false;
+            }
+            Ordering::Greater => (),
+        }
+    } else if let Some(i) = selected.filter(|i| *i != state.current) {
+        state.current = i;
+        state.loaded = false;
+    }
+    // so that saving and watching refer to the displayed map
+    ui_state
+        .map_path
+        .clone_from(&state.maps[state.current].name);
+}
+
+pub(crate) fn safe_json_write<P, C>(path: P, contents: C) -> std::io::Result<()>
 where
     P: AsRef<std::path::Path>,
     C: serde::Serialize,
@@ -702,8 +3675,7 @@ fn listen_js_escher(
     mut escher_resource: ResMut<MapState>,
 ) {
     if let Ok(escher_map) = receiver.rx.try_recv() {
-        escher_resource.escher_map = escher_asset.add(escher_map);
-        escher_resource.loaded = false;
+        escher_resource.open("map", escher_asset.add(escher_map));
     }
 }
 
@@ -714,8 +3686,7 @@ fn listen_js_data(
     mut data_resource: ResMut<ReactionState>,
 ) {
     if let Ok(escher_map) = receiver.rx.try_recv() {
-        data_resource.reaction_data = Some(data_asset.add(escher_map));
-        data_resource.loaded = false;
+        data_resource.add("data", data_asset.add(escher_map));
     }
 }
 
@@ -725,3 +3696,145 @@ fn listen_js_info(receiver: Res<ReceiverResource<&'static str>>, mut info_box: R
         info_box.notify(msg);
     }
 }
+
+#[cfg(target_arch = "wasm32")]
+/// Commands sent by the surrounding web page through the functions exported
+/// to JavaScript, e.g., to switch the condition from a dropdown outside the canvas.
+pub enum JsCommand {
+    Condition(String),
+    /// Hex colors of the lowest and highest values of the reactions or metabolites.
+    Colors {
+        met: bool,
+        min: String,
+        max: String,
+    },
+    /// Sizes of the lowest and highest values of the reactions or metabolites.
+    Sizes {
+        met: bool,
+        min: f32,
+        max: f32,
+    },
+    /// Export an image, whose format is given by the extension of the file name.
+    Export(String),
+}
+
+#[cfg(target_arch = "wasm32")]
+static JS_COMMANDS: std::sync::OnceLock<async_std::channel::Sender<JsCommand>> =
+    std::sync::OnceLock::new();
+
+#[cfg(target_arch = "wasm32")]
+/// Channel of the [`JsCommand`]s, to be inserted as a [`ReceiverResource`].
+pub fn js_command_receiver() -> async_std::channel::Receiver<JsCommand> {
+    let (sender, receiver) = async_std::channel::unbounded();
+    JS_COMMANDS
+        .set(sender)
+        .unwrap_or_else(|_| warn!("The JS commands channel was already set."));
+    receiver
+}
+
+#[cfg(target_arch = "wasm32")]
+fn send_js_command(command: JsCommand) {
+    if let Some(sender) = JS_COMMANDS.get() {
+        sender.try_send(command).ok();
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_name = setCondition)]
+pub fn set_condition(condition: String) {
+    send_js_command(JsCommand::Condition(condition));
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_name = setReactionColors)]
+pub fn set_reaction_colors(min: String, max: String) {
+    send_js_command(JsCommand::Colors {
+        met: false,
+        min,
+        max,
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_name = setMetaboliteColors)]
+pub fn set_metabolite_colors(min: String, max: String) {
+    send_js_command(JsCommand::Colors {
+        met: true,
+        min,
+        max,
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_name = setReactionSizes)]
+pub fn set_reaction_sizes(min: f32, max: f32) {
+    send_js_command(JsCommand::Sizes {
+        met: false,
+        min,
+        max,
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_name = setMetaboliteSizes)]
+pub fn set_metabolite_sizes(min: f32, max: f32) {
+    send_js_command(JsCommand::Sizes {
+        met: true,
+        min,
+        max,
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_name = exportImage)]
+pub fn export_image(path: String) {
+    send_js_command(JsCommand::Export(path));
+}
+
+#[cfg(target_arch = "wasm32")]
+fn listen_js_commands(
+    receiver: Res<ReceiverResource<JsCommand>>,
+    mut ui_state: ResMut<UiState>,
+    mut info_box: ResMut<Info>,
+    mut screen_events: EventWriter<ScreenshotEvent>,
+) {
+    while let Ok(command) = receiver.rx.try_recv() {
+        match command {
+            JsCommand::Condition(condition) => {
+                if ui_state.conditions.contains(&condition) {
+                    ui_state.condition = condition;
+                } else {
+                    info_box.notify("Condition not found in the data!");
+                }
+            }
+            JsCommand::Colors { met, min, max } => {
+                let (Ok(min), Ok(max)) = (rgba_from_hex(&min), rgba_from_hex(&max)) else {
+                    info_box.notify("Invalid hex color!");
+                    continue;
+                };
+                // the same scale for all conditions
+                if met {
+                    ui_state.min_metabolite_color = HashMap::from([(String::new(), min)]);
+                    ui_state.max_metabolite_color = HashMap::from([(String::new(), max)]);
+                } else {
+                    ui_state.min_reaction_color = HashMap::from([(String::new(), min)]);
+                    ui_state.max_reaction_color = HashMap::from([(String::new(), max)]);
+                }
+            }
+            JsCommand::Sizes { met, min, max } => {
+                if met {
+                    ui_state.min_metabolite = min;
+                    ui_state.max_metabolite = max;
+                } else {
+                    ui_state.min_reaction = min;
+                    ui_state.max_reaction = max;
+                }
+            }
+            JsCommand::Export(path) => {
+                screen_events.send(ScreenshotEvent { path });
+                // the settings are shown again after the screenshot
+                ui_state.hide = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,298 @@
+//! Rendering of maps without a window, e.g., to compare the output of shu
+//! against reference images in the tests of apps that embed it.
+//!
+//! The app is rendered to an image in the GPU, which is copied back after the
+//! last frame. The time advances by a fixed step every frame so that the same
+//! inputs produce the same image.
+use crate::data::ReactionState;
+use crate::escher::{EscherMap, MapState};
+use crate::gui::UiState;
+use crate::ShuPlugins;
+use bevy::app::PluginsState;
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::pipelined_rendering::PipelinedRenderingPlugin;
+use bevy::render::render_asset::{RenderAssetUsages, RenderAssets};
+use bevy::render::render_resource::{
+    BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
+    ImageDataLayout, Maintain, MapMode, TextureDimension, TextureFormat, TextureUsages,
+};
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::texture::GpuImage;
+use bevy::render::{Render, RenderApp, RenderSet};
+use bevy::time::TimeUpdateStrategy;
+use bevy::window::{ExitCondition, WindowResolution};
+use bevy::winit::WinitPlugin;
+use bevy_pancam::PanCamPlugin;
+use bevy_prototype_lyon::prelude::ShapePlugin;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Time given to the map and the data to load before rendering fails.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+pub(crate) const TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// What [`render`] draws.
+#[derive(Resource, Clone)]
+pub struct Snapshot {
+    /// Escher map, the default map is used if not given.
+    pub map: Option<PathBuf>,
+    /// Data files, plotted in order.
+    pub datasets: Vec<PathBuf>,
+    /// Size of the image in pixels.
+    pub size: UVec2,
+    /// Frames advanced after the map and the data are loaded.
+    pub frames: u32,
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Self {
+            map: None,
+            datasets: Vec::new(),
+            size: UVec2::new(800, 600),
+            frames: 60,
+        }
+    }
+}
+
+/// Image that the cameras render to and where its pixels are copied to.
+#[derive(Resource, Clone, ExtractResource)]
+struct HeadlessTarget {
+    image: Handle<Image>,
+    /// Copy the image in the next frame.
+    capture: bool,
+    pixels: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+/// Build shu without a window, rendering to an image. The app can be further
+/// configured (e.g., through the [`UiState`]) before calling [`render_frames`].
+///
+/// The settings windows are hidden through the [`UiState`].
+pub fn headless_app(snapshot: &Snapshot) -> App {
+    let mut app = App::new();
+    let (width, height) = (snapshot.size.x as f32, snapshot.size.y as f32);
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        1. / 60.,
+    )))
+    .add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                // the window is never opened, but the GUI needs one
+                primary_window: Some(Window {
+                    resolution: WindowResolution::new(width, height),
+                    visible: false,
+                    ..default()
+                }),
+                exit_condition: ExitCondition::DontExit,
+                close_when_requested: false,
+            })
+            .set(ImagePlugin::default_linear())
+            .disable::<WinitPlugin>()
+            // render in the same frame that is updated
+            .disable::<PipelinedRenderingPlugin>(),
+    )
+    .add_plugins((PanCamPlugin, ShapePlugin))
+    .add_plugins(ShuPlugins)
+    .add_plugins(ExtractResourcePlugin::<HeadlessTarget>::default())
+    .insert_resource(snapshot.clone())
+    .add_systems(PostStartup, start_snapshot)
+    .add_systems(Update, target_cameras);
+    app.sub_app_mut(RenderApp).add_systems(
+        Render,
+        copy_target
+            .after(RenderSet::Render)
+            .before(RenderSet::Cleanup),
+    );
+    app
+}
+
+/// Update the app until the map and the data are plotted, advance the
+/// [`Snapshot::frames`] and return the last frame.
+pub fn render_frames(app: &mut App) -> anyhow::Result<Image> {
+    while app.plugins_state() == PluginsState::Adding {
+        bevy::tasks::tick_global_task_pools_on_main_thread();
+    }
+    if app.plugins_state() != PluginsState::Cleaned {
+        app.finish();
+        app.cleanup();
+    }
+    let start = Instant::now();
+    loop {
+        app.update();
+        let map_state = app.world.resource::<MapState>();
+        let reaction_state = app.world.resource::<ReactionState>();
+        let asset_server = app.world.resource::<AssetServer>();
+        if let Some(LoadState::Failed) = asset_server.get_load_state(map_state.escher_map()) {
+            anyhow::bail!("the map could not be loaded");
+        }
+        if let Some(dataset) = reaction_state.datasets.iter().find(|dataset| {
+            matches!(
+                asset_server.get_load_state(&dataset.handle),
+                Some(LoadState::Failed)
+            )
+        }) {
+            anyhow::bail!("the dataset '{}' could not be loaded", dataset.name);
+        }
+        if map_state.loaded && reaction_state.loaded {
+            break;
+        }
+        if start.elapsed() > LOAD_TIMEOUT {
+            anyhow::bail!("the map and the data did not load in {LOAD_TIMEOUT:?}");
+        }
+    }
+    let Snapshot { frames, size, .. } = *app.world.resource::<Snapshot>();
+    for _ in 0..frames {
+        app.update();
+    }
+    app.world.resource_mut::<HeadlessTarget>().capture = true;
+    app.update();
+    let mut target = app.world.resource_mut::<HeadlessTarget>();
+    target.capture = false;
+    let Some(pixels) = target.pixels.lock().unwrap().take() else {
+        anyhow::bail!("the frame could not be copied from the GPU");
+    };
+    Ok(Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        pixels,
+        TEXTURE_FORMAT,
+        RenderAssetUsages::default(),
+    ))
+}
+
+/// Render the map and the data of the `snapshot` without a window.
+///
+/// ```no_run
+/// let image = shu::headless::render(&shu::headless::Snapshot {
+///     datasets: vec!["flux.metabolism.json".into()],
+///     ..Default::default()
+/// })
+/// .unwrap();
+/// image.try_into_dynamic().unwrap().save("flux.png").unwrap();
+/// ```
+pub fn render(snapshot: &Snapshot) -> anyhow::Result<Image> {
+    render_frames(&mut headless_app(snapshot))
+}
+
+/// Load the map and the data of the [`Snapshot`] and create the image to render to.
+fn start_snapshot(
+    mut commands: Commands,
+    snapshot: Res<Snapshot>,
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+    mut ui_state: ResMut<UiState>,
+    mut map_state: ResMut<MapState>,
+    mut reaction_state: ResMut<ReactionState>,
+) {
+    ui_state.hide = true;
+    if let Some(map) = snapshot.map.as_ref() {
+        let handle: Handle<EscherMap> = asset_server.load(map.clone());
+        map_state.open(map.to_string_lossy(), handle);
+    }
+    for path in snapshot.datasets.iter() {
+        reaction_state.add(path.to_string_lossy(), asset_server.load(path.clone()));
+    }
+    commands.insert_resource(HeadlessTarget {
+        image: render_target(&mut images, snapshot.size),
+        capture: false,
+        pixels: Arc::new(Mutex::new(None)),
+    });
+}
+
+/// Image of `size` pixels that cameras can render to and be copied from.
+pub(crate) fn render_target(images: &mut Assets<Image>, size: UVec2) -> Handle<Image> {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; 4],
+        TEXTURE_FORMAT,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+        | TextureUsages::COPY_SRC
+        | TextureUsages::COPY_DST
+        | TextureUsages::RENDER_ATTACHMENT;
+    images.add(image)
+}
+
+/// Render the cameras to the image instead of the window.
+fn target_cameras(target: Res<HeadlessTarget>, mut cameras: Query<&mut Camera, Added<Camera>>) {
+    for mut camera in cameras.iter_mut() {
+        camera.target = RenderTarget::Image(target.image.clone());
+    }
+}
+
+/// Copy the rendered image to the main world when requested.
+fn copy_target(
+    target: Option<Res<HeadlessTarget>>,
+    gpu_images: Res<RenderAssets<Image>>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+) {
+    let Some(target) = target.filter(|t| t.capture) else {
+        return;
+    };
+    let Some(gpu_image) = gpu_images.get(&target.image) else {
+        return;
+    };
+    *target.pixels.lock().unwrap() = Some(read_texture(gpu_image, &device, &queue));
+}
+
+/// Copy the pixels of a rendered image back from the GPU, row by row.
+pub(crate) fn read_texture(
+    gpu_image: &GpuImage,
+    device: &RenderDevice,
+    queue: &RenderQueue,
+) -> Vec<u8> {
+    let (width, height) = (gpu_image.size.x as u32, gpu_image.size.y as u32);
+    let row_bytes = width as usize * 4;
+    // rows of the buffer must be aligned to copy a texture into it
+    let padded_row_bytes = RenderDevice::align_copy_bytes_per_row(row_bytes);
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("headless_readback"),
+        size: (padded_row_bytes * height as usize) as u64,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+    encoder.copy_texture_to_buffer(
+        gpu_image.texture.as_image_copy(),
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes as u32),
+                rows_per_image: None,
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit([encoder.finish()]);
+    let slice = buffer.slice(..);
+    slice.map_async(MapMode::Read, |_| ());
+    device.wgpu_device().poll(Maintain::Wait);
+    let pixels = slice
+        .get_mapped_range()
+        .chunks(padded_row_bytes)
+        .flat_map(|row| &row[..row_bytes])
+        .copied()
+        .collect();
+    buffer.unmap();
+    pixels
+}
@@ -0,0 +1,179 @@
+//! Color scale of the arrow, metabolite and box legends, painted by a shader
+//! from the min and max colors so that changing them does not rewrite any image.
+
+use bevy::math::DVec3;
+use bevy::prelude::*;
+use bevy::render::render_resource::{AsBindGroup, ShaderRef};
+use bevy_egui::egui::Rgba;
+
+use crate::gui::UiState;
+
+/// Linear color at zero if [`UiState::zero_white`], the same as in
+/// [`crate::funcplot::build_grad`].
+pub const ZERO_COLOR: Vec4 = Vec4::new(0.83, 0.83, 0.89, 1.0);
+
+/// Gradient between two colors, drawn over the shape of the `mask`.
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct GradientMaterial {
+    /// Linear color at the left.
+    #[uniform(0)]
+    pub min_color: Vec4,
+    /// Linear color at the right.
+    #[uniform(1)]
+    pub max_color: Vec4,
+    /// Fraction of the width where the scale crosses zero, painted with
+    /// [`ZERO_COLOR`]; negative to go straight from min to max.
+    #[uniform(2)]
+    pub zero: f32,
+    /// Only the alpha of the image is used.
+    #[texture(3)]
+    #[sampler(4)]
+    pub mask: Handle<Image>,
+}
+
+impl UiMaterial for GradientMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "embedded://shu/legend/gradient.wgsl".into()
+    }
+}
+
+impl GradientMaterial {
+    pub fn new(mask: Handle<Image>) -> Self {
+        Self {
+            min_color: Vec4::ONE,
+            max_color: Vec4::ONE,
+            zero: -1.,
+            mask,
+        }
+    }
+
+    /// Linear color at `t`, between 0 (left) and 1 (right); the same as the shader.
+    pub fn color_at(&self, t: f32) -> Vec4 {
+        if self.zero < 0. {
+            mix_oklab(self.min_color, self.max_color, t)
+        } else if t < self.zero {
+            mix_oklab(self.min_color, ZERO_COLOR, t / self.zero)
+        } else {
+            mix_oklab(
+                ZERO_COLOR,
+                self.max_color,
+                (t - self.zero) / (1. - self.zero),
+            )
+        }
+    }
+
+    /// Paint the gradient on a copy of the `mask` in the CPU, e.g., to write it to an SVG.
+    pub fn bake(&self, mask: &Image) -> Image {
+        let width = mask.width().max(1) as usize;
+        let mut image = mask.clone();
+        image.data = mask
+            .data
+            .chunks(4)
+            .enumerate()
+            .flat_map(|(i, pixel)| {
+                let t = (i % width) as f32 / (width - 1).max(1) as f32;
+                let color = self.color_at(t);
+                let alpha = color.w * pixel[3] as f32 / 255.;
+                Color::rgba_linear(color.x, color.y, color.z, alpha).as_rgba_u8()
+            })
+            .collect();
+        image
+    }
+}
+
+/// Gradient legend of a geom, with the range of the data that it describes.
+#[derive(Component)]
+pub struct LegendGradient {
+    /// Geom whose color scale is shown, "Reaction" or "Metabolite".
+    pub geom: &'static str,
+    pub min: f32,
+    pub max: f32,
+    pub condition: Option<String>,
+}
+
+impl LegendGradient {
+    pub fn new(geom: &'static str) -> Self {
+        Self {
+            geom,
+            min: 0.,
+            max: 0.,
+            condition: None,
+        }
+    }
+
+    pub fn set_range(&mut self, min: f32, max: f32, condition: &Option<String>) {
+        self.min = min;
+        self.max = max;
+        self.condition.clone_from(condition);
+    }
+}
+
+/// Pass the color scale of each [`LegendGradient`] to its shader. Only a few
+/// uniforms change, so recoloring the legends is virtually free.
+pub fn paint_gradients(
+    ui_state: Res<UiState>,
+    gradient_query: Query<(&LegendGradient, &Handle<GradientMaterial>)>,
+    mut materials: ResMut<Assets<GradientMaterial>>,
+) {
+    for (gradient, handle) in &gradient_query {
+        let (min_color, max_color) =
+            ui_state.color_scale(gradient.geom, gradient.condition.as_deref());
+        let scale = (
+            linear(min_color),
+            linear(max_color),
+            zero_position(ui_state.zero_white, gradient.min, gradient.max),
+        );
+        let Some(material) = materials.get(handle) else {
+            continue;
+        };
+        // avoid uploading the material again if nothing changed
+        if (material.min_color, material.max_color, material.zero) == scale {
+            continue;
+        }
+        let material = materials.get_mut(handle).unwrap();
+        (material.min_color, material.max_color, material.zero) = scale;
+    }
+}
+
+fn linear(color: Rgba) -> Vec4 {
+    Vec4::new(color.r(), color.g(), color.b(), color.a())
+}
+
+/// Fraction of the range between `min` and `max` where zero lies, if it is
+/// painted white; -1 otherwise.
+pub fn zero_position(zero_white: bool, min: f32, max: f32) -> f32 {
+    if zero_white && (min * max < 0.) {
+        -min / (max - min)
+    } else {
+        -1.
+    }
+}
+
+// in double precision only to write the coefficients in full
+fn oklab(c: DVec3) -> DVec3 {
+    let l = (0.4122214708 * c.x + 0.5363325363 * c.y + 0.0514459929 * c.z).cbrt();
+    let m = (0.2119034982 * c.x + 0.6806995451 * c.y + 0.1073969566 * c.z).cbrt();
+    let s = (0.0883024619 * c.x + 0.2817188376 * c.y + 0.6299787005 * c.z).cbrt();
+    DVec3::new(
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    )
+}
+
+fn linear_rgb(c: DVec3) -> DVec3 {
+    let l = (c.x + 0.3963377774 * c.y + 0.2158037573 * c.z).powi(3);
+    let m = (c.x - 0.1055613458 * c.y - 0.0638541728 * c.z).powi(3);
+    let s = (c.x - 0.0894841775 * c.y - 1.2914855480 * c.z).powi(3);
+    DVec3::new(
+        4.0767416621 * l - 3.3077085340 * m + 0.2309708929 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    )
+    .clamp(DVec3::ZERO, DVec3::ONE)
+}
+
+fn mix_oklab(a: Vec4, b: Vec4, t: f32) -> Vec4 {
+    let lab = oklab(a.truncate().as_dvec3()).lerp(oklab(b.truncate().as_dvec3()), t as f64);
+    linear_rgb(lab).as_vec3().extend(a.w + (b.w - a.w) * t)
+}
@@ -0,0 +1,59 @@
+// Color scale of the arrow, metabolite and box legends: the min and max colors
+// are interpolated in Oklab along the x axis, through white at zero if requested.
+// The texture only gives the shape of the legend through its alpha.
+#import bevy_ui::ui_vertex_output::UiVertexOutput
+
+@group(1) @binding(0) var<uniform> min_color: vec4<f32>;
+@group(1) @binding(1) var<uniform> max_color: vec4<f32>;
+@group(1) @binding(2) var<uniform> zero: f32;
+@group(1) @binding(3) var mask_texture: texture_2d<f32>;
+@group(1) @binding(4) var mask_sampler: sampler;
+
+// keep in sync with `ZERO_COLOR` in gradient.rs
+const ZERO_COLOR: vec4<f32> = vec4<f32>(0.83, 0.83, 0.89, 1.0);
+
+fn cbrt(x: f32) -> f32 {
+    return sign(x) * pow(abs(x), 1.0 / 3.0);
+}
+
+fn oklab(c: vec3<f32>) -> vec3<f32> {
+    let l = cbrt(0.4122214708 * c.r + 0.5363325363 * c.g + 0.0514459929 * c.b);
+    let m = cbrt(0.2119034982 * c.r + 0.6806995451 * c.g + 0.1073969566 * c.b);
+    let s = cbrt(0.0883024619 * c.r + 0.2817188376 * c.g + 0.6299787005 * c.b);
+    return vec3<f32>(
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    );
+}
+
+fn linear_rgb(c: vec3<f32>) -> vec3<f32> {
+    let l = pow(c.x + 0.3963377774 * c.y + 0.2158037573 * c.z, 3.0);
+    let m = pow(c.x - 0.1055613458 * c.y - 0.0638541728 * c.z, 3.0);
+    let s = pow(c.x - 0.0894841775 * c.y - 1.2914855480 * c.z, 3.0);
+    return clamp(vec3<f32>(
+        4.0767416621 * l - 3.3077085340 * m + 0.2309708929 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn mix_oklab(a: vec4<f32>, b: vec4<f32>, t: f32) -> vec4<f32> {
+    let lab = mix(oklab(a.rgb), oklab(b.rgb), t);
+    return vec4<f32>(linear_rgb(lab), mix(a.a, b.a, t));
+}
+
+@fragment
+fn fragment(in: UiVertexOutput) -> @location(0) vec4<f32> {
+    let mask = textureSample(mask_texture, mask_sampler, in.uv);
+    let t = in.uv.x;
+    var color: vec4<f32>;
+    if zero < 0.0 {
+        color = mix_oklab(min_color, max_color, t);
+    } else if t < zero {
+        color = mix_oklab(min_color, ZERO_COLOR, t / zero);
+    } else {
+        color = mix_oklab(ZERO_COLOR, max_color, (t - zero) / (1.0 - zero));
+    }
+    return vec4<f32>(color.rgb, color.a * mask.a);
+}
@@ -1,16 +1,23 @@
 //! Procedural legend generation.
 
+use bevy::asset::embedded_asset;
 use bevy::prelude::*;
 
 use crate::{
-    aesthetics::{Aesthetics, Distribution, Gcolor, Gy, Point, Unscale},
-    funcplot::{linspace, max_f32, min_f32},
+    aesthetics::{
+        categories, scale_range, Aesthetics, ColumnName, Distribution, Gcolor, Gy, Parts, Point,
+        Units, Unscale, Untransformed,
+    },
+    funcplot::{category_color, max_f32, min_f32, unit_suffix},
     geom::{GeomArrow, GeomHist, GeomMetabolite, PopUp, Side, Xaxis},
     gui::{or_color, UiState},
 };
 
+mod gradient;
 mod setup;
-use setup::{spawn_legend, LegendArrow, LegendBox, LegendCircle};
+use gradient::{paint_gradients, LegendGradient};
+pub use gradient::{zero_position, GradientMaterial};
+use setup::{spawn_legend, LegendArrow, LegendBox, LegendCategories, LegendCircle};
 pub use setup::{LegendCondition, LegendHist, Xmax, Xmin};
 
 /// Procedural legend generation.
@@ -18,19 +25,61 @@ pub struct LegendPlugin;
 
 impl Plugin for LegendPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_legend).add_systems(
-            Update,
-            (
-                color_legend_arrow,
-                color_legend_circle,
-                color_legend_histograms,
-                color_legend_box,
-                display_conditions,
-            ),
-        );
+        embedded_asset!(app, "gradient.wgsl");
+        app.add_plugins(UiMaterialPlugin::<GradientMaterial>::default())
+            .add_systems(Startup, spawn_legend)
+            .add_systems(
+                Update,
+                (
+                    (color_legend_arrow, color_legend_circle, color_legend_box)
+                        .before(paint_gradients),
+                    paint_gradients,
+                    color_legend_histograms,
+                    display_conditions,
+                    display_categories,
+                ),
+            );
     }
 }
 
+/// Value to show in the legend for the plotted `value`: the value itself or, if
+/// [`UiState::show_original`], the untransformed value of the same element.
+fn original_value(
+    value: f32,
+    values: &[f32],
+    original: Option<&Untransformed<f32>>,
+    ui_state: &UiState,
+) -> f32 {
+    original
+        .filter(|_| ui_state.show_original)
+        .and_then(|original| {
+            values
+                .iter()
+                .position(|x| *x == value)
+                .and_then(|i| original.0.get(i).copied())
+        })
+        .unwrap_or(value)
+}
+
+/// Value to show in the legend for the end `value` of a scale, looked up in the
+/// values of the condition shown and then, since the scale may span all of
+/// them, in those of the other conditions.
+fn scale_label<'a>(
+    value: f32,
+    mut all: impl Iterator<Item = (&'a Vec<f32>, Option<&'a Untransformed<f32>>)>,
+    ui_state: &UiState,
+) -> f32 {
+    all.find(|(values, _)| values.contains(&value))
+        .map_or(value, |(values, original)| {
+            original_value(value, values, original, ui_state)
+        })
+}
+
+/// Unit of the plotted column, empty if it has none.
+fn column_unit<'a>(column: Option<&ColumnName>, units: &'a Units) -> &'a str {
+    column.map(|c| units.of(&c.0)).unwrap_or_default()
+}
+
 /// If a [`GeomArrow`] with color is added, and arrow is displayed showcasing the color scale with a gradient.
 ///
 /// The legend is displayed only if there is data with the right aes [`Gcolor`] and geom [`GeomArrow`].
@@ -44,15 +93,23 @@ impl Plugin for LegendPlugin {
 fn color_legend_arrow(
     ui_state: Res<UiState>,
     mut legend_query: Query<(Entity, &mut Style, &Children), With<LegendArrow>>,
-    mut img_query: Query<&UiImage>,
+    mut gradient_query: Query<&mut LegendGradient>,
     mut text_query: Query<&mut Text, With<Xmin>>,
     mut text_max_query: Query<&mut Text, Without<Xmin>>,
-    point_query: Query<(&Point<f32>, &Aesthetics), (With<Gcolor>, With<GeomArrow>)>,
-    mut images: ResMut<Assets<Image>>,
+    point_query: Query<
+        (
+            &Point<f32>,
+            &Aesthetics,
+            Option<&Untransformed<f32>>,
+            Option<&ColumnName>,
+        ),
+        (With<Gcolor>, With<GeomArrow>),
+    >,
+    units: Res<Units>,
 ) {
     for (_parent, mut style, children) in &mut legend_query {
         let mut displayed = Display::None;
-        for (colors, aes) in point_query.iter() {
+        for (colors, aes, original, column) in point_query.iter() {
             if let Some(condition) = &aes.condition {
                 if condition != &ui_state.condition {
                     if ui_state.condition == "ALL" {
@@ -64,37 +121,27 @@ fn color_legend_arrow(
                 }
             }
             displayed = Display::Flex;
-            let min_val = min_f32(&colors.0);
-            let max_val = max_f32(&colors.0);
-            let grad = crate::funcplot::build_grad(
-                ui_state.zero_white,
-                min_val,
-                max_val,
-                &ui_state.min_reaction_color,
-                &ui_state.max_reaction_color,
+            let all = || {
+                point_query
+                    .iter()
+                    .map(|(colors, _, original, _)| (&colors.0, original))
+            };
+            let (min_val, max_val) = scale_range(
+                &ui_state,
+                &colors.0,
+                all().map(|(values, _)| values.as_slice()),
             );
+            let current = || std::iter::once((&colors.0, original)).chain(all());
             for child in children.iter() {
                 if let Ok(mut text) = text_query.get_mut(*child) {
-                    text.sections[0].value = format!("{:.2e}", min_val);
+                    let label = scale_label(min_val, current(), &ui_state);
+                    text.sections[0].value = ui_state.number_format.label(label);
                 } else if let Ok(mut text) = text_max_query.get_mut(*child) {
-                    text.sections[0].value = format!("{:.2e}", max_val);
-                } else if let Ok(img_legend) = img_query.get_mut(*child) {
-                    // modify the image inplace
-                    let img = images.get_mut(&img_legend.texture).unwrap();
-
-                    let width = img.size().x as f64;
-                    let points = linspace(min_val, max_val, width as u32);
-                    let data = img.data.chunks(4).enumerate().flat_map(|(i, pixel)| {
-                        let row = (i as f64 / width).floor();
-                        let x = i as f64 - width * row;
-                        if pixel[3] != 0 {
-                            let color = grad.at(points[x as usize] as f64).to_rgba8();
-                            [color[0], color[1], color[2], color[3]].into_iter()
-                        } else {
-                            [0, 0, 0, 0].into_iter()
-                        }
-                    });
-                    img.data = data.collect::<Vec<u8>>();
+                    let label = scale_label(max_val, current(), &ui_state);
+                    text.sections[0].value = ui_state.number_format.label(label);
+                    text.sections[1].value = unit_suffix(column_unit(column, &units));
+                } else if let Ok(mut gradient) = gradient_query.get_mut(*child) {
+                    gradient.set_range(min_val, max_val, &aes.condition);
                 }
             }
         }
@@ -115,15 +162,23 @@ fn color_legend_arrow(
 fn color_legend_circle(
     ui_state: Res<UiState>,
     mut legend_query: Query<(Entity, &mut Style, &Children), With<LegendCircle>>,
-    mut img_query: Query<&UiImage>,
+    mut gradient_query: Query<&mut LegendGradient>,
     mut text_query: Query<&mut Text, With<Xmin>>,
     mut text_max_query: Query<&mut Text, Without<Xmin>>,
-    point_query: Query<(&Point<f32>, &Aesthetics), (With<Gcolor>, With<GeomMetabolite>)>,
-    mut images: ResMut<Assets<Image>>,
+    point_query: Query<
+        (
+            &Point<f32>,
+            &Aesthetics,
+            Option<&Untransformed<f32>>,
+            Option<&ColumnName>,
+        ),
+        (With<Gcolor>, With<GeomMetabolite>),
+    >,
+    units: Res<Units>,
 ) {
     for (_parent, mut style, children) in &mut legend_query {
         let mut displayed = Display::None;
-        for (colors, aes) in point_query.iter() {
+        for (colors, aes, original, column) in point_query.iter() {
             if let Some(condition) = &aes.condition {
                 if condition != &ui_state.condition {
                     if ui_state.condition == "ALL" {
@@ -133,37 +188,27 @@ fn color_legend_circle(
                 }
             }
             displayed = Display::Flex;
-            let min_val = min_f32(&colors.0);
-            let max_val = max_f32(&colors.0);
-            let grad = crate::funcplot::build_grad(
-                ui_state.zero_white,
-                min_val,
-                max_val,
-                &ui_state.min_metabolite_color,
-                &ui_state.max_metabolite_color,
+            let all = || {
+                point_query
+                    .iter()
+                    .map(|(colors, _, original, _)| (&colors.0, original))
+            };
+            let (min_val, max_val) = scale_range(
+                &ui_state,
+                &colors.0,
+                all().map(|(values, _)| values.as_slice()),
             );
+            let current = || std::iter::once((&colors.0, original)).chain(all());
             for child in children.iter() {
                 if let Ok(mut text) = text_query.get_mut(*child) {
-                    text.sections[0].value = format!("{:.2e}", min_val);
+                    let label = scale_label(min_val, current(), &ui_state);
+                    text.sections[0].value = ui_state.number_format.label(label);
                 } else if let Ok(mut text) = text_max_query.get_mut(*child) {
-                    text.sections[0].value = format!("{:.2e}", max_val);
-                } else if let Ok(img_legend) = img_query.get_mut(*child) {
-                    // modify the image inplace
-                    let img = images.get_mut(&img_legend.texture).unwrap();
-
-                    let width = img.size().x as f64;
-                    let points = linspace(min_val, max_val, width as u32);
-                    let data = img.data.chunks(4).enumerate().flat_map(|(i, pixel)| {
-                        let row = (i as f64 / width).floor();
-                        let x = i as f64 - width * row;
-                        if pixel[3] != 0 {
-                            let color = grad.at(points[x as usize] as f64).to_rgba8();
-                            [color[0], color[1], color[2], color[3]].into_iter()
-                        } else {
-                            [0, 0, 0, 0].into_iter()
-                        }
-                    });
-                    img.data = data.collect::<Vec<u8>>();
+                    let label = scale_label(max_val, current(), &ui_state);
+                    text.sections[0].value = ui_state.number_format.label(label);
+                    text.sections[1].value = unit_suffix(column_unit(column, &units));
+                } else if let Ok(mut gradient) = gradient_query.get_mut(*child) {
+                    gradient.set_range(min_val, max_val, &aes.condition);
                 }
             }
         }
@@ -191,13 +236,14 @@ fn color_legend_histograms(
     mut img_query: Query<(&UiImage, &mut BackgroundColor)>,
     mut text_query: Query<&mut Text, With<Xmin>>,
     mut text_max_query: Query<&mut Text, Without<Xmin>>,
+    units: Res<Units>,
 ) {
-    if !ui_state.is_changed() {
+    if !ui_state.is_changed() && !units.is_changed() {
         // the ui_state always changes on the creation of histograms
         return;
     }
-    let mut left: Option<((f32, f32), &Side, bool)> = None;
-    let mut right: Option<((f32, f32), &Side, bool)> = None;
+    let mut left: Option<((f32, f32), &Side, bool, &str)> = None;
+    let mut right: Option<((f32, f32), &Side, bool, &str)> = None;
     // gather axis limits for each axis if they exist
     for axis in axis_query.iter() {
         if left.is_some() & right.is_some() {
@@ -212,11 +258,17 @@ fn color_legend_histograms(
             axis.xlimits,
             &axis.side,
             hist_query.iter().any(|hist| hist.side == axis.side),
+            axis.titles
+                .first()
+                .map(|title| units.of(title))
+                .unwrap_or_default(),
         ));
     }
     let condition = ui_state.condition.clone();
+    let number_format = ui_state.number_format;
+    let global = ui_state.global_xlimits;
     // if an axis matches the legend in side, show the legend with bounds and color
-    for (xlimits, axis_side, display) in [left, right].iter().filter_map(|o| o.as_ref()) {
+    for (xlimits, axis_side, display, unit) in [left, right].iter().filter_map(|o| o.as_ref()) {
         for (_parent, mut style, side, children) in &mut legend_query {
             if !display {
                 style.display = Display::None;
@@ -224,10 +276,20 @@ fn color_legend_histograms(
             }
             for child in children.iter() {
                 if axis_side == &side {
+                    // each axis has its own range unless they are shared
                     if let Ok(mut text) = text_query.get_mut(*child) {
-                        text.sections[0].value = format!("{:.2e}", xlimits.0);
+                        text.sections[0].value = if global {
+                            number_format.label(xlimits.0)
+                        } else {
+                            String::from("min")
+                        };
                     } else if let Ok(mut text) = text_max_query.get_mut(*child) {
-                        text.sections[0].value = format!("{:.2e}", xlimits.1);
+                        text.sections[0].value = if global {
+                            number_format.label(xlimits.1)
+                        } else {
+                            String::from("max")
+                        };
+                        text.sections[1].value = unit_suffix(unit);
                     } else {
                         style.display = Display::Flex;
                         if let Ok((img_legend, mut background_color)) = img_query.get_mut(*child) {
@@ -318,15 +380,24 @@ fn color_legend_histograms(
 fn color_legend_box(
     ui_state: Res<UiState>,
     mut legend_query: Query<(Entity, &mut Style, &Side, &Children), With<LegendBox>>,
-    mut img_query: Query<&UiImage>,
+    mut gradient_query: Query<&mut LegendGradient>,
     mut text_query: Query<&mut Text, With<Xmin>>,
     mut text_max_query: Query<&mut Text, Without<Xmin>>,
-    point_query: Query<(&Point<f32>, &Aesthetics, &GeomHist), (With<Gy>, Without<PopUp>)>,
-    mut images: ResMut<Assets<Image>>,
+    point_query: Query<
+        (
+            &Point<f32>,
+            &Aesthetics,
+            &GeomHist,
+            Option<&Untransformed<f32>>,
+            Option<&ColumnName>,
+        ),
+        (With<Gy>, Without<PopUp>),
+    >,
+    units: Res<Units>,
 ) {
     for (_parent, mut style, side, children) in &mut legend_query {
         let mut displayed = Display::None;
-        for (colors, aes, geom_hist) in point_query.iter() {
+        for (colors, aes, geom_hist, original, column) in point_query.iter() {
             if let Some(condition) = &aes.condition {
                 if (condition != &ui_state.condition) & (ui_state.condition != "ALL") {
                     continue;
@@ -339,35 +410,16 @@ fn color_legend_box(
             displayed = Display::Flex;
             let min_val = min_f32(&colors.0);
             let max_val = max_f32(&colors.0);
-            let grad = crate::funcplot::build_grad(
-                ui_state.zero_white,
-                min_val,
-                max_val,
-                &ui_state.min_reaction_color,
-                &ui_state.max_reaction_color,
-            );
             for child in children.iter() {
                 if let Ok(mut text) = text_query.get_mut(*child) {
-                    text.sections[0].value = format!("{:.2e}", min_val);
+                    let label = original_value(min_val, &colors.0, original, &ui_state);
+                    text.sections[0].value = ui_state.number_format.label(label);
                 } else if let Ok(mut text) = text_max_query.get_mut(*child) {
-                    text.sections[0].value = format!("{:.2e}", max_val);
-                } else if let Ok(img_legend) = img_query.get_mut(*child) {
-                    // modify the image inplace
-                    let image = images.get_mut(&img_legend.texture).unwrap();
-
-                    let width = image.size().x as f64;
-                    let points = linspace(min_val, max_val, width as u32);
-                    let data = image.data.chunks(4).enumerate().flat_map(|(i, pixel)| {
-                        let row = (i as f64 / width).floor();
-                        let x = i as f64 - width * row;
-                        if pixel[3] != 0 {
-                            let color = grad.at(points[x as usize] as f64).to_rgba8();
-                            [color[0], color[1], color[2], color[3]].into_iter()
-                        } else {
-                            [0, 0, 0, 0].into_iter()
-                        }
-                    });
-                    image.data = data.collect::<Vec<u8>>();
+                    let label = original_value(max_val, &colors.0, original, &ui_state);
+                    text.sections[0].value = ui_state.number_format.label(label);
+                    text.sections[1].value = unit_suffix(column_unit(column, &units));
+                } else if let Ok(mut gradient) = gradient_query.get_mut(*child) {
+                    gradient.set_range(min_val, max_val, &aes.condition);
                 }
             }
         }
@@ -422,3 +474,65 @@ fn display_conditions(
         }
     }
 }
+
+/// Show the color of each category of the stacked bars and pies.
+fn display_categories(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    parts: Query<&Parts>,
+    mut legend_query: Query<(Entity, &mut Style, &mut LegendCategories)>,
+) {
+    let categories = categories(parts.iter());
+    for (parent, mut style, mut legend) in &mut legend_query {
+        if legend.state == categories {
+            continue;
+        }
+        style.display = if categories.is_empty() {
+            Display::None
+        } else {
+            Display::Flex
+        };
+        commands.entity(parent).despawn_descendants();
+        legend.state = categories.clone();
+        let font = asset_server.load("fonts/Assistant-Regular.ttf");
+        let rect = asset_server.load("rect_legend.png");
+        for (i, name) in categories.iter().enumerate() {
+            commands.entity(parent).with_children(|p| {
+                p.spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        margin: UiRect::horizontal(Val::Px(4.0)),
+                        ..Default::default()
+                    },
+                    focus_policy: bevy::ui::FocusPolicy::Pass,
+                    ..Default::default()
+                })
+                .with_children(|p| {
+                    p.spawn(ImageBundle {
+                        style: Style {
+                            width: Val::Px(12.0),
+                            height: Val::Px(12.0),
+                            margin: UiRect::right(Val::Px(3.0)),
+                            ..default()
+                        },
+                        image: UiImage::new(rect.clone()),
+                        background_color: BackgroundColor(category_color(i)),
+                        focus_policy: bevy::ui::FocusPolicy::Pass,
+                        ..default()
+                    });
+                    p.spawn(TextBundle {
+                        text: Text::from_section(
+                            name,
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 12.,
+                                color: Color::hex("504d50").unwrap(),
+                            },
+                        ),
+                        ..Default::default()
+                    });
+                });
+            });
+        }
+    }
+}
@@ -3,8 +3,9 @@
 
 use bevy::prelude::*;
 
+use super::gradient::{GradientMaterial, LegendGradient};
 use crate::{
-    funcplot::ScaleBundle,
+    funcplot::{NumberFormat, ScaleBundle},
     geom::{Drag, Side},
 };
 
@@ -30,6 +31,12 @@ pub struct LegendCondition {
 }
 #[derive(Component)]
 pub struct LegendHist;
+/// Colors of the categories of the stacked bars.
+#[derive(Component)]
+pub struct LegendCategories {
+    /// Current categories for change detection.
+    pub state: Vec<String>,
+}
 #[derive(Component)]
 pub struct LegendBox;
 #[derive(Component)]
@@ -41,13 +48,18 @@ pub struct Xmax;
 /// adds data corresponding to a part of the legend, that part is displayed.
 ///
 /// The legend is a Column with 4 row children:
-/// - arrow legend with 3 children: Text(min), UiImage(arrow), Text(max).
-/// - metabolite legend with 3 children: Text(min), UiImage(circle), Text(max).
+/// - arrow legend with 3 children: Text(min), LegendGradient(arrow), Text(max).
+/// - metabolite legend with 3 children: Text(min), LegendGradient(circle), Text(max).
 /// - histogram legend with 2 column children:
 ///     - Text(min), UiImage(histogram), Text(max).
 ///     - Text(min), UiImage(histogram), Text(maximum).
-/// - box legend, same as histogram but with Rects instead of images.
-pub fn spawn_legend(mut commands: Commands, asset_server: Res<AssetServer>) {
+/// - box legend, same as histogram but with LegendGradient(rect) instead of images.
+/// - categories legend, with one row of UiImage(rect) and Text(name) per category.
+pub fn spawn_legend(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<GradientMaterial>>,
+) {
     let font = asset_server.load("fonts/Assistant-Regular.ttf");
     let scales_arrow = ScaleBundle::new(
         0.,
@@ -55,6 +67,8 @@ pub fn spawn_legend(mut commands: Commands, asset_server: Res<AssetServer>) {
         0.,
         200.,
         200.,
+        "",
+        NumberFormat::default(),
         font,
         15.,
         Color::hex("504d50").unwrap(),
@@ -128,16 +142,19 @@ pub fn spawn_legend(mut commands: Commands, asset_server: Res<AssetServer>) {
                     ));
                 })
                 .with_children(|p| {
-                    p.spawn(ImageBundle {
-                        style: Style {
-                            width: CIRCLE_DIAM * 0.5,
-                            height: CIRCLE_DIAM * 0.5,
+                    p.spawn((
+                        MaterialNodeBundle {
+                            style: Style {
+                                width: CIRCLE_DIAM * 0.5,
+                                height: CIRCLE_DIAM * 0.5,
+                                ..default()
+                            },
+                            focus_policy: bevy::ui::FocusPolicy::Pass,
+                            material: materials.add(GradientMaterial::new(box_handle.clone())),
                             ..default()
                         },
-                        focus_policy: bevy::ui::FocusPolicy::Pass,
-                        image: UiImage::new(box_handle.clone()),
-                        ..default()
-                    });
+                        LegendGradient::new("Reaction"),
+                    ));
                 })
                 .with_children(|p| {
                     p.spawn((
@@ -176,16 +193,19 @@ pub fn spawn_legend(mut commands: Commands, asset_server: Res<AssetServer>) {
                     ));
                 })
                 .with_children(|p| {
-                    p.spawn(ImageBundle {
-                        style: Style {
-                            width: CIRCLE_DIAM * 0.5,
-                            height: CIRCLE_DIAM * 0.5,
+                    p.spawn((
+                        MaterialNodeBundle {
+                            style: Style {
+                                width: CIRCLE_DIAM * 0.5,
+                                height: CIRCLE_DIAM * 0.5,
+                                ..default()
+                            },
+                            focus_policy: bevy::ui::FocusPolicy::Pass,
+                            material: materials.add(GradientMaterial::new(box_handle.clone())),
                             ..default()
                         },
-                        focus_policy: bevy::ui::FocusPolicy::Pass,
-                        image: UiImage::new(box_handle.clone()),
-                        ..default()
-                    });
+                        LegendGradient::new("Reaction"),
+                    ));
                 })
                 .with_children(|p| {
                     p.spawn((
@@ -223,16 +243,19 @@ pub fn spawn_legend(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ));
             })
             .with_children(|p| {
-                p.spawn(ImageBundle {
-                    style: Style {
-                        width: ARROW_WIDTH,
-                        height: ARROW_HEIGHT,
+                p.spawn((
+                    MaterialNodeBundle {
+                        style: Style {
+                            width: ARROW_WIDTH,
+                            height: ARROW_HEIGHT,
+                            ..default()
+                        },
+                        focus_policy: bevy::ui::FocusPolicy::Pass,
+                        material: materials.add(GradientMaterial::new(arrow_handle)),
                         ..default()
                     },
-                    focus_policy: bevy::ui::FocusPolicy::Pass,
-                    image: UiImage::new(arrow_handle),
-                    ..default()
-                });
+                    LegendGradient::new("Reaction"),
+                ));
             })
             .with_children(|p| {
                 p.spawn((
@@ -269,16 +292,19 @@ pub fn spawn_legend(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ));
             })
             .with_children(|p| {
-                p.spawn(ImageBundle {
-                    style: Style {
-                        width: CIRCLE_DIAM,
-                        height: CIRCLE_DIAM * 0.8,
+                p.spawn((
+                    MaterialNodeBundle {
+                        style: Style {
+                            width: CIRCLE_DIAM,
+                            height: CIRCLE_DIAM * 0.8,
+                            ..default()
+                        },
+                        focus_policy: bevy::ui::FocusPolicy::Pass,
+                        material: materials.add(GradientMaterial::new(met_handle)),
                         ..default()
                     },
-                    focus_policy: bevy::ui::FocusPolicy::Pass,
-                    image: UiImage::new(met_handle),
-                    ..default()
-                });
+                    LegendGradient::new("Metabolite"),
+                ));
             })
             .with_children(|p| {
                 p.spawn((
@@ -429,5 +455,23 @@ pub fn spawn_legend(mut commands: Commands, asset_server: Res<AssetServer>) {
                     ));
                 });
             });
+        })
+        // categories legend, filled when there are stacked bars
+        .with_children(|p| {
+            p.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: ARROW_BUNDLE_WIDTH,
+                        display: Display::None,
+                        flex_wrap: FlexWrap::Wrap,
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        ..Default::default()
+                    },
+                    focus_policy: bevy::ui::FocusPolicy::Pass,
+                    ..Default::default()
+                },
+                LegendCategories { state: Vec::new() },
+            ));
         });
 }
@@ -0,0 +1,137 @@
+//! High-dimensional metabolic maps.
+//!
+//! Besides the `shu` application, the map view can be embedded in other Bevy
+//! apps by adding [`ShuPlugins`] next to the [`DefaultPlugins`] of Bevy and the
+//! plugins of its dependencies:
+//!
+//! ```no_run
+//! use bevy::prelude::*;
+//! use bevy_pancam::PanCamPlugin;
+//! use bevy_prototype_lyon::prelude::ShapePlugin;
+//!
+//! App::new()
+//!     .add_plugins(DefaultPlugins)
+//!     .add_plugins((PanCamPlugin, ShapePlugin))
+//!     .add_plugins(shu::ShuPlugins)
+//!     .run();
+//! ```
+//!
+//! Data is pushed by adding a [`data::Data`] asset to the [`data::ReactionState`]
+//! (or by spawning entities with the components of [`aesthetics`] and [`geom`]),
+//! and the appearance is driven through the [`gui::UiState`] resource. Without
+//! the default `gui` feature, the settings and selection windows are not shown.
+//!
+//! To check the rendered output programmatically, [`headless::render`] draws
+//! a map and its data without a window and returns the image.
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
+use bevy::app::PluginGroupBuilder;
+use bevy::prelude::*;
+use bevy_pancam::PanCam;
+
+pub mod aesthetics;
+pub mod annotation;
+pub mod background;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod batch;
+pub mod bookmark;
+pub mod camera;
+pub mod data;
+pub mod escher;
+pub mod extra_egui;
+pub mod font;
+pub mod funcplot;
+pub mod geom;
+pub mod grid;
+pub mod gui;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
+pub mod info;
+pub mod legend;
+pub mod onboarding;
+pub mod provenance;
+#[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+pub mod remote;
+pub mod scale;
+pub mod screenshot;
+pub mod stats;
+#[cfg(test)]
+mod tests;
+pub mod theme;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod watcher;
+
+pub use aesthetics::AesPlugin;
+pub use escher::EscherPlugin;
+pub use gui::GuiPlugin;
+pub use legend::LegendPlugin;
+
+use escher::{EscherMap, MapState};
+use screenshot::{RawAsset, RawFontStorage};
+
+/// The plugins that make up shu. Single plugins can be disabled or replaced
+/// as with any [`PluginGroup`], e.g., [`SetupPlugin`] to bring a camera.
+pub struct ShuPlugins;
+
+impl PluginGroup for ShuPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let group = PluginGroupBuilder::start::<Self>()
+            .add(camera::CameraPlugin)
+            .add(screenshot::ScreenShotPlugin)
+            .add(info::InfoPlugin)
+            .add(EscherPlugin)
+            .add(font::MapFontPlugin)
+            .add(background::BackgroundPlugin)
+            .add(theme::ThemePlugin)
+            .add(GuiPlugin)
+            .add(grid::GridPlugin)
+            .add(data::DataPlugin)
+            .add(SetupPlugin)
+            .add(AesPlugin)
+            .add(annotation::AnnotationPlugin)
+            .add(bookmark::BookmarkPlugin)
+            .add(provenance::ProvenancePlugin)
+            .add(LegendPlugin);
+        // file system and level of detail are only available in native
+        #[cfg(not(target_arch = "wasm32"))]
+        let group = group.add(watcher::WatcherPlugin).add(scale::ZoomPlugin);
+        #[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+        let group = group.add(remote::RemotePlugin);
+        group
+    }
+}
+
+/// Spawn the camera and load the default map and fonts.
+pub struct SetupPlugin;
+
+impl Plugin for SetupPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_system);
+    }
+}
+
+fn setup_system(mut commands: Commands, asset_server: Res<AssetServer>, theme: Res<theme::Theme>) {
+    let escher_handle: Handle<EscherMap> = asset_server.load("ecoli_core_map.json");
+    commands.insert_resource(MapState::new("ecoli_core_map.json", escher_handle));
+    commands.insert_resource(data::ReactionState::default());
+    let fira: Handle<RawAsset> = asset_server.load("fonts/FiraSans-Bold.tttx");
+    let assis: Handle<RawAsset> = asset_server.load("fonts/Assistant-Regular.tttx");
+    commands.insert_resource(RawFontStorage { fira, assis });
+
+    commands
+        .spawn(Camera2dBundle {
+            camera: Camera {
+                clear_color: ClearColorConfig::Custom(theme.background()),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(PanCam {
+            grab_buttons: vec![MouseButton::Left], // which buttons should drag the camera
+            enabled: true, // when false, controls are disabled. See toggle example.
+            zoom_to_cursor: true, // whether to zoom towards the mouse or the center of the screen
+            min_scale: 1., // prevent the camera from zooming too far in
+            max_scale: Some(40.), // prevent the camera from zooming too far out
+            ..Default::default()
+        });
+}
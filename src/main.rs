@@ -1,37 +1,44 @@
-#![allow(clippy::type_complexity, clippy::too_many_arguments)]
-
 use bevy::prelude::*;
 use bevy::winit::WinitSettings;
-use bevy_pancam::{PanCam, PanCamPlugin};
+use bevy_pancam::PanCamPlugin;
 use bevy_prototype_lyon::prelude::*;
 
-mod aesthetics;
-mod data;
-mod escher;
-mod extra_egui;
-mod funcplot;
-mod geom;
-mod gui;
-mod info;
-mod legend;
-mod scale;
-mod screenshot;
-#[cfg(test)]
-mod tests;
-
-use escher::{EscherMap, EscherPlugin, MapState};
-use screenshot::{RawAsset, RawFontStorage};
+#[cfg(not(target_arch = "wasm32"))]
+use shu::batch;
+use shu::ShuPlugins;
+#[cfg(target_arch = "wasm32")]
+use shu::{data, escher::EscherMap, gui};
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() {
-    App::new()
-        .insert_resource(Msaa::Sample4)
-        .insert_resource(WinitSettings::desktop_app())
+    let batch = match batch::BatchSpec::from_args() {
+        Ok(batch) => batch,
+        Err(e) => {
+            eprintln!("Could not read the batch spec: {e}");
+            std::process::exit(1);
+        }
+    };
+    let mut app = App::new();
+    app.insert_resource(Msaa::Sample4)
+        .insert_resource(if batch.is_some() {
+            // there is no user input to wake up the app in batch mode
+            WinitSettings::game()
+        } else {
+            WinitSettings::desktop_app()
+        })
         .add_plugins(
             DefaultPlugins
                 .set(WindowPlugin {
                     primary_window: Some(Window {
                         title: "shu".to_string(),
+                        visible: batch.is_none(),
+                        resolution: batch
+                            .as_ref()
+                            .and_then(|spec| spec.size)
+                            .map(|(width, height)| {
+                                bevy::window::WindowResolution::new(width, height)
+                            })
+                            .unwrap_or_default(),
                         ..default()
                     }),
                     ..default()
@@ -41,24 +48,25 @@ fn main() {
         // plugins from dependencies
         .add_plugins((PanCamPlugin, ShapePlugin))
         // internal plugins
-        .add_plugins(screenshot::ScreenShotPlugin)
-        .add_plugins(info::InfoPlugin)
-        .add_plugins(EscherPlugin)
-        .add_plugins(gui::GuiPlugin)
-        .add_plugins(data::DataPlugin)
-        .add_systems(Startup, setup_system)
-        .add_plugins(aesthetics::AesPlugin)
-        .add_plugins(scale::ZoomPlugin)
-        .add_plugins(legend::LegendPlugin)
-        .run();
+        .add_plugins(ShuPlugins);
+    if let Some(spec) = batch {
+        app.insert_resource(spec).add_plugins(batch::BatchPlugin);
+    }
+    app.run();
 }
 
 #[cfg(target_arch = "wasm32")]
 /// Main function with WASM additions.
-/// Three main differences:
+/// Four main differences:
 /// - Get WASM modules.
 /// - Create a button that sends data through a channel.
+/// - Create a button that pastes a map or data from the clipboard, since choosing
+///   files is awkward in some environments (e.g., notebooks or iframes).
 /// - Insert a Receiver resource so that systems can listen to that.
+///
+/// The map, the data and the condition can also be given as URL parameters
+/// (`?map=<url>&data=<url>&condition=<condition>`) so that a link reproduces a
+/// visualization.
 fn main() {
     use async_std::channel::{unbounded, Receiver, Sender};
     use gui::ReceiverResource;
@@ -75,6 +83,34 @@ fn main() {
     let (info_sender, info_receiver): (Sender<&'static str>, Receiver<&'static str>) = unbounded();
     let info_log1 = info_sender.clone();
 
+    let params = web_sys::window()
+        .unwrap()
+        .location()
+        .search()
+        .ok()
+        .and_then(|search| web_sys::UrlSearchParams::new_with_str(&search).ok());
+    let url_param = |key: &str| params.as_ref().and_then(|p| p.get(key));
+    if let Some(url) = url_param("map") {
+        spawn_local(send_from_url(
+            url,
+            map_sender.clone(),
+            info_sender.clone(),
+            "Failed loading map! Check that you JSON is correct.",
+        ));
+    }
+    if let Some(url) = url_param("data") {
+        spawn_local(send_from_url(
+            url,
+            data_sender.clone(),
+            info_sender.clone(),
+            "Failed loading data! Check that you metabolism.json is correct.",
+        ));
+    }
+    let mut ui_state = gui::UiState::default();
+    if let Some(condition) = url_param("condition") {
+        ui_state.condition = condition;
+    }
+
     // When building for WASM, print panics to the browser console
     console_error_panic_hook::set_once();
     let document = web_sys::window().unwrap().document().unwrap();
@@ -99,9 +135,57 @@ fn main() {
     target_data.set_id("fileData");
     target_data.set_class_name("fileData");
 
+    // button for pasting a map or data
+    let paste_button = document
+        .create_element("button")
+        .unwrap_throw()
+        .dyn_into::<web_sys::HtmlElement>()
+        .unwrap();
+    paste_button.set_inner_text("Paste");
+    paste_button.set_id("pasteb");
+    paste_button.set_class_name("pasteb");
+
     let body = document.body().unwrap();
     body.append_child(&target_map).unwrap();
     body.append_child(&target_data).unwrap();
+    body.append_child(&paste_button).unwrap();
+
+    let (paste_map, paste_data, paste_info) =
+        (map_sender.clone(), data_sender.clone(), info_sender.clone());
+    let paste_closure = Closure::wrap(Box::new(move |_: web_sys::Event| {
+        let (map_s, data_s, info_log) = (paste_map.clone(), paste_data.clone(), paste_info.clone());
+        spawn_local(async move {
+            let Some(clipboard) = web_sys::window().and_then(|w| w.navigator().clipboard()) else {
+                info_log
+                    .send("The clipboard is not available!")
+                    .await
+                    .unwrap();
+                return;
+            };
+            let Some(text) = JsFuture::from(clipboard.read_text())
+                .await
+                .ok()
+                .and_then(|text| text.as_string())
+            else {
+                info_log
+                    .send("Could not read the clipboard!")
+                    .await
+                    .unwrap();
+                return;
+            };
+            match data::sniff_json(text.as_bytes()) {
+                Ok(data::JsonFile::Map(escher_map)) => map_s.send(escher_map).await.unwrap(),
+                Ok(data::JsonFile::Data(data)) => data_s.send(data).await.unwrap(),
+                Err(e) => {
+                    console::warn_1(&format!("Pasted text is neither a map nor data: {e}").into());
+                    info_log
+                        .send("Failed pasting! The clipboard is not a map or data JSON.")
+                        .await
+                        .unwrap();
+                }
+            }
+        })
+    }) as Box<dyn FnMut(_)>);
 
     let map_closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
         let s = map_sender.clone();
@@ -160,6 +244,7 @@ fn main() {
     console::log_1(&"closure setup done!".into());
     target_map.set_onchange(Some(map_closure.as_ref().unchecked_ref()));
     target_data.set_onchange(Some(data_closure.as_ref().unchecked_ref()));
+    paste_button.set_onclick(Some(paste_closure.as_ref().unchecked_ref()));
 
     App::new()
         .insert_resource(Msaa::Sample4)
@@ -167,6 +252,9 @@ fn main() {
         .insert_resource(ReceiverResource { rx: map_receiver })
         .insert_resource(ReceiverResource { rx: data_receiver })
         .insert_resource(ReceiverResource { rx: info_receiver })
+        .insert_resource(ReceiverResource {
+            rx: gui::js_command_receiver(),
+        })
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "shu".to_string(),
@@ -179,45 +267,51 @@ fn main() {
         // plugins from dependencies
         .add_plugins((PanCamPlugin, ShapePlugin))
         // internal plugins
-        .add_plugins(screenshot::ScreenShotPlugin)
-        .add_plugins(info::InfoPlugin)
-        .add_plugins(EscherPlugin)
-        .add_plugins(gui::GuiPlugin)
-        .add_plugins(data::DataPlugin)
-        .add_systems(Startup, setup_system)
-        .add_plugins(aesthetics::AesPlugin)
-        .add_plugins(legend::LegendPlugin)
+        .add_plugins(ShuPlugins)
+        .insert_resource(ui_state)
         .run();
 }
 
-fn setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let escher_handle: Handle<EscherMap> = asset_server.load("ecoli_core_map.json");
-    commands.insert_resource(MapState {
-        escher_map: escher_handle,
-        loaded: false,
-    });
-    commands.insert_resource(data::ReactionState {
-        reaction_data: None,
-        loaded: false,
-    });
-    let fira: Handle<RawAsset> = asset_server.load("fonts/FiraSans-Bold.tttx");
-    let assis: Handle<RawAsset> = asset_server.load("fonts/Assistant-Regular.tttx");
-    commands.insert_resource(RawFontStorage { fira, assis });
+#[cfg(target_arch = "wasm32")]
+/// Fetch the text of the file at `url`.
+async fn fetch_text(url: &str) -> Result<String, wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let window = web_sys::window().ok_or("no window")?;
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(url))
+        .await?
+        .dyn_into()?;
+    if !response.ok() {
+        return Err(format!("{url} returned {}", response.status()).into());
+    }
+    JsFuture::from(response.text()?)
+        .await?
+        .as_string()
+        .ok_or_else(|| "the response is not text".into())
+}
 
-    commands
-        .spawn(Camera2dBundle {
-            camera: Camera {
-                clear_color: ClearColorConfig::Custom(Color::rgb(1., 1., 1.)),
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(PanCam {
-            grab_buttons: vec![MouseButton::Left], // which buttons should drag the camera
-            enabled: true, // when false, controls are disabled. See toggle example.
-            zoom_to_cursor: true, // whether to zoom towards the mouse or the center of the screen
-            min_scale: 1., // prevent the camera from zooming too far in
-            max_scale: Some(40.), // prevent the camera from zooming too far out
-            ..Default::default()
-        });
+#[cfg(target_arch = "wasm32")]
+/// Fetch the JSON file at `url` and send it to the app, notifying `failed` if
+/// it does not have the right shape.
+async fn send_from_url<T: serde::de::DeserializeOwned>(
+    url: String,
+    sender: async_std::channel::Sender<T>,
+    info_log: async_std::channel::Sender<&'static str>,
+    failed: &'static str,
+) {
+    match fetch_text(&url)
+        .await
+        .map(|text| serde_json::from_str(&text))
+    {
+        Ok(Ok(value)) => sender.send(value).await.unwrap(),
+        Ok(Err(_)) => info_log.send(failed).await.unwrap(),
+        Err(e) => {
+            web_sys::console::warn_1(&e);
+            info_log
+                .send("Failed fetching the file from the URL!")
+                .await
+                .unwrap();
+        }
+    }
 }
@@ -0,0 +1,233 @@
+//! Discovery of new features: a "What's new" window with the release notes
+//! and hints on the widgets of the features that the user has not tried yet.
+use crate::gui::UiState;
+use crate::theme::Theme;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// Version of the running app, compared against the last read release notes.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Feature announced in the [`RELEASE_NOTES`].
+pub struct Feature {
+    /// Identifier used to attach the hint to its widget.
+    pub id: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+}
+
+/// Features added in a version.
+pub struct ReleaseNotes {
+    pub version: &'static str,
+    pub features: &'static [Feature],
+}
+
+/// Notes of the releases, the newest first.
+pub const RELEASE_NOTES: &[ReleaseNotes] = &[ReleaseNotes {
+    version: "0.8.3",
+    features: &[
+        Feature {
+            id: "significance",
+            title: "Significance",
+            description: "Test the differences against the reference condition \
+                and mark them with asterisks.",
+        },
+        Feature {
+            id: "error_bars",
+            title: "Error bars",
+            description: "Summarize the distributions with the standard deviation, \
+                the standard error or a 95% interval.",
+        },
+        Feature {
+            id: "groups",
+            title: "Groups",
+            description: "Color the reactions by group or show a single group.",
+        },
+        Feature {
+            id: "coverage",
+            title: "Coverage",
+            description: "Fraction of the map with data in each condition.",
+        },
+        Feature {
+            id: "knockouts",
+            title: "Knockouts",
+            description: "Dash the reactions that carry no flux.",
+        },
+        Feature {
+            id: "units",
+            title: "Units",
+            description: "Write the units of the columns after the axis titles, \
+                the legend and the hover scales.",
+        },
+        Feature {
+            id: "number_format",
+            title: "Number format",
+            description: "Decimal comma and thousands grouping in the legend and the CSV.",
+        },
+        Feature {
+            id: "batch",
+            title: "Batch mode",
+            description: "Export figures from a JSON spec with `shu batch spec.json`.",
+        },
+    ],
+}];
+
+/// Numeric components of a version, to compare them.
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .split(['.', '-', '+'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+/// Order of two versions by their numeric components.
+pub fn cmp_versions(a: &str, b: &str) -> Ordering {
+    parse_version(a).cmp(&parse_version(b))
+}
+
+/// Release notes and hints that the user has already seen, remembered across sessions.
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct Onboarding {
+    /// Version whose release notes were last read, `None` on the first run.
+    pub seen_version: Option<String>,
+    /// Hints of features that were already shown.
+    pub seen_hints: HashSet<String>,
+    /// Hints first shown in this session, kept until the app is closed.
+    #[serde(skip)]
+    shown_hints: HashSet<String>,
+    /// Show the "What's new" window.
+    #[serde(skip)]
+    pub show_news: bool,
+}
+
+impl Onboarding {
+    /// Whether the feature was released after the last read release notes.
+    pub fn is_new(&self, feature: &str) -> bool {
+        RELEASE_NOTES
+            .iter()
+            .filter(|notes| {
+                self.seen_version
+                    .as_ref()
+                    .is_none_or(|seen| cmp_versions(notes.version, seen).is_gt())
+            })
+            .any(|notes| notes.features.iter().any(|f| f.id == feature))
+    }
+
+    /// Highlight the widget of a feature of the running version and describe
+    /// it on hover, until the hint has been shown once.
+    pub fn hint(&mut self, response: egui::Response, feature: &str) -> egui::Response {
+        if self.seen_hints.contains(feature) && !self.shown_hints.contains(feature) {
+            return response;
+        }
+        let Some(feature) = RELEASE_NOTES
+            .iter()
+            .filter(|notes| notes.version == VERSION)
+            .flat_map(|notes| notes.features)
+            .find(|f| f.id == feature)
+        else {
+            return response;
+        };
+        if response.hovered() && self.seen_hints.insert(feature.id.to_string()) {
+            self.shown_hints.insert(feature.id.to_string());
+            self.store();
+        }
+        let response = if self.shown_hints.contains(feature.id) {
+            response
+        } else {
+            response.highlight()
+        };
+        response.on_hover_text(format!("✨ New: {}", feature.description))
+    }
+
+    /// Mark the release notes of the running version as read.
+    pub fn read_news(&mut self) {
+        self.seen_version = Some(VERSION.to_string());
+        self.show_news = false;
+        self.store();
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn load() -> Self {
+        let mut onboarding = crate::gui::config_path("onboarding.json")
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice::<Self>(&bytes).ok())
+            .unwrap_or_default();
+        // only after an update, the first run has nothing to compare against
+        onboarding.show_news = onboarding
+            .seen_version
+            .as_ref()
+            .is_some_and(|seen| cmp_versions(VERSION, seen).is_gt());
+        onboarding
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn load() -> Self {
+        Self::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn store(&self) {
+        let Some(path) = crate::gui::config_path("onboarding.json") else {
+            return;
+        };
+        if let Err(e) = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| crate::gui::safe_json_write(&path, self))
+        {
+            warn!("Could not save the onboarding state: {e}");
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn store(&self) {}
+}
+
+/// Window with the release notes, the features that are new since the last
+/// read notes are marked.
+pub fn ui_news(
+    mut egui_context: EguiContexts,
+    mut onboarding: ResMut<Onboarding>,
+    state: Res<UiState>,
+    theme: Res<Theme>,
+) {
+    if state.hide || !onboarding.show_news {
+        return;
+    }
+    let mut open = true;
+    let mut read = false;
+    egui::Window::new("What's new")
+        .open(&mut open)
+        .collapsible(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            if theme.dark_ui {
+                ui.visuals_mut().override_text_color = Some(egui::Color32::WHITE);
+            }
+            egui::ScrollArea::vertical()
+                .max_height(400.)
+                .show(ui, |ui| {
+                    for notes in RELEASE_NOTES {
+                        ui.heading(format!("shu {}", notes.version));
+                        for feature in notes.features {
+                            let marker = if onboarding.is_new(feature.id) {
+                                "✨ "
+                            } else {
+                                ""
+                            };
+                            ui.label(
+                                egui::RichText::new(format!("{marker}{}", feature.title)).strong(),
+                            );
+                            ui.label(feature.description);
+                        }
+                        ui.separator();
+                    }
+                });
+            read = ui.button("Got it").clicked();
+        });
+    if read || !open {
+        onboarding.read_news();
+    }
+}
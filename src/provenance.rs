@@ -0,0 +1,156 @@
+//! Where the map and the data on screen come from, shown in the "About this
+//! map" window and stored in the saved maps and the SVG exports.
+use crate::data::ReactionState;
+use crate::escher::{EscherInfo, EscherMap, MapState};
+use crate::gui::UiState;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use chrono::{offset::Utc, SecondsFormat};
+use serde::{Deserialize, Serialize};
+
+pub struct ProvenancePlugin;
+
+impl Plugin for ProvenancePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Provenance>()
+            .add_systems(Update, track_provenance);
+    }
+}
+
+/// Metadata of the displayed map, the datasets plotted on it and when they
+/// were loaded.
+#[derive(Resource, Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Provenance {
+    /// Information of the escher map.
+    pub map: EscherInfo,
+    /// Path or name of the map.
+    pub map_file: String,
+    /// Paths or names of the active datasets, in the order they are plotted.
+    pub datasets: Vec<String>,
+    /// UTC time when the map or the data last changed, in RFC 3339.
+    pub loaded: String,
+    /// Version of shu that plotted them.
+    pub shu_version: String,
+}
+
+impl Provenance {
+    /// Provenance as a `<metadata>` element to be added to an SVG.
+    pub fn svg_metadata(&self) -> String {
+        let json = serde_json::to_string_pretty(self).unwrap_or_default();
+        let escaped = json
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        format!("<metadata id=\"shu-provenance\">{escaped}</metadata>")
+    }
+}
+
+/// Add the [`Provenance`] as the first child of the root element of an SVG
+/// that was already written to `path`.
+pub fn embed_in_svg(path: &str, provenance: &Provenance) -> std::io::Result<()> {
+    let svg = std::fs::read_to_string(path)?;
+    let Some(root_end) = svg
+        .find("<svg")
+        .and_then(|start| svg[start..].find('>').map(|end| start + end + 1))
+    else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "no <svg> element",
+        ));
+    };
+    let mut embedded = String::with_capacity(svg.len() + 512);
+    embedded.push_str(&svg[..root_end]);
+    embedded.push_str(&provenance.svg_metadata());
+    embedded.push_str(&svg[root_end..]);
+    std::fs::write(path, embedded)
+}
+
+/// Update the [`Provenance`] once a map or a set of datasets is loaded.
+fn track_provenance(
+    map_state: Option<Res<MapState>>,
+    reaction_state: Option<Res<ReactionState>>,
+    maps: Res<Assets<EscherMap>>,
+    mut provenance: ResMut<Provenance>,
+) {
+    let (Some(map_state), Some(reaction_state)) = (map_state, reaction_state) else {
+        return;
+    };
+    if !(map_state.is_changed() || reaction_state.is_changed()) {
+        return;
+    }
+    let map = maps
+        .get(map_state.escher_map())
+        .map(|map| map.info.clone())
+        .unwrap_or_default();
+    let map_file = map_state.maps[map_state.current].name.clone();
+    let datasets: Vec<String> = reaction_state
+        .datasets
+        .iter()
+        .filter(|dataset| dataset.active)
+        .map(|dataset| dataset.name.clone())
+        .collect();
+    if (&provenance.map, &provenance.map_file, &provenance.datasets) == (&map, &map_file, &datasets)
+    {
+        return;
+    }
+    *provenance = Provenance {
+        map,
+        map_file,
+        datasets,
+        loaded: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        shu_version: String::from(env!("CARGO_PKG_VERSION")),
+    };
+}
+
+/// Window with the [`Provenance`] of what is on screen.
+pub fn ui_about(
+    mut egui_context: EguiContexts,
+    mut state: ResMut<UiState>,
+    provenance: Option<Res<Provenance>>,
+) {
+    let Some(provenance) = provenance.filter(|_| !state.hide && state.show_about) else {
+        return;
+    };
+    egui::Window::new("About this map")
+        .open(&mut state.show_about)
+        .show(egui_context.ctx_mut(), |ui| {
+            let map = &provenance.map;
+            egui::Grid::new("about_grid").num_columns(2).show(ui, |ui| {
+                for (label, value) in [
+                    ("Map", map.map_name.as_str()),
+                    ("Identifier", map.map_id.as_str()),
+                    ("File", provenance.map_file.as_str()),
+                    ("Schema", map.schema.as_str()),
+                ] {
+                    ui.label(label);
+                    ui.label(value);
+                    ui.end_row();
+                }
+                ui.label("Homepage");
+                ui.hyperlink(&map.homepage);
+                ui.end_row();
+                ui.label("Data");
+                ui.vertical(|ui| {
+                    if provenance.datasets.is_empty() {
+                        ui.label("none");
+                    }
+                    for dataset in provenance.datasets.iter() {
+                        ui.label(dataset);
+                    }
+                });
+                ui.end_row();
+                ui.label("Loaded");
+                ui.label(&provenance.loaded);
+                ui.end_row();
+                ui.label("shu");
+                ui.label(&provenance.shu_version);
+                ui.end_row();
+            });
+            if !map.map_description.is_empty() {
+                ui.separator();
+                ui.label(&map.map_description);
+            }
+            ui.separator();
+            ui.label("Stored in the saved maps and the SVG images.");
+        });
+}
@@ -0,0 +1,101 @@
+//! Receive maps and data over HTTP, e.g., pushed from a running simulation at
+//! every iteration.
+//!
+//! Not available in WASM since it cannot listen to connections.
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+use crate::data::{sniff_json, Data, JsonFile, ReactionState};
+use crate::escher::{EscherMap, MapState};
+use crate::info::Info;
+use bevy::prelude::*;
+
+/// Address listened to if `SHU_REMOTE` is not set.
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8421";
+
+pub struct RemotePlugin;
+
+impl Plugin for RemotePlugin {
+    fn build(&self, app: &mut App) {
+        let address = std::env::var("SHU_REMOTE").unwrap_or_else(|_| DEFAULT_ADDRESS.to_string());
+        let (tx, rx) = channel();
+        match tiny_http::Server::http(&address) {
+            Ok(server) => {
+                info!("Listening for maps and data at http://{address}");
+                std::thread::spawn(move || serve(server, tx));
+            }
+            Err(e) => warn!("Could not listen at {address}: {e}"),
+        }
+        app.insert_resource(RemoteReceiver(Mutex::new(rx)))
+            .add_systems(Update, receive_remote);
+    }
+}
+
+/// Files received by the server with the name of the dataset or map.
+#[derive(Resource)]
+struct RemoteReceiver(Mutex<Receiver<(String, JsonFile)>>);
+
+/// Answer POST requests whose body is a map or data file, named after the path
+/// of the URL (e.g., `POST /fluxes`), which replace the ones with the same name.
+fn serve(server: tiny_http::Server, tx: Sender<(String, JsonFile)>) {
+    for mut request in server.incoming_requests() {
+        if *request.method() != tiny_http::Method::Post {
+            let response =
+                tiny_http::Response::from_string("Only POST is supported").with_status_code(405);
+            request.respond(response).ok();
+            continue;
+        }
+        let name = request
+            .url()
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .trim_matches('/')
+            .to_string();
+        let name = if name.is_empty() {
+            String::from("remote")
+        } else {
+            name
+        };
+        let mut body = Vec::new();
+        let file = request
+            .as_reader()
+            .read_to_end(&mut body)
+            .map_err(|e| e.to_string())
+            .and_then(|_| sniff_json(&body).map_err(|e| e.to_string()));
+        let response = match file {
+            Ok(file) => {
+                if tx.send((name, file)).is_err() {
+                    // the app was closed
+                    return;
+                }
+                tiny_http::Response::from_string("ok")
+            }
+            Err(e) => tiny_http::Response::from_string(e).with_status_code(400),
+        };
+        request.respond(response).ok();
+    }
+}
+
+/// Add the received files like dropped ones.
+fn receive_remote(
+    receiver: Res<RemoteReceiver>,
+    mut info_state: ResMut<Info>,
+    mut data_assets: ResMut<Assets<Data>>,
+    mut escher_assets: ResMut<Assets<EscherMap>>,
+    mut reaction_resource: ResMut<ReactionState>,
+    mut escher_resource: ResMut<MapState>,
+) {
+    let Ok(rx) = receiver.0.lock() else {
+        return;
+    };
+    for (name, file) in rx.try_iter() {
+        match file {
+            JsonFile::Data(data) => reaction_resource.add(name, data_assets.add(data)),
+            JsonFile::Map(escher_map) => {
+                escher_resource.open(name, escher_assets.add(escher_map));
+                info_state.notify("Loading map...");
+            }
+        }
+    }
+}
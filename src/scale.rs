@@ -1,13 +1,25 @@
 //! Module to handle dynamic scaling on zoom.
+use crate::escher::{ArrowPart, ArrowTag, MapLabel, Secondary, SecondaryStyle};
 use crate::funcplot::lerp;
+use crate::gui::UiState;
 use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowScaleFactorChanged};
+use bevy_prototype_lyon::prelude::Stroke;
 
 /// Constant that matches bevy_pancman Line pixel increment
 pub struct ZoomPlugin;
 
 impl Plugin for ZoomPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, zoom_fonts);
+        app.add_systems(
+            Update,
+            (
+                zoom_fonts,
+                refresh_ui_text,
+                level_of_detail,
+                style_arrow_parts,
+            ),
+        );
     }
 }
 
@@ -17,20 +29,192 @@ pub struct DefaultFontSize {
 }
 
 /// Rerender fonts on zoom to achieve a constantly-readable size.
+///
+/// Sizes are rounded to whole physical pixels so that glyphs are rasterized
+/// crisply. They are recomputed when the scale factor of the window changes
+/// (e.g., when it is moved to a HiDPI monitor) or when the size of the
+/// labels or of all the texts is changed in the settings.
 fn zoom_fonts(
-    mut text_query: Query<(&mut Text, &DefaultFontSize)>,
+    ui_state: Res<UiState>,
+    mut label_size: Local<Option<(f32, f32)>>,
+    mut text_query: Query<(&mut Text, &DefaultFontSize, Has<MapLabel>)>,
     proj_query: Query<&OrthographicProjection, (Changed<Transform>, Without<DefaultFontSize>)>,
+    any_proj_query: Query<&OrthographicProjection, Without<DefaultFontSize>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut scale_events: EventReader<WindowScaleFactorChanged>,
 ) {
-    let Ok(proj) = proj_query.get_single() else {
+    let sizes = (ui_state.label_size, ui_state.text_scale);
+    let resized = label_size.replace(sizes) != Some(sizes);
+    let dpi_changed = scale_events.read().count() > 0;
+    let proj = if dpi_changed | resized {
+        any_proj_query.get_single()
+    } else {
+        proj_query.get_single()
+    };
+    let Ok(proj) = proj else {
         return;
     };
-    for (mut text, def) in text_query.iter_mut() {
+    let scale_factor = windows
+        .get_single()
+        .map(|win| win.scale_factor())
+        .unwrap_or(1.);
+    for (mut text, def, is_label) in text_query.iter_mut() {
+        let size = if is_label {
+            def.size * ui_state.label_size
+        } else {
+            def.size
+        } * ui_state.text_scale;
         for section in text.sections.iter_mut() {
-            let new_font_size = lerp(proj.scale, 1., 40., def.size, def.size * 10.);
+            let new_font_size = lerp(proj.scale, 1., 40., size, size * 10.);
+            let new_font_size = (new_font_size * scale_factor).round() / scale_factor;
             // step update to enhance perfomance
-            if (new_font_size - section.style.font_size).abs() > 1.0 {
+            if dpi_changed | resized | ((new_font_size - section.style.font_size).abs() > 1.0) {
                 section.style.font_size = new_font_size;
             }
         }
     }
 }
+
+/// Re-layout the text of the legend (and any other UI text) at the new
+/// resolution when the scale factor of the window changes.
+fn refresh_ui_text(
+    mut scale_events: EventReader<WindowScaleFactorChanged>,
+    mut text_query: Query<&mut Text, Without<DefaultFontSize>>,
+) {
+    if scale_events.read().count() == 0 {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.set_changed();
+    }
+}
+
+/// Arrows thinner than this are hidden when zoomed out.
+const THIN_WIDTH: f32 = 8.;
+/// Tessellation tolerance of arrows when zoomed out (lyon's default is 0.1).
+const COARSE_TOLERANCE: f32 = 2.;
+
+/// Hide secondary metabolites, labels and thin arrows and simplify the
+/// tessellation of the arrows when zoomed out over [`UiState::lod_threshold`].
+/// Labels and secondary metabolites are also hidden when they are disabled
+/// in the settings, as well as the reactions filtered out by the pasted list
+/// of reactions (see [`UiState::hides_reaction`]).
+///
+/// The entities are only modified when the level of detail changes or when
+/// a new map is loaded.
+fn level_of_detail(
+    ui_state: Res<UiState>,
+    mut coarse: Local<bool>,
+    proj_query: Query<&OrthographicProjection>,
+    new_map: Query<(), Added<ArrowTag>>,
+    mut secondary_query: Query<
+        (&mut Visibility, Option<&ArrowPart>),
+        (With<Secondary>, Without<MapLabel>),
+    >,
+    // labels of reactions also have an ArrowTag but no Stroke
+    mut text_query: Query<
+        (&mut Visibility, Has<Secondary>, Option<&ArrowTag>),
+        (With<MapLabel>, Without<Stroke>),
+    >,
+    mut arrow_query: Query<(Entity, &mut Stroke, &mut Visibility, &ArrowTag), Without<Secondary>>,
+) {
+    let Ok(proj) = proj_query.get_single() else {
+        return;
+    };
+    let should_coarse = ui_state.lod & (proj.scale > ui_state.lod_threshold);
+    if (should_coarse == *coarse) & !ui_state.is_changed() & new_map.is_empty() {
+        return;
+    }
+    *coarse = should_coarse;
+    let vis = if should_coarse {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+    let secondary_vis = if ui_state.secondary == SecondaryStyle::Hide {
+        Visibility::Hidden
+    } else {
+        vis
+    };
+    let filtered: Vec<Entity> = arrow_query
+        .iter()
+        .filter(|(_, _, _, arrow)| ui_state.hides_reaction(&arrow.id))
+        .map(|(e, _, _, _)| e)
+        .collect();
+    for (mut visibility, part) in secondary_query.iter_mut() {
+        *visibility = if part.is_some_and(|part| filtered.contains(&part.arrow)) {
+            Visibility::Hidden
+        } else {
+            secondary_vis
+        };
+    }
+    for (mut visibility, is_secondary, arrow) in text_query.iter_mut() {
+        *visibility = match (ui_state.labels, is_secondary) {
+            (false, _) => Visibility::Hidden,
+            _ if arrow.is_some_and(|arrow| ui_state.hides_reaction(&arrow.id)) => {
+                Visibility::Hidden
+            }
+            (true, true) => secondary_vis,
+            (true, false) => vis,
+        };
+    }
+    for (entity, mut stroke, mut visibility, _) in arrow_query.iter_mut() {
+        let tolerance = if should_coarse {
+            COARSE_TOLERANCE
+        } else {
+            lyon_default_tolerance()
+        };
+        if stroke.options.tolerance != tolerance {
+            stroke.options.tolerance = tolerance;
+        }
+        *visibility = if (should_coarse & (stroke.options.line_width < THIN_WIDTH))
+            | filtered.contains(&entity)
+        {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
+
+/// Scale of the secondary metabolites and their segments when shrunk.
+const SHRINK_FACTOR: f32 = 0.5;
+
+/// Shrink secondary metabolites if requested and style the parts of the arrows
+/// (e.g., segments of secondary metabolites) as their reaction (size and color aesthetics).
+fn style_arrow_parts(
+    ui_state: Res<UiState>,
+    arrows: Query<&Stroke, (With<ArrowTag>, Without<ArrowPart>)>,
+    mut parts: Query<(&mut Stroke, &ArrowPart, Has<Secondary>)>,
+    mut nodes: Query<&mut Transform, (With<Secondary>, Without<ArrowPart>)>,
+) {
+    let factor = if ui_state.secondary == SecondaryStyle::Shrink {
+        SHRINK_FACTOR
+    } else {
+        1.
+    };
+    for (mut stroke, part, is_secondary) in parts.iter_mut() {
+        let Ok(arrow) = arrows.get(part.arrow) else {
+            continue;
+        };
+        let width = if is_secondary {
+            arrow.options.line_width * factor
+        } else {
+            arrow.options.line_width
+        };
+        // only touch the stroke when needed, since it triggers a new tessellation
+        if (stroke.color != arrow.color) | (stroke.options.line_width != width) {
+            stroke.color = arrow.color;
+            stroke.options.line_width = width;
+        }
+    }
+    for mut trans in nodes.iter_mut() {
+        if trans.scale.x != factor {
+            trans.scale = Vec3::new(factor, factor, 1.);
+        }
+    }
+}
+
+fn lyon_default_tolerance() -> f32 {
+    Stroke::color(Color::BLACK).options.tolerance
+}
@@ -1,10 +1,13 @@
 use crate::{
     escher::MapDimensions,
+    font::MapFont,
     funcplot::IgnoreSave,
     geom::Drag,
     gui::UiState,
     info::Info,
-    legend::{Xmax, Xmin},
+    legend::{GradientMaterial, Xmax, Xmin},
+    provenance::{embed_in_svg, Provenance},
+    theme::Theme,
 };
 use bevy::{asset::AsyncReadExt, window::PrimaryWindow};
 use bevy::{
@@ -15,8 +18,19 @@ use bevy::{
 use bevy::{reflect::TypePath, render::view::screenshot::ScreenshotManager};
 use bevy_prototype_lyon::prelude::{Fill, Path, Stroke};
 
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::render::{
+    camera::RenderTarget,
+    extract_resource::{ExtractResource, ExtractResourcePlugin},
+    render_asset::{RenderAssetUsages, RenderAssets},
+    render_resource::{Extent3d, TextureDimension},
+    renderer::{RenderDevice, RenderQueue},
+    view::RenderLayers,
+    Render, RenderApp, RenderSet,
+};
 use image::ImageFormat;
 use serde::Deserialize;
+use std::sync::{Arc, Mutex};
 
 pub struct ScreenShotPlugin;
 
@@ -24,6 +38,9 @@ impl Plugin for ScreenShotPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<ScreenshotEvent>()
             .add_event::<SvgScreenshotEvent>()
+            .add_event::<TiledScreenshotEvent>()
+            .add_event::<LegendExportEvent>()
+            .add_event::<AnimationExportEvent>()
             .init_asset::<RawAsset>()
             .init_asset_loader::<RawAssetLoader>()
             .add_systems(Startup, setup_timer)
@@ -32,8 +49,34 @@ impl Plugin for ScreenShotPlugin {
                 (
                     screenshot_on_event.before(crate::gui::ui_settings),
                     save_svg_file,
+                    start_tiled_export,
+                    tiled_export.after(start_tiled_export),
+                    start_animation_export,
+                    animation_export.after(start_animation_export),
                 ),
             );
+
+        // the legend is only written to files on native
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            app.add_plugins(ExtractResourcePlugin::<LegendExport>::default())
+                .add_systems(
+                    Update,
+                    (
+                        save_legend_svg,
+                        start_legend_export,
+                        legend_export.after(start_legend_export),
+                    ),
+                );
+            if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+                render_app.add_systems(
+                    Render,
+                    copy_legend
+                        .after(RenderSet::Render)
+                        .before(RenderSet::Cleanup),
+                );
+            }
+        }
     }
 }
 
@@ -47,6 +90,32 @@ pub struct SvgScreenshotEvent {
     pub file_path: String,
 }
 
+/// Export one raster screenshot per condition arranged in a grid, with the
+/// condition as title of each panel and the legend in a cell of its own.
+#[derive(Event)]
+pub struct TiledScreenshotEvent {
+    pub path: String,
+}
+
+/// Export only the legend, as an SVG or as a raster image rendered off-screen
+/// at `scale` times its size in the window.
+#[derive(Event)]
+pub struct LegendExportEvent {
+    pub path: String,
+    pub scale: f32,
+}
+
+/// Export the map cycling through the conditions (e.g., time points) as an
+/// animated GIF or, for other extensions, as numbered PNG frames.
+#[derive(Event)]
+pub struct AnimationExportEvent {
+    pub path: String,
+    /// Frames per second of the GIF.
+    pub fps: f32,
+    /// Width of the frames in pixels, 0 to keep the size of the window.
+    pub width: u32,
+}
+
 #[derive(Component, Deref, DerefMut)]
 struct HideUiTimer(Timer);
 
@@ -62,6 +131,7 @@ fn screenshot_on_event(
     mut info_state: ResMut<Info>,
     mut screenshot_manager: ResMut<ScreenshotManager>,
     main_window: Query<Entity, With<PrimaryWindow>>,
+    windows: Query<Entity, With<Window>>,
     mut timer: Query<&mut HideUiTimer>,
     mut counter: Local<u32>,
 ) {
@@ -89,7 +159,12 @@ fn screenshot_on_event(
         info!("Writing raster imag...");
         let path = format!("{path}{suffix}");
         *counter += 1;
-        if let Err(e) = screenshot_manager.save_screenshot_to_disk(main_window.single(), path) {
+        // the primary window may be closed while other windows are still open
+        let Some(window) = main_window.iter().chain(windows.iter()).next() else {
+            error!("There is no window to take a screenshot of");
+            continue;
+        };
+        if let Err(e) = screenshot_manager.save_screenshot_to_disk(window, path) {
             error!("Format not supported, try PNG, JPEG, BMP or TGA: {e}")
         }
     }
@@ -162,7 +237,10 @@ fn save_svg_file(
     legend_query: Query<(&GlobalTransform, &Node), With<Drag>>,
     legend_node_query: Query<(Entity, &GlobalTransform, &Style, &Children)>,
     img_query: Query<(&UiImage, &Node)>,
+    gradient_query: Query<(&Handle<GradientMaterial>, &Node)>,
+    materials: Res<Assets<GradientMaterial>>,
     legend_text_query: Query<(&Text, &GlobalTransform, &Style, &Node), Without<IgnoreSave>>,
+    provenance: Option<Res<Provenance>>,
 ) {
     for SvgScreenshotEvent { file_path } in save_events.read() {
         let RawAsset { value: fira } = raw_fonts.get(&fonts_storage.fira).unwrap();
@@ -254,78 +332,14 @@ fn save_svg_file(
             // legend is tricky because the reflection point is not the origin of each
             // element, all the legend itself. Thus, everything is added to a group node
             // which is then reflected.
-            let mut legend_nodes = Vec::new();
-            for (_parent, trans, style, children) in &legend_node_query {
-                if style.display == Display::None {
-                    continue;
-                }
-                for child in children.iter() {
-                    if let Ok((img_legend, ui_node)) = img_query.get(*child) {
-                        let img = images.get(&img_legend.texture).unwrap();
-                        let Ok(img) = img.clone().try_into_dynamic() else {
-                            continue;
-                        };
-                        let mut img_buffer = Vec::<u8>::new();
-                        img.write_to(&mut std::io::Cursor::new(&mut img_buffer), ImageFormat::Png)
-                            .unwrap();
-                        let trans = trans.compute_transform();
-                        legend_nodes.push(
-                            roarsvg::create_png_node(
-                                &img_buffer,
-                                roarsvg::SvgTransform::from_translate(
-                                    trans.translation.x - ui_node.size().x / 2.,
-                                    trans.translation.y - ui_node.size().y / 2.,
-                                ),
-                                ui_node.size().x,
-                                ui_node.size().y,
-                            )
-                            .unwrap(),
-                        );
-                    } else if let Ok((text, child_trans, vis, ui_node)) =
-                        legend_text_query.get(*child)
-                    {
-                        if Display::None == vis.display {
-                            continue;
-                        }
-                        let paragraph = text
-                            .sections
-                            .iter()
-                            .map(|ts| &ts.value)
-                            .fold(String::from(""), |acc, x| acc + x.as_str());
-                        if paragraph.is_empty() {
-                            continue;
-                        }
-                        let Some((font_size, _font, color)) = text
-                            .sections
-                            .iter()
-                            .map(|tx| (tx.style.font_size, &tx.style.font, tx.style.color))
-                            .next()
-                        else {
-                            continue;
-                        };
-                        let fill: [u8; 4] = color.as_rgba_u8();
-                        let trans = child_trans.compute_transform();
-                        legend_nodes.push(
-                            roarsvg::create_text_node(
-                                paragraph,
-                                roarsvg::SvgTransform::from_translate(
-                                    // I think this has to do with padding and margins
-                                    trans.translation.x - ui_node.size().x / 1.5,
-                                    trans.translation.y + ui_node.size().y / 2.8,
-                                ),
-                                Some(roarsvg::fill(
-                                    roarsvg::Color::new_rgb(fill[0], fill[1], fill[2]),
-                                    color.a(),
-                                )),
-                                None,
-                                vec![String::from("Assistant"), String::from("Regular")],
-                                font_size,
-                            )
-                            .unwrap(),
-                        );
-                    }
-                }
-            }
+            let legend_nodes = legend_svg_nodes(
+                &images,
+                &legend_node_query,
+                &img_query,
+                &gradient_query,
+                &materials,
+                &legend_text_query,
+            );
             if !legend_nodes.is_empty() {
                 writer
                     // undo the scaling done on the whole SVG only for the legend
@@ -344,7 +358,759 @@ fn save_svg_file(
             Err(e) => {
                 info_state.notify("Error writing SVG!");
                 info!("{:?}", e);
+                continue;
+            }
+        }
+        if let Some(provenance) = provenance.as_deref() {
+            if let Err(e) = embed_in_svg(file_path, provenance) {
+                warn!("Could not add the provenance to the SVG: {e}");
+            }
+        }
+    }
+}
+
+/// Images and texts of the visible legend, in the coordinates of the screen
+/// (y pointing down), to be added as a group to an SVG.
+fn legend_svg_nodes(
+    images: &Assets<Image>,
+    legend_node_query: &Query<(Entity, &GlobalTransform, &Style, &Children)>,
+    img_query: &Query<(&UiImage, &Node)>,
+    gradient_query: &Query<(&Handle<GradientMaterial>, &Node)>,
+    materials: &Assets<GradientMaterial>,
+    legend_text_query: &Query<(&Text, &GlobalTransform, &Style, &Node), Without<IgnoreSave>>,
+) -> Vec<roarsvg::NodeKind> {
+    let mut legend_nodes = Vec::new();
+    for (_parent, trans, style, children) in legend_node_query.iter() {
+        if style.display == Display::None {
+            continue;
+        }
+        for child in children.iter() {
+            // the gradients are painted by a shader, so they are baked here
+            let image = if let Ok((img_legend, ui_node)) = img_query.get(*child) {
+                images
+                    .get(&img_legend.texture)
+                    .map(|img| (img.clone(), ui_node))
+            } else if let Ok((handle, ui_node)) = gradient_query.get(*child) {
+                materials.get(handle).and_then(|material| {
+                    Some((material.bake(images.get(&material.mask)?), ui_node))
+                })
+            } else {
+                None
+            };
+            if let Some((img, ui_node)) = image {
+                let Ok(img) = img.try_into_dynamic() else {
+                    continue;
+                };
+                let mut img_buffer = Vec::<u8>::new();
+                img.write_to(&mut std::io::Cursor::new(&mut img_buffer), ImageFormat::Png)
+                    .unwrap();
+                let trans = trans.compute_transform();
+                legend_nodes.push(
+                    roarsvg::create_png_node(
+                        &img_buffer,
+                        roarsvg::SvgTransform::from_translate(
+                            trans.translation.x - ui_node.size().x / 2.,
+                            trans.translation.y - ui_node.size().y / 2.,
+                        ),
+                        ui_node.size().x,
+                        ui_node.size().y,
+                    )
+                    .unwrap(),
+                );
+            } else if let Ok((text, child_trans, vis, ui_node)) = legend_text_query.get(*child) {
+                if Display::None == vis.display {
+                    continue;
+                }
+                let paragraph = text
+                    .sections
+                    .iter()
+                    .map(|ts| &ts.value)
+                    .fold(String::from(""), |acc, x| acc + x.as_str());
+                if paragraph.is_empty() {
+                    continue;
+                }
+                let Some((font_size, _font, color)) = text
+                    .sections
+                    .iter()
+                    .map(|tx| (tx.style.font_size, &tx.style.font, tx.style.color))
+                    .next()
+                else {
+                    continue;
+                };
+                let fill: [u8; 4] = color.as_rgba_u8();
+                let trans = child_trans.compute_transform();
+                legend_nodes.push(
+                    roarsvg::create_text_node(
+                        paragraph,
+                        roarsvg::SvgTransform::from_translate(
+                            // I think this has to do with padding and margins
+                            trans.translation.x - ui_node.size().x / 1.5,
+                            trans.translation.y + ui_node.size().y / 2.8,
+                        ),
+                        Some(roarsvg::fill(
+                            roarsvg::Color::new_rgb(fill[0], fill[1], fill[2]),
+                            color.a(),
+                        )),
+                        None,
+                        vec![String::from("Assistant"), String::from("Regular")],
+                        font_size,
+                    )
+                    .unwrap(),
+                );
+            }
+        }
+    }
+    legend_nodes
+}
+
+/// Write the legend alone to an SVG, with its top left corner at the origin.
+#[cfg(not(target_arch = "wasm32"))]
+fn save_legend_svg(
+    mut events: EventReader<LegendExportEvent>,
+    mut info_state: ResMut<Info>,
+    images: Res<Assets<Image>>,
+    fonts_storage: Res<RawFontStorage>,
+    raw_fonts: Res<Assets<RawAsset>>,
+    legend_query: Query<(&GlobalTransform, &Node), With<Drag>>,
+    legend_node_query: Query<(Entity, &GlobalTransform, &Style, &Children)>,
+    img_query: Query<(&UiImage, &Node)>,
+    gradient_query: Query<(&Handle<GradientMaterial>, &Node)>,
+    materials: Res<Assets<GradientMaterial>>,
+    legend_text_query: Query<(&Text, &GlobalTransform, &Style, &Node), Without<IgnoreSave>>,
+) {
+    for LegendExportEvent { path, scale } in events.read() {
+        if !path.ends_with("svg") {
+            continue;
+        }
+        let Ok((legend_trans, legend_root)) = legend_query.get_single() else {
+            info_state.notify("There is no legend to export!");
+            continue;
+        };
+        let legend_nodes = legend_svg_nodes(
+            &images,
+            &legend_node_query,
+            &img_query,
+            &gradient_query,
+            &materials,
+            &legend_text_query,
+        );
+        if legend_nodes.is_empty() {
+            info_state.notify("There is no legend to export!");
+            continue;
+        }
+        let RawAsset { value: assis } = raw_fonts.get(&fonts_storage.assis).unwrap();
+        let corner = legend_trans.translation().truncate() - legend_root.size() / 2.;
+        let mut writer = roarsvg::LyonWriter::new().add_fonts_source(assis);
+        writer
+            .push_group(
+                legend_nodes,
+                roarsvg::SvgTransform::from_translate(-corner.x, -corner.y)
+                    .post_scale(*scale, *scale),
+            )
+            .unwrap();
+        match writer.write(path) {
+            Ok(_) => info_state.notify("Legend written"),
+            Err(e) => {
+                info_state.notify("Error writing legend!");
+                info!("{:?}", e);
+            }
+        }
+    }
+}
+
+/// State of an ongoing raster [`LegendExportEvent`]: the legend is moved to
+/// a camera that renders to an image, which is copied back from the GPU.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource, Clone, ExtractResource)]
+pub struct LegendExport {
+    path: String,
+    image: Handle<Image>,
+    size: UVec2,
+    camera: Entity,
+    legend: Entity,
+    /// Frames left for the legend to be laid out at the new scale.
+    wait: u32,
+    /// Copy the image in the next render.
+    capture: bool,
+    pixels: Arc<Mutex<Option<Vec<u8>>>>,
+    /// Position of the legend in the window and scale of the UI, restored afterwards.
+    restore: (Val, Val, f32),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn start_legend_export(
+    mut commands: Commands,
+    mut events: EventReader<LegendExportEvent>,
+    mut info_state: ResMut<Info>,
+    mut images: ResMut<Assets<Image>>,
+    mut ui_scale: ResMut<UiScale>,
+    mut legend_query: Query<(Entity, &Node, &mut Style), With<Drag>>,
+    ongoing: Option<Res<LegendExport>>,
+) {
+    for LegendExportEvent { path, scale } in events.read() {
+        if path.ends_with("svg") || ongoing.is_some() {
+            continue;
+        }
+        let Ok((legend, node, mut style)) = legend_query.get_single_mut() else {
+            info_state.notify("There is no legend to export!");
+            continue;
+        };
+        // the size of the legend without the scale of the UI
+        let size = (node.size() / ui_scale.0 * *scale).ceil().as_uvec2();
+        if size.cmpeq(UVec2::ZERO).any() {
+            info_state.notify("There is no legend to export!");
+            continue;
+        }
+        let image = crate::headless::render_target(&mut images, size);
+        let camera = commands
+            .spawn((
+                Camera2dBundle {
+                    camera: Camera {
+                        target: RenderTarget::Image(image.clone()),
+                        order: 1,
+                        clear_color: ClearColorConfig::Custom(Color::WHITE),
+                        ..default()
+                    },
+                    ..default()
+                },
+                // only the legend, not the map
+                RenderLayers::none(),
+            ))
+            .id();
+        commands.entity(legend).insert(TargetCamera(camera));
+        let restore = (style.left, style.bottom, ui_scale.0);
+        style.left = Val::Px(0.);
+        style.bottom = Val::Px(0.);
+        ui_scale.0 = *scale;
+        let path = if path.split('.').count() >= 2 {
+            path.clone()
+        } else {
+            format!("{path}.png")
+        };
+        info_state.notify("Writing legend...");
+        commands.insert_resource(LegendExport {
+            path,
+            image,
+            size,
+            camera,
+            legend,
+            wait: SETTLE_FRAMES,
+            capture: false,
+            pixels: Arc::new(Mutex::new(None)),
+            restore,
+        });
+    }
+}
+
+/// Write the legend once it has been copied from the GPU and put it back in the window.
+#[cfg(not(target_arch = "wasm32"))]
+fn legend_export(
+    mut commands: Commands,
+    export: Option<ResMut<LegendExport>>,
+    mut info_state: ResMut<Info>,
+    mut ui_scale: ResMut<UiScale>,
+    mut legend_query: Query<&mut Style, With<Drag>>,
+) {
+    let Some(mut export) = export else {
+        return;
+    };
+    if export.wait > 0 {
+        export.wait -= 1;
+        export.capture = export.wait == 0;
+        return;
+    }
+    let Some(pixels) = export
+        .pixels
+        .lock()
+        .ok()
+        .and_then(|mut pixels| pixels.take())
+    else {
+        return;
+    };
+    let image = Image::new(
+        Extent3d {
+            width: export.size.x,
+            height: export.size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        pixels,
+        crate::headless::TEXTURE_FORMAT,
+        RenderAssetUsages::default(),
+    );
+    match image
+        .try_into_dynamic()
+        .map(|img| img.to_rgb8().save(&export.path))
+    {
+        Ok(Ok(_)) => info_state.notify("Legend written"),
+        Ok(Err(e)) => {
+            error!("Format not supported, try PNG, JPEG, BMP or TGA: {e}");
+            info_state.notify("Error writing legend!");
+        }
+        Err(e) => {
+            error!("Could not convert the legend: {e}");
+            info_state.notify("Error writing legend!");
+        }
+    }
+    let (left, bottom, scale) = export.restore;
+    if let Ok(mut style) = legend_query.get_mut(export.legend) {
+        style.left = left;
+        style.bottom = bottom;
+    }
+    ui_scale.0 = scale;
+    commands.entity(export.legend).remove::<TargetCamera>();
+    commands.entity(export.camera).despawn();
+    commands.remove_resource::<LegendExport>();
+}
+
+/// Copy the off-screen legend to the main world when requested.
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_legend(
+    export: Option<Res<LegendExport>>,
+    gpu_images: Res<RenderAssets<Image>>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+) {
+    let Some(export) = export.filter(|e| e.capture) else {
+        return;
+    };
+    let Some(gpu_image) = gpu_images.get(&export.image) else {
+        return;
+    };
+    *export.pixels.lock().unwrap() =
+        Some(crate::headless::read_texture(gpu_image, &device, &queue));
+}
+
+/// Frames to wait after changing the condition so that the plots are updated.
+pub const SETTLE_FRAMES: u32 = 4;
+
+/// One screenshot per condition, each taken once the plots are updated to
+/// the condition. Shared by the tiled and the animation exports.
+struct ConditionSweep {
+    conditions: Vec<String>,
+    next: usize,
+    prepared: bool,
+    wait: u32,
+    /// Screenshots are received asynchronously, in the order of `conditions`.
+    shots: Arc<Mutex<Vec<Option<Image>>>>,
+    /// Condition selected before the export, restored afterwards.
+    condition: String,
+    /// A screenshot could not be taken, so the export is aborted.
+    failed: bool,
+}
+
+/// What a [`ConditionSweep`] did in a frame.
+enum SweepStep {
+    /// Waiting for the plots to be updated or for the screenshots.
+    Wait,
+    /// The condition at this index was just selected.
+    Selected(usize),
+    /// The screenshot of the condition at this index was just requested.
+    Captured(usize),
+    /// All the screenshots, in the order of the conditions.
+    Done(Vec<Image>),
+    Failed,
+}
+
+impl ConditionSweep {
+    fn new(conditions: Vec<String>, condition: String) -> Self {
+        Self {
+            shots: Arc::new(Mutex::new(vec![None; conditions.len()])),
+            conditions,
+            next: 0,
+            prepared: false,
+            wait: 0,
+            condition,
+            failed: false,
+        }
+    }
+
+    /// Select the next condition, hiding the windows, and take its screenshot
+    /// once the plots are updated.
+    fn step(
+        &mut self,
+        ui_state: &mut UiState,
+        screenshot_manager: &mut ScreenshotManager,
+        window: Entity,
+    ) -> SweepStep {
+        if self.failed {
+            return SweepStep::Failed;
+        }
+        if self.wait > 0 {
+            self.wait -= 1;
+            return SweepStep::Wait;
+        }
+        let i = self.next;
+        if i < self.conditions.len() {
+            if !self.prepared {
+                ui_state.condition.clone_from(&self.conditions[i]);
+                ui_state.hide = true;
+                self.prepared = true;
+                self.wait = SETTLE_FRAMES;
+                return SweepStep::Selected(i);
+            }
+            let shots = self.shots.clone();
+            if let Err(e) = screenshot_manager.take_screenshot(window, move |img| {
+                if let Ok(mut shots) = shots.lock() {
+                    shots[i] = Some(img);
+                }
+            }) {
+                // the missing screenshot would never be received
+                error!("Could not take screenshot: {e}");
+                self.failed = true;
+                return SweepStep::Failed;
+            }
+            self.next += 1;
+            self.prepared = false;
+            return SweepStep::Captured(i);
+        }
+        let Ok(mut shots) = self.shots.lock() else {
+            return SweepStep::Wait;
+        };
+        if shots.iter().any(Option::is_none) {
+            return SweepStep::Wait;
+        }
+        SweepStep::Done(shots.drain(..).flatten().collect())
+    }
+
+    /// Select the condition of before the sweep and show the windows again.
+    fn restore(&self, ui_state: &mut UiState) {
+        ui_state.condition.clone_from(&self.condition);
+        ui_state.hide = false;
+    }
+}
+
+/// State of an ongoing [`TiledScreenshotEvent`].
+#[derive(Resource)]
+pub struct TiledExport {
+    path: String,
+    /// One panel per condition; the last capture shows the first condition
+    /// again with only the legend, which is cropped from it.
+    sweep: ConditionSweep,
+    /// Area of the legend in the last capture, in physical pixels.
+    legend: Option<Rect>,
+}
+
+/// Title shown on top of each panel or frame while exporting.
+#[derive(Component)]
+struct PanelTitle;
+
+fn spawn_panel_title(commands: &mut Commands, map_font: &MapFont, theme: &Theme) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: map_font.handle.clone(),
+                font_size: 40.,
+                color: theme.text(),
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            left: Val::Px(10.),
+            ..default()
+        }),
+        PanelTitle,
+    ));
+}
+
+fn start_tiled_export(
+    mut commands: Commands,
+    mut events: EventReader<TiledScreenshotEvent>,
+    mut info_state: ResMut<Info>,
+    ui_state: Res<UiState>,
+    map_font: Res<MapFont>,
+    theme: Res<Theme>,
+    ongoing: Option<Res<TiledExport>>,
+    animation: Option<Res<AnimationExport>>,
+) {
+    for TiledScreenshotEvent { path } in events.read() {
+        if ongoing.is_some() || animation.is_some() {
+            continue;
+        }
+        let mut conditions = ui_state
+            .conditions
+            .iter()
+            .filter(|c| !c.is_empty() && (c.as_str() != "ALL"))
+            .cloned()
+            .collect::<Vec<_>>();
+        let Some(first) = conditions.first().cloned() else {
+            info_state.notify("There are no conditions to tile!");
+            continue;
+        };
+        conditions.push(first);
+        info_state.notify("Writing tiles...");
+        commands.insert_resource(TiledExport {
+            path: path.clone(),
+            sweep: ConditionSweep::new(conditions, ui_state.condition.clone()),
+            legend: None,
+        });
+        spawn_panel_title(&mut commands, &map_font, &theme);
+    }
+}
+
+/// Step through the captures of a [`TiledExport`], one condition at a time,
+/// and write the grid once all screenshots have been received.
+fn tiled_export(
+    mut commands: Commands,
+    export: Option<ResMut<TiledExport>>,
+    mut ui_state: ResMut<UiState>,
+    mut info_state: ResMut<Info>,
+    mut screenshot_manager: ResMut<ScreenshotManager>,
+    main_window: Query<(Entity, &Window), With<PrimaryWindow>>,
+    mut title_query: Query<(Entity, &mut Text, &mut Style), With<PanelTitle>>,
+    mut legend_query: Query<
+        (&Node, &GlobalTransform, &mut Style),
+        (With<Drag>, Without<PanelTitle>),
+    >,
+) {
+    let Some(mut export) = export else {
+        return;
+    };
+    let Ok((window, win)) = main_window.get_single() else {
+        return;
+    };
+    let legend_capture = export.sweep.conditions.len() - 1;
+    match export
+        .sweep
+        .step(&mut ui_state, &mut screenshot_manager, window)
+    {
+        SweepStep::Wait => return,
+        SweepStep::Selected(i) => {
+            let is_legend = i == legend_capture;
+            for (_, mut text, mut style) in title_query.iter_mut() {
+                text.sections[0].value.clone_from(&ui_state.condition);
+                style.display = if is_legend {
+                    Display::None
+                } else {
+                    Display::Flex
+                };
+            }
+            for (_, _, mut style) in legend_query.iter_mut() {
+                style.display = if is_legend {
+                    Display::Flex
+                } else {
+                    Display::None
+                };
+            }
+            return;
+        }
+        SweepStep::Captured(i) => {
+            if i == legend_capture {
+                let scale = win.scale_factor();
+                export.legend = legend_query.iter().next().map(|(node, trans, _)| {
+                    Rect::from_center_size(
+                        trans.translation().truncate() * scale,
+                        node.size() * scale,
+                    )
+                });
             }
+            return;
+        }
+        SweepStep::Done(tiles) => match assemble_tiles(tiles, export.legend) {
+            Some(grid) => {
+                let path = if export.path.split('.').count() >= 2 {
+                    export.path.clone()
+                } else {
+                    format!("{}.png", export.path)
+                };
+                match image::DynamicImage::ImageRgba8(grid).to_rgb8().save(path) {
+                    Ok(_) => info_state.notify("Tiles written"),
+                    Err(e) => {
+                        error!("Format not supported, try PNG, JPEG, BMP or TGA: {e}");
+                        info_state.notify("Error writing tiles!");
+                    }
+                }
+            }
+            None => info_state.notify("Error writing tiles!"),
+        },
+        SweepStep::Failed => info_state.notify("Error writing tiles!"),
+    }
+    export.sweep.restore(&mut ui_state);
+    for (entity, _, _) in title_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (_, _, mut style) in legend_query.iter_mut() {
+        style.display = Display::Flex;
+    }
+    commands.remove_resource::<TiledExport>();
+}
+
+/// Arrange the panels in a grid as square as possible. The last tile is only
+/// used to crop the `legend` from it, which is placed in the following cell.
+fn assemble_tiles(tiles: Vec<Image>, legend: Option<Rect>) -> Option<image::RgbaImage> {
+    let mut tiles = tiles
+        .into_iter()
+        .map(|tile| tile.try_into_dynamic().ok().map(|img| img.to_rgba8()))
+        .collect::<Option<Vec<_>>>()?;
+    let legend_tile = tiles.pop()?;
+    let (width, height) = tiles.first()?.dimensions();
+    let cells = tiles.len() as u32 + 1;
+    let cols = (cells as f32).sqrt().ceil() as u32;
+    let rows = cells.div_ceil(cols);
+    let mut grid = image::RgbaImage::from_pixel(cols * width, rows * height, image::Rgba([255; 4]));
+    for (i, tile) in tiles.iter().enumerate() {
+        let i = i as u32;
+        image::imageops::overlay(
+            &mut grid,
+            tile,
+            ((i % cols) * width) as i64,
+            ((i / cols) * height) as i64,
+        );
+    }
+    if let Some(legend) = legend {
+        let x = legend.min.x.max(0.) as u32;
+        let y = legend.min.y.max(0.) as u32;
+        let w = (legend.width() as u32).min(legend_tile.width().saturating_sub(x));
+        let h = (legend.height() as u32).min(legend_tile.height().saturating_sub(y));
+        let crop = image::imageops::crop_imm(&legend_tile, x, y, w, h).to_image();
+        let i = tiles.len() as u32;
+        image::imageops::overlay(
+            &mut grid,
+            &crop,
+            ((i % cols) * width + width.saturating_sub(w) / 2) as i64,
+            ((i / cols) * height + height.saturating_sub(h) / 2) as i64,
+        );
+    }
+    Some(grid)
+}
+
+/// State of an ongoing [`AnimationExportEvent`].
+#[derive(Resource)]
+pub struct AnimationExport {
+    path: String,
+    fps: f32,
+    width: u32,
+    /// One frame per condition, in the order of the data.
+    sweep: ConditionSweep,
+}
+
+fn start_animation_export(
+    mut commands: Commands,
+    mut events: EventReader<AnimationExportEvent>,
+    mut info_state: ResMut<Info>,
+    ui_state: Res<UiState>,
+    map_font: Res<MapFont>,
+    theme: Res<Theme>,
+    ongoing: Option<Res<AnimationExport>>,
+    tiled: Option<Res<TiledExport>>,
+) {
+    for AnimationExportEvent { path, fps, width } in events.read() {
+        if ongoing.is_some() || tiled.is_some() {
+            continue;
+        }
+        // in the order of the data, so that time points are played in sequence
+        let conditions = ui_state
+            .conditions
+            .iter()
+            .filter(|c| !c.is_empty() && (c.as_str() != "ALL"))
+            .cloned()
+            .collect::<Vec<_>>();
+        if conditions.is_empty() {
+            info_state.notify("There are no conditions to animate!");
+            continue;
+        }
+        info_state.notify("Writing animation...");
+        commands.insert_resource(AnimationExport {
+            path: path.clone(),
+            fps: *fps,
+            width: *width,
+            sweep: ConditionSweep::new(conditions, ui_state.condition.clone()),
+        });
+        spawn_panel_title(&mut commands, &map_font, &theme);
+    }
+}
+
+/// Take one screenshot per condition of an [`AnimationExport`], with the
+/// condition as title, and write the animation once all of them are received.
+fn animation_export(
+    mut commands: Commands,
+    export: Option<ResMut<AnimationExport>>,
+    mut ui_state: ResMut<UiState>,
+    mut info_state: ResMut<Info>,
+    mut screenshot_manager: ResMut<ScreenshotManager>,
+    main_window: Query<Entity, With<PrimaryWindow>>,
+    mut title_query: Query<(Entity, &mut Text), With<PanelTitle>>,
+) {
+    let Some(mut export) = export else {
+        return;
+    };
+    let Ok(window) = main_window.get_single() else {
+        return;
+    };
+    match export
+        .sweep
+        .step(&mut ui_state, &mut screenshot_manager, window)
+    {
+        SweepStep::Wait | SweepStep::Captured(_) => return,
+        SweepStep::Selected(_) => {
+            for (_, mut text) in title_query.iter_mut() {
+                text.sections[0].value.clone_from(&ui_state.condition);
+            }
+            return;
+        }
+        SweepStep::Done(frames) => {
+            let frames = frames
+                .into_iter()
+                .map(|frame| frame.try_into_dynamic().ok().map(|img| img.to_rgba8()))
+                .collect::<Option<Vec<_>>>();
+            match frames
+                .map(|frames| write_animation(frames, &export.path, export.fps, export.width))
+            {
+                Some(Ok(())) => info_state.notify("Animation written"),
+                Some(Err(e)) => {
+                    error!("Could not write the animation: {e}");
+                    info_state.notify("Error writing animation!");
+                }
+                None => info_state.notify("Error writing animation!"),
+            }
+        }
+        SweepStep::Failed => info_state.notify("Error writing animation!"),
+    }
+    export.sweep.restore(&mut ui_state);
+    for (entity, _) in title_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<AnimationExport>();
+}
+
+/// Write the `frames` as a looping GIF at `fps` if `path` ends with ".gif" (in
+/// any case) or
+/// as numbered PNGs next to `path` otherwise (e.g., to be joined into an MP4
+/// with ffmpeg). The frames are resized to `width` pixels unless it is 0.
+pub fn write_animation(
+    frames: Vec<image::RgbaImage>,
+    path: &str,
+    fps: f32,
+    width: u32,
+) -> image::ImageResult<()> {
+    let frames = frames.into_iter().map(|frame| {
+        if (width == 0) || (width == frame.width()) {
+            return frame;
+        }
+        let height = (frame.height() as f32 * width as f32 / frame.width() as f32).round() as u32;
+        image::imageops::resize(
+            &frame,
+            width,
+            height.max(1),
+            image::imageops::FilterType::Triangle,
+        )
+    });
+    let is_gif = std::path::Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+    if is_gif {
+        let file = std::fs::File::create(path)?;
+        let mut encoder = image::codecs::gif::GifEncoder::new(std::io::BufWriter::new(file));
+        encoder.set_repeat(image::codecs::gif::Repeat::Infinite)?;
+        let delay = image::Delay::from_numer_denom_ms((1000. / fps.max(0.01)).round() as u32, 1);
+        encoder.encode_frames(frames.map(|frame| image::Frame::from_parts(frame, 0, 0, delay)))
+    } else {
+        let stem = std::path::Path::new(path).with_extension("");
+        for (i, frame) in frames.enumerate() {
+            frame.save_with_format(format!("{}-{i:03}.png", stem.display()), ImageFormat::Png)?;
         }
+        Ok(())
     }
 }
@@ -0,0 +1,361 @@
+//! Summary statistics to compare samples between conditions.
+
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// Arithmetic mean of a slice, 0 if it is empty.
+pub fn mean(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.;
+    }
+    samples.iter().sum::<f32>() / samples.len() as f32
+}
+
+/// Sample standard deviation of a slice.
+pub fn std_dev(samples: &[f32]) -> f32 {
+    if samples.len() < 2 {
+        return 0.;
+    }
+    let mu = mean(samples);
+    (samples.iter().map(|x| (x - mu).powi(2)).sum::<f32>() / (samples.len() - 1) as f32).sqrt()
+}
+
+/// Quantile `q` (between 0 and 1) of a slice, linearly interpolated between
+/// the closest samples.
+pub fn quantile(samples: &[f32], q: f32) -> f32 {
+    if samples.is_empty() {
+        return f32::NAN;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let pos = q.clamp(0., 1.) * (sorted.len() - 1) as f32;
+    let (low, high) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[low] + (sorted[high] - sorted[low]) * (pos - low as f32)
+}
+
+/// Uniform subsample of at most `cap` samples, with their weights, so that the
+/// histograms and densities of long sampling chains are fast to draw. It is
+/// drawn by reservoir sampling with a fixed seed to get the same figure every
+/// time; the samples are borrowed as they are if there are no more than `cap`,
+/// or if `cap` is 0.
+pub fn subsample<'a>(
+    samples: &'a [f32],
+    weights: Option<&'a [f32]>,
+    cap: usize,
+) -> (Cow<'a, [f32]>, Option<Cow<'a, [f32]>>) {
+    if cap == 0 || samples.len() <= cap {
+        return (Cow::Borrowed(samples), weights.map(Cow::Borrowed));
+    }
+    let mut rng = fastrand::Rng::with_seed(samples.len() as u64);
+    let mut picked: Vec<usize> = (0..cap).collect();
+    for i in cap..samples.len() {
+        let j = rng.usize(..=i);
+        if j < cap {
+            picked[j] = i;
+        }
+    }
+    picked.sort_unstable();
+    let weights = weights
+        .filter(|weights| weights.len() == samples.len())
+        .map(|weights| Cow::Owned(picked.iter().map(|&i| weights[i]).collect()));
+    (
+        Cow::Owned(picked.iter().map(|&i| samples[i]).collect()),
+        weights,
+    )
+}
+
+/// Mean and standard deviation of samples with a weight each, e.g., from
+/// importance sampling. Falls back to [`mean`] and [`std_dev`] if the weights
+/// do not add up to a positive number.
+pub fn weighted_mean_std(samples: &[f32], weights: &[f32]) -> (f32, f32) {
+    let total = weights.iter().sum::<f32>();
+    if total <= 0. || samples.len() != weights.len() {
+        return (mean(samples), std_dev(samples));
+    }
+    let mu = samples.iter().zip(weights).map(|(x, w)| x * w).sum::<f32>() / total;
+    let var = samples
+        .iter()
+        .zip(weights)
+        .map(|(x, w)| w * (x - mu).powi(2))
+        .sum::<f32>()
+        / total;
+    (mu, var.sqrt())
+}
+
+/// Standardized difference of means (Cohen's d) using the pooled standard deviation.
+pub fn cohen_d(a: &[f32], b: &[f32]) -> Option<f32> {
+    let (n_a, n_b) = (a.len() as f32, b.len() as f32);
+    if n_a + n_b <= 2. {
+        return None;
+    }
+    let pooled = (((n_a - 1.) * std_dev(a).powi(2) + (n_b - 1.) * std_dev(b).powi(2))
+        / (n_a + n_b - 2.))
+        .sqrt();
+    if pooled < 1e-8 {
+        return None;
+    }
+    Some((mean(a) - mean(b)) / pooled)
+}
+
+/// Probability that a random draw from `a` is greater than a random draw from `b`,
+/// counting ties as half.
+///
+/// `b` is sorted to avoid comparing all pairs.
+pub fn prob_superiority(a: &[f32], b: &[f32]) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return 0.5;
+    }
+    let mut sorted_b = b.to_vec();
+    sorted_b.sort_by(|x, y| x.total_cmp(y));
+    let wins = a
+        .iter()
+        .map(|x| {
+            let below = sorted_b.partition_point(|y| y < x);
+            let not_above = sorted_b.partition_point(|y| y <= x);
+            below as f32 + (not_above - below) as f32 / 2.
+        })
+        .sum::<f32>();
+    wins / (a.len() as f32 * b.len() as f32)
+}
+
+/// Comparison of the samples of an element between two conditions.
+pub struct Comparison {
+    pub mean_diff: f32,
+    pub cohen_d: Option<f32>,
+    /// Probability of direction: how likely the difference has the sign of `mean_diff`.
+    pub prob_direction: f32,
+}
+
+impl Comparison {
+    pub fn new(current: &[f32], reference: &[f32]) -> Self {
+        let superiority = prob_superiority(current, reference);
+        Self {
+            mean_diff: mean(current) - mean(reference),
+            cohen_d: cohen_d(current, reference),
+            prob_direction: f32::max(superiority, 1. - superiority),
+        }
+    }
+}
+
+/// Statistical test of a difference between the samples of two conditions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SigTest {
+    MannWhitney,
+    Welch,
+}
+
+impl SigTest {
+    pub const ALL: [SigTest; 2] = [SigTest::MannWhitney, SigTest::Welch];
+
+    /// Two-sided p-value of the test, `None` if there are not enough samples.
+    pub fn p_value(self, a: &[f32], b: &[f32]) -> Option<f32> {
+        match self {
+            SigTest::MannWhitney => mann_whitney_p(a, b),
+            SigTest::Welch => welch_t_p(a, b),
+        }
+    }
+}
+
+impl std::fmt::Display for SigTest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SigTest::MannWhitney => write!(f, "Mann-Whitney U"),
+            SigTest::Welch => write!(f, "Welch's t-test"),
+        }
+    }
+}
+
+/// Statistic that summarizes the samples of a distribution mapped to a color or a size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Summary {
+    #[default]
+    Mean,
+    Median,
+    /// Width of the 95% interval between the 2.5% and 97.5% quantiles.
+    CiWidth,
+    Variance,
+    /// Quantile given apart, e.g., by [`crate::gui::UiState::summary_quantile`].
+    Quantile,
+}
+
+impl Summary {
+    pub const ALL: [Summary; 5] = [
+        Summary::Mean,
+        Summary::Median,
+        Summary::CiWidth,
+        Summary::Variance,
+        Summary::Quantile,
+    ];
+
+    /// Summarize the `samples`; `q` is only used by [`Summary::Quantile`].
+    pub fn apply(self, samples: &[f32], q: f32) -> f32 {
+        match self {
+            Summary::Mean => mean(samples),
+            Summary::Median => quantile(samples, 0.5),
+            Summary::CiWidth => quantile(samples, 0.975) - quantile(samples, 0.025),
+            Summary::Variance => std_dev(samples).powi(2),
+            Summary::Quantile => quantile(samples, q),
+        }
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Summary::Mean => write!(f, "mean"),
+            Summary::Median => write!(f, "median"),
+            Summary::CiWidth => write!(f, "95% interval width"),
+            Summary::Variance => write!(f, "variance"),
+            Summary::Quantile => write!(f, "quantile"),
+        }
+    }
+}
+
+/// Asterisks conventionally used to report a p-value.
+pub fn stars(p: f32) -> &'static str {
+    match p {
+        p if p < 0.001 => "***",
+        p if p < 0.01 => "**",
+        p if p < 0.05 => "*",
+        _ => "ns",
+    }
+}
+
+/// Two-sided p-value of the Mann-Whitney U test, using the normal approximation
+/// with tie and continuity corrections.
+pub fn mann_whitney_p(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let n = n_a + n_b;
+    let u = prob_superiority(a, b) as f64 * n_a * n_b;
+    let mut all: Vec<f32> = a.iter().chain(b).copied().collect();
+    all.sort_by(|x, y| x.total_cmp(y));
+    let mut ties = 0.;
+    let mut start = 0;
+    for i in 1..=all.len() {
+        if i == all.len() || all[i] != all[start] {
+            let run = (i - start) as f64;
+            ties += run.powi(3) - run;
+            start = i;
+        }
+    }
+    let var = n_a * n_b / 12. * ((n + 1.) - ties / (n * (n - 1.)));
+    if var <= 0. {
+        return Some(1.);
+    }
+    let diff = (u - n_a * n_b / 2.).abs();
+    let z = (diff - 0.5).max(0.) / var.sqrt();
+    Some(erfc(z / std::f64::consts::SQRT_2) as f32)
+}
+
+/// Two-sided p-value of Welch's t-test, which does not assume equal variances.
+pub fn welch_t_p(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    // squared standard errors of the means
+    let var_a = (std_dev(a) as f64).powi(2) / n_a;
+    let var_b = (std_dev(b) as f64).powi(2) / n_b;
+    let diff = (mean(a) - mean(b)) as f64;
+    if var_a + var_b <= 0. {
+        return Some(if diff == 0. { 1. } else { 0. });
+    }
+    let t = diff / (var_a + var_b).sqrt();
+    let df = (var_a + var_b).powi(2) / (var_a.powi(2) / (n_a - 1.) + var_b.powi(2) / (n_b - 1.));
+    Some(inc_beta(df / 2., 0.5, df / (df + t * t)) as f32)
+}
+
+/// Complementary error function, with a fractional error below 1.2e-7.
+fn erfc(x: f64) -> f64 {
+    const COF: [f64; 10] = [
+        -1.26551223,
+        1.00002368,
+        0.37409196,
+        0.09678418,
+        -0.18628806,
+        0.27886807,
+        -1.13520398,
+        1.48851587,
+        -0.82215223,
+        0.17087277,
+    ];
+    let z = x.abs();
+    let t = 1. / (1. + 0.5 * z);
+    let poly = COF.iter().rev().fold(0., |acc, c| c + t * acc);
+    let ans = t * (-z * z + poly).exp();
+    if x >= 0. {
+        ans
+    } else {
+        2. - ans
+    }
+}
+
+/// Logarithm of the gamma function (Lanczos approximation).
+fn ln_gamma(x: f64) -> f64 {
+    const COF: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut y = x;
+    let mut ser = 1.000000000190015;
+    for c in COF {
+        y += 1.;
+        ser += c / y;
+    }
+    -tmp + (2.5066282746310005 * ser / x).ln()
+}
+
+/// Continued fraction of the regularized incomplete beta function.
+fn beta_cf(a: f64, b: f64, x: f64) -> f64 {
+    const EPS: f64 = 3e-14;
+    const TINY: f64 = 1e-300;
+    let clamp_tiny = |v: f64| if v.abs() < TINY { TINY } else { v };
+    let (qab, qap, qam) = (a + b, a + 1., a - 1.);
+    let mut c = 1.;
+    let mut d = 1. / clamp_tiny(1. - qab * x / qap);
+    let mut h = d;
+    for m in 1..=200 {
+        let m = m as f64;
+        let m2 = 2. * m;
+        let aa = m * (b - m) * x / ((qam + m2) * (a + m2));
+        d = 1. / clamp_tiny(1. + aa * d);
+        c = clamp_tiny(1. + aa / c);
+        h *= d * c;
+        let aa = -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2));
+        d = 1. / clamp_tiny(1. + aa * d);
+        c = clamp_tiny(1. + aa / c);
+        let del = d * c;
+        h *= del;
+        if (del - 1.).abs() < EPS {
+            break;
+        }
+    }
+    h
+}
+
+/// Regularized incomplete beta function I_x(a, b).
+fn inc_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+    if x >= 1. {
+        return 1.;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1. - x).ln()).exp();
+    if x < (a + 1.) / (a + b + 2.) {
+        front * beta_cf(a, b, x) / a
+    } else {
+        1. - front * beta_cf(b, a, 1. - x) / b
+    }
+}
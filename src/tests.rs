@@ -1,8 +1,14 @@
 //! Unit testing on app-updates.
-use crate::aesthetics::{AesPlugin, Aesthetics, Distribution, Gy, Point, RestoreEvent, Unscale};
-use crate::geom::{AesFilter, GeomHist, HistTag, Xaxis};
-use crate::gui::{file_drop, ActiveData, UiState};
-use crate::{data, escher, geom, info};
+use crate::aesthetics::{
+    AesPlugin, Aesthetics, ColumnName, Distribution, Groups, Gy, Point, RestoreEvent, Units,
+    Unscale,
+};
+use crate::geom::{AesFilter, GeomHist, HistTag, RegisterGeom, Xaxis};
+use crate::gui::{
+    arrange, compute_coverage, file_drop, pick_distance, smoothing_factor, ActiveData, Arrange,
+    Coverage, UiState,
+};
+use crate::{batch, data, escher, funcplot, geom, info, onboarding, stats, theme};
 use bevy::prelude::*;
 use bevy::time::TimePlugin;
 use bevy_prototype_lyon::prelude::{GeometryBuilder, Path, PathBuilder, ShapeBundle, Stroke};
@@ -11,11 +17,31 @@ use bevy::tasks::IoTaskPool;
 
 /// Setup to test systems that require [`AsserServer`] as an argument.
 /// Adapted form bevy source code.
-fn setup<'appl>(app: &'appl mut App, asset_path: &str) {
+fn setup(app: &mut App, asset_path: &str) {
     IoTaskPool::get_or_init(Default::default);
-    let mut asset_plug = AssetPlugin::default();
-    asset_plug.file_path = asset_path.to_string();
-    app.add_plugins(asset_plug);
+    app.add_plugins(AssetPlugin {
+        file_path: asset_path.to_string(),
+        ..default()
+    });
+}
+
+#[test]
+fn files_are_watched_before_they_are_written() {
+    use crate::watcher::watched_path;
+    let assets = std::fs::canonicalize("assets").unwrap();
+    assert_eq!(
+        watched_path("assets/flux.metabolism.json"),
+        Some(assets.join("flux.metabolism.json"))
+    );
+    assert_eq!(
+        watched_path("flux.metabolism.json"),
+        Some(
+            std::env::current_dir()
+                .unwrap()
+                .join("flux.metabolism.json")
+        )
+    );
+    assert_eq!(watched_path("no_such_dir/flux.metabolism.json"), None);
 }
 
 #[test]
@@ -55,6 +81,7 @@ fn gy_dist_aes_spaws_xaxis_spawns_hist() {
         escher::ArrowTag {
             id: String::from("a"),
             hists: None,
+            hist_heights: None,
             node_id: 9,
             direction: Vec2::new(0., 1.),
         },
@@ -65,8 +92,10 @@ fn gy_dist_aes_spaws_xaxis_spawns_hist() {
     ));
 
     setup(&mut app, "assets");
+    app.init_asset::<Font>();
     app.insert_resource(ActiveData::default());
     app.insert_resource(UiState::default());
+    app.insert_resource(theme::Theme::default());
     app.add_plugins(AesPlugin);
     app.update();
 
@@ -88,6 +117,86 @@ fn gy_dist_aes_spaws_xaxis_spawns_hist() {
         .is_some());
 }
 
+#[test]
+fn side_colors_follow_the_settings() {
+    let mut ui_state = UiState::default();
+    let color = bevy_egui::egui::Rgba::from_srgba_unmultiplied(10, 20, 30, 200);
+    ui_state.color_right.insert(String::from("c1"), color);
+    assert_eq!(
+        ui_state.side_color(&geom::Side::Right, Some("c1")),
+        Color::rgba_linear(color.r(), color.g(), color.b(), color.a())
+    );
+    // new conditions keep the transparency of the side
+    let alpha = ui_state.color_left[""].a();
+    assert_eq!(
+        ui_state.side_color(&geom::Side::Left, Some("c2")).a(),
+        alpha
+    );
+}
+
+/// Custom geom that draws a vertical line at the first sample.
+fn plot_first_sample(
+    samples: &[f32],
+    _weights: Option<&[f32]>,
+    size: f32,
+    xlimits: (f32, f32),
+) -> Option<Path> {
+    let x = funcplot::lerp(samples[0], xlimits.0, xlimits.1, -size / 2., size / 2.);
+    let mut path_builder = PathBuilder::new();
+    path_builder.move_to(Vec2::new(x, 0.));
+    path_builder.line_to(Vec2::new(x, 10.));
+    Some(path_builder.build())
+}
+
+#[test]
+fn registered_custom_geom_is_plotted() {
+    let mut app = App::new();
+    app.world.spawn((
+        Aesthetics {
+            identifiers: vec!["a".to_string()],
+            condition: None,
+        },
+        Gy {},
+        Distribution(vec![vec![1f32, 2., 3.]]),
+        AesFilter {
+            met: false,
+            pbox: false,
+        },
+        GeomHist::right(geom::HistPlot::Custom("first")),
+    ));
+    let line = PathBuilder::new().build();
+    app.world.spawn((
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&line),
+            ..default()
+        },
+        escher::ArrowTag {
+            id: String::from("a"),
+            hists: None,
+            hist_heights: None,
+            node_id: 9,
+            direction: Vec2::new(0., 1.),
+        },
+    ));
+
+    setup(&mut app, "assets");
+    app.init_asset::<Font>();
+    app.insert_resource(ActiveData::default());
+    app.insert_resource(UiState::default());
+    app.insert_resource(theme::Theme::default());
+    app.register_geom("first", plot_first_sample);
+    app.add_plugins(AesPlugin);
+    app.update();
+    app.update();
+
+    assert!(app
+        .world
+        .query::<(&HistTag, &Path)>()
+        .iter(&app.world)
+        .next()
+        .is_some());
+}
+
 #[test]
 fn point_dist_aes_spaws_box_axis_spawns_box() {
     // Setup app
@@ -121,6 +230,7 @@ fn point_dist_aes_spaws_box_axis_spawns_box() {
         escher::ArrowTag {
             id: String::from("a"),
             hists: None,
+            hist_heights: None,
             node_id: 9,
             direction: Vec2::new(0., 1.),
         },
@@ -131,7 +241,9 @@ fn point_dist_aes_spaws_box_axis_spawns_box() {
     ));
 
     setup(&mut app, "asset1");
+    app.init_asset::<Font>();
     app.insert_resource(UiState::default());
+    app.insert_resource(theme::Theme::default());
     app.insert_resource(ActiveData::default());
     app.add_plugins(AesPlugin);
     app.update();
@@ -154,29 +266,69 @@ fn point_dist_aes_spaws_box_axis_spawns_box() {
         .is_some());
 }
 
+#[test]
+fn column_names_are_axis_titles() {
+    let mut app = App::new();
+    for (column, condition) in [("kcat", "a"), ("kcat", "b"), ("km", "a")] {
+        app.world.spawn((
+            Aesthetics {
+                identifiers: vec!["a".to_string()],
+                condition: Some(condition.to_string()),
+            },
+            Gy {},
+            Point(vec![1f32]),
+            AesFilter {
+                met: false,
+                pbox: true,
+            },
+            GeomHist::right(geom::HistPlot::BoxPoint),
+            ColumnName(column.to_string()),
+        ));
+    }
+    app.world.spawn((
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&PathBuilder::new().build()),
+            ..default()
+        },
+        escher::ArrowTag {
+            id: String::from("a"),
+            hists: None,
+            hist_heights: None,
+            node_id: 9,
+            direction: Vec2::new(0., 1.),
+        },
+    ));
+
+    setup(&mut app, "assets");
+    app.init_asset::<Font>();
+    app.insert_resource(UiState::default());
+    app.insert_resource(theme::Theme::default());
+    app.insert_resource(ActiveData::default());
+    app.add_plugins(AesPlugin);
+    app.update();
+
+    let axis = app.world.query::<&Xaxis>().single(&app.world);
+    assert_eq!(axis.titles, vec!["kcat".to_string(), "km".to_string()]);
+}
+
 #[test]
 fn loading_file_drop_does_not_crash() {
     // Setup app
     let mut app = App::new();
     app.insert_resource(UiState::default());
+    app.insert_resource(theme::Theme::default());
     app.add_event::<RestoreEvent>();
     setup(&mut app, "assets");
-    app.insert_resource(data::ReactionState {
-        reaction_data: None,
-        loaded: false,
-    });
+    app.init_asset::<Font>();
+    app.insert_resource(data::ReactionState::default());
     app.add_plugins(TimePlugin);
     app.add_plugins(info::InfoPlugin);
     app.add_event::<FileDragAndDrop>();
     app.add_plugins(data::DataPlugin);
     app.add_plugins(escher::EscherPlugin);
-    app.init_asset::<Font>();
     let asset_server = app.world.get_resource::<AssetServer>().unwrap();
     let escher_handle: Handle<escher::EscherMap> = asset_server.load("ecoli_core_map.json");
-    app.insert_resource(escher::MapState {
-        escher_map: escher_handle,
-        loaded: false,
-    });
+    app.insert_resource(escher::MapState::new("ecoli_core_map.json", escher_handle));
     app.add_systems(Update, file_drop);
 
     app.update();
@@ -185,4 +337,1138 @@ fn loading_file_drop_does_not_crash() {
         path_buf: "assets/ecoli_core_map.json".into(),
     });
     app.update();
+    let ui_state = app.world.resource::<UiState>();
+    assert_eq!(ui_state.dropped[0].1, Ok("map"));
+}
+
+#[test]
+fn coverage_is_computed_per_condition() {
+    let mut app = App::new();
+    app.init_resource::<Coverage>();
+    app.add_systems(Update, compute_coverage);
+    for (i, id) in ["r1", "r2"].into_iter().enumerate() {
+        app.world.spawn(escher::ArrowTag {
+            id: String::from(id),
+            hists: None,
+            hist_heights: None,
+            node_id: i as u64,
+            direction: Vec2::X,
+        });
+    }
+    app.world.spawn(escher::CircleTag {
+        id: String::from("m1"),
+    });
+    for (condition, ids) in [("a", vec!["r1"]), ("b", vec!["r1", "r2", "m1"])] {
+        app.world.spawn(Aesthetics {
+            identifiers: ids.into_iter().map(String::from).collect(),
+            condition: Some(String::from(condition)),
+        });
+    }
+    app.update();
+    let coverage = &app.world.resource::<Coverage>().0;
+    assert_eq!(coverage[0], (String::from("a"), 0.5, 0.));
+    assert_eq!(coverage[1], (String::from("b"), 1., 1.));
+}
+
+#[test]
+fn sniffing_json_tells_maps_from_data() {
+    let map = std::fs::read("assets/ecoli_core_map.json").unwrap();
+    assert!(matches!(data::sniff_json(&map), Ok(data::JsonFile::Map(_))));
+    let data = std::fs::read("assets/flux_kcat.metabolism.json").unwrap();
+    assert!(matches!(
+        data::sniff_json(&data),
+        Ok(data::JsonFile::Data(_))
+    ));
+    assert!(data::sniff_json(b"[1, 2, 3]").is_err());
+}
+
+#[test]
+fn groups_are_read_by_identifier() {
+    let data: data::Data = serde_json::from_str(
+        r#"{"reactions": ["PFK", "CS", "PGI"], "groups": ["glycolysis", "tca", "glycolysis"],
+        "metabolites": ["atp_c"], "met_groups": ["cytosol"], "colors": [1, 2, 3]}"#,
+    )
+    .unwrap();
+    let (reactions, metabolites) = data.groups();
+    assert_eq!(reactions["PGI"], "glycolysis");
+    let groups = Groups {
+        reactions,
+        metabolites,
+    };
+    assert_eq!(groups.names(), vec!["cytosol", "glycolysis", "tca"]);
+}
+
+#[test]
+fn units_follow_the_titles_of_their_columns() {
+    let data: data::Data = serde_json::from_str(
+        r#"{"reactions": ["PFK"], "y": [[1, 2]], "colors": [1],
+        "labels": {"y": "flux"}, "units": {"y": "mmol/gDW/h"}}"#,
+    )
+    .unwrap();
+    let units = Units(data.units());
+    assert_eq!(units.label("flux"), "flux [mmol/gDW/h]");
+    assert_eq!(units.label("colors"), "colors");
+}
+
+#[test]
+fn direction_without_primary_metabolites_follows_segments() {
+    let met = |x: f32| {
+        format!(
+            r#"{{"node_type": "metabolite", "x": {x}, "y": 0, "label_x": 0, "label_y": 0,
+            "name": "", "bigg_id": "m{x}", "node_is_primary": false}}"#
+        )
+    };
+    let map = format!(
+        r#"[{{"map_name": "", "map_id": "", "map_description": "", "homepage": "", "schema": ""}},
+        {{"nodes": {{"1": {}, "2": {{"node_type": "midmarker", "x": 10, "y": 0}}, "3": {}}},
+        "reactions": {{"7": {{"name": "", "bigg_id": "r", "reversibility": false,
+        "label_x": 0, "label_y": 0, "gene_reaction_rule": "", "metabolites": [],
+        "segments": {{"1": {{"from_node_id": "1", "to_node_id": "2", "b1": null, "b2": null}},
+        "2": {{"from_node_id": "2", "to_node_id": "3", "b1": null, "b2": null}}}}}}}}}}]"#,
+        met(0.),
+        met(20.)
+    );
+    let map: escher::EscherMap = serde_json::from_str(&map).unwrap();
+    let direction = map.main_direction(&map.metabolism.reactions[&7]);
+    assert!((direction.x.abs() - 1.).abs() < 1e-6);
+}
+
+#[test]
+fn reactions_drawn_on_top_of_each_other_are_bundled() {
+    let met = |x: f32| {
+        format!(
+            r#"{{"node_type": "metabolite", "x": {x}, "y": 0, "label_x": 0, "label_y": 0,
+            "name": "", "bigg_id": "m{x}", "node_is_primary": true}}"#
+        )
+    };
+    let reac = |id: &str, from: &str, to: &str| {
+        format!(
+            r#""{id}": {{"name": "", "bigg_id": "r{id}", "reversibility": false,
+            "label_x": 0, "label_y": 0, "gene_reaction_rule": "", "metabolites": [],
+            "segments": {{"1": {{"from_node_id": "{from}", "to_node_id": "{to}", "b1": null, "b2": null}}}}}}"#
+        )
+    };
+    let map = format!(
+        r#"[{{"map_name": "", "map_id": "", "map_description": "", "homepage": "", "schema": ""}},
+        {{"nodes": {{"1": {}, "2": {}, "3": {}}}, "reactions": {{{}, {}, {}}}}}]"#,
+        met(0.),
+        met(100.),
+        met(300.),
+        reac("7", "1", "2"),
+        // an isozyme drawn in the other direction
+        reac("8", "2", "1"),
+        reac("9", "2", "3"),
+    );
+    let map: escher::EscherMap = serde_json::from_str(&map).unwrap();
+    let bundles = map.duplicate_bundles(&map.metabolism.reactions);
+    assert_eq!(bundles.get(&7), Some(&(0, 2)));
+    assert_eq!(bundles.get(&8), Some(&(1, 2)));
+    assert!(!bundles.contains_key(&9));
+}
+
+#[test]
+fn comparison_of_shifted_samples_has_full_direction() {
+    let reference = [1f32, 2., 3.];
+    let current = [4f32, 5., 6.];
+    let comp = stats::Comparison::new(&current, &reference);
+    assert_eq!(comp.mean_diff, 3.);
+    assert_eq!(comp.prob_direction, 1.);
+    // ties count as half
+    assert_eq!(stats::prob_superiority(&reference, &reference), 0.5);
+}
+
+#[test]
+fn significance_tests_match_reference_p_values() {
+    let a = [1f32, 2., 3., 4., 5., 6., 7., 8.];
+    let b = [3f32, 4., 5., 6., 7., 8., 9., 10.];
+    // scipy.stats.ttest_ind(a, b, equal_var=False).pvalue
+    let p = stats::SigTest::Welch.p_value(&a, &b).unwrap();
+    assert!((p - 0.1248).abs() < 1e-3);
+    assert_eq!(stats::SigTest::MannWhitney.p_value(&a, &a), Some(1.));
+    let shifted = a.map(|x| x + 20.);
+    let p = stats::SigTest::MannWhitney.p_value(&a, &shifted).unwrap();
+    assert_eq!(stats::stars(p), "***");
+    assert_eq!(stats::SigTest::Welch.p_value(&a[..1], &b), None);
+}
+
+#[test]
+fn quantiles_interpolate_between_samples() {
+    let samples = [4f32, 1., 3., 2.];
+    assert_eq!(stats::quantile(&samples, 0.), 1.);
+    assert_eq!(stats::quantile(&samples, 0.5), 2.5);
+    assert_eq!(stats::quantile(&samples, 1.), 4.);
+}
+
+#[test]
+fn weighted_summary_ignores_samples_without_weight() {
+    let (mean, sd) = stats::weighted_mean_std(&[1., 3., 100.], &[1., 1., 0.]);
+    assert_eq!(mean, 2.);
+    assert_eq!(sd, 1.);
+}
+
+#[test]
+fn zscore_transform_standardizes_across_values() {
+    let mut values = [1f32, 2., 3.];
+    data::DataTransform::ZScore.apply(values.iter_mut());
+    assert_eq!(values[1], 0.);
+    assert!((values[2] + values[0]).abs() < 1e-6);
+    let mut values = vec![2f32, 4.];
+    data::DataTransform::MinMax.apply(values.iter_mut());
+    assert_eq!(values, vec![0., 1.]);
+}
+
+#[test]
+fn flat_distributions_are_still_plotted() {
+    let samples = [0f32; 6];
+    let mut xlimits = (0f32, 0.);
+    assert!(funcplot::widen_degenerate(&mut xlimits));
+    assert!(xlimits.0 < 0. && xlimits.1 > 0.);
+    let hist = funcplot::plot_hist(&samples, None, 160, 600., xlimits).unwrap();
+    assert!(funcplot::path_to_vec(&hist).y > 0.);
+    // a bandwidth this small misses every evaluation point
+    let kde = funcplot::plot_kde(
+        &samples,
+        None,
+        100,
+        600.,
+        xlimits,
+        funcplot::Kernel::default(),
+        1e-9,
+    )
+    .unwrap();
+    assert!(funcplot::path_to_vec(&kde).y > 0.);
+}
+
+#[test]
+fn error_bars_span_the_chosen_spread() {
+    let samples = [1f32, 2., 3.];
+    let width = |kind| {
+        let bar = funcplot::plot_error_bar(&samples, None, kind, 400., (0., 4.), 14.).unwrap();
+        funcplot::path_to_vec(&bar).x
+    };
+    // one sd to each side of the mean plus the width of the caps
+    assert_eq!(width(funcplot::ErrorBar::Sd), 202.);
+    assert!(width(funcplot::ErrorBar::Sem) < width(funcplot::ErrorBar::Sd));
+}
+
+#[test]
+fn dashed_paths_alternate_dashes_and_gaps() {
+    let mut builder = PathBuilder::new();
+    builder.move_to(Vec2::ZERO);
+    builder.line_to(Vec2::new(100., 0.));
+    let dashed = funcplot::dash_path(&builder.build(), 10., 5.);
+    // dashes at 0, 15, 30, 45, 60, 75 and 90
+    assert_eq!(
+        dashed
+            .0
+            .iter()
+            .filter(|event| matches!(
+                event,
+                bevy_prototype_lyon::prelude::tess::path::PathEvent::Begin { .. }
+            ))
+            .count(),
+        7
+    );
+    assert_eq!(funcplot::path_to_vec(&dashed).x, 100.);
+}
+
+#[test]
+fn numbers_follow_the_locale() {
+    let comma = funcplot::NumberFormat::for_locale("de_DE.UTF-8");
+    assert!(comma.decimal_comma);
+    assert!(!funcplot::NumberFormat::for_locale("en_US.UTF-8").decimal_comma);
+    assert_eq!(comma.sci(1234.5, 2), "1,23e3");
+    let grouped = funcplot::NumberFormat {
+        grouping: true,
+        ..comma
+    };
+    assert_eq!(grouped.plain(-1234567.5), "-1.234.567,5");
+    assert_eq!(funcplot::NumberFormat::default().plain(1234.5), "1234.5");
+}
+
+#[test]
+fn labels_follow_the_notation() {
+    use funcplot::{Notation, NumberFormat};
+    let format = |notation, decimals| NumberFormat {
+        notation,
+        decimals,
+        ..NumberFormat::default()
+    };
+    assert_eq!(NumberFormat::default().label(0.52), "5.20e-1");
+    assert_eq!(format(Notation::Plain, 2).label(0.52), "0.52");
+    assert_eq!(format(Notation::Si, 1).label(0.52), "520.0m");
+    assert_eq!(format(Notation::Si, 0).label(-1500.), "-2k");
+    assert_eq!(format(Notation::Si, 1).label(0.), "0.0");
+    assert_eq!(format(Notation::Plain, 1).signed_label(3.), "+3.0");
+}
+
+#[test]
+fn batch_spec_paths_are_relative_to_the_spec() {
+    let dir = std::env::temp_dir().join("shu_batch_spec");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("spec.json");
+    std::fs::write(
+        &path,
+        r##"{
+            "map": "map.json",
+            "datasets": [{"path": "a.metabolism.json", "transform": "z_score"}],
+            "style": {"kde_kernel": "epanechnikov", "max_reaction_color": "#ff0000"},
+            "exports": [{"path": "/tmp/out.svg", "condition": "x"}]
+        }"##,
+    )
+    .unwrap();
+    let spec = batch::BatchSpec::from_file(&path).unwrap();
+    assert_eq!(spec.map.unwrap(), dir.join("map.json").to_string_lossy());
+    assert_eq!(spec.datasets[0].transform, data::DataTransform::ZScore);
+    assert_eq!(spec.exports[0].path, "/tmp/out.svg");
+    assert!(!spec.exports[0].tiles);
+
+    let path = dir.join("spec.YML");
+    std::fs::write(
+        &path,
+        "datasets:\n  - path: a.metabolism.json\nexports:\n  - path: panels.png\n    tiles: true\n",
+    )
+    .unwrap();
+    let spec = batch::BatchSpec::from_file(&path).unwrap();
+    assert_eq!(
+        spec.datasets[0].path,
+        dir.join("a.metabolism.json").to_string_lossy()
+    );
+    assert!(spec.exports[0].tiles);
+}
+
+#[test]
+fn distributed_histograms_are_evenly_spaced() {
+    let mut transforms = vec![
+        Transform::from_xyz(0., 5., 0.),
+        Transform::from_xyz(100., -3., 0.),
+        Transform::from_xyz(10., 8., 0.),
+    ];
+    arrange(Arrange::DistributeX, &mut transforms);
+    let xs: Vec<f32> = transforms.iter().map(|t| t.translation.x).collect();
+    assert_eq!(xs, vec![0., 100., 50.]);
+    arrange(Arrange::AlignRow, &mut transforms);
+    assert!(transforms.iter().all(|t| t.translation.y == 10. / 3.));
+}
+
+#[test]
+fn smoothing_is_frame_rate_independent() {
+    let at_30_fps = smoothing_factor(0.1, 1. / 30.);
+    // two frames at 60 FPS cover the same distance as one at 30 FPS
+    let at_60_fps = 1. - (1. - smoothing_factor(0.1, 1. / 60.)).powi(2);
+    assert!((at_30_fps - at_60_fps).abs() < 1e-6);
+    assert_eq!(smoothing_factor(0., 1. / 60.), 1.);
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn long_tables_need_reactions_and_values() {
+    use arrow::array::{ArrayRef, Float64Array, StringArray};
+    use arrow::record_batch::RecordBatch;
+    use std::sync::Arc;
+
+    let reactions: ArrayRef = Arc::new(StringArray::from(vec!["PFK", "PFK", "PGI"]));
+    let values: ArrayRef = Arc::new(Float64Array::from(vec![1., 2., 3.]));
+    let batch =
+        RecordBatch::try_from_iter([("reaction", reactions.clone()), ("value", values)]).unwrap();
+    assert!(data::Data::from_long_table(&[batch]).unwrap().has_values());
+    let batch = RecordBatch::try_from_iter([("reaction", reactions)]).unwrap();
+    assert!(matches!(
+        data::Data::from_long_table(&[batch]),
+        Err(data::LongTableError::Column("value"))
+    ));
+}
+
+#[test]
+fn news_are_new_after_an_update() {
+    use std::cmp::Ordering;
+    assert_eq!(
+        onboarding::cmp_versions("0.10.0", "0.9.2"),
+        Ordering::Greater
+    );
+    assert_eq!(
+        onboarding::cmp_versions("0.8.3", "0.8.3-rc1"),
+        Ordering::Equal
+    );
+    let release = onboarding::RELEASE_NOTES[0].features[0].id;
+    let mut seen = onboarding::Onboarding::default();
+    assert!(seen.is_new(release));
+    seen.seen_version = Some(onboarding::RELEASE_NOTES[0].version.to_string());
+    assert!(!seen.is_new(release));
+    seen.seen_version = Some(String::from("0.1.0"));
+    assert!(seen.is_new(release));
+}
+
+#[test]
+fn shared_xlimits_span_the_side() {
+    let mut app = App::new();
+    let mut ui_state = UiState::default();
+    ui_state.global_xlimits = true;
+    app.insert_resource(ui_state);
+    app.add_systems(Update, crate::aesthetics::share_xlimits);
+    let axes: Vec<Entity> = [
+        (geom::Side::Left, (0., 1.)),
+        (geom::Side::Left, (-2., 0.5)),
+        (geom::Side::Right, (3., 4.)),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(i, (side, xlimits))| {
+        app.world
+            .spawn(Xaxis {
+                id: format!("r{i}"),
+                arrow_size: 100.,
+                xlimits,
+                own_xlimits: xlimits,
+                side,
+                plot: geom::HistPlot::Hist,
+                node_id: i as u64,
+                conditions: Vec::new(),
+                titles: Vec::new(),
+                flat: false,
+                height: 1.,
+                met: false,
+            })
+            .id()
+    })
+    .collect();
+    app.update();
+    let xlimits = |app: &App| -> Vec<(f32, f32)> {
+        axes.iter()
+            .map(|e| app.world.get::<Xaxis>(*e).unwrap().xlimits)
+            .collect()
+    };
+    assert_eq!(xlimits(&app), [(-2., 1.), (-2., 1.), (3., 4.)]);
+    app.world.resource_mut::<UiState>().global_xlimits = false;
+    app.update();
+    assert_eq!(xlimits(&app), [(0., 1.), (-2., 0.5), (3., 4.)]);
+}
+
+#[test]
+fn color_scales_can_differ_per_condition() {
+    use bevy_egui::egui::Rgba;
+    let mut ui_state = UiState::default();
+    let blue = Rgba::from_rgb(0., 0., 1.);
+    ui_state
+        .max_reaction_color
+        .insert(String::from("aerobic"), blue);
+    assert_eq!(ui_state.color_scale("Reaction", Some("aerobic")).1, blue);
+    let all = ui_state.max_reaction_color[""];
+    assert_eq!(ui_state.color_scale("Reaction", Some("anaerobic")).1, all);
+    assert_eq!(ui_state.color_scale("Reaction", None).1, all);
+    assert_ne!(ui_state.color_scale("Metabolite", Some("aerobic")).1, blue);
+}
+
+#[test]
+fn legend_gradient_is_white_at_zero() {
+    use crate::legend::{zero_position, GradientMaterial};
+    let mut material = GradientMaterial::new(Handle::default());
+    material.min_color = Vec4::new(1., 0., 0., 1.);
+    material.max_color = Vec4::new(0., 0., 1., 1.);
+    material.zero = zero_position(true, -1., 3.);
+    assert!((material.zero - 0.25).abs() < 1e-6);
+    assert!(material.color_at(0.).abs_diff_eq(material.min_color, 1e-3));
+    assert!(material.color_at(1.).abs_diff_eq(material.max_color, 1e-3));
+    assert!(material
+        .color_at(0.25)
+        .abs_diff_eq(Vec4::new(0.83, 0.83, 0.89, 1.), 1e-3));
+    assert_eq!(zero_position(true, 1., 3.), -1.);
+    assert_eq!(zero_position(false, -1., 3.), -1.);
+}
+
+#[test]
+fn unchanged_colors_do_not_tessellate_again() {
+    let mut app = App::new();
+    app.world.spawn((
+        Aesthetics {
+            identifiers: vec!["a".to_string(), "b".to_string()],
+            condition: None,
+        },
+        crate::aesthetics::Gcolor {},
+        Point(vec![1f32, 2.]),
+        geom::GeomArrow {
+            plotted: false,
+            style: funcplot::ArrowStyle::default(),
+        },
+    ));
+    let arrow = app
+        .world
+        .spawn((
+            ShapeBundle {
+                path: GeometryBuilder::build_as(&PathBuilder::new().build()),
+                ..default()
+            },
+            Stroke::new(Color::BLACK, 10.),
+            escher::ArrowTag {
+                id: String::from("a"),
+                hists: None,
+                hist_heights: None,
+                node_id: 1,
+                direction: Vec2::new(0., 1.),
+            },
+        ))
+        .id();
+    app.insert_resource(UiState::default());
+    app.add_systems(Update, crate::aesthetics::plot_arrow_color);
+    app.update();
+    let painted = app.world.entity(arrow).get_ref::<Stroke>().unwrap();
+    assert_ne!(painted.color, Color::BLACK);
+    let painted_tick = painted.last_changed();
+    app.update();
+    let stroke = app.world.entity(arrow).get_ref::<Stroke>().unwrap();
+    assert_eq!(stroke.last_changed(), painted_tick);
+}
+
+#[test]
+fn moved_metabolites_keep_the_histograms_in_place() {
+    let met = |x: f32| {
+        format!(
+            r#"{{"node_type": "metabolite", "x": {x}, "y": 0, "label_x": {x}, "label_y": 0,
+            "name": "", "bigg_id": "m{x}", "node_is_primary": true}}"#
+        )
+    };
+    let map = format!(
+        r#"[{{"map_name": "", "map_id": "", "map_description": "", "homepage": "", "schema": ""}},
+        {{"nodes": {{"1": {}, "2": {}}},
+        "reactions": {{"7": {{"name": "", "bigg_id": "r", "reversibility": false,
+        "label_x": 0, "label_y": 0, "gene_reaction_rule": "", "metabolites": [],
+        "hist_position": {{"Right": {{"translation": [10, 0, 0], "rotation": [0, 0, 0, 1], "scale": [1, 1, 1]}}}},
+        "segments": {{"1": {{"from_node_id": "1", "to_node_id": "2", "b1": null, "b2": null}}}}}}}}}}]"#,
+        met(0.),
+        met(100.)
+    );
+    let mut map: escher::EscherMap = serde_json::from_str(&map).unwrap();
+    assert_eq!(map.center(), Vec2::new(50., 0.));
+    map.move_element(escher::Editable::Metabolite(1), Vec2::new(40., 20.));
+    assert_eq!(map.met_coords("1"), Some(Vec2::new(40., 20.)));
+    assert_eq!(map.center(), Vec2::new(70., 10.));
+    // the world is centered on the metabolites, so the histogram moves the other way
+    let hist: Transform = map.metabolism.reactions[&7].hist_position.as_ref().unwrap()
+        [&geom::Side::Right]
+        .clone()
+        .into();
+    assert_eq!(hist.translation, Vec3::new(-10., 10., 0.));
+}
+
+#[test]
+fn annotations_are_saved_with_the_map_and_can_be_grabbed() {
+    use crate::annotation::{Annotation, AnnotationKind};
+    let arrow = Annotation::new(
+        AnnotationKind::Arrow {
+            tip: Vec2::new(100., 0.),
+            width: 4.,
+        },
+        Vec2::new(10., 20.),
+    );
+    assert!(arrow.contains(Vec2::new(50., 5.), None));
+    assert!(!arrow.contains(Vec2::new(50., 30.), None));
+    assert!(!arrow.contains(Vec2::new(130., 0.), None));
+    let text = Annotation::new(
+        AnnotationKind::Text {
+            text: String::from("glycolysis"),
+            font_size: 40.,
+        },
+        Vec2::ZERO,
+    );
+    assert!(text.contains(Vec2::new(90., 0.), Some(Vec2::new(200., 40.))));
+    assert!(!text.contains(Vec2::new(0., 30.), Some(Vec2::new(200., 40.))));
+
+    let map = std::fs::read_to_string("assets/ecoli_core_map.json").unwrap();
+    let mut map: escher::EscherMap = serde_json::from_str(&map).unwrap();
+    assert!(map.annotations.is_empty());
+    map.annotations = vec![arrow, text];
+    let saved = serde_json::to_string(&map).unwrap();
+    assert!(saved.contains(r#""type":"arrow""#));
+    let map: escher::EscherMap = serde_json::from_str(&saved).unwrap();
+    assert_eq!(map.annotations.len(), 2);
+    assert_eq!(map.annotations[0].position, Vec2::new(10., 20.));
+}
+
+#[test]
+fn reactions_and_metabolites_are_linked_both_ways() {
+    let map = std::fs::read_to_string("assets/ecoli_core_map.json").unwrap();
+    let map: escher::EscherMap = serde_json::from_str(&map).unwrap();
+    let adjacency = escher::Adjacency::new(&map.metabolism);
+    let (reactions, metabolites) = adjacency.linked("PGI", false);
+    assert_eq!(reactions, std::collections::HashSet::from(["PGI"]));
+    assert!(metabolites.contains("g6p_c") && metabolites.contains("f6p_c"));
+    let (reactions, metabolites) = adjacency.linked("g6p_c", true);
+    assert!(reactions.contains("PGI"));
+    assert_eq!(metabolites, std::collections::HashSet::from(["g6p_c"]));
+}
+
+#[test]
+fn load_errors_point_to_the_problem() {
+    let error = data::sniff_json(b"{\"reactions\": [\"PFK\"],\n \"colors\": [1.0,]}")
+        .err()
+        .expect("invalid file");
+    let error = data::LoadError::from_json("bad.metabolism.json", &error);
+    assert!(matches!(error.position, Some((2, _))));
+    assert!(error.message.starts_with("The file is not valid JSON"));
+
+    let error = data::sniff_json(br#"{"reactions": ["PFK", "PGI"], "colors": [1.0]}"#)
+        .err()
+        .expect("invalid file")
+        .to_string();
+    assert!(error.contains("`colors` has 1 values but `reactions` has 2 identifiers"));
+    let error =
+        data::sniff_json(br#"{"reactions": ["PFK"], "metrics": {"flux": {"sizes": [1.0, 2.0]}}}"#)
+            .err()
+            .expect("invalid file")
+            .to_string();
+    assert!(error.starts_with("Metric `flux`"));
+    assert!(data::sniff_json(br#"{"met_colors": [1.0]}"#).is_err());
+}
+
+#[test]
+fn map_texts_follow_the_chosen_font() {
+    let mut app = App::new();
+    setup(&mut app, "assets");
+    app.insert_resource(UiState::default());
+    app.init_asset::<Font>();
+    app.add_plugins(TimePlugin);
+    app.add_plugins(info::InfoPlugin);
+    app.add_plugins(crate::font::MapFontPlugin);
+    let default_font = app.world.resource::<crate::font::MapFont>().handle.clone();
+    let label = app
+        .world
+        .spawn(Text2dBundle {
+            text: Text::from_section(
+                "PFK",
+                TextStyle {
+                    font: default_font.clone(),
+                    ..default()
+                },
+            ),
+            ..default()
+        })
+        .id();
+    app.update();
+    app.world.resource_mut::<UiState>().font = String::from(crate::font::BUNDLED_FONTS[1].1);
+    app.update();
+
+    let font = app.world.resource::<crate::font::MapFont>().handle.clone();
+    assert_ne!(font, default_font);
+    let text = app.world.get::<Text>(label).unwrap();
+    assert_eq!(text.sections[0].style.font, font);
+}
+
+#[test]
+fn fingers_keep_the_map_under_them() {
+    use crate::camera::follow_fingers;
+    let window = Vec2::new(800., 600.);
+    let limits = (1., 40.);
+    // one finger pans
+    let (camera, scale) = follow_fingers(
+        Vec2::ZERO,
+        2.,
+        &[Vec2::new(100., 100.)],
+        &[Vec2::new(150., 80.)],
+        window,
+        limits,
+    );
+    assert_eq!((camera, scale), (Vec2::new(-100., -40.), 2.));
+    // spreading two fingers zooms in around their middle
+    let to_world = |camera: Vec2, scale: f32, point: Vec2| {
+        let from_center = point - window / 2.;
+        camera + Vec2::new(from_center.x, -from_center.y) * scale
+    };
+    let previous = [Vec2::new(500., 200.), Vec2::new(600., 200.)];
+    let current = [Vec2::new(450., 200.), Vec2::new(650., 200.)];
+    let (camera, scale) = follow_fingers(Vec2::ZERO, 4., &previous, &current, window, limits);
+    assert_eq!(scale, 2.);
+    let before = to_world(Vec2::ZERO, 4., Vec2::new(550., 200.));
+    assert!(to_world(camera, scale, Vec2::new(550., 200.)).distance(before) < 1e-3);
+    // but not past the limits of the camera
+    let (_, scale) = follow_fingers(Vec2::ZERO, 1., &previous, &current, window, limits);
+    assert_eq!(scale, 1.);
+}
+
+#[test]
+fn metabolite_side_hists_are_anchored_to_circles() {
+    let mut app = App::new();
+    app.world.spawn((
+        Aesthetics {
+            identifiers: vec![String::from("glc__D_c")],
+            condition: None,
+        },
+        Gy {},
+        Distribution(vec![vec![1f32, 2., 2.]]),
+        AesFilter {
+            met: true,
+            pbox: false,
+        },
+        GeomHist::right(geom::HistPlot::Hist),
+    ));
+    // the circle and the label of the metabolite share the tag
+    app.world.spawn((
+        Transform::from_xyz(10., 20., 2.),
+        escher::CircleTag {
+            id: String::from("glc__D_c"),
+        },
+        escher::Editable::Metabolite(3),
+    ));
+    app.world.spawn((
+        Transform::from_xyz(-50., -50., 2.),
+        escher::CircleTag {
+            id: String::from("glc__D_c"),
+        },
+        escher::Editable::MetaboliteLabel(3),
+    ));
+
+    setup(&mut app, "assets");
+    app.init_asset::<Font>();
+    app.insert_resource(ActiveData::default());
+    app.insert_resource(UiState::default());
+    app.insert_resource(theme::Theme::default());
+    app.add_plugins(AesPlugin);
+    app.update();
+
+    let (trans, axis) = app.world.query::<(&Transform, &Xaxis)>().single(&app.world);
+    assert!(axis.met);
+    assert_eq!(axis.node_id, 3);
+    assert_eq!(trans.translation.x, 10.);
+    assert!(trans.translation.y > 20.);
+
+    app.update();
+    assert_eq!(
+        app.world
+            .query::<(&HistTag, &AesFilter)>()
+            .iter(&app.world)
+            .filter(|(_, filter)| filter.met)
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn condition_sweep_is_written_as_gif_or_frames() {
+    use image::AnimationDecoder;
+    let dir = std::env::temp_dir().join("shu_animation");
+    std::fs::create_dir_all(&dir).unwrap();
+    let frames: Vec<image::RgbaImage> = [[255, 0, 0, 255], [0, 0, 255, 255], [0, 255, 0, 255]]
+        .into_iter()
+        .map(|color| image::RgbaImage::from_pixel(40, 20, image::Rgba(color)))
+        .collect();
+
+    let gif = dir.join("sweep.gif");
+    crate::screenshot::write_animation(frames.clone(), gif.to_str().unwrap(), 2., 20).unwrap();
+    let decoder = image::codecs::gif::GifDecoder::new(std::io::BufReader::new(
+        std::fs::File::open(&gif).unwrap(),
+    ))
+    .unwrap();
+    let decoded = decoder.into_frames().collect_frames().unwrap();
+    assert_eq!(decoded.len(), 3);
+    // resized keeping the aspect ratio
+    assert_eq!(decoded[0].buffer().dimensions(), (20, 10));
+    let (numer, denom) = decoded[0].delay().numer_denom_ms();
+    assert_eq!(numer / denom, 500);
+
+    let upper = dir.join("upper.GIF");
+    crate::screenshot::write_animation(frames.clone(), upper.to_str().unwrap(), 2., 0).unwrap();
+    assert_eq!(
+        image::ImageFormat::from_path(&upper).unwrap(),
+        image::guess_format(&std::fs::read(&upper).unwrap()).unwrap()
+    );
+
+    let png = dir.join("sweep.mp4");
+    crate::screenshot::write_animation(frames, png.to_str().unwrap(), 2., 0).unwrap();
+    let frame = image::open(dir.join("sweep-002.png")).unwrap();
+    assert_eq!((frame.width(), frame.height()), (40, 20));
+}
+
+#[test]
+fn color_distributions_are_summarized_with_the_chosen_statistic() {
+    use crate::aesthetics::Summarized;
+    use crate::stats::Summary;
+    let samples = [1f32, 2., 3., 10.];
+    assert_eq!(Summary::Mean.apply(&samples, 0.5), 4.);
+    assert_eq!(Summary::Median.apply(&samples, 0.5), 2.5);
+    assert_eq!(Summary::Quantile.apply(&samples, 1.), 10.);
+    assert!((Summary::Variance.apply(&samples, 0.5) - 16.6667).abs() < 1e-3);
+    assert!(Summary::CiWidth.apply(&samples, 0.5) > 8.);
+
+    let mut app = App::new();
+    let mut ui_state = UiState::default();
+    ui_state.summary = Summary::Median;
+    app.insert_resource(ui_state);
+    app.add_systems(Update, crate::aesthetics::summarize_samples);
+    let entity = app
+        .world
+        .spawn((
+            Summarized {
+                samples: vec![samples.to_vec(), vec![5.]],
+                original: None,
+            },
+            Point(vec![4f32, 5.]),
+        ))
+        .id();
+    app.update();
+    assert_eq!(
+        app.world.get::<Point<f32>>(entity).unwrap().0,
+        vec![2.5, 5.]
+    );
+    app.world.resource_mut::<UiState>().summary = Summary::Mean;
+    app.update();
+    assert_eq!(app.world.get::<Point<f32>>(entity).unwrap().0, vec![4., 5.]);
+}
+
+#[test]
+fn elements_out_of_the_value_filter_are_dimmed() {
+    let mut app = App::new();
+    for (i, id) in ["r1", "r2", "r3"].into_iter().enumerate() {
+        app.world.spawn((
+            ShapeBundle::default(),
+            Stroke::new(Color::BLACK, 10.0),
+            escher::ArrowTag {
+                id: String::from(id),
+                hists: None,
+                hist_heights: None,
+                node_id: i as u64,
+                direction: Vec2::X,
+            },
+        ));
+    }
+    app.world.spawn((
+        Aesthetics {
+            identifiers: vec![String::from("r1"), String::from("r2")],
+            condition: None,
+        },
+        crate::aesthetics::Gcolor {},
+        geom::GeomArrow {
+            plotted: false,
+            style: funcplot::ArrowStyle::default(),
+        },
+        Point(vec![0.5f32, 2.]),
+    ));
+    setup(&mut app, "assets");
+    app.init_asset::<Font>();
+    app.insert_resource(ActiveData::default());
+    let mut ui_state = UiState::default();
+    ui_state.reaction_filter = Some((1., f32::INFINITY));
+    app.insert_resource(ui_state);
+    app.insert_resource(theme::Theme::default());
+    app.add_plugins(AesPlugin);
+    app.update();
+
+    let alphas: std::collections::HashMap<String, f32> = app
+        .world
+        .query::<(&Stroke, &escher::ArrowTag)>()
+        .iter(&app.world)
+        .map(|(stroke, arrow)| (arrow.id.clone(), stroke.color.a()))
+        .collect();
+    // below the minimum and without a value
+    assert!(alphas["r1"] < 1.);
+    assert!(alphas["r3"] < 1.);
+    assert_eq!(alphas["r2"], 1.);
+
+    let ui_state = app.world.resource::<UiState>();
+    assert!(ui_state.passes_filter(true, None));
+    assert!(!ui_state.passes_filter(false, Some(0.99)));
+}
+
+#[test]
+fn global_scale_spans_all_conditions() {
+    let mut ui_state = UiState::default();
+    let current = vec![-1f32, 2.];
+    let other = vec![-3f32, 5.];
+    let all = || [current.as_slice(), other.as_slice()].into_iter();
+    assert_eq!(
+        crate::aesthetics::scale_range(&ui_state, &current, all()),
+        (-1., 2.)
+    );
+    ui_state.global_scale = true;
+    assert_eq!(
+        crate::aesthetics::scale_range(&ui_state, &current, all()),
+        (-3., 5.)
+    );
+}
+
+#[test]
+fn long_distributions_are_subsampled_to_draw_them() {
+    let samples: Vec<f32> = (0..100_000).map(|x| x as f32).collect();
+    let weights: Vec<f32> = samples.iter().map(|x| 2. * x).collect();
+    let (short, _) = crate::stats::subsample(&samples[..10], None, 1000);
+    assert!(matches!(short, std::borrow::Cow::Borrowed(_)));
+    let (all, _) = crate::stats::subsample(&samples, None, 0);
+    assert_eq!(all.len(), samples.len());
+
+    let (picked, picked_weights) = crate::stats::subsample(&samples, Some(&weights), 1000);
+    let picked_weights = picked_weights.unwrap();
+    assert_eq!(picked.len(), 1000);
+    // weights follow their samples
+    assert!(picked
+        .iter()
+        .zip(picked_weights.iter())
+        .all(|(x, w)| *w == 2. * x));
+    // uniform over the chain and the same every time
+    let mean = crate::stats::mean(&picked);
+    assert!((mean - 50_000.).abs() < 5_000.);
+    assert_eq!(crate::stats::subsample(&samples, None, 1000).0, picked);
+}
+
+#[test]
+fn provenance_is_stored_in_maps_and_svgs() {
+    let map = std::fs::read_to_string("assets/ecoli_core_map.json").unwrap();
+    let mut map: escher::EscherMap = serde_json::from_str(&map).unwrap();
+    assert!(map.provenance.is_none());
+    let provenance = crate::provenance::Provenance {
+        map: map.info.clone(),
+        map_file: String::from("ecoli_core_map.json"),
+        datasets: vec![String::from("fluxes.metabolism.json")],
+        loaded: String::from("2024-05-01T10:00:00Z"),
+        shu_version: String::from(env!("CARGO_PKG_VERSION")),
+    };
+    assert_eq!(provenance.map.map_name, "e_coli_core.Core metabolism");
+    map.provenance = Some(provenance.clone());
+    let saved = serde_json::to_string(&map).unwrap();
+    let map: escher::EscherMap = serde_json::from_str(&saved).unwrap();
+    assert_eq!(map.provenance, Some(provenance.clone()));
+
+    let dir = std::env::temp_dir().join("shu_provenance");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("map.svg");
+    let path = path.to_str().unwrap();
+    std::fs::write(
+        path,
+        r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M0 0"/></svg>"#,
+    )
+    .unwrap();
+    crate::provenance::embed_in_svg(path, &provenance).unwrap();
+    let svg = std::fs::read_to_string(path).unwrap();
+    assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg"><metadata"#));
+    assert!(svg.contains("fluxes.metabolism.json"));
+    assert!(svg.ends_with(r#"<path d="M0 0"/></svg>"#));
+}
+
+#[test]
+fn negative_fluxes_are_ribbons_narrowing_towards_the_reactants() {
+    use bevy_prototype_lyon::prelude::shapes;
+    let arrow_path = GeometryBuilder::new()
+        .add(&shapes::Line(Vec2::ZERO, Vec2::new(100., 0.)))
+        .add(&shapes::Circle {
+            radius: 5.,
+            center: Vec2::new(100., 0.),
+        })
+        .build();
+    // half width at both ends of the ribbon
+    let ends = |ribbon: &Path| {
+        let points: Vec<Vec2> = ribbon
+            .0
+            .iter()
+            .map(|event| Vec2::new(event.to().x, event.to().y))
+            .collect();
+        let half = |x: f32| {
+            points
+                .iter()
+                .filter(|p| (p.x - x).abs() < 1e-3)
+                .map(|p| p.y.abs())
+                .fold(0f32, f32::max)
+        };
+        (half(0.), half(100.))
+    };
+    let (start, end) = ends(&funcplot::ribbon_path(&arrow_path, Vec2::Y, 20., false));
+    assert!((start - 10.).abs() < 1e-3);
+    assert!((end - 2.).abs() < 1e-3);
+    let (start, end) = ends(&funcplot::ribbon_path(&arrow_path, Vec2::Y, 20., true));
+    assert!(start < end);
+
+    let mut app = App::new();
+    app.world.spawn((
+        ShapeBundle {
+            path: arrow_path,
+            ..default()
+        },
+        Stroke::new(Color::BLACK, 10.0),
+        escher::ArrowTag {
+            id: String::from("r1"),
+            hists: None,
+            hist_heights: None,
+            node_id: 1,
+            direction: Vec2::X,
+        },
+    ));
+    app.world.spawn((
+        Aesthetics {
+            identifiers: vec![String::from("r1")],
+            condition: None,
+        },
+        crate::aesthetics::Gsize {},
+        geom::GeomArrow {
+            plotted: false,
+            style: funcplot::ArrowStyle::Ribbon,
+        },
+        Point(vec![-5f32]),
+    ));
+    setup(&mut app, "assets");
+    app.init_asset::<Font>();
+    app.insert_resource(ActiveData::default());
+    app.insert_resource(UiState::default());
+    app.insert_resource(theme::Theme::default());
+    app.add_plugins(AesPlugin);
+    app.update();
+    app.update();
+
+    assert_eq!(
+        app.world
+            .query::<&geom::RibbonTag>()
+            .iter(&app.world)
+            .count(),
+        1
+    );
+    let (stroke, _) = app
+        .world
+        .query::<(&Stroke, &escher::ArrowTag)>()
+        .single(&app.world);
+    assert!(stroke.options.line_width < 10.);
+}
+
+#[test]
+fn popups_of_all_conditions_are_stacked() {
+    let mut app = App::new();
+    app.world.spawn((
+        Transform::default(),
+        escher::Hover {
+            id: String::from("r1"),
+            node_id: 1,
+            xlimits: None,
+        },
+    ));
+    for (condition, samples) in [("a", vec![1f32, 2., 2., 3.]), ("b", vec![2f32, 3., 3., 4.])] {
+        app.world.spawn((
+            Aesthetics {
+                identifiers: vec![String::from("r1")],
+                condition: Some(String::from(condition)),
+            },
+            Distribution(vec![samples]),
+            GeomHist::up(geom::HistPlot::Kde),
+            Gy {},
+            geom::PopUp,
+            AesFilter {
+                met: false,
+                pbox: false,
+            },
+        ));
+    }
+    setup(&mut app, "assets");
+    app.init_asset::<Image>();
+    app.init_asset::<Font>();
+    app.insert_resource(ActiveData::default());
+    let mut ui_state = UiState::default();
+    ui_state.condition = String::from("ALL");
+    ui_state.conditions = vec![String::from("a"), String::from("b"), String::from("ALL")];
+    app.insert_resource(ui_state);
+    app.insert_resource(theme::Theme::default());
+    app.add_plugins(AesPlugin);
+    for _ in 0..3 {
+        app.update();
+    }
+    let popups = |app: &mut App| {
+        let mut popups: Vec<(Option<String>, f32)> = app
+            .world
+            .query::<(&Transform, &geom::AnyTag, &geom::VisCondition)>()
+            .iter(&app.world)
+            .map(|(trans, _, vis)| (vis.condition.clone(), trans.translation.y))
+            .collect();
+        popups.sort_by(|a, b| a.0.cmp(&b.0));
+        popups
+    };
+    let stacked = popups(&mut app);
+    assert_eq!(stacked.len(), 2);
+    // the first condition on top
+    assert!(stacked[0].1 > stacked[1].1);
+
+    app.world.resource_mut::<UiState>().condition = String::from("a");
+    app.update();
+    let single = popups(&mut app);
+    assert_eq!(single[0].1, single[1].1);
+}
+
+#[test]
+fn picking_radius_is_constant_on_screen() {
+    let radius = crate::gui::DragBindings::default().pick_radius;
+    // zoomed in, only elements close in the world are picked
+    assert_eq!(pick_distance(radius, 1.), radius);
+    // zoomed out, the same pixels span more of the world
+    let zoomed_out = pick_distance(radius, 20.);
+    assert!(zoomed_out > radius);
+    assert_eq!(zoomed_out / 20., radius);
+}
+
+#[test]
+fn bookmarks_are_saved_with_the_map_and_move_the_camera() {
+    use crate::bookmark::{jump_to_bookmark, Bookmark, JumpToBookmark};
+    use bevy_pancam::PanCam;
+    let map = std::fs::read_to_string("assets/ecoli_core_map.json").unwrap();
+    let mut map: escher::EscherMap = serde_json::from_str(&map).unwrap();
+    assert!(map.bookmarks.is_empty());
+    map.bookmarks = vec![
+        Bookmark {
+            name: String::from("glycolysis"),
+            position: Vec2::new(-300., 200.),
+            scale: 2.,
+            condition: String::from("y"),
+        },
+        Bookmark {
+            name: String::from("TCA"),
+            position: Vec2::new(500., -100.),
+            scale: 100.,
+            condition: String::from("missing"),
+        },
+    ];
+    let saved = serde_json::to_string(&map).unwrap();
+    let map: escher::EscherMap = serde_json::from_str(&saved).unwrap();
+    assert_eq!(map.bookmarks[0].name, "glycolysis");
+
+    let mut app = App::new();
+    setup(&mut app, "assets");
+    app.init_asset::<escher::EscherMap>();
+    app.add_event::<JumpToBookmark>();
+    app.add_systems(Update, jump_to_bookmark);
+    let handle = app
+        .world
+        .resource_mut::<Assets<escher::EscherMap>>()
+        .add(map);
+    app.insert_resource(escher::MapState::new("ecoli_core_map.json", handle));
+    let mut ui_state = UiState::default();
+    ui_state.condition = String::from("x");
+    ui_state.conditions = vec![String::from("x"), String::from("y")];
+    app.insert_resource(ui_state);
+    let camera = app
+        .world
+        .spawn((
+            Transform::from_xyz(0., 0., 10.),
+            OrthographicProjection::default(),
+            PanCam {
+                min_scale: 1.,
+                max_scale: Some(40.),
+                ..default()
+            },
+        ))
+        .id();
+    let view = |app: &App| {
+        let trans = app.world.get::<Transform>(camera).unwrap().translation;
+        let scale = app
+            .world
+            .get::<OrthographicProjection>(camera)
+            .unwrap()
+            .scale;
+        (trans, scale)
+    };
+
+    app.world.send_event(JumpToBookmark(0));
+    app.update();
+    assert_eq!(view(&app), (Vec3::new(-300., 200., 10.), 2.));
+    assert_eq!(app.world.resource::<UiState>().condition, "y");
+
+    // zoom is kept within the limits of the camera and unknown conditions are ignored
+    app.world.send_event(JumpToBookmark(1));
+    app.update();
+    assert_eq!(view(&app), (Vec3::new(500., -100., 10.), 40.));
+    assert_eq!(app.world.resource::<UiState>().condition, "y");
+
+    // keys without a bookmark do nothing
+    app.world.send_event(JumpToBookmark(5));
+    app.update();
+    assert_eq!(view(&app).1, 40.);
 }
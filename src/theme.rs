@@ -0,0 +1,126 @@
+//! Colors of the canvas and the settings windows, switchable between presets
+//! and savable as a JSON file.
+use crate::escher::{ArrowTag, CircleTag, MapLabel};
+use crate::geom::{AxisTitle, SigLabel};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_pancam::PanCam;
+use bevy_prototype_lyon::prelude::{Fill, Stroke};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Theme::default())
+            .add_systems(Update, apply_theme);
+    }
+}
+
+/// Colors (sRGB) used by default for the map elements, i.e., when they are not
+/// mapped to data, and appearance of the settings windows.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Theme {
+    pub background: [u8; 3],
+    pub arrow: [u8; 3],
+    pub metabolite: [u8; 3],
+    pub metabolite_stroke: [u8; 3],
+    /// Labels of the map and titles of the axes.
+    pub text: [u8; 3],
+    /// Use the dark visuals of egui for the settings windows.
+    pub dark_ui: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::light()
+    }
+}
+
+impl Theme {
+    /// Escher palette on a white canvas.
+    pub fn light() -> Self {
+        Self {
+            background: [255, 255, 255],
+            arrow: [95, 94, 95],
+            metabolite: [190, 185, 185],
+            metabolite_stroke: [95, 94, 95],
+            text: [95, 94, 95],
+            dark_ui: true,
+        }
+    }
+
+    /// Light elements on a dark canvas, e.g., for dark slides.
+    pub fn dark() -> Self {
+        Self {
+            background: [30, 32, 36],
+            arrow: [170, 170, 175],
+            metabolite: [95, 100, 110],
+            metabolite_stroke: [200, 200, 205],
+            text: [220, 220, 225],
+            dark_ui: true,
+        }
+    }
+
+    pub fn background(&self) -> Color {
+        to_color(self.background)
+    }
+    pub fn arrow(&self) -> Color {
+        to_color(self.arrow)
+    }
+    pub fn metabolite(&self) -> Color {
+        to_color(self.metabolite)
+    }
+    pub fn metabolite_stroke(&self) -> Color {
+        to_color(self.metabolite_stroke)
+    }
+    pub fn text(&self) -> Color {
+        to_color(self.text)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+fn to_color([r, g, b]: [u8; 3]) -> Color {
+    Color::rgb_u8(r, g, b)
+}
+
+/// Propagate the [`Theme`] to the camera, egui and the map elements.
+///
+/// Elements mapped to data are recolored too, but the aesthetics systems
+/// paint them again afterwards.
+fn apply_theme(
+    theme: Res<Theme>,
+    mut egui_context: EguiContexts,
+    mut cameras: Query<&mut Camera, With<PanCam>>,
+    mut arrows: Query<&mut Stroke, (With<ArrowTag>, Without<CircleTag>)>,
+    mut circles: Query<(&mut Fill, &mut Stroke), With<CircleTag>>,
+    mut labels: Query<&mut Text, Or<(With<MapLabel>, With<AxisTitle>, With<SigLabel>)>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    for mut camera in cameras.iter_mut() {
+        camera.clear_color = ClearColorConfig::Custom(theme.background());
+    }
+    egui_context.ctx_mut().set_visuals(if theme.dark_ui {
+        egui::Visuals::dark()
+    } else {
+        egui::Visuals::light()
+    });
+    for mut stroke in arrows.iter_mut() {
+        stroke.color = theme.arrow();
+    }
+    for (mut fill, mut stroke) in circles.iter_mut() {
+        fill.color = theme.metabolite();
+        stroke.color = theme.metabolite_stroke();
+    }
+    for mut text in labels.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = theme.text();
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Reload the map and data files when they are modified on disk.
+//!
+//! Not available in WASM since there is no file system.
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+
+use crate::data::{Data, LoadError, ReactionState, Validate};
+use crate::escher::{EscherMap, MapState};
+use crate::gui::UiState;
+use crate::info::Info;
+use bevy::prelude::*;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+pub struct WatcherPlugin;
+
+impl Plugin for WatcherPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, watch_files);
+    }
+}
+
+#[derive(Default)]
+struct WatchState {
+    /// Settings the watcher was built from: map path, data path and enabled.
+    source: (String, String, bool),
+    watcher: Option<RecommendedWatcher>,
+    rx: Option<Receiver<notify::Result<notify::Event>>>,
+    map: Option<PathBuf>,
+    data: Option<PathBuf>,
+}
+
+impl WatchState {
+    /// Watch the parent directories of the files instead of the files themselves
+    /// since many programs write files by replacing them.
+    fn rebuild(&mut self, map: Option<PathBuf>, data: Option<PathBuf>) -> notify::Result<()> {
+        self.watcher = None;
+        self.rx = None;
+        self.map = map;
+        self.data = data;
+        let dirs: HashSet<&Path> = [&self.map, &self.data]
+            .into_iter()
+            .filter_map(|p| p.as_ref().and_then(|p| p.parent()))
+            .collect();
+        if dirs.is_empty() {
+            return Ok(());
+        }
+        let (tx, rx) = channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        for dir in dirs {
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+        self.watcher = Some(watcher);
+        self.rx = Some(rx);
+        Ok(())
+    }
+}
+
+/// Watch [`UiState::map_path`] and [`UiState::data_path`] if [`UiState::watch`]
+/// is enabled and load them again when they change.
+fn watch_files(
+    mut ui_state: ResMut<UiState>,
+    mut state: Local<WatchState>,
+    mut info_state: ResMut<Info>,
+    mut data_assets: ResMut<Assets<Data>>,
+    mut map_assets: ResMut<Assets<EscherMap>>,
+    mut reaction_resource: ResMut<ReactionState>,
+    mut escher_resource: ResMut<MapState>,
+) {
+    let source = (
+        ui_state.map_path.clone(),
+        ui_state.data_path.clone(),
+        ui_state.watch,
+    );
+    if source != state.source {
+        let (map, data) = if ui_state.watch {
+            (
+                watched_path(&ui_state.map_path),
+                watched_path(&ui_state.data_path),
+            )
+        } else {
+            (None, None)
+        };
+        if let Err(e) = state.rebuild(map, data) {
+            warn!("Could not watch files: {e}");
+            info_state.notify("Files could not be watched!");
+        }
+        state.source = source;
+    }
+    let Some(rx) = state.rx.as_ref() else {
+        return;
+    };
+    // several events are usually emitted for one write
+    let changed: HashSet<PathBuf> = rx
+        .try_iter()
+        .filter_map(|event| event.ok())
+        .filter(|event| matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)))
+        .flat_map(|event| event.paths)
+        .collect();
+    let is_changed = |watched: &Option<PathBuf>| {
+        watched
+            .as_ref()
+            .is_some_and(|p| changed.iter().any(|c| same_file(c, p)))
+    };
+    if is_changed(&state.data) {
+        match read_json::<Data>(state.data.as_ref().unwrap()) {
+            Ok(data) => match data.validate() {
+                Ok(()) => {
+                    reaction_resource.add(ui_state.data_path.clone(), data_assets.add(data));
+                    info_state.notify("Data changed, reloading...");
+                }
+                // the file is complete but would not be plotted right
+                Err(e) => {
+                    ui_state.load_error = Some(LoadError::new(ui_state.data_path.clone(), e));
+                }
+            },
+            // the file may be still being written
+            Err(e) => warn!("Could not reload data: {e}"),
+        }
+    }
+    if is_changed(&state.map) {
+        match read_json::<EscherMap>(state.map.as_ref().unwrap()) {
+            Ok(escher_map) => {
+                escher_resource.replace(&ui_state.map_path, map_assets.add(escher_map));
+                info_state.notify("Map changed, reloading...");
+            }
+            Err(e) => warn!("Could not reload map: {e}"),
+        }
+    }
+}
+
+/// Absolute path of a file to be watched, which does not need to exist yet
+/// (e.g., the output of a simulation that has not started); only its
+/// directory has to.
+pub fn watched_path(path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    let name = path.file_name()?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    Some(std::fs::canonicalize(dir).ok()?.join(name))
+}
+
+/// Whether a path reported by the watcher is the watched file, by its name in
+/// the watched directory.
+fn same_file(changed: &Path, watched: &Path) -> bool {
+    changed.file_name() == watched.file_name() && changed.parent() == watched.parent()
+}
+
+fn read_json<T>(path: &Path) -> anyhow::Result<T>
+where
+    for<'de> T: serde::Deserialize<'de>,
+{
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}